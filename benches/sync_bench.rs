@@ -0,0 +1,127 @@
+// ABOUTME: Criterion benchmarks for delta serialization and merge throughput.
+// ABOUTME: Establishes a baseline to catch regressions in DSON or the wire format.
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use dson::{Dot, Identifier, crdts::mvreg::MvRegValue};
+use dson_p2p_todo::{
+    app::{App, DEFAULT_LIST, ReplicaId, TodoStore},
+    network::{self, InMemoryTransport, NetworkMessage, WireFormat},
+    priority::DotKey,
+};
+use std::hint::black_box;
+
+const SIZES: [usize; 3] = [10, 100, 1000];
+
+/// Build a store with `n` todos in the default list, all created by replica 1.
+fn store_with_todos(n: usize) -> TodoStore {
+    let mut store = TodoStore::default();
+    let id = Identifier::new(1, 0);
+    let mut tx = store.transact(id);
+
+    tx.in_map(DEFAULT_LIST, |list_tx| {
+        for i in 0..n {
+            let dot_key = DotKey::new(&Dot::mint(id, i as u64 + 1));
+            list_tx.in_map(dot_key.as_str(), |todo_tx| {
+                todo_tx.write_register("text", MvRegValue::String(format!("Todo number {i}")));
+                todo_tx.write_register("done", MvRegValue::Bool(i % 2 == 0));
+            });
+            list_tx.in_array("priority", |arr_tx| {
+                arr_tx.insert_register(arr_tx.len(), MvRegValue::String(dot_key.into_inner()));
+            });
+        }
+    });
+
+    let _delta = tx.commit();
+    store
+}
+
+/// Serialization of a delta carrying `n` todos, reported in bytes per second.
+fn bench_serialize(c: &mut Criterion) {
+    let mut group = c.benchmark_group("serialize_message");
+
+    for n in SIZES {
+        let msg = NetworkMessage::Delta {
+            schema_version: network::CURRENT_VERSION,
+            sender_id: ReplicaId::new(1),
+            sent_at: Some(0),
+            delta: dson::Delta(store_with_todos(n)),
+            generation: 0,
+        };
+        let size = network::serialize_message(&msg, WireFormat::MessagePack)
+            .expect("serialize")
+            .len();
+
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(n), &msg, |b, msg| {
+            b.iter(|| {
+                network::serialize_message(black_box(msg), WireFormat::MessagePack)
+                    .expect("serialize")
+            });
+        });
+    }
+
+    group.finish();
+}
+
+/// Merging a single-todo delta into a store of `n` todos, reported in operations per second.
+fn bench_merge(c: &mut Criterion) {
+    let mut group = c.benchmark_group("join_or_replace_with");
+    group.throughput(Throughput::Elements(1));
+
+    for n in SIZES {
+        let base = store_with_todos(n);
+
+        let delta = {
+            let mut remote = base.clone();
+            let id = Identifier::new(2, 0);
+            let dot_key = DotKey::new(&Dot::mint(id, 1));
+            let mut tx = remote.transact(id);
+            tx.in_map(DEFAULT_LIST, |list_tx| {
+                list_tx.in_map(dot_key.as_str(), |todo_tx| {
+                    todo_tx.write_register("text", MvRegValue::String("Remote todo".to_string()));
+                    todo_tx.write_register("done", MvRegValue::Bool(false));
+                });
+                list_tx.in_array("priority", |arr_tx| {
+                    arr_tx.insert_register(0, MvRegValue::String(dot_key.into_inner()));
+                });
+            });
+            tx.commit()
+        };
+
+        group.bench_with_input(BenchmarkId::from_parameter(n), &delta, |b, delta| {
+            b.iter_batched(
+                || base.clone(),
+                |mut store| {
+                    store.join_or_replace_with(delta.0.store.clone(), &delta.0.context);
+                    store
+                },
+                criterion::BatchSize::LargeInput,
+            );
+        });
+    }
+
+    group.finish();
+}
+
+/// Reading 1000 todos in priority order.
+fn bench_get_todos_ordered(c: &mut Criterion) {
+    let transport = InMemoryTransport::hub(1).remove(0);
+    let mut app = App::with_transport(
+        ReplicaId::new(1),
+        network::DEFAULT_PORT,
+        Box::new(transport),
+    );
+    app.store = store_with_todos(1000);
+
+    c.bench_function("get_todos_ordered/1000", |b| {
+        b.iter(|| black_box(app.get_todos_ordered()));
+    });
+}
+
+criterion_group!(
+    benches,
+    bench_serialize,
+    bench_merge,
+    bench_get_todos_ordered
+);
+criterion_main!(benches);
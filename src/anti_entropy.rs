@@ -2,7 +2,11 @@
 // ABOUTME: Periodically exchanges causal contexts to detect and repair missing deltas.
 
 use dson::CausalContext;
-use std::time::{Duration, Instant};
+use std::{
+    collections::BTreeMap,
+    ops::RangeInclusive,
+    time::{Duration, Instant},
+};
 
 /// Anti-entropy configuration and state.
 pub struct AntiEntropy {
@@ -42,6 +46,23 @@ impl AntiEntropy {
         }
     }
 
+    /// Check if a scheduled context broadcast would be redundant.
+    /// That is the case while deltas flow in both directions: we sent and received
+    /// one within the last half interval, so neither side is likely to be behind.
+    pub fn is_redundant(
+        &self,
+        last_delta_sent: Option<Instant>,
+        last_delta_received: Option<Instant>,
+    ) -> bool {
+        let recent = |at: Option<Instant>| at.is_some_and(|at| at.elapsed() < self.interval / 2);
+        recent(last_delta_sent) && recent(last_delta_received)
+    }
+
+    /// Restart the interval, e.g. after an out-of-schedule broadcast.
+    pub fn reset(&mut self) {
+        self.last_broadcast = Instant::now();
+    }
+
     // DEMO BEGIN #5: Anti-entropy via causal context comparison
     /// Compare two causal contexts to determine if one is behind the other.
     /// Returns SyncNeeded indicating what action should be taken.
@@ -58,6 +79,38 @@ impl AntiEntropy {
     // DEMO END #5
 }
 
+/// Retry schedule with exponential backoff.
+/// Used to repeat the startup full-state request until a peer answers.
+#[derive(Debug, Clone, Copy)]
+pub struct Backoff {
+    next_attempt: Instant,
+    delay: Duration,
+    max_delay: Duration,
+}
+
+impl Backoff {
+    /// Create a backoff whose first attempt is due immediately.
+    pub fn new(initial_delay: Duration, max_delay: Duration) -> Self {
+        Self {
+            next_attempt: Instant::now(),
+            delay: initial_delay,
+            max_delay,
+        }
+    }
+
+    /// Check if an attempt is due.
+    /// Returns true at most once per delay, doubling the delay each time.
+    pub fn should_attempt(&mut self) -> bool {
+        let now = Instant::now();
+        if now < self.next_attempt {
+            return false;
+        }
+        self.next_attempt = now + self.delay;
+        self.delay = (self.delay * 2).min(self.max_delay);
+        true
+    }
+}
+
 /// Result of comparing two causal contexts.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[allow(clippy::enum_variant_names)]
@@ -72,6 +125,103 @@ pub enum SyncNeeded {
     BothNeedSync,
 }
 
+/// How far two replicas' causal contexts are apart, in operations (dots).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct ContextDiff {
+    /// Dots the remote has seen and we have not.
+    pub local_missing: usize,
+    /// Dots we have seen and the remote has not.
+    pub remote_missing: usize,
+}
+
+impl ContextDiff {
+    /// The four-way summary, for callers that only need to know who is behind.
+    pub fn sync_needed(&self) -> SyncNeeded {
+        match (self.local_missing > 0, self.remote_missing > 0) {
+            (false, false) => SyncNeeded::InSync,
+            (false, true) => SyncNeeded::RemoteNeedsSync,
+            (true, false) => SyncNeeded::LocalNeedsSync,
+            (true, true) => SyncNeeded::BothNeedSync,
+        }
+    }
+
+    /// Whether we lack more than the remote does, so asking for its state
+    /// should come before sending ours.
+    pub fn should_pull_first(&self) -> bool {
+        self.local_missing > self.remote_missing
+    }
+}
+
+/// Count the dots each context has that the other lacks.
+///
+/// Works on the contexts' per-replica intervals, so a compacted context of
+/// thousands of operations costs no more than a handful of dots.
+pub fn diff_contexts(local: &CausalContext, remote: &CausalContext) -> ContextDiff {
+    let missing_from = |context: &CausalContext, other: &CausalContext| {
+        let mut missing = other.clone();
+        missing.remove_dots_in(context);
+        usize::try_from(missing.dot_count()).unwrap_or(usize::MAX)
+    };
+    ContextDiff {
+        local_missing: missing_from(local, remote),
+        remote_missing: missing_from(remote, local),
+    }
+}
+
+/// Received and missing sequence ranges of one node within `1..=max_seq`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NodeGaps {
+    pub node: u8,
+    pub received: Vec<RangeInclusive<u64>>,
+    pub missing: Vec<RangeInclusive<u64>>,
+}
+
+/// List, per node, which sequence numbers we have and which are missing.
+/// Missing ranges are deltas we have not received yet.
+pub fn context_gaps(context: &CausalContext) -> Vec<NodeGaps> {
+    // Works on the context's interval sets, so the cost grows with the
+    // number of gaps rather than the number of dots
+    let mut ranges: BTreeMap<u8, Vec<RangeInclusive<u64>>> = BTreeMap::new();
+    for (id, intervals) in context.intervals() {
+        ranges
+            .entry(id.node().value())
+            .or_default()
+            .extend(intervals.map(|(start, end)| start.get()..=end.unwrap_or(start).get()));
+    }
+
+    ranges
+        .into_iter()
+        .map(|(node, mut ranges)| {
+            // Identifiers of the same node with different apps share the sequence space
+            ranges.sort_unstable_by_key(|range| *range.start());
+            let mut received: Vec<RangeInclusive<u64>> = Vec::new();
+            for range in ranges {
+                match received.last_mut() {
+                    Some(last) if *range.start() <= last.end() + 1 => {
+                        *last = *last.start()..=*last.end().max(range.end());
+                    }
+                    _ => received.push(range),
+                }
+            }
+
+            let mut missing = Vec::new();
+            let mut next = 1;
+            for range in &received {
+                if *range.start() > next {
+                    missing.push(next..=range.start() - 1);
+                }
+                next = range.end() + 1;
+            }
+
+            NodeGaps {
+                node,
+                received,
+                missing,
+            }
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -95,6 +245,57 @@ mod tests {
         assert!(!ae.should_broadcast());
     }
 
+    #[test]
+    fn test_is_redundant_only_when_deltas_flow_both_ways() {
+        let ae = AntiEntropy::new(Duration::from_millis(100));
+        let now = Instant::now();
+
+        assert!(ae.is_redundant(Some(now), Some(now)));
+        assert!(!ae.is_redundant(None, None));
+        assert!(!ae.is_redundant(Some(now), None));
+        assert!(!ae.is_redundant(None, Some(now)));
+    }
+
+    #[test]
+    fn test_is_redundant_expires_after_half_interval() {
+        let ae = AntiEntropy::new(Duration::from_millis(100));
+        let received = Some(Instant::now());
+
+        std::thread::sleep(Duration::from_millis(60));
+        let sent = Some(Instant::now());
+
+        // Still sending, but nothing received for more than half an interval
+        assert!(!ae.is_redundant(sent, received));
+    }
+
+    #[test]
+    fn test_reset_restarts_interval() {
+        let mut ae = AntiEntropy::new(Duration::from_millis(100));
+
+        std::thread::sleep(Duration::from_millis(150));
+        ae.reset();
+
+        assert!(!ae.should_broadcast());
+    }
+
+    #[test]
+    fn test_backoff_doubles_delay() {
+        let mut backoff = Backoff::new(Duration::from_millis(50), Duration::from_millis(80));
+
+        // First attempt is due immediately, then we wait for the delay
+        assert!(backoff.should_attempt());
+        assert!(!backoff.should_attempt());
+
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(backoff.should_attempt());
+
+        // Delay doubled to 100ms but is capped at 80ms
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(!backoff.should_attempt());
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(backoff.should_attempt());
+    }
+
     #[test]
     fn test_compare_contexts_in_sync() {
         let mut store_a = TodoStore::default();
@@ -182,4 +383,101 @@ mod tests {
         let result = AntiEntropy::compare_contexts(&store_a.context, &store_b.context);
         assert_eq!(result, SyncNeeded::BothNeedSync);
     }
+
+    #[test]
+    fn test_context_gaps() {
+        let mut context = CausalContext::new();
+        for seq in [2, 3, 5, 7, 8, 9] {
+            context.insert_dot(dson::Dot::mint(Identifier::new(0x3a, 0), seq));
+        }
+        for seq in 1..=4 {
+            context.insert_dot(dson::Dot::mint(Identifier::new(0x01, 0), seq));
+        }
+        for seq in [3, 4, 6] {
+            context.insert_dot(dson::Dot::mint(Identifier::new(0x01, 1), seq));
+        }
+
+        let gaps = context_gaps(&context);
+
+        assert_eq!(
+            gaps,
+            vec![
+                NodeGaps {
+                    node: 0x01,
+                    received: vec![1..=4, 6..=6],
+                    missing: vec![5..=5],
+                },
+                NodeGaps {
+                    node: 0x3a,
+                    received: vec![2..=3, 5..=5, 7..=9],
+                    missing: vec![1..=1, 4..=4, 6..=6],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_contexts_counts_missing_dots_both_ways() {
+        let mut store_a = TodoStore::default();
+        let mut store_b = TodoStore::default();
+
+        for (store, id, writes) in [
+            (&mut store_a, Identifier::new(1, 0), 2),
+            (&mut store_b, Identifier::new(2, 0), 1),
+        ] {
+            for i in 0..writes {
+                let mut tx = store.transact(id);
+                tx.write_register(format!("key{i}"), MvRegValue::U64(i));
+                let _delta = tx.commit();
+            }
+        }
+
+        let diff = diff_contexts(&store_a.context, &store_b.context);
+        assert_eq!(diff.local_missing, 1);
+        assert_eq!(diff.remote_missing, 2);
+        assert_eq!(
+            diff_contexts(&store_a.context, &store_a.context),
+            ContextDiff::default()
+        );
+        assert_eq!(diff.sync_needed(), SyncNeeded::BothNeedSync);
+        assert!(!diff.should_pull_first());
+    }
+
+    #[test]
+    fn test_diff_contexts_agrees_with_compare_contexts() {
+        let mut store_a = TodoStore::default();
+        let mut store_b = TodoStore::default();
+        let id_a = Identifier::new(1, 0);
+        let write = |store: &mut TodoStore, id, i: u64| {
+            let mut tx = store.transact(id);
+            tx.write_register(format!("key{i}"), MvRegValue::U64(i));
+            tx.commit()
+        };
+
+        let check = |a: &TodoStore, b: &TodoStore| {
+            let diff = diff_contexts(&a.context, &b.context);
+            assert_eq!(
+                diff.sync_needed(),
+                AntiEntropy::compare_contexts(&a.context, &b.context)
+            );
+            diff
+        };
+        check(&store_a, &store_b);
+
+        // Many operations on our side, with a gap in the middle
+        for i in 0..100 {
+            let delta = write(&mut store_a, id_a, i);
+            if i != 50 {
+                store_b.join_or_replace_with(delta.0.store, &delta.0.context);
+            }
+        }
+        let diff = check(&store_a, &store_b);
+        assert_eq!(diff.remote_missing, 1);
+        let _delta = write(&mut store_b, Identifier::new(2, 0), 0);
+        let diff = check(&store_b, &store_a);
+        assert_eq!((diff.local_missing, diff.remote_missing), (1, 1));
+        let diff = check(&store_b, &TodoStore::default());
+        assert_eq!(diff.remote_missing, 100);
+        assert!(check(&TodoStore::default(), &store_b).should_pull_first());
+    }
 }
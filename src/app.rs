@@ -2,17 +2,31 @@
 // ABOUTME: Coordinates CRDT store, network layer, and UI state.
 
 use crate::{
-    anti_entropy::{AntiEntropy, SyncNeeded},
-    network::{self, NetworkMessage},
+    anti_entropy::{AntiEntropy, Backoff, ContextDiff, SyncNeeded},
+    history::EditHistory,
+    network::{self, NetworkMessage, Transport},
     todo::Todo,
+    undo::{UndoEntry, UndoHistory},
+};
+use dson::{CausalDotStore, Dot, Identifier, OrMap, transaction::MapTransaction};
+use lru::LruCache;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque},
+    hash::{Hash, Hasher},
+    io::{self, Write},
+    net::SocketAddr,
+    num::NonZeroUsize,
+    sync::LazyLock,
+    time::{Duration, Instant},
 };
-use dson::{CausalDotStore, Dot, Identifier, OrMap};
-use std::{io, net::UdpSocket};
 
-type TodoStore = CausalDotStore<OrMap<String>>;
+/// CRDT document holding every todo and the priority array.
+pub type TodoStore = CausalDotStore<OrMap<String>>;
 
 /// Unique identifier for a replica, derived from timestamp.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, serde::Serialize, serde::Deserialize,
+)]
 pub struct ReplicaId(u8);
 
 impl ReplicaId {
@@ -36,6 +50,25 @@ impl ReplicaId {
     pub fn value(self) -> u8 {
         self.0
     }
+
+    /// Stable adjective-animal name, e.g. "brave-otter", for demos.
+    /// The high nibble picks the adjective and the low one the animal, so
+    /// every id has its own name.
+    pub fn friendly_name(self) -> String {
+        const ADJECTIVES: [&str; 16] = [
+            "brave", "calm", "eager", "fancy", "gentle", "happy", "jolly", "keen", "lucky",
+            "mighty", "nimble", "proud", "quick", "shy", "witty", "zesty",
+        ];
+        const ANIMALS: [&str; 16] = [
+            "otter", "badger", "crane", "dingo", "eagle", "ferret", "gecko", "heron", "ibis",
+            "jackal", "koala", "lemur", "moose", "newt", "owl", "panda",
+        ];
+        format!(
+            "{}-{}",
+            ADJECTIVES[usize::from(self.0 >> 4)],
+            ANIMALS[usize::from(self.0 & 0x0f)]
+        )
+    }
 }
 
 impl std::fmt::Display for ReplicaId {
@@ -44,11 +77,187 @@ impl std::fmt::Display for ReplicaId {
     }
 }
 
+impl std::str::FromStr for ReplicaId {
+    type Err = String;
+
+    /// Parse the hex form shown in the UI, e.g. `3a`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        u8::from_str_radix(s, 16)
+            .map(Self)
+            .map_err(|_| format!("invalid replica id {s} (expected hex, e.g. 3a)"))
+    }
+}
+
+/// Which replicas we accept messages from.
+///
+/// This only keeps honest replicas on a shared network apart; sender ids
+/// are not authenticated.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum PeerFilter {
+    /// Accept every replica.
+    #[default]
+    All,
+    /// Accept only these replicas.
+    Allow(BTreeSet<ReplicaId>),
+    /// Accept every replica except these.
+    Deny(BTreeSet<ReplicaId>),
+}
+
+impl PeerFilter {
+    /// Whether messages from `id` are accepted.
+    pub fn permits(&self, id: ReplicaId) -> bool {
+        match self {
+            PeerFilter::All => true,
+            PeerFilter::Allow(ids) => ids.contains(&id),
+            PeerFilter::Deny(ids) => !ids.contains(&id),
+        }
+    }
+}
+
+/// A peer we have received messages from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PeerInfo {
+    /// Address the peer's last message came from.
+    pub addr: SocketAddr,
+    /// When we last heard from the peer.
+    pub last_seen: Instant,
+}
+
+/// Rolling average of apparent propagation latency of peers' deltas.
+///
+/// Samples compare the sender's wall clock with ours, so between machines
+/// they include clock skew (and may even be negative). Only instances on the
+/// same host share a clock and give exact values.
+#[derive(Debug, Clone, Default)]
+pub struct LatencyStats {
+    samples: VecDeque<i64>,
+}
+
+impl LatencyStats {
+    /// Add a sample in milliseconds, dropping the oldest beyond the window.
+    pub fn record(&mut self, millis: i64) {
+        self.samples.push_back(millis);
+        if self.samples.len() > LATENCY_SAMPLES {
+            self.samples.pop_front();
+        }
+    }
+
+    /// Average of the recorded samples, if any.
+    pub fn average(&self) -> Option<i64> {
+        let count = i64::try_from(self.samples.len()).ok().filter(|&n| n > 0)?;
+        Some(self.samples.iter().sum::<i64>() / count)
+    }
+
+    /// Number of samples in the window.
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// Whether no sample has been recorded yet.
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+}
+
+/// How conflicting text values are resolved automatically when deltas arrive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AutoResolveStrategy {
+    /// Keep conflicts until the user resolves them.
+    #[default]
+    None,
+    TakeLonger,
+    TakeShorter,
+    TakeAlphabeticallyFirst,
+}
+
+impl AutoResolveStrategy {
+    /// Pick the winning value. Ties go to the alphabetically first value so
+    /// that every replica using the same strategy picks the same one.
+    pub fn pick(self, values: &[String]) -> Option<&str> {
+        let length = |s: &&String| s.chars().count();
+        let winner = match self {
+            AutoResolveStrategy::None => return None,
+            AutoResolveStrategy::TakeLonger => values
+                .iter()
+                .max_by(|a, b| length(a).cmp(&length(b)).then_with(|| b.cmp(a))),
+            AutoResolveStrategy::TakeShorter => values
+                .iter()
+                .min_by(|a, b| length(a).cmp(&length(b)).then_with(|| a.cmp(b))),
+            AutoResolveStrategy::TakeAlphabeticallyFirst => values.iter().min(),
+        };
+        winner.map(String::as_str)
+    }
+}
+
+impl std::str::FromStr for AutoResolveStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(AutoResolveStrategy::None),
+            "longer" => Ok(AutoResolveStrategy::TakeLonger),
+            "shorter" => Ok(AutoResolveStrategy::TakeShorter),
+            "alphabetical" => Ok(AutoResolveStrategy::TakeAlphabeticallyFirst),
+            _ => Err(format!(
+                "unknown strategy {s} (none, longer, shorter, alphabetical)"
+            )),
+        }
+    }
+}
+
+/// Name of the list every replica starts on.
+pub const DEFAULT_LIST: &str = "Todos";
+
+/// Stand-in for a list nothing has been written to yet.
+static EMPTY_LIST: LazyLock<OrMap<String>> = LazyLock::new(OrMap::default);
+
+/// Random peers each delta is sent to once we know more peers than this.
+pub const DEFAULT_GOSSIP_FANOUT: usize = 3;
+
 /// Maximum number of log messages to keep in the buffer.
 const MAX_LOG_MESSAGES: usize = 50;
 
+/// First retry delay for the startup full-state request.
+const FULL_STATE_REQUEST_INITIAL_DELAY: Duration = Duration::from_millis(250);
+
+/// Upper bound for the startup full-state request retry delay.
+const FULL_STATE_REQUEST_MAX_DELAY: Duration = Duration::from_secs(8);
+
+/// Upper bound of the random delay before answering a full-state request.
+/// Gives other peers a chance to answer first so only one reply is sent.
+const FULL_STATE_REPLY_MAX_JITTER: Duration = Duration::from_millis(150);
+
+/// How often the causal context is checked for compaction.
+const CONTEXT_COMPACTION_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Minimum time between manual resync requests, so holding the key can't flood peers.
+const MANUAL_RESYNC_COOLDOWN: Duration = Duration::from_secs(2);
+
+/// Maximum number of messages read per tick, so a sync storm can't hold up
+/// drawing and input. The rest stay queued in the socket for the next tick.
+pub const MAX_MESSAGES_PER_TICK: usize = 64;
+
+/// How often messages dropped by the peer filter are summed up in the log.
+const FILTER_SUMMARY_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How often the priority arrays are checked for repeated and missing entries.
+const PRIORITY_REPAIR_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Time without incoming deltas before the priority arrays are repaired, so
+/// moves and repairs still in flight from peers land first.
+const PRIORITY_REPAIR_QUIET: Duration = Duration::from_secs(5);
+
+/// Number of latency samples in the rolling average.
+const LATENCY_SAMPLES: usize = 20;
+
+/// Number of recently received deltas remembered to skip duplicates.
+const SEEN_MESSAGES: NonZeroUsize = NonZeroUsize::new(1000).expect("nonzero");
+
+/// Number of recently auto-resolved conflicts remembered so they aren't resolved again.
+const AUTO_RESOLVED_CONFLICTS: NonZeroUsize = NonZeroUsize::new(1000).expect("nonzero");
+
 /// Star Wars themed sample todos.
-const SAMPLE_TODOS: &[&str] = &[
+pub(crate) const SAMPLE_TODOS: &[&str] = &[
     "Train with the Jedi master",
     "Fix the spaceship engine",
     "Deliver secret plans to the rebels",
@@ -86,8 +295,75 @@ pub struct UiState {
     pub selected_index: usize,
     pub mode: Mode,
     pub input_buffer: String,
+    /// Insert position in `input_buffer` while in insert mode, in characters.
+    pub cursor_pos: usize,
     pub editing_dot: Option<dson::Dot>,
     pub log_scroll: usize,
+    pub show_detail: bool,
+    /// Only show todos carrying all of these tags.
+    pub filter_tags: HashSet<String>,
+    /// Only show todos in this category.
+    pub category_filter: Option<String>,
+    /// Only show todos created by this replica.
+    pub filter_creator: Option<u8>,
+    /// Show received/missing sequence ranges instead of the version vector.
+    pub show_context_gaps: bool,
+    /// Selected subtask of the expanded todo (`editing_dot`) in subtasks mode.
+    pub subtask_index: usize,
+    /// Whether j/k scroll the causal context panel instead of the list.
+    pub context_focused: bool,
+    /// Number of context rows scrolled past.
+    pub context_scroll: usize,
+    /// Group the list by level, keeping priority order within each group.
+    pub group_by_level: bool,
+    /// Order the list is shown in; within level groups when grouped.
+    pub sort_mode: SortMode,
+    /// Prefix each todo with its 1-based rank in the priority list.
+    pub show_ranks: bool,
+    /// Register whose values the conflict popup is choosing between.
+    pub conflict_field: ConflictField,
+    /// Selected value in the conflict popup.
+    pub conflict_index: usize,
+    /// Priority index the grabbed todo (`editing_dot`) will be dropped at.
+    pub grab_target: usize,
+    /// How todo texts wider than the list are shown.
+    pub wrap_mode: WrapMode,
+    /// Show replicas by friendly name instead of hex id in the logs and context.
+    pub friendly_names: bool,
+    /// Only show todos whose text contains this, ignoring case; empty shows all.
+    pub search_query: String,
+    /// Keep todos not matching `search_query` in the list, so n/N can jump
+    /// between the highlighted matches.
+    pub search_shows_all: bool,
+    /// Destructive action waiting for a y/n confirmation about `editing_dot`.
+    pub pending_action: Option<crate::input::Action>,
+    /// Show the key bindings overlay.
+    pub show_help: bool,
+    /// First line shown in the help overlay.
+    pub help_scroll: usize,
+    /// Todo whose edit history is shown in a popup.
+    pub history_dot: Option<Dot>,
+    /// First entry shown in the edit history popup.
+    pub history_scroll: usize,
+    /// Show the peer list comparing our causal context with each peer's.
+    pub show_context_report: bool,
+    /// Selected peer in the peer list, by position in [`App::sorted_peers`].
+    pub peer_index: usize,
+    /// Leave done todos out of the list; they stay in the store and the progress.
+    pub hide_done: bool,
+    /// Selected replica in the assign popup, by position in [`App::assignee_candidates`].
+    pub assign_index: usize,
+    /// The edit form's focused row and values while in form mode.
+    pub form: Option<EditForm>,
+}
+
+impl UiState {
+    /// The tag filter in alphabetical order, for showing and saving it.
+    pub fn sorted_filter_tags(&self) -> Vec<String> {
+        let mut tags: Vec<String> = self.filter_tags.iter().cloned().collect();
+        tags.sort();
+        tags
+    }
 }
 
 impl Default for UiState {
@@ -96,8 +372,128 @@ impl Default for UiState {
             selected_index: 0,
             mode: Mode::Normal,
             input_buffer: String::new(),
+            cursor_pos: 0,
             editing_dot: None,
             log_scroll: 0,
+            show_detail: false,
+            filter_tags: HashSet::new(),
+            category_filter: None,
+            filter_creator: None,
+            show_context_gaps: false,
+            subtask_index: 0,
+            context_focused: false,
+            context_scroll: 0,
+            group_by_level: false,
+            sort_mode: SortMode::Priority,
+            show_ranks: false,
+            conflict_field: ConflictField::Text,
+            conflict_index: 0,
+            grab_target: 0,
+            wrap_mode: WrapMode::Truncate,
+            friendly_names: false,
+            search_query: String::new(),
+            search_shows_all: false,
+            pending_action: None,
+            show_help: false,
+            help_scroll: 0,
+            history_dot: None,
+            history_scroll: 0,
+            show_context_report: false,
+            peer_index: 0,
+            hide_done: false,
+            assign_index: 0,
+            form: None,
+        }
+    }
+}
+
+/// The part of [`UiState`] kept across restarts: selection, log scroll and
+/// the active filters.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct SavedUiState {
+    pub selected_index: usize,
+    pub log_scroll: usize,
+    /// Sorted, so equal filters save the same bytes.
+    pub filter_tags: Vec<String>,
+    pub category_filter: Option<String>,
+    pub filter_creator: Option<u8>,
+    pub search_query: String,
+    pub hide_done: bool,
+}
+
+impl From<&UiState> for SavedUiState {
+    fn from(ui_state: &UiState) -> Self {
+        Self {
+            selected_index: ui_state.selected_index,
+            log_scroll: ui_state.log_scroll,
+            filter_tags: ui_state.sorted_filter_tags(),
+            category_filter: ui_state.category_filter.clone(),
+            filter_creator: ui_state.filter_creator,
+            search_query: ui_state.search_query.clone(),
+            hide_done: ui_state.hide_done,
+        }
+    }
+}
+
+/// Sidecar file holding the [`SavedUiState`] of the instance on `port`, in
+/// the working directory.
+pub fn ui_state_path(port: u16) -> std::path::PathBuf {
+    std::path::PathBuf::from(format!("ui-state-{port}.bin"))
+}
+
+/// How todo texts wider than the list are shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum WrapMode {
+    /// Cut with an ellipsis; the selected todo's full text is shown below the list.
+    #[default]
+    Truncate,
+    /// Continue on further rows of the same list item.
+    Wrap,
+}
+
+impl WrapMode {
+    /// The other mode.
+    pub fn toggle(self) -> Self {
+        match self {
+            WrapMode::Truncate => WrapMode::Wrap,
+            WrapMode::Wrap => WrapMode::Truncate,
+        }
+    }
+}
+
+/// Order the list is shown in. Only the view changes: the shared priority
+/// array keeps its order, and edits find todos by dot, not by row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum SortMode {
+    /// Priority array order.
+    #[default]
+    Priority,
+    /// By text, ignoring case.
+    Alphabetical,
+    /// Open todos first, each group in priority order.
+    DoneLast,
+    /// Conflicted todos first, each group in priority order.
+    ConflictedFirst,
+}
+
+impl SortMode {
+    /// The next mode, wrapping around to priority order.
+    pub fn cycle(self) -> Self {
+        match self {
+            SortMode::Priority => SortMode::Alphabetical,
+            SortMode::Alphabetical => SortMode::DoneLast,
+            SortMode::DoneLast => SortMode::ConflictedFirst,
+            SortMode::ConflictedFirst => SortMode::Priority,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SortMode::Priority => "priority",
+            SortMode::Alphabetical => "alphabetical",
+            SortMode::DoneLast => "done last",
+            SortMode::ConflictedFirst => "conflicted first",
         }
     }
 }
@@ -107,19 +503,301 @@ impl Default for UiState {
 pub enum Mode {
     Normal,
     Insert,
+    /// Multi-line editing of the selected todo's notes.
+    Notes,
+    /// Navigating the checklist of the expanded todo.
+    Subtasks,
+    /// Popup for picking the winning value of a conflicted todo (`editing_dot`).
+    ResolveConflict,
+    /// Single-line prompt; the input goes to `UiState::input_buffer`.
+    Prompt(PromptKind),
+    /// Moving the picked-up todo (`editing_dot`) to `UiState::grab_target`.
+    Grab,
+    /// Popup for picking who the todo (`editing_dot`) is assigned to.
+    Assign,
+    /// Form editing several fields of the todo (`editing_dot`) at once;
+    /// the values are in `UiState::form`.
+    Form,
+}
+
+/// Conflicted register shown in the conflict popup.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ConflictField {
+    Text,
+    Done,
+}
+
+impl ConflictField {
+    /// The register stored under this field's key.
+    pub fn key(self) -> &'static str {
+        match self {
+            ConflictField::Text => "text",
+            ConflictField::Done => "done",
+        }
+    }
+
+    /// The other register.
+    pub fn other(self) -> Self {
+        match self {
+            ConflictField::Text => ConflictField::Done,
+            ConflictField::Done => ConflictField::Text,
+        }
+    }
+}
+
+/// Row of the edit form.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FormField {
+    #[default]
+    Text,
+    Done,
+    Level,
+    Recur,
+    Color,
+}
+
+impl FormField {
+    /// Every row, top to bottom.
+    pub const ALL: [FormField; 5] = [
+        FormField::Text,
+        FormField::Done,
+        FormField::Level,
+        FormField::Recur,
+        FormField::Color,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            FormField::Text => "Text",
+            FormField::Done => "Done",
+            FormField::Level => "Level",
+            FormField::Recur => "Repeat",
+            FormField::Color => "Color",
+        }
+    }
+
+    /// The row below, or `None` on the last one.
+    pub fn next(self) -> Option<Self> {
+        let index = Self::ALL.iter().position(|field| *field == self)?;
+        Self::ALL.get(index + 1).copied()
+    }
+
+    /// The row above, staying on the first one.
+    pub fn previous(self) -> Self {
+        let index = Self::ALL.iter().position(|field| *field == self);
+        match index {
+            Some(index) if index > 0 => Self::ALL[index - 1],
+            _ => self,
+        }
+    }
+}
+
+/// Values of the fields the edit form shows.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormValues {
+    pub text: String,
+    pub done: bool,
+    pub level: Option<crate::todo::Level>,
+    pub recur: Option<crate::todo::Recurrence>,
+    pub color: Option<crate::todo::TodoColor>,
+}
+
+impl FormValues {
+    /// The values of `todo` as the list shows them: the primary value of a
+    /// conflicted register, and `done` as resolved by the caller.
+    pub fn of(todo: &Todo, done: bool) -> Self {
+        Self {
+            text: todo.primary_text().to_string(),
+            done,
+            level: todo.primary_level(),
+            recur: todo.primary_recur(),
+            color: todo.primary_color(),
+        }
+    }
+}
+
+/// State of the edit form: the values being edited and those it opened with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EditForm {
+    pub field: FormField,
+    pub values: FormValues,
+    /// Fields still equal to these are not written, so saving leaves a
+    /// conflict on a field the user did not touch in place.
+    pub original: FormValues,
+}
+
+impl EditForm {
+    pub fn new(values: FormValues) -> Self {
+        Self {
+            field: FormField::default(),
+            original: values.clone(),
+            values,
+        }
+    }
+
+    /// Fields whose value differs from the one the form opened with.
+    pub fn changed_fields(&self) -> Vec<FormField> {
+        let (values, original) = (&self.values, &self.original);
+        FormField::ALL
+            .into_iter()
+            .filter(|field| match field {
+                FormField::Text => values.text != original.text,
+                FormField::Done => values.done != original.done,
+                FormField::Level => values.level != original.level,
+                FormField::Recur => values.recur != original.recur,
+                FormField::Color => values.color != original.color,
+            })
+            .collect()
+    }
+}
+
+/// What a prompt's input is used for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PromptKind {
+    AddTag,
+    RemoveTag,
+    TagFilter,
+    NewList,
+    AddSubtask,
+    Search,
+    SetCategory,
+    CategoryFilter,
+    CreatorFilter,
+}
+
+impl PromptKind {
+    /// Label shown in front of the input.
+    pub fn label(self) -> &'static str {
+        match self {
+            PromptKind::AddTag => "Add tag",
+            PromptKind::RemoveTag => "Remove tag",
+            PromptKind::TagFilter => "Filter by tags (all of them, space separated)",
+            PromptKind::NewList => "New list",
+            PromptKind::AddSubtask => "Add subtask",
+            PromptKind::Search => "Search",
+            PromptKind::SetCategory => "Category (empty: none)",
+            PromptKind::CategoryFilter => "Filter by category (empty: all)",
+            PromptKind::CreatorFilter => "Filter by creator (hex ID, *: all)",
+        }
+    }
+}
+
+/// Where copied text goes. Tests swap in their own to stay off the system clipboard.
+pub trait Clipboard: Send {
+    /// Replace the clipboard's content with `text`.
+    fn set_text(&mut self, text: &str) -> io::Result<()>;
+}
+
+/// The system clipboard, opened on the first copy and kept open: on X11 the
+/// copied text is only served while it is.
+#[derive(Default)]
+pub struct SystemClipboard(Option<arboard::Clipboard>);
+
+impl Clipboard for SystemClipboard {
+    /// # Errors
+    /// Fails without a clipboard, e.g. over SSH or in a headless session.
+    fn set_text(&mut self, text: &str) -> io::Result<()> {
+        let clipboard = match self.0.take() {
+            Some(clipboard) => clipboard,
+            None => arboard::Clipboard::new().map_err(io::Error::other)?,
+        };
+        self.0
+            .insert(clipboard)
+            .set_text(text)
+            .map_err(io::Error::other)
+    }
 }
 
 /// Main application state.
 pub struct App {
     pub replica_id: ReplicaId,
     pub store: TodoStore,
-    pub socket: UdpSocket,
+    pub transport: Box<dyn Transport>,
     pub network_isolated: bool,
+    /// When isolation was last turned on.
+    pub isolated_since: Option<Instant>,
+    /// Local edits committed since then, which peers have yet to receive.
+    pub isolated_changes: usize,
     pub ui_state: UiState,
     pub counter: u16,
     pub port: u16,
     pub log_buffer: Vec<String>,
     pub anti_entropy: AntiEntropy,
+    pub peers: HashMap<ReplicaId, PeerInfo>,
+    /// Retry schedule for the startup full-state request, until any peer is heard.
+    pub startup_sync: Option<Backoff>,
+    /// When to answer a pending full-state request from a peer.
+    pub full_state_reply_at: Option<Instant>,
+    /// When we last broadcast a delta.
+    pub last_delta_sent: Option<Instant>,
+    /// When we last received a delta from a peer.
+    pub last_delta_received: Option<Instant>,
+    /// Whether the last compaction found the context to be a plain version vector.
+    pub context_compacted: bool,
+    /// When the causal context was last compacted.
+    last_compaction: Instant,
+    /// When the user last requested a resync.
+    last_manual_resync: Option<Instant>,
+    /// Snapshots of todos before local edits, for undo and redo.
+    pub undo_history: UndoHistory,
+    /// Uncapped, timestamped copy of every log message, if `--log-file` is given.
+    log_file: Option<io::BufWriter<std::fs::File>>,
+    /// Whether verbose log messages are shown.
+    pub verbose: bool,
+    /// Name of the list shown and edited.
+    pub current_list: String,
+    /// Quiet period after which coalesced local edits are broadcast.
+    /// `None` broadcasts every delta immediately.
+    pub coalesce_window: Option<Duration>,
+    /// Local deltas joined together and not yet broadcast.
+    pending_delta: Option<TodoStore>,
+    /// When the last delta was added to `pending_delta`.
+    last_local_edit: Option<Instant>,
+    /// Apparent propagation latency of deltas carrying new dots.
+    pub latency: LatencyStats,
+    /// Strategy for resolving text conflicts as soon as they arrive.
+    pub auto_resolve: AutoResolveStrategy,
+    /// Recently auto-resolved conflicts, by todo and sorted values. Replicas
+    /// with different strategies would otherwise overwrite each other forever.
+    auto_resolved: LruCache<(Dot, Vec<String>), ()>,
+    /// Show a todo as done when any concurrent value says so.
+    pub done_wins: bool,
+    /// Mark a todo done once its last subtask is checked off here.
+    pub auto_complete: bool,
+    /// Write `done = true` back when an incoming delta causes a done conflict.
+    pub resolve_done: bool,
+    /// How far each peer that answered our last context request is from us.
+    pub context_reports: HashMap<ReplicaId, ContextDiff>,
+    /// Peer we sent our full state to and are waiting on for theirs.
+    pending_peer_sync: Option<ReplicaId>,
+    /// Replicas whose messages are processed; the rest are dropped unread.
+    pub peer_filter: PeerFilter,
+    /// Messages dropped by `peer_filter` since the last summary, by sender.
+    filtered_messages: BTreeMap<ReplicaId, usize>,
+    /// When dropped messages were last summed up in the log.
+    last_filter_summary: Instant,
+    /// When the priority arrays were last checked by `auto_repair_priority`.
+    last_priority_repair: Instant,
+    /// Changes to each todo made or received since startup.
+    pub history: EditHistory,
+    /// Ids of recently received deltas, see [`delta_id`]. Joining a delta
+    /// twice is harmless, so this only saves the work and the log lines.
+    seen_message_ids: LruCache<u64, ()>,
+    /// How often the store was rebuilt by [`App::compact`], here or by a peer
+    /// whose compacted state we adopted. Only peers on the same generation sync.
+    pub generation: u32,
+    /// Causal context the current generation was compacted from, offered with
+    /// the compacted state to peers still on the previous generation.
+    compaction_base: Option<dson::CausalContext>,
+    /// Clipboard the copy action writes to.
+    pub clipboard: Box<dyn Clipboard>,
+    /// Most characters a todo's text can be typed to.
+    pub max_text_len: usize,
+    /// Encoding of the messages we send and expect from peers.
+    pub wire_format: network::WireFormat,
+    /// With more known peers than this, deltas are sent to this many random
+    /// peers (which pass them on once) instead of broadcast to everyone.
+    pub gossip_fanout: usize,
 }
 
 impl std::fmt::Debug for App {
@@ -130,40 +808,189 @@ impl std::fmt::Debug for App {
             .field("counter", &self.counter)
             .field("port", &self.port)
             .field("log_buffer_len", &self.log_buffer.len())
+            .field("peers", &self.peers.len())
             .finish_non_exhaustive()
     }
 }
 
 impl App {
-    /// Create a new app instance.
-    pub fn new(port: u16) -> io::Result<Self> {
-        let replica_id = ReplicaId::from_timestamp();
-        let socket = network::create_broadcast_socket(port)?;
+    /// Create a new app instance reaching peers over the given IP version.
+    pub fn new(port: u16, ip_version: network::IpVersion) -> io::Result<Self> {
+        let transport = network::UdpTransport::bind(port, ip_version)?;
+        Ok(Self::with_transport(
+            ReplicaId::from_timestamp(),
+            port,
+            Box::new(transport),
+        ))
+    }
 
-        Ok(Self {
+    /// Create an app instance on top of an existing transport.
+    pub fn with_transport(replica_id: ReplicaId, port: u16, transport: Box<dyn Transport>) -> Self {
+        Self {
             replica_id,
             store: TodoStore::default(),
-            socket,
+            transport,
             network_isolated: false,
+            isolated_since: None,
+            isolated_changes: 0,
             ui_state: UiState::default(),
             counter: 0,
             port,
             log_buffer: Vec::new(),
             anti_entropy: AntiEntropy::default(),
-        })
+            peers: HashMap::new(),
+            startup_sync: Some(Backoff::new(
+                FULL_STATE_REQUEST_INITIAL_DELAY,
+                FULL_STATE_REQUEST_MAX_DELAY,
+            )),
+            full_state_reply_at: None,
+            last_delta_sent: None,
+            last_delta_received: None,
+            verbose: false,
+            current_list: DEFAULT_LIST.to_string(),
+            coalesce_window: None,
+            pending_delta: None,
+            last_local_edit: None,
+            latency: LatencyStats::default(),
+            auto_resolve: AutoResolveStrategy::None,
+            auto_resolved: LruCache::new(AUTO_RESOLVED_CONFLICTS),
+            done_wins: false,
+            auto_complete: false,
+            resolve_done: false,
+            context_compacted: false,
+            last_compaction: Instant::now(),
+            last_manual_resync: None,
+            log_file: None,
+            undo_history: UndoHistory::default(),
+            context_reports: HashMap::new(),
+            pending_peer_sync: None,
+            peer_filter: PeerFilter::All,
+            filtered_messages: BTreeMap::new(),
+            last_filter_summary: Instant::now(),
+            last_priority_repair: Instant::now(),
+            seen_message_ids: LruCache::new(SEEN_MESSAGES),
+            history: EditHistory::default(),
+            generation: 0,
+            compaction_base: None,
+            clipboard: Box::new(SystemClipboard::default()),
+            max_text_len: crate::todo::DEFAULT_MAX_TEXT_LEN,
+            wire_format: network::WireFormat::default(),
+            gossip_fanout: DEFAULT_GOSSIP_FANOUT,
+        }
+    }
+
+    /// Put `text` on the clipboard.
+    ///
+    /// # Errors
+    /// Fails without a clipboard, e.g. over SSH or in a headless session.
+    pub fn copy_to_clipboard(&mut self, text: &str) -> io::Result<()> {
+        self.clipboard.set_text(text)
     }
 
     /// Add a log message to the buffer.
     pub fn log(&mut self, msg: String) {
+        if let Some(file) = self.log_file.as_mut()
+            && let Err(e) = writeln!(
+                file,
+                "{} {msg}",
+                format_timestamp(crate::todo::now_millis())
+            )
+        {
+            // Keep running without the file rather than failing every log call
+            self.log_file = None;
+            self.log_buffer.push(format!("Log file disabled: {e}"));
+        }
+
         self.log_buffer.push(msg);
-        if self.log_buffer.len() > MAX_LOG_MESSAGES {
+        while self.log_buffer.len() > MAX_LOG_MESSAGES {
             self.log_buffer.remove(0);
         }
     }
 
+    /// Also append every log message to `path`, with a UTC timestamp.
+    /// Writes are buffered; call [`App::flush_log`] before exiting.
+    ///
+    /// # Errors
+    /// Returns an error if the file cannot be opened for appending.
+    pub fn open_log_file(&mut self, path: &std::path::Path) -> io::Result<()> {
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        self.log_file = Some(io::BufWriter::new(file));
+        Ok(())
+    }
+
+    /// Write buffered log lines to the log file, if any.
+    pub fn flush_log(&mut self) -> io::Result<()> {
+        match self.log_file.as_mut() {
+            Some(file) => file.flush(),
+            None => Ok(()),
+        }
+    }
+
+    /// Add a log message only when verbose logging is enabled.
+    pub fn log_verbose(&mut self, msg: String) {
+        if self.verbose {
+            self.log(msg);
+        }
+    }
+
     /// Toggle network isolation state.
+    /// When reconnecting, logs how long we were isolated and how many local
+    /// edits will now sync, and immediately starts an anti-entropy exchange
+    /// instead of waiting for the next scheduled context broadcast.
     pub fn toggle_isolation(&mut self) -> io::Result<()> {
         self.network_isolated = !self.network_isolated;
+
+        if self.network_isolated {
+            self.isolated_since = Some(Instant::now());
+            self.isolated_changes = 0;
+        } else {
+            let elapsed = self
+                .isolated_since
+                .take()
+                .map_or(Duration::ZERO, |since| since.elapsed());
+            let changes = std::mem::take(&mut self.isolated_changes);
+            self.log(format!(
+                "[Replica {}] Reconnected after {}, {} local change(s) to sync, requesting sync",
+                self.replica_id,
+                format_elapsed(elapsed),
+                changes
+            ));
+            self.broadcast_context()?;
+            self.send_context_to_peers()?;
+            self.anti_entropy.reset();
+        }
+        Ok(())
+    }
+
+    /// Pull missing state from peers right away instead of waiting for anti-entropy:
+    /// broadcast our context and ask every peer for its full state.
+    /// Does nothing while isolated or within [`MANUAL_RESYNC_COOLDOWN`] of the last request.
+    pub fn request_resync(&mut self) -> io::Result<()> {
+        if self.network_isolated {
+            self.log(format!(
+                "[Replica {}] Isolated, resync not sent",
+                self.replica_id
+            ));
+            return Ok(());
+        }
+        if let Some(last) = self.last_manual_resync
+            && last.elapsed() < MANUAL_RESYNC_COOLDOWN
+        {
+            self.log(format!(
+                "[Replica {}] Resync already requested, wait {}s",
+                self.replica_id,
+                MANUAL_RESYNC_COOLDOWN.as_secs()
+            ));
+            return Ok(());
+        }
+
+        self.last_manual_resync = Some(Instant::now());
+        self.broadcast_context()?;
+        self.broadcast_full_state_request()?;
+        self.anti_entropy.reset();
         Ok(())
     }
 
@@ -183,192 +1010,3422 @@ impl App {
         (key, dot)
     }
 
-    /// Get all todos in priority order.
+    /// Get all todos of the current list in priority order.
     pub fn get_todos_ordered(&self) -> Vec<(Dot, Todo)> {
-        let priority = crate::priority::read_priority(&self.store.store);
+        let list = self.list_store();
+        let priority = crate::priority::read_priority(list);
 
         priority
             .into_iter()
-            .filter_map(|dot| {
-                crate::todo::read_todo(&self.store.store, &dot).map(|todo| (dot, todo))
-            })
+            .filter_map(|dot| crate::todo::read_todo(list, &dot).map(|todo| (dot, todo)))
             .collect()
     }
 
-    /// Broadcast a delta to all peers.
-    pub fn broadcast_delta(&mut self, delta: dson::Delta<TodoStore>) -> io::Result<()> {
-        let msg = NetworkMessage::Delta {
-            sender_id: self.replica_id,
-            delta,
-        };
+    /// Todos of the current list that have a map entry but no priority entry,
+    /// sorted by dot key.
+    ///
+    /// They are invisible in the list. An edit concurrent with a delete leaves
+    /// one behind: the observed-remove map keeps the edited registers while
+    /// the priority entry is gone.
+    pub fn find_orphans(&self) -> Vec<Dot> {
+        crate::priority::orphaned_dots(self.list_store())
+    }
+
+    /// Append every orphan of the current list to the bottom of the priority list.
+    /// Returns the number of restored todos.
+    pub fn restore_orphans(&mut self) -> io::Result<usize> {
+        let orphans = self.find_orphans();
+        if orphans.is_empty() {
+            return Ok(0);
+        }
 
-        let data = network::serialize_message(&msg)?;
-        network::broadcast(&self.socket, &data, self.port, self.network_isolated)?;
+        let end = crate::priority::priority_entries(self.list_store()).len();
+        let delta = self.transact_list(|list_tx| {
+            list_tx.in_array("priority", |arr_tx| {
+                for (i, dot) in orphans.iter().enumerate() {
+                    let dot_key = crate::priority::DotKey::new(dot);
+                    arr_tx.insert_register(
+                        end + i,
+                        dson::crdts::mvreg::MvRegValue::String(dot_key.into_inner()),
+                    );
+                }
+            });
+        });
+        self.broadcast_delta(delta)?;
         self.log(format!(
-            "[Replica {}] Broadcast delta: {} bytes (isolated: {})",
+            "[Replica {}] Restored {} orphaned todo(s)",
             self.replica_id,
-            data.len(),
-            self.network_isolated
+            orphans.len()
         ));
-        Ok(())
+        Ok(orphans.len())
     }
 
-    /// Broadcast our causal context for anti-entropy.
-    fn broadcast_context(&mut self) -> io::Result<()> {
-        let msg = NetworkMessage::Context {
-            sender_id: self.replica_id,
-            context: self.store.context.clone(),
-        };
+    /// Remove the priority entries of the current list that repeat a todo
+    /// listed earlier, as concurrent moves of the same todo leave behind.
+    /// Returns the number of removed entries.
+    ///
+    /// Only the repeats are removed, not reinserted, so replicas repairing
+    /// concurrently remove the same entries and agree on the result.
+    pub fn repair_priority(&mut self) -> io::Result<usize> {
+        let duplicates = crate::priority::duplicate_priority_indices(self.list_store());
+        if duplicates.is_empty() {
+            return Ok(0);
+        }
 
-        let data = network::serialize_message(&msg)?;
-        network::broadcast(&self.socket, &data, self.port, self.network_isolated)?;
+        let delta = self.transact_list(|list_tx| {
+            list_tx.in_array("priority", |arr_tx| {
+                for index in duplicates.iter().rev() {
+                    arr_tx.remove(*index);
+                }
+            });
+        });
+        self.broadcast_delta(delta)?;
         self.log(format!(
-            "[Replica {}] Broadcast context: {} bytes",
+            "[Replica {}] Removed {} repeated priority entries",
             self.replica_id,
-            data.len()
+            duplicates.len()
         ));
-        Ok(())
+        Ok(duplicates.len())
     }
 
-    /// Process all incoming messages from the network.
-    /// Returns the number of deltas processed.
-    pub fn process_incoming_deltas(&mut self) -> io::Result<usize> {
-        let mut count = 0;
-
-        while let Some((data, addr)) = network::try_receive(&self.socket, self.network_isolated)? {
-            match network::deserialize_message(&data) {
-                Ok(msg) => {
-                    if msg.sender_id() == self.replica_id {
-                        continue; // Ignore own messages
-                    }
-
-                    self.log(format!(
-                        "[Replica {}] Received {} bytes from {}",
-                        msg.sender_id(),
-                        data.len(),
-                        addr
-                    ));
+    /// Repair the priority array of every list in one transaction: remove the
+    /// entries repeating a todo listed earlier and append orphans at the bottom.
+    /// Returns the number of removed and appended entries.
+    ///
+    /// Run from [`App::tick`] every `PRIORITY_REPAIR_INTERVAL` once deltas have
+    /// paused. Consistent arrays are left alone, so passes are idempotent.
+    /// Replicas repairing concurrently remove the same repeats; an orphan both
+    /// append is a repeat the next pass removes, keeping the same entry on each.
+    pub fn auto_repair_priority(&mut self) -> io::Result<usize> {
+        self.last_priority_repair = Instant::now();
+        let repairs: Vec<(String, usize, Vec<usize>, Vec<Dot>)> = self
+            .store
+            .store
+            .inner()
+            .iter()
+            .filter_map(|(name, list)| {
+                let list = &list.map;
+                let duplicates = crate::priority::duplicate_priority_indices(list);
+                let orphans = crate::priority::orphaned_dots(list);
+                let len = crate::priority::priority_entries(list).len();
+                (!duplicates.is_empty() || !orphans.is_empty())
+                    .then(|| (name.clone(), len, duplicates, orphans))
+            })
+            .collect();
+        if repairs.is_empty() {
+            return Ok(0);
+        }
 
-                    match msg {
-                        NetworkMessage::Delta { sender_id, delta } => {
-                            self.log(format!(
-                                "[Replica {}] Received delta: {} bytes",
-                                sender_id,
-                                data.len()
-                            ));
-                            self.store
-                                .join_or_replace_with(delta.0.store, &delta.0.context);
-                            count += 1;
-                            self.log(format!("[Replica {}] Applied delta", sender_id));
+        let delta = self.with_transaction(|tx| {
+            for (name, len, duplicates, orphans) in &repairs {
+                tx.in_map(name.as_str(), |list_tx| {
+                    list_tx.in_array("priority", |arr_tx| {
+                        for index in duplicates.iter().rev() {
+                            arr_tx.remove(*index);
                         }
-                        NetworkMessage::Context { sender_id, context } => {
-                            self.log(format!(
-                                "[Replica {}] Received context: {} bytes",
-                                sender_id,
-                                data.len()
-                            ));
-
-                            // Compare contexts and decide what to do
-                            let sync_needed =
-                                AntiEntropy::compare_contexts(&self.store.context, &context);
-                            match sync_needed {
-                                SyncNeeded::InSync => {
-                                    self.log(format!("[Replica {}] Already in sync", sender_id));
-                                }
-                                SyncNeeded::RemoteNeedsSync | SyncNeeded::BothNeedSync => {
-                                    // They're missing operations, send our full state
-                                    let msg = NetworkMessage::Delta {
-                                        sender_id: self.replica_id,
-                                        delta: dson::Delta(self.store.clone()),
-                                    };
-                                    let data = network::serialize_message(&msg)?;
-                                    network::broadcast(
-                                        &self.socket,
-                                        &data,
-                                        self.port,
-                                        self.network_isolated,
-                                    )?;
-                                    self.log(format!(
-                                        "[Replica {}] Needs sync, sent full state: {} bytes",
-                                        sender_id,
-                                        data.len()
-                                    ));
-                                }
-                                SyncNeeded::LocalNeedsSync => {
-                                    self.log(format!(
-                                        "[Replica {}] Has updates for us (waiting for delta)",
-                                        sender_id
-                                    ));
-                                    // We're missing operations - they'll send us their state when they see our context
-                                }
-                            }
+                        let end = len - duplicates.len();
+                        for (i, dot) in orphans.iter().enumerate() {
+                            arr_tx.insert_register(
+                                end + i,
+                                dson::crdts::mvreg::MvRegValue::String(
+                                    crate::priority::DotKey::new(dot).into_inner(),
+                                ),
+                            );
                         }
-                    }
-                }
-                Err(e) => {
-                    self.log(format!("Failed to deserialize message: {e}"));
-                }
+                    });
+                });
             }
-        }
+        });
+        self.broadcast_delta(delta)?;
 
-        Ok(count)
+        let removed: usize = repairs.iter().map(|(_, _, d, _)| d.len()).sum();
+        let appended: usize = repairs.iter().map(|(_, _, _, o)| o.len()).sum();
+        self.log(format!(
+            "[Replica {}] Repaired priority order: removed {removed} repeated entries, restored {appended} orphaned todo(s)",
+            self.replica_id
+        ));
+        Ok(removed + appended)
     }
 
-    /// Called every frame to process network events.
-    pub fn tick(&mut self) -> io::Result<()> {
-        // Process incoming messages
-        self.process_incoming_deltas()?;
+    /// Remove the map entries of every orphan of the current list.
+    /// Returns the number of purged todos.
+    pub fn purge_orphans(&mut self) -> io::Result<usize> {
+        let orphans = self.find_orphans();
+        if orphans.is_empty() {
+            return Ok(0);
+        }
 
-        // Check if it's time for anti-entropy broadcast
+        let delta = self.transact_list(|list_tx| {
+            for dot in &orphans {
+                list_tx.remove(crate::priority::DotKey::new(dot).as_str());
+            }
+        });
+        self.broadcast_delta(delta)?;
+        self.log(format!(
+            "[Replica {}] Purged {} orphaned todo(s)",
+            self.replica_id,
+            orphans.len()
+        ));
+        Ok(orphans.len())
+    }
+
+    /// Dots of the todos in the current list shown as done.
+    pub fn done_dots(&self) -> Vec<Dot> {
+        self.get_todos_ordered()
+            .into_iter()
+            .filter(|(_, todo)| self.is_done(todo))
+            .map(|(dot, _)| dot)
+            .collect()
+    }
+
+    /// Delete every done todo of the current list in one transaction, keeping
+    /// the order of the rest. Returns the number of deleted todos.
+    pub fn clear_done(&mut self) -> io::Result<usize> {
+        let priority = crate::priority::priority_entries(self.list_store());
+        let done: HashSet<Dot> = self.done_dots().into_iter().collect();
+        // Every entry of a done todo, repeated ones included
+        let mut indices: Vec<usize> = (0..priority.len())
+            .filter(|index| priority[*index].is_some_and(|dot| done.contains(&dot)))
+            .collect();
+        if indices.is_empty() {
+            return Ok(0);
+        }
+        // Removing an entry shifts every later one, so go from the highest index down
+        indices.sort_unstable_by(|a, b| b.cmp(a));
+        self.remove_todos(&priority, &indices, &[])?;
+        self.log(format!(
+            "[Replica {}] Cleared {} done todo(s)",
+            self.replica_id,
+            done.len()
+        ));
+        Ok(done.len())
+    }
+
+    /// Delete every todo of the current list, orphans included, in one
+    /// transaction. Todos a peer adds concurrently survive, since the removal
+    /// only covers what this replica has seen. Returns the number of deleted todos.
+    pub fn clear_all(&mut self) -> io::Result<usize> {
+        let priority = crate::priority::priority_entries(self.list_store());
+        let orphans = self.find_orphans();
+        let count = crate::priority::read_priority(self.list_store()).len() + orphans.len();
+        if count == 0 {
+            return Ok(0);
+        }
+
+        let indices: Vec<usize> = (0..priority.len()).rev().collect();
+        self.remove_todos(&priority, &indices, &orphans)?;
+        self.log(format!(
+            "[Replica {}] Cleared all {count} todo(s) of {}",
+            self.replica_id, self.current_list
+        ));
+        Ok(count)
+    }
+
+    /// Reverse the priority order of the current list in one transaction that
+    /// removes every entry and inserts them again in reverse. Returns the
+    /// number of todos reordered.
+    ///
+    /// Like any move, this is a remove and an insert per entry: a peer that
+    /// reverses or moves the same todos concurrently adds its own entries.
+    /// Each todo is still shown once, at its first entry, and the repeats
+    /// stay in the array until repaired (`=`, or the periodic repair).
+    pub fn reverse_priority(&mut self) -> io::Result<usize> {
+        let mut priority = crate::priority::read_priority(self.list_store());
+        if priority.len() < 2 {
+            return Ok(0);
+        }
+
+        priority.reverse();
+        self.rewrite_priority(&priority)?;
+        self.log(format!(
+            "[Replica {}] Reversed the order of {} todos",
+            self.replica_id,
+            priority.len()
+        ));
+        Ok(priority.len())
+    }
+
+    /// Sort the priority order of the current list by text, ignoring case,
+    /// like [`reverse_priority`](Self::reverse_priority) does. Returns the
+    /// number of todos reordered.
+    pub fn sort_priority_alphabetically(&mut self) -> io::Result<usize> {
+        let count = self.sort_priority_by_key(|_, todo| todo.primary_text().to_lowercase())?;
+        if count > 0 {
+            self.log(format!("Sorted {count} todos alphabetically"));
+        }
+        Ok(count)
+    }
+
+    /// Move done todos below open ones in the priority order of the current
+    /// list, keeping the order within each group. Returns the number of todos
+    /// reordered.
+    pub fn sort_priority_by_done(&mut self) -> io::Result<usize> {
+        let count = self.sort_priority_by_key(|app, todo| app.is_done(todo))?;
+        if count > 0 {
+            self.log(format!("Sorted {count} todos with done ones last"));
+        }
+        Ok(count)
+    }
+
+    /// Stably sort the priority order by `key`, entries without a todo last,
+    /// and write it back if it changed. Returns the number of entries, or 0 if
+    /// the order was already sorted.
+    fn sort_priority_by_key<K: Ord>(
+        &mut self,
+        key: impl Fn(&App, &Todo) -> K,
+    ) -> io::Result<usize> {
+        let list = self.list_store();
+        let priority = crate::priority::read_priority(list);
+        let mut sorted = priority.clone();
+        sorted.sort_by_cached_key(|dot| {
+            let todo = crate::todo::read_todo(list, dot);
+            (todo.is_none(), todo.map(|todo| key(self, &todo)))
+        });
+        if sorted == priority {
+            return Ok(0);
+        }
+
+        self.rewrite_priority(&sorted)?;
+        Ok(sorted.len())
+    }
+
+    /// Replace the priority order of the current list with `order`, in one
+    /// broadcast transaction that removes every entry and inserts them again.
+    fn rewrite_priority(&mut self, order: &[Dot]) -> io::Result<()> {
+        let len = crate::priority::priority_entries(self.list_store()).len();
+        let delta = self.transact_list(|list_tx| {
+            list_tx.in_array("priority", |arr_tx| {
+                for index in (0..len).rev() {
+                    arr_tx.remove(index);
+                }
+                for (index, dot) in order.iter().enumerate() {
+                    arr_tx.insert_register(
+                        index,
+                        dson::crdts::mvreg::MvRegValue::String(
+                            crate::priority::DotKey::new(dot).into_inner(),
+                        ),
+                    );
+                }
+            });
+        });
+        self.broadcast_delta(delta)
+    }
+
+    /// Remove the priority entries at `indices`, highest first, with the maps
+    /// of their todos and of `orphans`, in one broadcast transaction.
+    fn remove_todos(
+        &mut self,
+        priority: &[Option<Dot>],
+        indices: &[usize],
+        orphans: &[Dot],
+    ) -> io::Result<()> {
+        // Recorded highest first, so undo brings them back lowest first and
+        // each lands at its old index. Only a todo's first entry is recorded,
+        // so undo doesn't bring back its repeats
+        for index in indices {
+            let Some(dot) = priority[*index] else {
+                continue;
+            };
+            if priority.iter().position(|d| *d == Some(dot)) == Some(*index) {
+                self.record_undo(&dot);
+            }
+        }
+        let delta = self.transact_list(|list_tx| {
+            for index in indices {
+                list_tx.in_array("priority", |arr_tx| arr_tx.remove(*index));
+                if let Some(dot) = &priority[*index] {
+                    list_tx.remove(crate::priority::DotKey::new(dot).as_str());
+                }
+            }
+            for dot in orphans {
+                list_tx.remove(crate::priority::DotKey::new(dot).as_str());
+            }
+        });
+        self.broadcast_delta(delta)?;
+        self.clamp_selection();
+        Ok(())
+    }
+
+    /// Snapshot `dot` in the current list before a local edit, so it can be undone.
+    pub fn record_undo(&mut self, dot: &Dot) {
+        let entry = UndoEntry::capture(&self.current_list, self.list_store(), dot);
+        self.undo_history.record(entry);
+    }
+
+    /// Undo the most recent recorded local edit with a compensating transaction.
+    /// Returns whether there was anything to undo.
+    pub fn undo(&mut self) -> io::Result<bool> {
+        let Some(entry) = self.undo_history.pop_undo() else {
+            self.log("Nothing to undo".to_string());
+            return Ok(false);
+        };
+        let inverse = self.restore(&entry)?;
+        self.undo_history.push_redo(inverse);
+        self.log(format!(
+            "[Replica {}] Undid change to {}",
+            self.replica_id,
+            crate::priority::DotKey::new(&entry.dot)
+        ));
+        Ok(true)
+    }
+
+    /// Redo the most recently undone edit. Returns whether there was anything to redo.
+    pub fn redo(&mut self) -> io::Result<bool> {
+        let Some(entry) = self.undo_history.pop_redo() else {
+            self.log("Nothing to redo".to_string());
+            return Ok(false);
+        };
+        let inverse = self.restore(&entry)?;
+        self.undo_history.push_undo(inverse);
+        self.log(format!(
+            "[Replica {}] Redid change to {}",
+            self.replica_id,
+            crate::priority::DotKey::new(&entry.dot)
+        ));
+        Ok(true)
+    }
+
+    /// Write `entry` back and broadcast it, returning a snapshot of what it replaced.
+    fn restore(&mut self, entry: &UndoEntry) -> io::Result<UndoEntry> {
+        let list_store = self
+            .store
+            .store
+            .get(&entry.list)
+            .map(|list| list.map.clone())
+            .unwrap_or_default();
+        let inverse = UndoEntry::capture(&entry.list, &list_store, &entry.dot);
+
+        let mut tx = self.store.transact(self.identifier());
+        tx.in_map(entry.list.as_str(), |list_tx| {
+            entry.restore(list_tx, &list_store)
+        });
+        let delta = tx.commit();
+        self.broadcast_delta(delta)?;
+
+        if entry.list == self.current_list {
+            self.select_dot(&entry.dot);
+            self.clamp_selection();
+        }
+        Ok(inverse)
+    }
+
+    /// The todo maps and priority array of the current list.
+    pub fn list_store(&self) -> &OrMap<String> {
+        self.store
+            .store
+            .get(&self.current_list)
+            .map(|list| &list.map)
+            .unwrap_or(&EMPTY_LIST)
+    }
+
+    /// Names of all lists, sorted, including the current one even if still empty.
+    pub fn list_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.store.store.inner().keys().cloned().collect();
+        if !names.contains(&self.current_list) {
+            names.push(self.current_list.clone());
+        }
+        names.sort();
+        names
+    }
+
+    /// Apply `f` to the whole store (every list) in a new transaction and
+    /// return the delta, for read-modify-writes that must not interleave.
+    ///
+    /// `f` works on a copy of the store that is swapped in on commit. Its reads
+    /// see its own writes but nothing else: the transaction borrows the app, so
+    /// deltas from peers can't be applied until it has committed, and other
+    /// replicas' transactions only show up once their deltas arrive. Writes
+    /// racing with such a transaction merge as concurrent values afterwards.
+    pub fn with_transaction(
+        &mut self,
+        f: impl FnOnce(&mut MapTransaction<'_, String>),
+    ) -> dson::Delta<TodoStore> {
+        let id = self.identifier();
+        let mut tx = self.store.transact(id);
+        f(&mut tx);
+        tx.commit()
+    }
+
+    /// Apply `f` to the current list in a new transaction and return the delta.
+    pub fn transact_list(
+        &mut self,
+        f: impl FnOnce(&mut MapTransaction<'_, String>),
+    ) -> dson::Delta<TodoStore> {
+        let list = self.current_list.clone();
+        self.with_transaction(|tx| tx.in_map(list.as_str(), f))
+    }
+
+    /// Create a list (if new) and switch to it.
+    ///
+    /// A creation timestamp is written into the list map so that the list
+    /// exists on every replica before it holds any todo. Concurrent creation
+    /// of the same name just unions into one list.
+    pub fn create_list(&mut self, name: &str) -> io::Result<()> {
+        self.current_list = name.to_string();
+        self.ui_state.selected_index = 0;
+        if self.store.store.get(&self.current_list).is_some() {
+            return Ok(());
+        }
+
+        let created_at = crate::todo::now_millis();
+        let delta = self.transact_list(|list_tx| {
+            list_tx.write_register(
+                "created_at",
+                dson::crdts::mvreg::MvRegValue::I64(created_at),
+            );
+        });
+        self.broadcast_delta(delta)?;
+        self.log(format!(
+            "[Replica {}] Created list \"{}\"",
+            self.replica_id, name
+        ));
+        Ok(())
+    }
+
+    /// Switch to the list `offset` positions away, wrapping around.
+    pub fn cycle_list(&mut self, offset: isize) {
+        let names = self.list_names();
+        let current = names
+            .iter()
+            .position(|name| *name == self.current_list)
+            .unwrap_or(0);
+        let next = (current as isize + offset).rem_euclid(names.len() as isize) as usize;
+        self.current_list = names[next].clone();
+        self.ui_state.selected_index = 0;
+    }
+
+    /// Get the todos of the current list created by the replica in
+    /// `filter_creator`, in priority order; all of them without a filter.
+    pub fn get_todos_filtered_by_creator(&self) -> Vec<(Dot, Todo)> {
+        let mut todos = self.get_todos_ordered();
+        if let Some(creator) = self.ui_state.filter_creator {
+            let creator = ReplicaId::new(creator).to_string();
+            todos.retain(|(_, todo)| todo.creator.as_ref() == Some(&creator));
+        }
+        todos
+    }
+
+    /// Get the todos shown in the list, in priority order, honoring the
+    /// creator, tag and category filters.
+    pub fn visible_todos(&self) -> Vec<(Dot, Todo)> {
+        let mut todos = self.get_todos_filtered_by_creator();
+        todos.retain(|(_, todo)| !self.is_hidden(todo));
+        let tags = &self.ui_state.filter_tags;
+        if !tags.is_empty() {
+            todos.retain(|(_, todo)| tags.iter().all(|tag| todo.tags.contains(tag)));
+        }
+        if let Some(category) = &self.ui_state.category_filter {
+            // A conflicted todo is in every category it holds
+            todos.retain(|(_, todo)| todo.category.contains(category));
+        }
+        if !self.ui_state.search_shows_all {
+            todos.retain(|(_, todo)| self.matches_search(todo));
+        }
+        // Stable sorts, so ties keep priority order
+        match self.ui_state.sort_mode {
+            SortMode::Priority => {}
+            SortMode::Alphabetical => {
+                todos.sort_by_cached_key(|(_, todo)| todo.primary_text().to_lowercase());
+            }
+            SortMode::DoneLast => todos.sort_by_key(|(_, todo)| self.is_done(todo)),
+            SortMode::ConflictedFirst => todos.sort_by_key(|(_, todo)| !todo.has_conflicts()),
+        }
+        if self.ui_state.group_by_level {
+            // Stable sort: high, medium, low, then todos without a level
+            todos.sort_by_key(|(_, todo)| {
+                let level = todo.primary_level();
+                (level.is_none(), level)
+            });
+        }
+        todos
+    }
+
+    /// Distinct categories of the todos in the current list, sorted.
+    pub fn categories(&self) -> Vec<String> {
+        let categories: BTreeSet<String> = self
+            .get_todos_ordered()
+            .into_iter()
+            .flat_map(|(_, todo)| todo.category)
+            .collect();
+        categories.into_iter().collect()
+    }
+
+    /// Distinct tags of the todos in the current list, sorted.
+    pub fn tags(&self) -> Vec<String> {
+        let tags: BTreeSet<String> = self
+            .get_todos_ordered()
+            .into_iter()
+            .flat_map(|(_, todo)| todo.tags)
+            .collect();
+        tags.into_iter().collect()
+    }
+
+    /// Whether `todo` is left out of the list because done todos are hidden.
+    pub fn is_hidden(&self, todo: &Todo) -> bool {
+        self.ui_state.hide_done && todo.primary_done()
+    }
+
+    /// Nearest priority index below (`down`) or above `index` whose todo is
+    /// not hidden, so moves step over done todos the user can't see.
+    pub fn adjacent_shown_index(&self, index: usize, down: bool) -> Option<usize> {
+        let store = self.list_store();
+        let priority = crate::priority::priority_entries(store);
+        let shown = |i: &usize| {
+            priority[*i].is_some_and(|dot| {
+                crate::todo::read_todo(store, &dot).is_none_or(|todo| !self.is_hidden(&todo))
+            })
+        };
+        if down {
+            (index + 1..priority.len()).find(shown)
+        } else {
+            (0..index.min(priority.len())).rev().find(shown)
+        }
+    }
+
+    /// Whether any text value of `todo` contains the search query, ignoring
+    /// case. Every todo matches an empty query.
+    pub fn matches_search(&self, todo: &Todo) -> bool {
+        let query = &self.ui_state.search_query;
+        query.is_empty()
+            || todo
+                .text
+                .iter()
+                .any(|text| !crate::todo::match_ranges(text, query).is_empty())
+    }
+
+    /// Select the next (or previous) todo matching the search, wrapping
+    /// around the list. Returns false if nothing else matches.
+    pub fn select_next_match(&mut self, forward: bool) -> bool {
+        let todos = self.visible_todos();
+        let len = todos.len();
+        let selected = self.ui_state.selected_index.min(len.saturating_sub(1));
+        let found = (1..len)
+            .map(|step| {
+                if forward {
+                    (selected + step) % len
+                } else {
+                    (selected + len - step) % len
+                }
+            })
+            .find(|&i| self.matches_search(&todos[i].1));
+        if let Some(index) = found {
+            self.ui_state.selected_index = index;
+        }
+        found.is_some()
+    }
+
+    /// Dot of the selected todo, if the list is not empty.
+    pub fn selected_dot(&self) -> Option<Dot> {
+        self.visible_todos()
+            .get(self.ui_state.selected_index)
+            .map(|(dot, _)| *dot)
+    }
+
+    /// Select the todo with the given dot, if it is visible.
+    pub fn select_dot(&mut self, dot: &Dot) {
+        if let Some(index) = self.visible_todos().iter().position(|(d, _)| d == dot) {
+            self.ui_state.selected_index = index;
+        }
+    }
+
+    /// Clamp the selection to the current list length.
+    pub fn clamp_selection(&mut self) {
+        let len = self.visible_todos().len();
+        self.ui_state.selected_index = self.ui_state.selected_index.min(len.saturating_sub(1));
+    }
+
+    /// Clamp the log scroll offset so a window of `visible_lines` stays filled.
+    pub fn clamp_log_scroll(&mut self, visible_lines: usize) {
+        let max_scroll = self.log_buffer.len().saturating_sub(visible_lines);
+        self.ui_state.log_scroll = self.ui_state.log_scroll.min(max_scroll);
+    }
+
+    /// Check whether the causal context is already a version vector.
+    ///
+    /// DSON stores each replica's dots as an interval set, so contiguous
+    /// sequences already collapse into a single `1..=max` range on insert and
+    /// there is nothing left to rewrite. This checks whether every node is in
+    /// that form, i.e. the context is exactly the version vector node → max_seq
+    /// shown in the context panel. Returns the result, which is also kept in
+    /// `context_compacted`.
+    pub fn check_context_compaction(&mut self) -> bool {
+        let context = &self.store.context;
+        let compacted = context
+            .intervals()
+            .all(|(id, _)| context.is_compact_for_node(id.node().value()));
+        self.context_compacted = compacted;
+        self.last_compaction = Instant::now();
+        compacted
+    }
+
+    /// Rebuild the store with a minimal causal context and offer it to peers.
+    ///
+    /// The rebuilt store holds the same todos, but written as one transaction
+    /// of fresh dots, so the context shrinks to a single interval. Its dots
+    /// mean nothing to stores from before, so it starts a new generation:
+    /// peers adopt it only if it covers everything they have, and replicas
+    /// of different generations don't sync. A peer still on the previous
+    /// generation, e.g. because it missed the broadcast, gets the compacted
+    /// state again whenever its context or a delta arrives. Only a peer with
+    /// edits the compacted state lacks refuses it and stays behind; it has to
+    /// be restarted (losing those edits) to join the new generation.
+    ///
+    /// Refused while isolated or while conflicts exist, since one transaction
+    /// can't keep them. The store is only replaced once the compacted state
+    /// has been broadcast.
+    pub fn compact(&mut self) -> io::Result<()> {
+        if self.network_isolated {
+            self.log(format!(
+                "[Replica {}] Not compacted: peers can't be told while isolated",
+                self.replica_id
+            ));
+            return Ok(());
+        }
+        self.flush_pending_delta()?;
+        let conflicts = crate::compaction::count_conflicts(&self.store.store);
+        if conflicts > 0 {
+            self.log(format!(
+                "[Replica {}] Not compacted: resolve {} conflict(s) first",
+                self.replica_id, conflicts
+            ));
+            return Ok(());
+        }
+
+        let base = self.store.context.clone();
+        let before = network::serialize_message(
+            &NetworkMessage::Context {
+                schema_version: network::CURRENT_VERSION,
+                sender_id: self.replica_id,
+                context: base.clone(),
+                generation: self.generation,
+            },
+            self.wire_format,
+        )?
+        .len();
+        let store = crate::compaction::rebuild(&self.store.store, self.identifier());
+        let generation = self.generation + 1;
+
+        let msg = NetworkMessage::Compacted {
+            schema_version: network::CURRENT_VERSION,
+            sender_id: self.replica_id,
+            generation,
+            base: base.clone(),
+            state: dson::Delta(store.clone()),
+        };
+        let data = network::serialize_message(&msg, self.wire_format)?;
+        if !self.broadcast_within_mtu("compacted state", &data)? {
+            return Ok(());
+        }
+
+        let after = network::serialize_message(
+            &NetworkMessage::Context {
+                schema_version: network::CURRENT_VERSION,
+                sender_id: self.replica_id,
+                context: store.context.clone(),
+                generation,
+            },
+            self.wire_format,
+        )?
+        .len();
+        self.store = store;
+        self.generation = generation;
+        self.compaction_base = Some(base);
+        // Reports compare contexts of the old generation
+        self.context_reports.clear();
+        self.log(format!(
+            "[Replica {}] Compacted: context broadcast {} → {} bytes (generation {})",
+            self.replica_id, before, after, self.generation
+        ));
+        Ok(())
+    }
+
+    /// Send our compacted state to the peer at `addr` if it is still on the
+    /// generation we compacted from, having missed or been unable to adopt
+    /// the broadcast.
+    fn offer_compacted_state(&mut self, generation: u32, addr: SocketAddr) -> io::Result<()> {
+        let Some(base) = self
+            .compaction_base
+            .clone()
+            .filter(|_| generation + 1 == self.generation)
+        else {
+            return Ok(());
+        };
+        let msg = NetworkMessage::Compacted {
+            schema_version: network::CURRENT_VERSION,
+            sender_id: self.replica_id,
+            generation: self.generation,
+            base,
+            state: dson::Delta(self.store.clone()),
+        };
+        let data = network::serialize_message(&msg, self.wire_format)?;
+        if self.send_to_within_mtu("compacted state", &data, addr)? {
+            self.log_verbose(format!(
+                "[Replica {}] Offered compacted state (generation {}) to a peer on generation {}",
+                self.replica_id, self.generation, generation
+            ));
+        }
+        Ok(())
+    }
+
+    /// Log how many messages the peer filter dropped since the last summary,
+    /// if any, and start counting again.
+    fn log_filter_summary(&mut self) {
+        self.last_filter_summary = Instant::now();
+        if self.filtered_messages.is_empty() {
+            return;
+        }
+        let filtered = std::mem::take(&mut self.filtered_messages);
+        let total: usize = filtered.values().sum();
+        let senders: Vec<_> = filtered
+            .iter()
+            .map(|(id, count)| format!("{id}: {count}"))
+            .collect();
+        self.log(format!(
+            "[Replica {}] Dropped {} messages from filtered peers ({})",
+            self.replica_id,
+            total,
+            senders.join(", ")
+        ));
+    }
+
+    /// Broadcast a delta to all peers, recording it in the edit history as ours.
+    ///
+    /// With a coalescing window set, the delta is joined into a pending batch
+    /// instead, which [`App::tick`] broadcasts once edits pause.
+    pub fn broadcast_delta(&mut self, delta: dson::Delta<TodoStore>) -> io::Result<()> {
+        if self.network_isolated {
+            self.isolated_changes += 1;
+        }
+        self.history
+            .record(&delta.0, self.replica_id, crate::todo::now_millis());
+        if self.coalesce_window.is_none() {
+            return self.send_delta(delta);
+        }
+
+        // Deltas are causal dot stores themselves, so joining them yields a
+        // single delta covering all batched edits.
+        let dson::Delta(delta) = delta;
+        match self.pending_delta.as_mut() {
+            Some(pending) => pending.join_or_replace_with(delta.store, &delta.context),
+            None => self.pending_delta = Some(delta),
+        }
+        self.last_local_edit = Some(Instant::now());
+        Ok(())
+    }
+
+    /// Broadcast the pending batch of local edits, if any.
+    pub fn flush_pending_delta(&mut self) -> io::Result<()> {
+        match self.pending_delta.take() {
+            Some(pending) => self.send_delta(dson::Delta(pending)),
+            None => Ok(()),
+        }
+    }
+
+    /// Serialize and broadcast a single delta.
+    fn send_delta(&mut self, delta: dson::Delta<TodoStore>) -> io::Result<()> {
+        let msg = NetworkMessage::Delta {
+            schema_version: network::CURRENT_VERSION,
+            sender_id: self.replica_id,
+            sent_at: Some(crate::todo::now_millis()),
+            delta,
+            generation: self.generation,
+        };
+
+        let data = network::serialize_message(&msg, self.wire_format)?;
+        let gossiping = self.gossiping();
+        if !self.gossip_within_mtu("delta", &data, None)? {
+            return Ok(());
+        }
+        self.last_delta_sent = Some(Instant::now());
+        let how = if gossiping {
+            format!("Gossiped delta to {} peers", self.gossip_fanout)
+        } else {
+            "Broadcast delta".to_string()
+        };
+        self.log(format!(
+            "[Replica {}] {how}: {} bytes (isolated: {})",
+            self.replica_id,
+            data.len(),
+            self.network_isolated
+        ));
+        Ok(())
+    }
+
+    /// Whether deltas go to [`Self::gossip_fanout`] random peers instead of
+    /// being broadcast.
+    fn gossiping(&self) -> bool {
+        self.peers.len() > self.gossip_fanout
+    }
+
+    /// Send `data` to [`Self::gossip_fanout`] random known peers other than
+    /// `except`, or broadcast it while we know no more peers than that.
+    /// Returns `false` if it exceeds the MTU.
+    fn gossip_within_mtu(
+        &mut self,
+        what: &str,
+        data: &[u8],
+        except: Option<ReplicaId>,
+    ) -> io::Result<bool> {
+        use rand::{seq::SliceRandom, thread_rng};
+
+        if !self.gossiping() {
+            return self.broadcast_within_mtu(what, data);
+        }
+        let candidates: Vec<SocketAddr> = self
+            .peers
+            .iter()
+            .filter(|(id, _)| Some(**id) != except)
+            .map(|(_, peer)| peer.addr)
+            .collect();
+        let targets: Vec<SocketAddr> = candidates
+            .choose_multiple(&mut thread_rng(), self.gossip_fanout)
+            .copied()
+            .collect();
+        for addr in targets {
+            if !self.send_to_within_mtu(what, data, addr)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Broadcast our causal context for anti-entropy.
+    pub fn broadcast_context(&mut self) -> io::Result<()> {
+        // Peers must not see dots in our context before the matching delta.
+        self.flush_pending_delta()?;
+
+        let msg = NetworkMessage::Context {
+            schema_version: network::CURRENT_VERSION,
+            sender_id: self.replica_id,
+            context: self.store.context.clone(),
+            generation: self.generation,
+        };
+
+        let data = network::serialize_message(&msg, self.wire_format)?;
+        self.transport.broadcast(&data, self.network_isolated)?;
+        self.log(format!(
+            "[Replica {}] Broadcast context: {} bytes",
+            self.replica_id,
+            data.len()
+        ));
+        Ok(())
+    }
+
+    /// Broadcast our entire store as a delta.
+    /// Returns the number of bytes sent, or `None` if the state exceeds the MTU.
+    fn broadcast_full_state(&mut self) -> io::Result<Option<usize>> {
+        let Some(data) = self.preview_full_state()? else {
+            return Ok(None);
+        };
+        // Already checked against the MTU
+        self.transport.broadcast(&data, self.network_isolated)?;
+        Ok(Some(data.len()))
+    }
+
+    /// Serialize our entire store as the message a full-state sync sends,
+    /// without sending it, and log its size. Returns `None` if it exceeds the
+    /// transport's MTU: without fragmentation it can't be sent, so nobody
+    /// should try.
+    pub fn preview_full_state(&mut self) -> io::Result<Option<Vec<u8>>> {
+        let msg = NetworkMessage::Delta {
+            schema_version: network::CURRENT_VERSION,
+            sender_id: self.replica_id,
+            sent_at: None,
+            delta: dson::Delta(self.store.clone()),
+            generation: self.generation,
+        };
+        let data = network::serialize_message(&msg, self.wire_format)?;
+        if let Some(mtu) = self.transport.mtu()
+            && data.len() > mtu
+        {
+            self.log(format!(
+                "[Replica {}] Full state is {} bytes, {} over the {mtu}-byte MTU; not sent until fragmentation is supported",
+                self.replica_id,
+                data.len(),
+                data.len() - mtu,
+            ));
+            return Ok(None);
+        }
+        let limit = self
+            .transport
+            .mtu()
+            .map_or(String::new(), |mtu| format!(", within the {mtu}-byte MTU"));
+        self.log(format!(
+            "[Replica {}] Full state is {} bytes{limit}",
+            self.replica_id,
+            data.len(),
+        ));
+        Ok(Some(data))
+    }
+
+    /// Broadcast `data`, logging instead of failing when it exceeds the MTU so a
+    /// large list stops syncing visibly rather than taking the app down.
+    /// Returns whether the data was handed to the transport.
+    fn broadcast_within_mtu(&mut self, what: &str, data: &[u8]) -> io::Result<bool> {
+        match self.transport.broadcast(data, self.network_isolated) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == io::ErrorKind::InvalidInput => {
+                self.log(format!(
+                    "[Replica {}] Not sent: {what} {e}",
+                    self.replica_id
+                ));
+                Ok(false)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Like [`Self::broadcast_within_mtu`], but to a single peer.
+    fn send_to_within_mtu(
+        &mut self,
+        what: &str,
+        data: &[u8],
+        addr: SocketAddr,
+    ) -> io::Result<bool> {
+        match self.transport.send_to(data, addr, self.network_isolated) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == io::ErrorKind::InvalidInput => {
+                self.log(format!(
+                    "[Replica {}] Not sent: {what} {e}",
+                    self.replica_id
+                ));
+                Ok(false)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Ask all peers for their full state.
+    fn broadcast_full_state_request(&mut self) -> io::Result<()> {
+        let msg = NetworkMessage::FullStateRequest {
+            schema_version: network::CURRENT_VERSION,
+            sender_id: self.replica_id,
+        };
+        let data = network::serialize_message(&msg, self.wire_format)?;
+        self.transport.broadcast(&data, self.network_isolated)?;
+        self.log(format!(
+            "[Replica {}] Requested full state from peers",
+            self.replica_id
+        ));
+        Ok(())
+    }
+
+    /// Ask every known peer for its causal context and open the report popup,
+    /// which fills in as the replies arrive.
+    pub fn request_context_report(&mut self) -> io::Result<()> {
+        if self.network_isolated {
+            self.log("Isolated, can't ask peers for their context".to_string());
+            return Ok(());
+        }
+        if self.peers.is_empty() {
+            self.log("No known peers to compare contexts with".to_string());
+            return Ok(());
+        }
+
+        let msg = NetworkMessage::ContextRequest {
+            schema_version: network::CURRENT_VERSION,
+            sender_id: self.replica_id,
+        };
+        let data = network::serialize_message(&msg, self.wire_format)?;
+        for peer in self.peers.values() {
+            self.transport
+                .send_to(&data, peer.addr, self.network_isolated)?;
+        }
+        self.context_reports.clear();
+        self.ui_state.show_context_report = true;
+        self.ui_state.peer_index = 0;
+        self.log(format!(
+            "[Replica {}] Asked {} known peers for their context",
+            self.replica_id,
+            self.peers.len()
+        ));
+        Ok(())
+    }
+
+    /// Known peers, ordered by id.
+    pub fn sorted_peers(&self) -> Vec<ReplicaId> {
+        let mut peers: Vec<ReplicaId> = self.peers.keys().copied().collect();
+        peers.sort();
+        peers
+    }
+
+    /// Replicas a todo can be assigned to: we and every known peer, ordered by id.
+    pub fn assignee_candidates(&self) -> Vec<ReplicaId> {
+        let mut candidates = self.sorted_peers();
+        candidates.push(self.replica_id);
+        candidates.sort();
+        candidates
+    }
+
+    /// Exchange full states with one peer right away: send ours straight to it
+    /// and ask it for its own, which completes the sync when it arrives.
+    pub fn sync_with_peer(&mut self, peer_id: ReplicaId) -> io::Result<()> {
+        if self.network_isolated {
+            self.log(format!(
+                "[Replica {}] Isolated, sync with peer {peer_id} not sent",
+                self.replica_id
+            ));
+            return Ok(());
+        }
+        let Some(addr) = self.peers.get(&peer_id).map(|peer| peer.addr) else {
+            self.log(format!("Unknown peer {peer_id}"));
+            return Ok(());
+        };
+
+        let Some(state) = self.preview_full_state()? else {
+            return Ok(());
+        };
+        // Already checked against the MTU
+        self.transport
+            .send_to(&state, addr, self.network_isolated)?;
+        let request = network::serialize_message(
+            &NetworkMessage::FullStateRequest {
+                schema_version: network::CURRENT_VERSION,
+                sender_id: self.replica_id,
+            },
+            self.wire_format,
+        )?;
+        self.transport
+            .send_to(&request, addr, self.network_isolated)?;
+
+        self.pending_peer_sync = Some(peer_id);
+        self.log(format!("Manual sync initiated with peer {peer_id}"));
+        Ok(())
+    }
+
+    /// Send our causal context directly to every known peer.
+    fn send_context_to_peers(&mut self) -> io::Result<()> {
+        if self.peers.is_empty() {
+            return Ok(());
+        }
+
+        let msg = NetworkMessage::Context {
+            schema_version: network::CURRENT_VERSION,
+            sender_id: self.replica_id,
+            context: self.store.context.clone(),
+            generation: self.generation,
+        };
+        let data = network::serialize_message(&msg, self.wire_format)?;
+
+        for peer in self.peers.values() {
+            self.transport
+                .send_to(&data, peer.addr, self.network_isolated)?;
+        }
+        self.log(format!(
+            "[Replica {}] Sent context to {} known peers",
+            self.replica_id,
+            self.peers.len()
+        ));
+        Ok(())
+    }
+
+    /// Process up to [`MAX_MESSAGES_PER_TICK`] incoming messages from the network.
+    /// Returns the number of deltas processed.
+    pub fn process_incoming_deltas(&mut self) -> io::Result<usize> {
+        self.process_incoming(MAX_MESSAGES_PER_TICK)
+    }
+
+    /// Process all incoming messages, however many are queued.
+    /// Returns the number of deltas processed.
+    pub fn drain_incoming_deltas(&mut self) -> io::Result<usize> {
+        self.process_incoming(usize::MAX)
+    }
+
+    /// Process up to `limit` incoming messages. Returns the number of deltas processed.
+    fn process_incoming(&mut self, limit: usize) -> io::Result<usize> {
+        let mut count = 0;
+        let mut received = 0;
+
+        while received < limit
+            && let Some((data, addr)) = self.transport.try_receive(self.network_isolated)?
+        {
+            received += 1;
+            match network::deserialize_message(&data, self.wire_format) {
+                Ok(msg) => {
+                    if msg.sender_id() == self.replica_id {
+                        continue; // Ignore own messages
+                    }
+                    // Counted, not logged, so a chatty blocked peer can't flood the log
+                    if !self.peer_filter.permits(msg.sender_id()) {
+                        *self.filtered_messages.entry(msg.sender_id()).or_default() += 1;
+                        continue;
+                    }
+
+                    self.peers.insert(
+                        msg.sender_id(),
+                        PeerInfo {
+                            addr,
+                            last_seen: Instant::now(),
+                        },
+                    );
+                    // Hearing from anybody means we are part of the network now
+                    self.startup_sync = None;
+
+                    // Other messages, like an unchanged context, legitimately repeat
+                    if let NetworkMessage::Delta {
+                        sent_at,
+                        delta,
+                        generation,
+                        ..
+                    } = &msg
+                        && self
+                            .seen_message_ids
+                            .put(delta_id(delta, *sent_at, *generation), ())
+                            .is_some()
+                    {
+                        self.log_verbose(format!(
+                            "[Replica {}] Skipped duplicate delta",
+                            msg.sender_id()
+                        ));
+                        continue;
+                    }
+
+                    self.log(format!(
+                        "[Replica {}] Received {} bytes from {}",
+                        msg.sender_id(),
+                        data.len(),
+                        addr
+                    ));
+
+                    match msg {
+                        NetworkMessage::Delta {
+                            sender_id,
+                            sent_at,
+                            delta,
+                            generation,
+                            ..
+                        } => {
+                            self.log(format!(
+                                "[Replica {}] Received delta: {} bytes",
+                                sender_id,
+                                data.len()
+                            ));
+
+                            // Dots of another generation mean something else; an
+                            // empty store has nothing to lose and joins theirs
+                            if generation != self.generation {
+                                if !self.store.context.is_empty() {
+                                    self.log(format!(
+                                        "[Replica {}] Ignored delta of generation {} (ours: {})",
+                                        sender_id, generation, self.generation
+                                    ));
+                                    self.offer_compacted_state(generation, addr)?;
+                                    continue;
+                                }
+                                self.generation = generation;
+                            }
+
+                            // Another peer's state already covers ours, so a pending
+                            // full-state reply would tell the network nothing new
+                            if self.full_state_reply_at.is_some()
+                                && matches!(
+                                    AntiEntropy::compare_contexts(
+                                        &self.store.context,
+                                        &delta.0.context
+                                    ),
+                                    SyncNeeded::InSync | SyncNeeded::LocalNeedsSync
+                                )
+                            {
+                                self.full_state_reply_at = None;
+                                self.log(format!(
+                                    "[Replica {}] Answered full-state request first, suppressing ours",
+                                    sender_id
+                                ));
+                            }
+
+                            // Only first arrivals of dots measure propagation; re-sent
+                            // deltas would count the time until the retry
+                            if let Some(sent_at) = sent_at
+                                && delta
+                                    .0
+                                    .context
+                                    .dots()
+                                    .any(|dot| !self.store.context.dot_in(dot))
+                            {
+                                let latency = crate::todo::now_millis() - sent_at;
+                                self.latency.record(latency);
+                                self.log(format!(
+                                    "[Replica {}] Apparent propagation latency: {} ms",
+                                    sender_id, latency
+                                ));
+                            }
+
+                            // New dots always change the state. Without any, the delta can
+                            // still remove entries we hold, so compare the store itself; that
+                            // costs about as much as the join and only happens for re-sends
+                            let has_new_dots = delta
+                                .0
+                                .context
+                                .dots()
+                                .any(|dot| !self.store.context.dot_in(dot));
+                            let before = (!has_new_dots).then(|| self.store.clone());
+                            let context_before = self.store.context.clone();
+                            let invalid_before = self.invalid_priority_counts();
+                            // Each replica passes a gossiped edit on once, when it
+                            // first sees it, so it spreads to peers not picked yet
+                            let forward = (self.gossiping() && has_new_dots && sent_at.is_some())
+                                .then(|| delta.clone());
+                            let overlong = crate::todo::count_overlong_texts(&delta.0.store);
+                            // A forwarded edit keeps its author's dots; full states
+                            // and re-sends are no news
+                            if let Some(sent_at) = sent_at
+                                && has_new_dots
+                            {
+                                let author =
+                                    delta.0.context.dots().next().map_or(sender_id, |dot| {
+                                        ReplicaId::new(dot.actor().node().value())
+                                    });
+                                self.history.record(&delta.0, author, sent_at);
+                            }
+                            self.store
+                                .join_or_replace_with(delta.0.store, &delta.0.context);
+                            if overlong > 0 {
+                                self.log(format!(
+                                    "[Replica {}] WARNING {overlong} todo text(s) longer than {} characters, shown truncated",
+                                    sender_id,
+                                    crate::todo::MAX_DISPLAY_TEXT_LEN
+                                ));
+                            }
+                            let changed = before.is_none_or(|before| before != self.store);
+                            for problem in self.check_invariants(&context_before, &invalid_before) {
+                                self.log(format!(
+                                    "[Replica {}] WARNING suspicious delta: {problem}",
+                                    sender_id
+                                ));
+                            }
+
+                            self.last_delta_received = Some(Instant::now());
+                            count += 1;
+                            // Full states carry no send time; one from the peer we
+                            // are syncing with is its answer
+                            if sent_at.is_none() && self.pending_peer_sync == Some(sender_id) {
+                                self.pending_peer_sync = None;
+                                self.log("Manual sync complete".to_string());
+                            }
+                            let outcome = if changed {
+                                "state changed"
+                            } else {
+                                "no change"
+                            };
+                            self.log(format!("[Replica {}] Applied delta ({outcome})", sender_id));
+
+                            if let Some(delta) = forward {
+                                let msg = NetworkMessage::Delta {
+                                    schema_version: network::CURRENT_VERSION,
+                                    sender_id: self.replica_id,
+                                    sent_at,
+                                    delta,
+                                    generation: self.generation,
+                                };
+                                let data = network::serialize_message(&msg, self.wire_format)?;
+                                if self.gossip_within_mtu(
+                                    "forwarded delta",
+                                    &data,
+                                    Some(sender_id),
+                                )? {
+                                    self.log_verbose(format!(
+                                        "[Replica {}] Forwarded delta from {} to {} peers",
+                                        self.replica_id, sender_id, self.gossip_fanout
+                                    ));
+                                }
+                            }
+                        }
+                        NetworkMessage::Context {
+                            sender_id,
+                            context,
+                            generation,
+                            ..
+                        } => {
+                            self.log(format!(
+                                "[Replica {}] Received context: {} bytes",
+                                sender_id,
+                                data.len()
+                            ));
+                            if generation != self.generation && !self.store.context.is_empty() {
+                                self.log_verbose(format!(
+                                    "[Replica {}] Ignored context of generation {} (ours: {})",
+                                    sender_id, generation, self.generation
+                                ));
+                                self.offer_compacted_state(generation, addr)?;
+                                continue;
+                            }
+
+                            // Compare contexts and decide what to do. When both
+                            // sides are missing operations, the side missing fewer
+                            // sends its state; the other only asks for it
+                            let diff =
+                                crate::anti_entropy::diff_contexts(&self.store.context, &context);
+                            let sync_needed = match diff.sync_needed() {
+                                SyncNeeded::BothNeedSync if diff.should_pull_first() => {
+                                    SyncNeeded::LocalNeedsSync
+                                }
+                                sync_needed => sync_needed,
+                            };
+                            match sync_needed {
+                                SyncNeeded::InSync => {
+                                    self.log(format!("[Replica {}] Already in sync", sender_id));
+                                }
+                                SyncNeeded::RemoteNeedsSync | SyncNeeded::BothNeedSync => {
+                                    // They're missing operations, send our full state
+                                    if let Some(size) = self.broadcast_full_state()? {
+                                        self.log(format!(
+                                            "[Replica {}] Needs sync (missing {}, we miss {}), sent full state: {} bytes",
+                                            sender_id, diff.remote_missing, diff.local_missing, size
+                                        ));
+                                    }
+                                }
+                                SyncNeeded::LocalNeedsSync => {
+                                    // We're missing operations - reply with our context so they
+                                    // send us their state right away
+                                    let msg = NetworkMessage::Context {
+                                        schema_version: network::CURRENT_VERSION,
+                                        sender_id: self.replica_id,
+                                        context: self.store.context.clone(),
+                                        generation: self.generation,
+                                    };
+                                    let data = network::serialize_message(&msg, self.wire_format)?;
+                                    self.transport.send_to(&data, addr, self.network_isolated)?;
+                                    self.log(format!(
+                                        "[Replica {}] Has {} updates for us (missing {}), requested sync",
+                                        sender_id, diff.local_missing, diff.remote_missing
+                                    ));
+                                }
+                            }
+                        }
+                        NetworkMessage::ContextRequest { sender_id, .. } => {
+                            let msg = NetworkMessage::ContextReply {
+                                schema_version: network::CURRENT_VERSION,
+                                sender_id: self.replica_id,
+                                context: self.store.context.clone(),
+                            };
+                            let data = network::serialize_message(&msg, self.wire_format)?;
+                            if self.send_to_within_mtu("context reply", &data, addr)? {
+                                self.log(format!(
+                                    "[Replica {}] Requested our context for a report",
+                                    sender_id
+                                ));
+                            }
+                        }
+                        NetworkMessage::ContextReply {
+                            sender_id, context, ..
+                        } => {
+                            let diff =
+                                crate::anti_entropy::diff_contexts(&self.store.context, &context);
+                            self.context_reports.insert(sender_id, diff);
+                            self.log(format!(
+                                "[Replica {}] Context report: we miss {}, they miss {}",
+                                sender_id, diff.local_missing, diff.remote_missing
+                            ));
+                        }
+                        NetworkMessage::Compacted {
+                            sender_id,
+                            generation,
+                            base,
+                            state,
+                            ..
+                        } => {
+                            if generation <= self.generation {
+                                self.log_verbose(format!(
+                                    "[Replica {}] Compacted state of generation {} is not newer",
+                                    sender_id, generation
+                                ));
+                            } else if matches!(
+                                AntiEntropy::compare_contexts(&self.store.context, &base),
+                                SyncNeeded::InSync | SyncNeeded::LocalNeedsSync
+                            ) {
+                                self.store = state.0;
+                                self.generation = generation;
+                                self.compaction_base = Some(base);
+                                self.context_reports.clear();
+                                count += 1;
+                                self.log(format!(
+                                    "[Replica {}] Adopted compacted state (generation {})",
+                                    sender_id, generation
+                                ));
+                            } else {
+                                let unseen =
+                                    crate::anti_entropy::diff_contexts(&base, &self.store.context)
+                                        .local_missing;
+                                self.log(format!(
+                                    "[Replica {}] WARNING not adopting compacted state: it lacks {} of our operations; staying on generation {}",
+                                    sender_id, unseen, self.generation
+                                ));
+                            }
+                        }
+                        NetworkMessage::FullStateRequest { sender_id, .. } => {
+                            if self.store.context.is_empty() {
+                                self.log(format!(
+                                    "[Replica {}] Requested full state, but ours is empty",
+                                    sender_id
+                                ));
+                            } else if self.full_state_reply_at.is_none() {
+                                use rand::Rng;
+                                let jitter = rand::thread_rng()
+                                    .gen_range(Duration::ZERO..=FULL_STATE_REPLY_MAX_JITTER);
+                                self.full_state_reply_at = Some(Instant::now() + jitter);
+                                self.log(format!(
+                                    "[Replica {}] Requested full state, answering in {} ms",
+                                    sender_id,
+                                    jitter.as_millis()
+                                ));
+                            }
+                        }
+                    }
+                }
+                Err(e) if e.kind() == io::ErrorKind::Unsupported => {
+                    self.log(format!("[{addr}] {e}"));
+                }
+                Err(e) => {
+                    self.log(format!("Failed to deserialize message: {e}"));
+                }
+            }
+        }
+
+        if received == limit {
+            self.log_verbose(format!(
+                "[Replica {}] Read {limit} messages, leaving the rest for the next tick",
+                self.replica_id
+            ));
+        }
+
+        if count > 0 {
+            self.auto_resolve_conflicts()?;
+            self.resolve_done_conflicts()?;
+        }
+
+        Ok(count)
+    }
+
+    /// Number of priority entries that are not dot keys, per list that has any.
+    fn invalid_priority_counts(&self) -> HashMap<String, usize> {
+        self.store
+            .store
+            .inner()
+            .iter()
+            .map(|(list_name, list)| {
+                (
+                    list_name.clone(),
+                    crate::priority::invalid_priority_entries(&list.map),
+                )
+            })
+            .filter(|(_, invalid)| *invalid > 0)
+            .collect()
+    }
+
+    /// Check what a join must never break, given the context and the invalid
+    /// priority entries before it.
+    ///
+    /// Joining only adds dots, and every priority entry is written as a dot
+    /// key. A violation means a buggy or malicious peer, so the problems are
+    /// reported for the log instead of failing.
+    fn check_invariants(
+        &self,
+        context_before: &dson::CausalContext,
+        invalid_before: &HashMap<String, usize>,
+    ) -> Vec<String> {
+        use std::cmp::Ordering;
+
+        let mut problems = Vec::new();
+        if !matches!(
+            self.store.context.partial_cmp(context_before),
+            Some(Ordering::Greater | Ordering::Equal)
+        ) {
+            problems.push("causal context did not grow".to_string());
+        }
+        for (list_name, invalid) in self.invalid_priority_counts() {
+            let added =
+                invalid.saturating_sub(invalid_before.get(&list_name).copied().unwrap_or(0));
+            if added > 0 {
+                problems.push(format!(
+                    "list '{list_name}' got {added} priority entries that are not todo keys"
+                ));
+            }
+        }
+        problems
+    }
+
+    /// Resolve text conflicts in every list with the `auto_resolve` strategy.
+    ///
+    /// The winner is written like any other edit, so it causally dominates the
+    /// conflicting values. Two replicas resolving concurrently write the same
+    /// text, which leaves identical values that are not resolved again.
+    fn auto_resolve_conflicts(&mut self) -> io::Result<()> {
+        if self.auto_resolve == AutoResolveStrategy::None {
+            return Ok(());
+        }
+
+        let mut resolutions = Vec::new();
+        for (list_name, list) in self.store.store.inner().iter() {
+            let list = &list.map;
+            for dot in crate::priority::read_priority(list) {
+                let Some(todo) = crate::todo::read_todo(list, &dot) else {
+                    continue;
+                };
+                let mut values = todo.text.clone();
+                values.sort();
+                values.dedup();
+                if values.len() < 2 || self.auto_resolved.put((dot, values.clone()), ()).is_some() {
+                    continue;
+                }
+                if let Some(winner) = self.auto_resolve.pick(&values) {
+                    resolutions.push((list_name.clone(), dot, winner.to_string()));
+                }
+            }
+        }
+        if resolutions.is_empty() {
+            return Ok(());
+        }
+
+        let mut tx = self.store.transact(self.identifier());
+        for (list_name, dot, text) in &resolutions {
+            let dot_key = crate::priority::DotKey::new(dot);
+            tx.in_map(list_name.as_str(), |list_tx| {
+                list_tx.in_map(dot_key.as_str(), |todo_tx| {
+                    todo_tx.write_register(
+                        "text",
+                        dson::crdts::mvreg::MvRegValue::String(text.clone()),
+                    );
+                    crate::todo::touch(todo_tx);
+                });
+            });
+        }
+        let delta = tx.commit();
+        self.log(format!(
+            "[Replica {}] Auto-resolved {} conflict(s) ({:?})",
+            self.replica_id,
+            resolutions.len(),
+            self.auto_resolve
+        ));
+        self.broadcast_delta(delta)
+    }
+
+    /// Collapse done conflicts in every list by writing `true`, if `resolve_done` is set.
+    ///
+    /// Every resolving replica writes the same value, so concurrent resolutions
+    /// agree and need no bookkeeping like `auto_resolved`.
+    fn resolve_done_conflicts(&mut self) -> io::Result<()> {
+        if !self.resolve_done {
+            return Ok(());
+        }
+
+        let mut conflicted = Vec::new();
+        for (list_name, list) in self.store.store.inner().iter() {
+            let list = &list.map;
+            for dot in crate::priority::read_priority(list) {
+                if crate::todo::read_todo(list, &dot).is_some_and(|todo| todo.done.len() > 1) {
+                    conflicted.push((list_name.clone(), dot));
+                }
+            }
+        }
+        if conflicted.is_empty() {
+            return Ok(());
+        }
+
+        let mut tx = self.store.transact(self.identifier());
+        for (list_name, dot) in &conflicted {
+            let dot_key = crate::priority::DotKey::new(dot);
+            tx.in_map(list_name.as_str(), |list_tx| {
+                list_tx.in_map(dot_key.as_str(), |todo_tx| {
+                    todo_tx.write_register("done", dson::crdts::mvreg::MvRegValue::Bool(true));
+                    crate::todo::touch(todo_tx);
+                });
+            });
+        }
+        let delta = tx.commit();
+        self.log(format!(
+            "[Replica {}] Resolved {} done conflict(s) as done",
+            self.replica_id,
+            conflicted.len()
+        ));
+        self.broadcast_delta(delta)
+    }
+
+    /// Done and total todos in the current list, ignoring filters.
+    ///
+    /// A todo counts as done when [`Self::is_done`] shows it so, keeping the
+    /// summary in line with the checkboxes and the list title.
+    pub fn progress(&self) -> (usize, usize) {
+        let todos = self.get_todos_ordered();
+        let done = todos.iter().filter(|(_, todo)| self.is_done(todo)).count();
+        (done, todos.len())
+    }
+
+    /// Whether `todo` is shown as done under the current `done_wins` policy.
+    pub fn is_done(&self, todo: &Todo) -> bool {
+        todo.resolved_done(self.done_wins)
+    }
+
+    /// With [`Self::auto_complete`] on, mark the todo done in a transaction of
+    /// its own if all its subtasks are done. Only local checks call this, so
+    /// replicas receiving the last subtask don't all write the same edit.
+    pub fn complete_if_subtasks_done(&mut self, dot: &Dot) -> io::Result<()> {
+        let Some(todo) = crate::todo::read_todo(self.list_store(), dot) else {
+            return Ok(());
+        };
+        let (done, total) = todo.subtask_progress();
+        if !self.auto_complete || total == 0 || done < total || todo.done == [true] {
+            return Ok(());
+        }
+
+        self.record_undo(dot);
+        let dot_key = crate::priority::DotKey::new(dot);
+        let delta = self.transact_list(|list_tx| {
+            list_tx.in_map(dot_key.as_str(), |todo_tx| {
+                todo_tx.write_register("done", dson::crdts::mvreg::MvRegValue::Bool(true));
+                crate::todo::touch(todo_tx);
+            });
+        });
+        self.broadcast_delta(delta)?;
+        self.log(format!(
+            "All subtasks of '{}' done, marked it done",
+            todo.primary_text()
+        ));
+        Ok(())
+    }
+
+    /// Called every frame to process network events.
+    pub fn tick(&mut self) -> io::Result<()> {
+        // Process incoming messages
+        self.process_incoming_deltas()?;
+
+        // Broadcast coalesced local edits once input has paused
+        if let (Some(window), Some(last_edit)) = (self.coalesce_window, self.last_local_edit)
+            && last_edit.elapsed() >= window
+        {
+            self.last_local_edit = None;
+            self.flush_pending_delta()?;
+        }
+
+        // Keep asking for the full state until some peer shows up
+        if !self.network_isolated
+            && let Some(backoff) = self.startup_sync.as_mut()
+            && backoff.should_attempt()
+        {
+            self.broadcast_full_state_request()?;
+        }
+
+        // Answer a full-state request once our random delay has passed
+        if let Some(reply_at) = self.full_state_reply_at
+            && Instant::now() >= reply_at
+        {
+            self.full_state_reply_at = None;
+            if let Some(size) = self.broadcast_full_state()? {
+                self.log(format!(
+                    "[Replica {}] Answered full-state request: {} bytes",
+                    self.replica_id, size
+                ));
+            }
+        }
+
+        if self.last_compaction.elapsed() >= CONTEXT_COMPACTION_INTERVAL {
+            self.check_context_compaction();
+        }
+
+        if self.last_filter_summary.elapsed() >= FILTER_SUMMARY_INTERVAL {
+            self.log_filter_summary();
+        }
+
+        if self.last_priority_repair.elapsed() >= PRIORITY_REPAIR_INTERVAL
+            && self
+                .last_delta_received
+                .is_none_or(|at| at.elapsed() >= PRIORITY_REPAIR_QUIET)
+        {
+            self.auto_repair_priority()?;
+        }
+
+        // Check if it's time for anti-entropy broadcast
         if self.anti_entropy.should_broadcast() && !self.network_isolated {
-            self.broadcast_context()?;
+            if self
+                .anti_entropy
+                .is_redundant(self.last_delta_sent, self.last_delta_received)
+            {
+                self.log_verbose(format!(
+                    "[Replica {}] Deltas flowing, skipped context broadcast",
+                    self.replica_id
+                ));
+            } else {
+                self.broadcast_context()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Write the selection, log scroll and filters to `path` as MessagePack.
+    pub fn save_ui_state(&self, path: &std::path::Path) -> io::Result<()> {
+        let data = rmp_serde::to_vec_named(&SavedUiState::from(&self.ui_state))
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        std::fs::write(path, data)
+    }
+
+    /// Restore what [`App::save_ui_state`] wrote to `path`; a missing file
+    /// leaves the defaults.
+    ///
+    /// The selection is clamped to the list if it has todos. An empty list is
+    /// the usual case at startup, before peers sent theirs, so the index is
+    /// then kept for the todos to come.
+    pub fn restore_ui_state(&mut self, path: &std::path::Path) -> io::Result<()> {
+        let data = match std::fs::read(path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        let saved: SavedUiState = rmp_serde::from_slice(&data)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        self.ui_state.selected_index = saved.selected_index;
+        self.ui_state.log_scroll = saved.log_scroll;
+        self.ui_state.filter_tags = saved.filter_tags.into_iter().collect();
+        self.ui_state.category_filter = saved.category_filter;
+        self.ui_state.filter_creator = saved.filter_creator;
+        self.ui_state.search_query = saved.search_query;
+        self.ui_state.hide_done = saved.hide_done;
+        if !self.visible_todos().is_empty() {
+            self.clamp_selection();
+        }
+        Ok(())
+    }
+
+    /// Write the entire CRDT store to `path` as a MessagePack snapshot.
+    pub fn export_binary_snapshot(&self, path: &std::path::Path) -> io::Result<()> {
+        crate::snapshot::write_snapshot(&self.store, path)
+    }
+
+    /// Add 3 random Star Wars themed todos to the bottom of the list.
+    pub fn add_random_todos(&mut self) -> io::Result<()> {
+        use rand::{seq::SliceRandom, thread_rng};
+
+        // Pick 3 unique random todos
+        let mut rng = thread_rng();
+        let selected: Vec<_> = SAMPLE_TODOS.choose_multiple(&mut rng, 3).collect();
+
+        // DEMO BEGIN #3: Array operations with self-contained state
+        // Generate unique keys for all 3 todos
+        let dot_keys: Vec<_> = selected.iter().map(|_| self.next_dot_key().0).collect();
+
+        // Create all 3 todos in a single transaction, inside the current list
+        let replica_id = self.replica_id;
+        let created_at = crate::todo::now_millis();
+        let mut tx = self.store.transact(self.identifier());
+
+        tx.in_map(self.current_list.as_str(), |list_tx| {
+            for (text, dot_key) in selected.iter().zip(dot_keys.iter()) {
+                // Create the todo with text and done fields
+                list_tx.in_map(dot_key.as_str(), |todo_tx| {
+                    todo_tx.write_register(
+                        "text",
+                        dson::crdts::mvreg::MvRegValue::String(text.to_string()),
+                    );
+                    todo_tx.write_register("done", dson::crdts::mvreg::MvRegValue::Bool(false));
+                    crate::todo::write_creation_metadata(todo_tx, replica_id, created_at);
+                });
+
+                // Add to priority array - arr_tx.len() grows with each insert!
+                list_tx.in_array("priority", |arr_tx| {
+                    arr_tx.insert_register(
+                        arr_tx.len(),
+                        dson::crdts::mvreg::MvRegValue::String(dot_key.as_str().to_string()),
+                    );
+                });
+            }
+        });
+
+        let delta = tx.commit();
+        self.broadcast_delta(delta)?;
+        // DEMO END #3
+
+        self.log(format!(
+            "[Replica {}] Added 3 random Star Wars todos",
+            self.replica_id
+        ));
+        Ok(())
+    }
+}
+
+/// Format Unix milliseconds as an ISO 8601 UTC timestamp with milliseconds.
+fn format_timestamp(millis: i64) -> String {
+    dson::chrono::DateTime::from_timestamp_millis(millis)
+        .map(|dt| dt.format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string())
+        .unwrap_or_else(|| format!("{millis} ms"))
+}
+
+/// Format a duration as "mm:ss", or "h:mm:ss" from an hour on.
+pub fn format_elapsed(elapsed: Duration) -> String {
+    let secs = elapsed.as_secs();
+    match secs / 3600 {
+        0 => format!("{:02}:{:02}", secs / 60, secs % 60),
+        hours => format!("{hours}:{:02}:{:02}", secs / 60 % 60, secs % 60),
+    }
+}
+
+/// Identify a received delta by its payload rather than its bytes: a gossiped
+/// delta is re-sent under each forwarder's id, and dson's maps serialize in a
+/// per-process order. The sorted dots of its context are unique to the edit
+/// that made it.
+fn delta_id(
+    delta: &dson::Delta<CausalDotStore<OrMap<String>>>,
+    sent_at: Option<i64>,
+    generation: u32,
+) -> u64 {
+    let mut dots: Vec<Dot> = delta.0.context.dots().collect();
+    dots.sort_unstable();
+    let mut hasher = fxhash::FxHasher64::default();
+    (sent_at, generation, dots).hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::InMemoryTransport;
+    use crate::test_util::{apps_on, connected_apps};
+    use crate::todo::Level;
+
+    #[test]
+    fn test_replicas_sync_over_json() {
+        let mut apps = connected_apps(2);
+        for app in apps.iter_mut() {
+            app.wire_format = network::WireFormat::Json;
+        }
+        apps[0].add_random_todos().expect("add todos");
+        apps[1].drain_incoming_deltas().expect("receive");
+        assert_eq!(apps[1].get_todos_ordered(), apps[0].get_todos_ordered());
+        assert!(!apps[1].get_todos_ordered().is_empty());
+
+        // A MessagePack replica can't read the JSON deltas
+        apps[1].wire_format = network::WireFormat::MessagePack;
+        crate::input::execute_action(&mut apps[0], crate::input::Action::ToggleDone)
+            .expect("toggle");
+        assert_eq!(apps[1].drain_incoming_deltas().expect("receive"), 0);
+    }
+
+    #[test]
+    fn test_delta_forwarded_by_two_peers_is_applied_once() {
+        // The author's deltas are captured on a hub of their own
+        let mut source = InMemoryTransport::hub(2);
+        let listener = source.pop().expect("listener");
+        let mut author = App::with_transport(
+            ReplicaId::new(9),
+            network::DEFAULT_PORT,
+            Box::new(source.pop().expect("author")),
+        );
+        author.add_random_todos().expect("add todos");
+        let deltas: Vec<_> = std::iter::from_fn(|| listener.try_receive(false).expect("receive"))
+            .filter_map(|(data, _)| {
+                match network::deserialize_message(&data, network::WireFormat::default()) {
+                    Ok(NetworkMessage::Delta {
+                        sent_at,
+                        delta,
+                        generation,
+                        ..
+                    }) => Some((sent_at, delta, generation)),
+                    _ => None,
+                }
+            })
+            .collect();
+        assert!(!deltas.is_empty());
+
+        // Two peers forward every delta under their own id
+        let mut transports = InMemoryTransport::hub(3);
+        let forwarders = transports.split_off(1);
+        let receiver_addr = transports[0].addr();
+        let mut receiver = apps_on(transports).remove(0);
+        for (i, forwarder) in forwarders.iter().enumerate() {
+            for (sent_at, delta, generation) in &deltas {
+                let msg = NetworkMessage::Delta {
+                    schema_version: network::CURRENT_VERSION,
+                    sender_id: ReplicaId::new(i as u8 + 2),
+                    sent_at: *sent_at,
+                    delta: delta.clone(),
+                    generation: *generation,
+                };
+                let data = network::serialize_message(&msg, network::WireFormat::default())
+                    .expect("serialize");
+                forwarder
+                    .send_to(&data, receiver_addr, false)
+                    .expect("forward");
+            }
+            let applied = receiver.process_incoming_deltas().expect("receive");
+            assert_eq!(applied, if i == 0 { deltas.len() } else { 0 });
+        }
+        assert_eq!(receiver.get_todos_ordered(), author.get_todos_ordered());
+    }
+
+    #[test]
+    fn test_replayed_deltas_are_skipped() {
+        let mut transports = InMemoryTransport::hub(3);
+        // The third transport records what is sent and replays it
+        let listener = transports.pop().expect("listener");
+        let receiver = transports[1].addr();
+        let mut apps = apps_on(transports);
+
+        apps[0].add_random_todos().expect("add todos");
+        let sent: Vec<_> =
+            std::iter::from_fn(|| listener.try_receive(false).expect("receive")).collect();
+        assert!(apps[1].process_incoming_deltas().expect("receive") > 0);
+
+        for (data, _) in &sent {
+            listener.send_to(data, receiver, false).expect("replay");
+        }
+        let logged = apps[1].log_buffer.len();
+        assert_eq!(apps[1].process_incoming_deltas().expect("receive"), 0);
+        assert_eq!(apps[1].log_buffer.len(), logged);
+        assert_eq!(apps[1].get_todos_ordered().len(), 3);
+    }
+
+    #[test]
+    fn test_ui_state_survives_restart_with_selection_clamped() {
+        let path = std::env::temp_dir().join(format!(
+            "dson-p2p-todo-{}-ui-state-test.bin",
+            std::process::id()
+        ));
+        let mut apps = connected_apps(3);
+        apps[0].ui_state.selected_index = 5;
+        apps[0].ui_state.log_scroll = 4;
+        apps[0].ui_state.category_filter = Some("home".to_string());
+        apps[0].ui_state.filter_creator = Some(0x3a);
+        apps[0].ui_state.search_query = "milk".to_string();
+        apps[0].save_ui_state(&path).expect("save");
+
+        // Nothing synced yet: the selection waits for the todos
+        apps[1].restore_ui_state(&path).expect("restore");
+        assert_eq!(
+            SavedUiState::from(&apps[1].ui_state),
+            SavedUiState::from(&apps[0].ui_state)
+        );
+
+        apps[0].ui_state = UiState::default();
+        apps[0].ui_state.selected_index = 5;
+        apps[0].save_ui_state(&path).expect("save");
+        apps[2].add_random_todos().expect("add todos");
+        apps[2].restore_ui_state(&path).expect("restore");
+        assert_eq!(apps[2].ui_state.selected_index, 2);
+
+        std::fs::remove_file(&path).expect("remove");
+        apps[2]
+            .restore_ui_state(&path)
+            .expect("missing file is fine");
+    }
+
+    #[test]
+    fn test_clamp_selection_and_log_scroll() {
+        let mut app = connected_apps(1).remove(0);
+        app.add_random_todos().expect("add todos");
+        let len = app.get_todos_ordered().len();
+
+        app.ui_state.selected_index = len + 10;
+        app.clamp_selection();
+        assert_eq!(app.ui_state.selected_index, len - 1);
+
+        let logs = app.log_buffer.len();
+        app.ui_state.log_scroll = logs + 10;
+        app.clamp_log_scroll(2);
+        assert_eq!(app.ui_state.log_scroll, logs.saturating_sub(2));
+    }
+
+    #[test]
+    fn test_coalesced_edits_are_broadcast_as_one_delta() {
+        let mut apps = connected_apps(2);
+        apps[0].coalesce_window = Some(Duration::from_secs(60));
+        apps[0].startup_sync = None;
+
+        apps[0].add_random_todos().expect("first batch");
+        apps[0].add_random_todos().expect("second batch");
+        apps[0].tick().expect("tick within window");
+        assert!(
+            apps[1]
+                .transport
+                .try_receive(false)
+                .expect("receive")
+                .is_none()
+        );
+
+        apps[0].flush_pending_delta().expect("flush");
+        let (data, _) = apps[1]
+            .transport
+            .try_receive(false)
+            .expect("receive")
+            .expect("coalesced delta should be broadcast");
+        let msg = network::deserialize_message(&data, network::WireFormat::default())
+            .expect("deserialize");
+        let NetworkMessage::Delta { delta, .. } = msg else {
+            panic!("expected a delta, got {msg:?}");
+        };
+        assert!(
+            apps[1]
+                .transport
+                .try_receive(false)
+                .expect("receive")
+                .is_none()
+        );
+
+        apps[1]
+            .store
+            .join_or_replace_with(delta.0.store, &delta.0.context);
+        assert_eq!(apps[1].get_todos_ordered().len(), 6);
+        assert_eq!(apps[1].store, apps[0].store);
+    }
+
+    #[test]
+    fn test_context_broadcast_flushes_pending_delta() {
+        let mut apps = connected_apps(2);
+        apps[0].coalesce_window = Some(Duration::from_secs(60));
+
+        apps[0].add_random_todos().expect("add todos");
+        apps[0].broadcast_context().expect("broadcast context");
+
+        let (data, _) = apps[1]
+            .transport
+            .try_receive(false)
+            .expect("receive")
+            .expect("pending delta should be flushed first");
+        let msg = network::deserialize_message(&data, network::WireFormat::default())
+            .expect("deserialize");
+        assert!(matches!(msg, NetworkMessage::Delta { .. }));
+    }
+
+    #[test]
+    fn test_compacted_context_drives_compare_contexts() {
+        let mut apps = connected_apps(2);
+        apps[0].add_random_todos().expect("add todos");
+        apps[1].tick().expect("receive delta");
+
+        assert!(apps[0].check_context_compaction());
+        assert!(apps[1].check_context_compaction());
+        assert_eq!(
+            AntiEntropy::compare_contexts(&apps[0].store.context, &apps[1].store.context),
+            SyncNeeded::InSync
+        );
+
+        // Edits after compaction are still detected in both directions.
+        apps[0].add_random_todos().expect("add more todos");
+        assert!(apps[0].check_context_compaction());
+        assert_eq!(
+            AntiEntropy::compare_contexts(&apps[0].store.context, &apps[1].store.context),
+            SyncNeeded::RemoteNeedsSync
+        );
+        assert_eq!(
+            AntiEntropy::compare_contexts(&apps[1].store.context, &apps[0].store.context),
+            SyncNeeded::LocalNeedsSync
+        );
+    }
+
+    #[test]
+    fn test_context_with_gap_is_not_compacted() {
+        let mut apps = connected_apps(2);
+        let mut deltas = Vec::new();
+        for _ in 0..2 {
+            apps[0].add_random_todos().expect("add todos");
+            let (data, _) = apps[1]
+                .transport
+                .try_receive(false)
+                .expect("receive")
+                .expect("delta");
+            deltas.push(
+                network::deserialize_message(&data, network::WireFormat::default())
+                    .expect("deserialize"),
+            );
+        }
+
+        // Only the second delta arrives, leaving a gap in replica 1's dots.
+        let Some(NetworkMessage::Delta { delta, .. }) = deltas.pop() else {
+            panic!("expected a delta");
+        };
+        apps[1]
+            .store
+            .join_or_replace_with(delta.0.store, &delta.0.context);
+
+        assert!(!apps[1].check_context_compaction());
+        assert!(!apps[1].context_compacted);
+    }
+
+    #[test]
+    fn test_lists_keep_todos_separate() {
+        let mut app = connected_apps(1).remove(0);
+        app.add_random_todos().expect("add to default list");
+
+        app.create_list("Work").expect("create list");
+        assert_eq!(app.current_list, "Work");
+        assert!(app.get_todos_ordered().is_empty());
+        app.add_random_todos().expect("add to work list");
+        app.add_random_todos().expect("add more to work list");
+        assert_eq!(app.get_todos_ordered().len(), 6);
+
+        assert_eq!(app.list_names(), vec!["Todos", "Work"]);
+        app.cycle_list(1);
+        assert_eq!(app.current_list, DEFAULT_LIST);
+        assert_eq!(app.get_todos_ordered().len(), 3);
+        app.cycle_list(-1);
+        assert_eq!(app.current_list, "Work");
+    }
+
+    #[test]
+    fn test_concurrent_list_creation_unions() {
+        let mut apps = connected_apps(2);
+        for app in &mut apps {
+            app.startup_sync = None;
+        }
+        apps[0].create_list("Home").expect("create home");
+        apps[0].add_random_todos().expect("add on 0");
+        apps[1].create_list("Home").expect("create home");
+        apps[1].create_list("Work").expect("create work");
+        apps[1].create_list("Home").expect("switch back");
+        apps[1].add_random_todos().expect("add on 1");
+
+        for _ in 0..2 {
+            for app in &mut apps {
+                app.tick().expect("tick");
+            }
+        }
+
+        assert_eq!(apps[0].store, apps[1].store);
+        assert_eq!(apps[0].list_names(), vec!["Home", "Work"]);
+        assert_eq!(apps[0].get_todos_ordered().len(), 6);
+    }
+
+    #[test]
+    fn test_reconnect_broadcasts_context_immediately() {
+        let mut apps = connected_apps(2);
+
+        apps[0].toggle_isolation().expect("isolate");
+        assert!(
+            apps[1]
+                .transport
+                .try_receive(false)
+                .expect("receive")
+                .is_none()
+        );
+
+        apps[0].toggle_isolation().expect("reconnect");
+
+        let (data, _) = apps[1]
+            .transport
+            .try_receive(false)
+            .expect("receive")
+            .expect("context should be broadcast on reconnect");
+        let msg = network::deserialize_message(&data, network::WireFormat::default())
+            .expect("deserialize");
+        assert!(matches!(msg, NetworkMessage::Context { .. }));
+        assert!(apps[0].log_buffer.iter().any(|l| l.contains("Reconnected")));
+    }
+
+    #[test]
+    fn test_reconnect_logs_isolation_time_and_pending_changes() {
+        let mut app = connected_apps(1).remove(0);
+        app.add_random_todos().expect("add todos");
+
+        app.toggle_isolation().expect("isolate");
+        assert!(app.isolated_since.is_some());
+        app.add_random_todos().expect("add todos");
+        app.add_random_todos().expect("add todos");
+        assert_eq!(app.isolated_changes, 2);
+
+        app.toggle_isolation().expect("reconnect");
+        assert!(
+            app.log_buffer.iter().any(|line| {
+                line.contains("Reconnected after 00:00, 2 local change(s) to sync")
+            })
+        );
+        assert_eq!((app.isolated_since, app.isolated_changes), (None, 0));
+    }
+
+    #[test]
+    fn test_format_elapsed() {
+        assert_eq!(format_elapsed(Duration::from_secs(42)), "00:42");
+        assert_eq!(format_elapsed(Duration::from_secs(61 * 60 + 5)), "1:01:05");
+    }
+
+    #[test]
+    fn test_reconnect_converges_partitioned_edits() {
+        let mut apps = connected_apps(2);
+
+        apps[0].toggle_isolation().expect("isolate");
+        apps[0].add_random_todos().expect("add todos");
+        apps[1].process_incoming_deltas().expect("process");
+        assert!(apps[1].get_todos_ordered().is_empty());
+
+        apps[0].toggle_isolation().expect("reconnect");
+        apps[1].process_incoming_deltas().expect("process context");
+        apps[0]
+            .process_incoming_deltas()
+            .expect("process context reply");
+        apps[1]
+            .process_incoming_deltas()
+            .expect("process full state");
+
+        assert_eq!(apps[1].get_todos_ordered().len(), 3);
+        assert_eq!(apps[0].store, apps[1].store);
+    }
+
+    /// Drain an app's inbox, returning whether a context message was among it.
+    fn received_context(app: &App) -> bool {
+        let mut found = false;
+        while let Some((data, _)) = app.transport.try_receive(false).expect("receive") {
+            let msg = network::deserialize_message(&data, network::WireFormat::default())
+                .expect("deserialize");
+            found |= matches!(msg, NetworkMessage::Context { .. });
         }
+        found
+    }
 
-        Ok(())
+    #[test]
+    fn test_context_broadcast_skipped_while_deltas_flow() {
+        let mut apps = connected_apps(2);
+        apps[0].anti_entropy = AntiEntropy::new(Duration::from_millis(100));
+        apps[0].startup_sync = None;
+        apps[0].verbose = true;
+
+        std::thread::sleep(Duration::from_millis(110));
+        apps[0].last_delta_sent = Some(Instant::now());
+        apps[0].last_delta_received = Some(Instant::now());
+        apps[0].tick().expect("tick");
+
+        assert!(!received_context(&apps[1]));
+        assert!(apps[0].log_buffer.iter().any(|l| l.contains("skipped")));
     }
 
-    /// Add 3 random Star Wars themed todos to the bottom of the list.
-    pub fn add_random_todos(&mut self) -> io::Result<()> {
-        use rand::{seq::SliceRandom, thread_rng};
+    #[test]
+    fn test_sending_replica_still_broadcasts_when_receiving_nothing() {
+        let mut apps = connected_apps(2);
+        apps[0].anti_entropy = AntiEntropy::new(Duration::from_millis(100));
+        apps[0].startup_sync = None;
 
-        // Pick 3 unique random todos
-        let mut rng = thread_rng();
-        let selected: Vec<_> = SAMPLE_TODOS.choose_multiple(&mut rng, 3).collect();
+        // Nothing received for a full interval, while we keep sending
+        std::thread::sleep(Duration::from_millis(110));
+        apps[0].add_random_todos().expect("send delta");
+        apps[0].tick().expect("tick");
 
-        // DEMO BEGIN #3: Array operations with self-contained state
-        // Generate unique keys for all 3 todos
-        let dot_keys: Vec<_> = selected.iter().map(|_| self.next_dot_key().0).collect();
+        assert!(received_context(&apps[1]));
+    }
 
-        // Create all 3 todos in a single transaction
-        let mut tx = self.store.transact(self.identifier());
+    #[test]
+    fn test_late_joiner_requests_full_state() {
+        let mut apps = connected_apps(2);
+        apps[0].startup_sync = None;
+        apps[0].add_random_todos().expect("add todos");
+        // Replica 2 starts after the delta was broadcast
+        while apps[1]
+            .transport
+            .try_receive(false)
+            .expect("drain")
+            .is_some()
+        {}
+
+        // Fresh replica asks on its first tick
+        apps[1].tick().expect("tick");
+        apps[0].process_incoming_deltas().expect("process request");
+        let reply_at = apps[0].full_state_reply_at.expect("reply scheduled");
+        assert!(reply_at <= Instant::now() + FULL_STATE_REPLY_MAX_JITTER);
+
+        apps[0].full_state_reply_at = Some(Instant::now());
+        apps[0].tick().expect("tick");
+        apps[1].process_incoming_deltas().expect("process reply");
+
+        assert_eq!(apps[0].store, apps[1].store);
+        assert!(apps[1].startup_sync.is_none());
+    }
+
+    #[test]
+    fn test_full_state_reply_suppressed_when_peer_answers_first() {
+        let mut apps = connected_apps(3);
+        apps[0].add_random_todos().expect("add todos");
+        apps[1].process_incoming_deltas().expect("sync");
+        apps[2].process_incoming_deltas().expect("sync");
+
+        let request = network::serialize_message(
+            &NetworkMessage::FullStateRequest {
+                schema_version: network::CURRENT_VERSION,
+                sender_id: ReplicaId::new(9),
+            },
+            network::WireFormat::default(),
+        )
+        .expect("serialize");
+        apps[0].transport.broadcast(&request, false).expect("send");
+        apps[1].process_incoming_deltas().expect("process request");
+        apps[2].process_incoming_deltas().expect("process request");
+        assert!(apps[1].full_state_reply_at.is_some());
+        assert!(apps[2].full_state_reply_at.is_some());
+
+        // Replica 2 answers first, replica 3 sees the answer and stays quiet
+        apps[1].full_state_reply_at = Some(Instant::now());
+        apps[1].tick().expect("tick");
+        apps[2].process_incoming_deltas().expect("process answer");
+
+        assert!(apps[2].full_state_reply_at.is_none());
+    }
+
+    #[test]
+    fn test_new_dots_record_propagation_latency() {
+        let mut apps = connected_apps(2);
+        apps[0].add_random_todos().expect("add todos");
+        apps[1].tick().expect("receive delta");
+
+        assert_eq!(apps[1].latency.len(), 1);
+        assert!(apps[1].latency.average().expect("one sample") >= 0);
+
+        // Full-state replies carry old dots and are not measured
+        apps[0].add_random_todos().expect("add more todos");
+        while apps[1]
+            .transport
+            .try_receive(false)
+            .expect("drain")
+            .is_some()
+        {}
+        apps[0].broadcast_full_state().expect("full state");
+        apps[1].tick().expect("receive full state");
+        assert_eq!(apps[1].latency.len(), 1);
+    }
+
+    #[test]
+    fn test_full_state_over_mtu_is_previewed_and_not_sent() {
+        let mut apps = apps_on(
+            InMemoryTransport::hub(2)
+                .into_iter()
+                .map(InMemoryTransport::limit_to_mtu),
+        );
+        apps[0].add_random_todos().expect("add todos");
+        apps[1].drain_incoming_deltas().expect("receive");
+        let data = apps[0]
+            .preview_full_state()
+            .expect("preview")
+            .expect("a few todos fit");
+        assert!(data.len() <= network::MTU);
+        assert!(
+            apps[0]
+                .log_buffer
+                .last()
+                .is_some_and(|line| line.ends_with(&format!(
+                    "Full state is {} bytes, within the 1472-byte MTU",
+                    data.len()
+                )))
+        );
+
+        apps[0].network_isolated = true;
+        while apps[0].preview_full_state().expect("preview").is_some() {
+            apps[0].add_random_todos().expect("add more todos");
+        }
+        apps[0].network_isolated = false;
+        assert_eq!(apps[0].broadcast_full_state().expect("broadcast"), None);
+        assert!(
+            apps[1]
+                .transport
+                .try_receive(false)
+                .expect("poll")
+                .is_none()
+        );
+        assert!(
+            apps[0]
+                .log_buffer
+                .iter()
+                .any(|line| line.contains("over the 1472-byte MTU; not sent"))
+        );
+    }
+
+    #[test]
+    fn test_latency_average_is_rolling() {
+        let mut stats = LatencyStats::default();
+        assert_eq!(stats.average(), None);
+
+        for _ in 0..LATENCY_SAMPLES {
+            stats.record(100);
+        }
+        for _ in 0..LATENCY_SAMPLES / 2 {
+            stats.record(0);
+        }
+        assert_eq!(stats.len(), LATENCY_SAMPLES);
+        assert_eq!(stats.average(), Some(50));
+    }
+
+    #[test]
+    fn test_group_by_level_keeps_priority_order_within_groups() {
+        let mut app = connected_apps(1).remove(0);
+        app.add_random_todos().expect("add todos");
+        app.add_random_todos().expect("add more todos");
+        let order: Vec<Dot> = app.visible_todos().iter().map(|(dot, _)| *dot).collect();
+
+        // Mark the 2nd and 5th todo as high, the 4th as low
+        for (index, level) in [(1, Level::High), (4, Level::High), (3, Level::Low)] {
+            let dot_key = crate::priority::DotKey::new(&order[index]);
+            let _delta = app.transact_list(|list_tx| {
+                list_tx.in_map(dot_key.as_str(), |todo_tx| {
+                    crate::todo::set_level(todo_tx, Some(level));
+                });
+            });
+        }
+
+        app.ui_state.group_by_level = true;
+        let grouped: Vec<Dot> = app.visible_todos().iter().map(|(dot, _)| *dot).collect();
+        assert_eq!(
+            grouped,
+            vec![order[1], order[4], order[3], order[0], order[2], order[5]]
+        );
+    }
+
+    #[test]
+    fn test_sort_modes_only_change_the_view() {
+        let mut app = connected_apps(1).remove(0);
+        app.add_random_todos().expect("add todos");
+        app.add_random_todos().expect("add more todos");
+        let order: Vec<Dot> = app.visible_todos().iter().map(|(dot, _)| *dot).collect();
+        let priority = crate::priority::read_priority(app.list_store());
+
+        // Mark the 2nd and 4th todo as done
+        for index in [1, 3] {
+            let dot_key = crate::priority::DotKey::new(&order[index]);
+            let _delta = app.transact_list(|list_tx| {
+                list_tx.in_map(dot_key.as_str(), |todo_tx| {
+                    todo_tx.write_register("done", dson::crdts::mvreg::MvRegValue::Bool(true));
+                });
+            });
+        }
+        let shown =
+            |app: &App| -> Vec<Dot> { app.visible_todos().iter().map(|(dot, _)| *dot).collect() };
+
+        app.ui_state.sort_mode = SortMode::Alphabetical;
+        let texts: Vec<String> = app
+            .visible_todos()
+            .iter()
+            .map(|(_, todo)| todo.primary_text().to_lowercase())
+            .collect();
+        assert!(texts.is_sorted());
+
+        app.ui_state.sort_mode = SortMode::DoneLast;
+        assert_eq!(
+            shown(&app),
+            vec![order[0], order[2], order[4], order[5], order[1], order[3]]
+        );
+
+        // A concurrent text edit from another replica conflicts the 3rd todo
+        let dot_key = crate::priority::DotKey::new(&order[2]);
+        let mut remote = app.store.clone();
+        let mut tx = remote.transact(Identifier::new(9, 0));
+        tx.in_map(app.current_list.as_str(), |list_tx| {
+            list_tx.in_map(dot_key.as_str(), |todo_tx| {
+                todo_tx.write_register(
+                    "text",
+                    dson::crdts::mvreg::MvRegValue::String("remote".to_string()),
+                );
+            });
+        });
+        let remote_delta = tx.commit();
+        let _delta = app.transact_list(|list_tx| {
+            list_tx.in_map(dot_key.as_str(), |todo_tx| {
+                todo_tx.write_register(
+                    "text",
+                    dson::crdts::mvreg::MvRegValue::String("local".to_string()),
+                );
+            });
+        });
+        app.store
+            .join_or_replace_with(remote_delta.0.store, &remote_delta.0.context);
+
+        app.ui_state.sort_mode = SortMode::ConflictedFirst;
+        assert_eq!(
+            shown(&app),
+            vec![order[2], order[0], order[1], order[3], order[4], order[5]]
+        );
 
-        for (text, dot_key) in selected.iter().zip(dot_keys.iter()) {
-            // Create the todo with text and done fields
-            tx.in_map(dot_key.as_str(), |todo_tx| {
+        assert_eq!(crate::priority::read_priority(app.list_store()), priority);
+        assert_eq!(SortMode::ConflictedFirst.cycle(), SortMode::Priority);
+    }
+
+    #[test]
+    fn test_auto_resolve_strategies_pick_deterministically() {
+        let values: Vec<String> = ["oat", "whole", "skim"].map(String::from).to_vec();
+        assert_eq!(AutoResolveStrategy::None.pick(&values), None);
+        assert_eq!(AutoResolveStrategy::TakeLonger.pick(&values), Some("whole"));
+        // "oat" is the only 3-letter value; "skim" ties with nobody
+        assert_eq!(AutoResolveStrategy::TakeShorter.pick(&values), Some("oat"));
+        assert_eq!(
+            AutoResolveStrategy::TakeAlphabeticallyFirst.pick(&values),
+            Some("oat")
+        );
+
+        // Equal lengths fall back to alphabetical order
+        let tied: Vec<String> = ["milk", "eggs"].map(String::from).to_vec();
+        assert_eq!(AutoResolveStrategy::TakeLonger.pick(&tied), Some("eggs"));
+        assert_eq!(AutoResolveStrategy::TakeShorter.pick(&tied), Some("eggs"));
+    }
+
+    /// Let both replicas edit the first todo's text without seeing each other.
+    fn concurrent_text_edits(apps: &mut [App], texts: [&str; 2]) -> Dot {
+        apps[0].add_random_todos().expect("add todos");
+        apps[1].tick().expect("receive todos");
+        let dot = apps[0].get_todos_ordered()[0].0;
+        let dot_key = crate::priority::DotKey::new(&dot);
+
+        let deltas: Vec<_> = apps
+            .iter_mut()
+            .zip(texts)
+            .map(|(app, text)| {
+                app.transact_list(|list_tx| {
+                    list_tx.in_map(dot_key.as_str(), |todo_tx| {
+                        todo_tx.write_register(
+                            "text",
+                            dson::crdts::mvreg::MvRegValue::String(text.to_string()),
+                        );
+                    });
+                })
+            })
+            .collect();
+        for (app, delta) in apps.iter_mut().zip(deltas) {
+            app.broadcast_delta(delta).expect("broadcast edit");
+        }
+        dot
+    }
+
+    #[test]
+    fn test_auto_resolve_collapses_incoming_conflict() {
+        let mut apps = connected_apps(2);
+        apps[1].auto_resolve = AutoResolveStrategy::TakeLonger;
+        let dot = concurrent_text_edits(&mut apps, ["Buy oat milk", "Buy whole milk"]);
+
+        apps[1].tick().expect("receive edit and resolve");
+        apps[0].tick().expect("receive edit and resolution");
+
+        for app in &apps {
+            let todo = crate::todo::read_todo(app.list_store(), &dot).expect("todo");
+            assert_eq!(todo.text, vec!["Buy whole milk"]);
+        }
+        assert_eq!(apps[0].store, apps[1].store);
+    }
+
+    #[test]
+    fn test_different_auto_resolve_strategies_settle() {
+        let mut apps = connected_apps(2);
+        apps[0].auto_resolve = AutoResolveStrategy::TakeShorter;
+        apps[1].auto_resolve = AutoResolveStrategy::TakeLonger;
+        let dot = concurrent_text_edits(&mut apps, ["Buy oat milk", "Buy whole milk"]);
+
+        // Hold back replica 1's resolution so both resolve concurrently
+        apps[0].coalesce_window = Some(Duration::from_secs(60));
+        apps[0].process_incoming_deltas().expect("resolve shorter");
+        apps[1].process_incoming_deltas().expect("resolve longer");
+        apps[0].flush_pending_delta().expect("send resolution");
+
+        // The resolutions conflict again, but the same conflict is not resolved twice
+        for _ in 0..3 {
+            for app in apps.iter_mut() {
+                app.process_incoming_deltas().expect("process");
+                app.flush_pending_delta().expect("flush");
+            }
+        }
+        assert!(
+            apps[0]
+                .transport
+                .try_receive(false)
+                .expect("receive")
+                .is_none()
+        );
+        assert!(
+            apps[1]
+                .transport
+                .try_receive(false)
+                .expect("receive")
+                .is_none()
+        );
+
+        let todo = crate::todo::read_todo(apps[0].list_store(), &dot).expect("todo");
+        let mut texts = todo.text.clone();
+        texts.sort();
+        assert_eq!(texts, vec!["Buy oat milk", "Buy whole milk"]);
+        assert_eq!(apps[0].store, apps[1].store);
+    }
+
+    /// Let the first replica mark the first todo done while the second marks it not done.
+    fn concurrent_done_edits(apps: &mut [App]) -> Dot {
+        apps[0].add_random_todos().expect("add todos");
+        apps[1].tick().expect("receive todos");
+        let dot = apps[0].get_todos_ordered()[0].0;
+        let dot_key = crate::priority::DotKey::new(&dot);
+
+        let deltas: Vec<_> = apps
+            .iter_mut()
+            .zip([true, false])
+            .map(|(app, done)| {
+                app.transact_list(|list_tx| {
+                    list_tx.in_map(dot_key.as_str(), |todo_tx| {
+                        todo_tx.write_register("done", dson::crdts::mvreg::MvRegValue::Bool(done));
+                    });
+                })
+            })
+            .collect();
+        for (app, delta) in apps.iter_mut().zip(deltas) {
+            app.broadcast_delta(delta).expect("broadcast edit");
+        }
+        dot
+    }
+
+    #[test]
+    fn test_done_wins_only_changes_display() {
+        let mut apps = connected_apps(2);
+        apps[1].done_wins = true;
+        let dot = concurrent_done_edits(&mut apps);
+        apps[0].tick().expect("receive edit");
+        apps[1].tick().expect("receive edit");
+
+        for app in &apps {
+            let todo = crate::todo::read_todo(app.list_store(), &dot).expect("todo");
+            assert_eq!(todo.done.len(), 2, "conflict stays in the data");
+            assert!(todo.has_conflicts());
+            assert_eq!(app.is_done(&todo), app.done_wins || todo.primary_done());
+        }
+        let todo = crate::todo::read_todo(apps[1].list_store(), &dot).expect("todo");
+        assert!(apps[1].is_done(&todo));
+        assert!(todo.resolved_done(true));
+        assert_eq!(apps[0].store, apps[1].store);
+    }
+
+    #[test]
+    fn test_resolve_done_collapses_conflict_to_done() {
+        let mut apps = connected_apps(2);
+        apps[1].resolve_done = true;
+        let dot = concurrent_done_edits(&mut apps);
+
+        apps[1].tick().expect("receive edit and resolve");
+        apps[0].tick().expect("receive edit and resolution");
+
+        for app in &apps {
+            let todo = crate::todo::read_todo(app.list_store(), &dot).expect("todo");
+            assert_eq!(todo.done, vec![true]);
+        }
+        assert_eq!(apps[0].store, apps[1].store);
+    }
+
+    /// Drain an app's inbox, returning the messages in it.
+    fn received_messages(app: &App) -> Vec<NetworkMessage> {
+        let mut messages = Vec::new();
+        while let Some((data, _)) = app.transport.try_receive(false).expect("receive") {
+            messages.push(
+                network::deserialize_message(&data, network::WireFormat::default())
+                    .expect("deserialize"),
+            );
+        }
+        messages
+    }
+
+    #[test]
+    fn test_request_resync_sends_context_and_full_state_request() {
+        let mut apps = connected_apps(2);
+        apps[0].request_resync().expect("resync");
+
+        let messages = received_messages(&apps[1]);
+        assert_eq!(messages.len(), 2);
+        assert!(matches!(messages[0], NetworkMessage::Context { .. }));
+        assert!(matches!(
+            messages[1],
+            NetworkMessage::FullStateRequest { .. }
+        ));
+    }
+
+    #[test]
+    fn test_request_resync_is_rate_limited() {
+        let mut apps = connected_apps(2);
+        apps[0].request_resync().expect("resync");
+        received_messages(&apps[1]);
+
+        apps[0].request_resync().expect("second resync");
+        assert!(received_messages(&apps[1]).is_empty());
+
+        apps[0].last_manual_resync = Some(Instant::now() - MANUAL_RESYNC_COOLDOWN);
+        apps[0].request_resync().expect("resync after cooldown");
+        assert_eq!(received_messages(&apps[1]).len(), 2);
+    }
+
+    #[test]
+    fn test_request_resync_suppressed_while_isolated() {
+        let mut apps = connected_apps(2);
+        apps[0].network_isolated = true;
+        apps[0].request_resync().expect("resync");
+
+        assert!(received_messages(&apps[1]).is_empty());
+        assert!(
+            apps[0].last_manual_resync.is_none(),
+            "isolation uses no cooldown"
+        );
+    }
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(0), "1970-01-01T00:00:00.000Z");
+        assert_eq!(
+            format_timestamp(1_700_000_000_123),
+            "2023-11-14T22:13:20.123Z"
+        );
+        assert_eq!(
+            format_timestamp(951_782_400_000),
+            "2000-02-29T00:00:00.000Z"
+        );
+    }
+
+    #[test]
+    fn test_log_file_keeps_every_message() {
+        let path =
+            std::env::temp_dir().join(format!("dson-p2p-todo-{}-log-test.log", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut app = connected_apps(1).remove(0);
+        app.open_log_file(&path).expect("open log file");
+
+        for i in 0..MAX_LOG_MESSAGES + 10 {
+            app.log(format!("message {i}"));
+        }
+        app.flush_log().expect("flush");
+
+        let contents = std::fs::read_to_string(&path).expect("read log file");
+        std::fs::remove_file(&path).expect("remove log file");
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines.len(), MAX_LOG_MESSAGES + 10);
+        assert!(lines[0].ends_with("Z message 0"), "{}", lines[0]);
+        assert_eq!(app.log_buffer.len(), MAX_LOG_MESSAGES);
+    }
+
+    /// Delete the first todo on `apps[0]` while `apps[1]` edits it, and exchange the deltas.
+    fn concurrent_delete_and_edit(apps: &mut [App]) -> Dot {
+        apps[0].add_random_todos().expect("add todos");
+        apps[1].tick().expect("receive todos");
+        let dot = apps[0].get_todos_ordered()[0].0;
+        let dot_key = crate::priority::DotKey::new(&dot);
+
+        crate::input::execute_action(&mut apps[0], crate::input::Action::Delete).expect("delete");
+        let delta = apps[1].transact_list(|list_tx| {
+            list_tx.in_map(dot_key.as_str(), |todo_tx| {
                 todo_tx.write_register(
                     "text",
-                    dson::crdts::mvreg::MvRegValue::String(text.to_string()),
+                    dson::crdts::mvreg::MvRegValue::String("Edited meanwhile".to_string()),
+                );
+            });
+        });
+        apps[1].broadcast_delta(delta).expect("broadcast edit");
+        apps[0].tick().expect("receive edit");
+        apps[1].tick().expect("receive delete");
+        dot
+    }
+
+    #[test]
+    fn test_find_orphans_after_concurrent_delete_and_edit() {
+        let mut apps = connected_apps(2);
+        assert!(apps[0].find_orphans().is_empty());
+        let dot = concurrent_delete_and_edit(&mut apps);
+
+        for app in &apps {
+            assert_eq!(app.find_orphans(), vec![dot]);
+            assert_eq!(app.get_todos_ordered().len(), 2);
+        }
+    }
+
+    #[test]
+    fn test_restore_orphans_appends_to_priority() {
+        let mut apps = connected_apps(2);
+        let dot = concurrent_delete_and_edit(&mut apps);
+
+        assert_eq!(apps[0].restore_orphans().expect("restore"), 1);
+        apps[1].tick().expect("receive restore");
+
+        for app in &apps {
+            assert!(app.find_orphans().is_empty());
+            let todos = app.get_todos_ordered();
+            assert_eq!(todos.len(), 3);
+            assert_eq!(todos[2].0, dot);
+            assert_eq!(todos[2].1.primary_text(), "Edited meanwhile");
+        }
+    }
+
+    #[test]
+    fn test_purge_orphans_removes_map_entries() {
+        let mut apps = connected_apps(2);
+        let dot = concurrent_delete_and_edit(&mut apps);
+
+        assert_eq!(apps[1].purge_orphans().expect("purge"), 1);
+        apps[0].tick().expect("receive purge");
+
+        for app in &apps {
+            assert!(app.find_orphans().is_empty());
+            assert!(crate::todo::read_todo(app.list_store(), &dot).is_none());
+        }
+        assert_eq!(apps[0].store, apps[1].store);
+    }
+
+    #[test]
+    fn test_interleaved_transactions_see_only_their_own_writes() {
+        let mut apps = connected_apps(2);
+        apps[0].add_random_todos().expect("add todos");
+        apps[1].tick().expect("receive todos");
+        let (dot, todo) = apps[0].get_todos_ordered()[0].clone();
+        let original = todo.primary_text().to_string();
+        let key = crate::priority::DotKey::new(&dot);
+        let list = apps[0].current_list.clone();
+        let read_text = |tx: &MapTransaction<'_, String>| match tx.get(&list) {
+            Some(dson::transaction::CrdtValue::Map(list)) => {
+                crate::todo::read_todo(list, &dot).map(|todo| todo.text)
+            }
+            _ => None,
+        };
+        let write_text = |tx: &mut MapTransaction<'_, String>, text: String| {
+            tx.in_map(list.as_str(), |list_tx| {
+                list_tx.in_map(key.as_str(), |todo_tx| {
+                    todo_tx.write_register("text", dson::crdts::mvreg::MvRegValue::String(text));
+                });
+            });
+        };
+
+        let (first, rest) = apps.split_at_mut(1);
+        let delta = first[0].with_transaction(|tx| {
+            let before = read_text(tx).expect("todo");
+            assert_eq!(before, [original.as_str()]);
+
+            // The other replica edits and commits while this transaction is open
+            let other = rest[0].with_transaction(|tx| {
+                write_text(tx, "Edited elsewhere".to_string());
+                assert_eq!(read_text(tx).expect("todo"), ["Edited elsewhere"]);
+            });
+            rest[0].broadcast_delta(other).expect("broadcast edit");
+
+            // Its commit is not seen here, only our own write is
+            assert_eq!(read_text(tx).expect("todo"), before);
+            write_text(tx, format!("{}!", before[0]));
+            assert_eq!(read_text(tx).expect("todo"), [format!("{original}!")]);
+        });
+        apps[0].broadcast_delta(delta).expect("broadcast");
+        for app in apps.iter_mut() {
+            app.tick().expect("sync");
+        }
+
+        // Neither write saw the other, so both survive as concurrent values
+        assert_eq!(apps[0].store, apps[1].store);
+        let mut texts = crate::todo::read_todo(apps[0].list_store(), &dot)
+            .expect("todo")
+            .text;
+        texts.sort();
+        let mut expected = vec!["Edited elsewhere".to_string(), format!("{original}!")];
+        expected.sort();
+        assert_eq!(texts, expected);
+    }
+
+    #[test]
+    fn test_concurrent_auto_repairs_converge_after_double_move() {
+        let mut apps = connected_apps(2);
+        let orphan = concurrent_delete_and_edit(&mut apps);
+        // A buggy peer's entry that doesn't parse, ahead of every todo
+        let delta = apps[0].transact_list(|list_tx| {
+            list_tx.in_array("priority", |arr_tx| {
+                arr_tx.insert_register(
+                    0,
+                    dson::crdts::mvreg::MvRegValue::String("garbage".to_string()),
                 );
-                todo_tx.write_register("done", dson::crdts::mvreg::MvRegValue::Bool(false));
             });
+        });
+        apps[0].broadcast_delta(delta).expect("broadcast");
+        for app in apps.iter_mut() {
+            app.tick().expect("sync garbage");
+        }
+        let listed: Vec<Dot> = apps[0]
+            .get_todos_ordered()
+            .iter()
+            .map(|(dot, _)| *dot)
+            .collect();
+
+        // Both replicas move the same todo to the top before syncing
+        for app in apps.iter_mut() {
+            app.ui_state.selected_index = 1;
+            crate::input::execute_action(app, crate::input::Action::MoveTo(0)).expect("top");
+        }
+        for app in apps.iter_mut() {
+            app.tick().expect("sync moves");
+        }
+        let moved = listed[1];
+        assert_eq!(
+            crate::priority::priority_indices(apps[0].list_store(), &moved).len(),
+            2
+        );
+
+        // Both repair before seeing the other's repair, and again once synced
+        for pass in 0..2 {
+            for app in apps.iter_mut() {
+                assert!(
+                    app.auto_repair_priority().expect("repair") > 0,
+                    "pass {pass}"
+                );
+            }
+            for app in apps.iter_mut() {
+                app.tick().expect("sync repairs");
+            }
+        }
+        for app in apps.iter_mut() {
+            assert_eq!(app.auto_repair_priority().expect("repair"), 0);
+        }
+
+        assert_eq!(apps[0].store, apps[1].store);
+        let list = apps[0].list_store();
+        assert!(crate::priority::duplicate_priority_indices(list).is_empty());
+        assert!(crate::priority::orphaned_dots(list).is_empty());
+        assert_eq!(
+            crate::priority::read_priority(list),
+            [moved, listed[0], orphan]
+        );
+        assert_eq!(crate::priority::invalid_priority_entries(list), 1);
+    }
+
+    #[test]
+    fn test_tick_repairs_priority_once_due_and_quiet() {
+        let mut apps = connected_apps(2);
+        let orphan = concurrent_delete_and_edit(&mut apps);
+        let make_due = |app: &mut App| {
+            app.last_priority_repair = Instant::now() - PRIORITY_REPAIR_INTERVAL;
+        };
 
-            // Add to priority array - arr_tx.len() grows with each insert!
-            tx.in_array("priority", |arr_tx| {
+        // A delta was just received, so the repair waits for quiet
+        make_due(&mut apps[0]);
+        apps[0].tick().expect("tick");
+        assert_eq!(apps[0].find_orphans(), [orphan]);
+
+        apps[0].last_delta_received = Some(Instant::now() - PRIORITY_REPAIR_QUIET);
+        apps[0].tick().expect("tick");
+        assert!(apps[0].find_orphans().is_empty());
+        apps[1].tick().expect("receive repair");
+        assert!(apps[1].find_orphans().is_empty());
+
+        // Not due again until the interval has passed
+        let first = crate::priority::DotKey::new(&apps[0].get_todos_ordered()[0].0);
+        let delta = apps[0].transact_list(|list_tx| {
+            list_tx.in_array("priority", |arr_tx| {
                 arr_tx.insert_register(
-                    arr_tx.len(),
-                    dson::crdts::mvreg::MvRegValue::String(dot_key.as_str().to_string()),
+                    3,
+                    dson::crdts::mvreg::MvRegValue::String(first.into_inner()),
                 );
             });
+        });
+        apps[0].broadcast_delta(delta).expect("broadcast repeat");
+        apps[0].last_delta_received = None;
+        apps[0].tick().expect("tick");
+        let list = apps[0].list_store();
+        assert_eq!(crate::priority::duplicate_priority_indices(list), [3]);
+    }
+
+    /// Whether `app`'s log has an "Applied delta" line with `outcome`, clearing the log.
+    fn applied_with(app: &mut App, outcome: &str) -> bool {
+        let found = app
+            .log_buffer
+            .iter()
+            .any(|line| line.ends_with(&format!("Applied delta ({outcome})")));
+        app.log_buffer.clear();
+        found
+    }
+
+    #[test]
+    fn test_applied_delta_logs_whether_state_changed() {
+        let mut apps = connected_apps(2);
+        apps[0].add_random_todos().expect("add todos");
+        apps[1].tick().expect("receive todos");
+        assert!(applied_with(&mut apps[1], "state changed"));
+
+        // A re-sent full state carries nothing new
+        apps[0].broadcast_full_state().expect("full state");
+        apps[1].tick().expect("receive full state");
+        assert!(applied_with(&mut apps[1], "no change"));
+        assert!(!applied_with(&mut apps[1], "state changed"));
+
+        // A delete only removes entries the receiver already knows
+        crate::input::execute_action(&mut apps[0], crate::input::Action::Delete).expect("delete");
+        apps[1].tick().expect("receive delete");
+        assert!(applied_with(&mut apps[1], "state changed"));
+        assert_eq!(apps[1].get_todos_ordered().len(), 2);
+    }
+
+    #[test]
+    fn test_friendly_names_are_stable_and_unique() {
+        assert_eq!(ReplicaId::new(0x00).friendly_name(), "brave-otter");
+        assert_eq!(ReplicaId::new(0x3a).friendly_name(), "fancy-koala");
+        let names: HashSet<String> = (0..=u8::MAX)
+            .map(|id| ReplicaId::new(id).friendly_name())
+            .collect();
+        assert_eq!(names.len(), 256);
+    }
+
+    #[test]
+    fn test_receive_is_capped_per_tick() {
+        let mut apps = connected_apps(2);
+        let sent = MAX_MESSAGES_PER_TICK + 6;
+        for i in 0..sent {
+            let delta = apps[0].transact_list(|list_tx| {
+                list_tx.write_register("created_at", dson::crdts::mvreg::MvRegValue::I64(i as i64));
+            });
+            apps[0].broadcast_delta(delta).expect("send");
         }
 
-        let delta = tx.commit();
-        self.broadcast_delta(delta)?;
-        // DEMO END #3
+        assert_eq!(
+            apps[1].process_incoming_deltas().expect("first tick"),
+            MAX_MESSAGES_PER_TICK
+        );
+        assert_ne!(apps[0].store, apps[1].store);
+        assert_eq!(apps[1].process_incoming_deltas().expect("second tick"), 6);
+        assert_eq!(apps[0].store, apps[1].store);
+    }
 
-        self.log(format!(
-            "[Replica {}] Added 3 random Star Wars todos",
-            self.replica_id
-        ));
-        Ok(())
+    #[test]
+    fn test_progress_counts_todos_shown_done() {
+        let mut apps = connected_apps(2);
+        concurrent_done_edits(&mut apps);
+        apps[0].tick().expect("receive edit");
+        let todos = apps[0].get_todos_ordered();
+        let (_, todo) = todos
+            .iter()
+            .find(|(_, todo)| todo.done.len() > 1)
+            .expect("done conflict");
+        let shown_done = usize::from(apps[0].is_done(todo));
+        assert_eq!(apps[0].progress(), (shown_done, 3));
+
+        apps[0].done_wins = true;
+        assert_eq!(apps[0].progress(), (1, 3));
+    }
+
+    #[test]
+    fn test_context_report_compares_with_each_peer() {
+        let mut apps = connected_apps(2);
+        apps[1].add_random_todos().expect("add todos");
+        apps[0].tick().expect("receive todos");
+
+        // Edits the peer never gets, since we are isolated while making them
+        apps[0].network_isolated = true;
+        apps[0].add_random_todos().expect("add todos");
+        apps[0].network_isolated = false;
+
+        apps[0].request_context_report().expect("request report");
+        assert!(apps[0].ui_state.show_context_report);
+        apps[1].process_incoming_deltas().expect("answer request");
+        apps[0].process_incoming_deltas().expect("receive reply");
+
+        let expected =
+            crate::anti_entropy::diff_contexts(&apps[0].store.context, &apps[1].store.context);
+        let report = apps[0].context_reports[&apps[1].replica_id];
+        assert_eq!(report, expected);
+        assert_eq!(report.local_missing, 0);
+        assert!(report.remote_missing > 0);
+    }
+
+    #[test]
+    fn test_delta_breaking_invariants_is_logged() {
+        let mut apps = connected_apps(2);
+        let delta = apps[0].transact_list(|list_tx| {
+            list_tx.in_array("priority", |arr_tx| {
+                arr_tx.insert_register(
+                    0,
+                    dson::crdts::mvreg::MvRegValue::String("not-a-dot".to_string()),
+                );
+            });
+        });
+        apps[0].broadcast_delta(delta).expect("send");
+
+        assert_eq!(apps[1].process_incoming_deltas().expect("receive"), 1);
+        assert!(apps[1].log_buffer.iter().any(|line| line.contains(
+            "WARNING suspicious delta: list 'Todos' got 1 priority entries that are not todo keys"
+        )));
+        assert!(apps[1].get_todos_ordered().is_empty());
+
+        // Later deltas don't repeat the warning for the same entry
+        apps[0].add_random_todos().expect("add todos");
+        apps[1].log_buffer.clear();
+        apps[1].process_incoming_deltas().expect("receive");
+        assert!(
+            !apps[1]
+                .log_buffer
+                .iter()
+                .any(|line| line.contains("WARNING"))
+        );
+    }
+
+    #[test]
+    fn test_side_missing_more_pulls_instead_of_pushing() {
+        let mut apps = connected_apps(2);
+        for app in &mut apps {
+            app.network_isolated = true;
+        }
+        apps[0].add_random_todos().expect("add todos");
+        apps[1].add_random_todos().expect("add todos");
+        apps[1].add_random_todos().expect("add todos");
+        for app in &mut apps {
+            app.network_isolated = false;
+        }
+
+        // We lack twice as many operations as they do: ask them first
+        apps[1].broadcast_context().expect("anti-entropy");
+        apps[0].process_incoming_deltas().expect("receive context");
+        assert!(apps[0].log_buffer.last().is_some_and(|line| {
+            line.ends_with("Has 54 updates for us (missing 27), requested sync")
+        }));
+
+        // They miss fewer, so they send their state
+        apps[1].process_incoming_deltas().expect("receive request");
+        assert!(
+            apps[1]
+                .log_buffer
+                .iter()
+                .any(|line| line.contains("Needs sync (missing 54, we miss 27)"))
+        );
+        apps[0].process_incoming_deltas().expect("receive state");
+        assert_eq!(apps[0].get_todos_ordered().len(), 9);
+    }
+
+    #[test]
+    fn test_gossip_spreads_deltas_and_anti_entropy_fills_gaps() {
+        let mut apps = connected_apps(6);
+        for app in &mut apps {
+            app.gossip_fanout = 2;
+            app.verbose = true;
+            app.broadcast_context().expect("announce");
+        }
+        for app in &mut apps {
+            app.process_incoming_deltas().expect("meet peers");
+            assert_eq!(app.peers.len(), 5);
+        }
+
+        apps[0].add_random_todos().expect("add todos");
+        assert!(
+            apps[0]
+                .log_buffer
+                .iter()
+                .any(|line| line.contains("Gossiped delta to 2 peers"))
+        );
+
+        // The picked peers pass it on once, which may still miss a peer
+        let rounds = |apps: &mut [App]| {
+            for _ in 0..5 {
+                for app in apps.iter_mut() {
+                    app.process_incoming_deltas().expect("receive");
+                }
+            }
+        };
+        rounds(&mut apps);
+        let reached = |apps: &[App]| {
+            apps[1..]
+                .iter()
+                .filter(|app| app.get_todos_ordered().len() == 3)
+                .count()
+        };
+        assert!(reached(&apps) >= 2);
+        assert!(apps[1..].iter().any(|app| {
+            app.log_buffer
+                .iter()
+                .any(|line| line.contains("Forwarded delta from"))
+        }));
+
+        // Anti-entropy catches up whoever was missed
+        apps[0].broadcast_context().expect("anti-entropy");
+        rounds(&mut apps);
+        assert_eq!(reached(&apps), 5);
+    }
+
+    #[test]
+    fn test_overlong_incoming_text_is_truncated_for_display() {
+        let mut apps = connected_apps(2);
+        apps[0].add_random_todos().expect("add todos");
+        apps[1].process_incoming_deltas().expect("receive");
+        let dot = apps[0].get_todos_ordered()[0].0;
+        let dot_key = crate::priority::DotKey::new(&dot);
+
+        // A client without the input limit writes a huge text
+        let long = "x".repeat(crate::todo::MAX_DISPLAY_TEXT_LEN + 1);
+        let delta = apps[0].transact_list(|list_tx| {
+            list_tx.in_map(dot_key.as_str(), |todo_tx| {
+                todo_tx
+                    .write_register("text", dson::crdts::mvreg::MvRegValue::String(long.clone()));
+            });
+        });
+        apps[0].broadcast_delta(delta).expect("send");
+        apps[1].process_incoming_deltas().expect("receive");
+
+        assert!(apps[1].log_buffer.iter().any(|line| {
+            line.contains("WARNING 1 todo text(s) longer than 1000 characters, shown truncated")
+        }));
+        let text = apps[1].get_todos_ordered()[0].1.primary_text().to_string();
+        assert_eq!(text.chars().count(), crate::todo::MAX_DISPLAY_TEXT_LEN);
+        assert!(text.ends_with('…'));
+        // The store keeps the whole value
+        assert_eq!(crate::todo::count_overlong_texts(&apps[1].store.store), 1);
+    }
+
+    #[test]
+    fn test_sync_with_peer_exchanges_full_states() {
+        let mut apps = connected_apps(2);
+        // Hear from each other, then diverge
+        apps[1].add_random_todos().expect("add todos");
+        apps[0].process_incoming_deltas().expect("receive");
+        apps[0].add_random_todos().expect("add todos");
+        apps[1].process_incoming_deltas().expect("receive");
+        for app in apps.iter_mut() {
+            app.network_isolated = true;
+            app.add_random_todos().expect("add todos while isolated");
+            app.network_isolated = false;
+        }
+        assert_ne!(apps[0].store, apps[1].store);
+
+        let peer = apps[1].replica_id;
+        apps[0].sync_with_peer(peer).expect("sync");
+        apps[1]
+            .process_incoming_deltas()
+            .expect("receive state and request");
+        // The peer answers the request after its random reply delay
+        std::thread::sleep(FULL_STATE_REPLY_MAX_JITTER + Duration::from_millis(50));
+        apps[1].tick().expect("answer request");
+        apps[0].process_incoming_deltas().expect("receive answer");
+
+        assert_eq!(apps[0].store, apps[1].store);
+        let logs = &apps[0].log_buffer;
+        assert!(
+            logs.iter()
+                .any(|line| line == &format!("Manual sync initiated with peer {peer}"))
+        );
+        assert!(logs.iter().any(|line| line == "Manual sync complete"));
+    }
+
+    #[test]
+    fn test_peer_filter_drops_messages_before_applying() {
+        let mut apps = connected_apps(3);
+        apps[0].peer_filter = PeerFilter::Deny([ReplicaId::new(2)].into());
+        apps[1].add_random_todos().expect("add todos");
+        apps[1].add_random_todos().expect("add more todos");
+        apps[2].add_random_todos().expect("add todos");
+
+        assert_eq!(apps[0].process_incoming_deltas().expect("receive"), 1);
+        assert_eq!(apps[0].get_todos_ordered().len(), 3);
+        assert!(!apps[0].peers.contains_key(&ReplicaId::new(2)));
+
+        // Dropped messages show up as one summary line, then the count restarts
+        let logged = apps[0].log_buffer.len();
+        apps[0].log_filter_summary();
+        assert_eq!(apps[0].log_buffer.len(), logged + 1);
+        assert!(
+            apps[0].log_buffer[logged].contains("Dropped 2 messages from filtered peers (02: 2)")
+        );
+        apps[0].log_filter_summary();
+        assert_eq!(apps[0].log_buffer.len(), logged + 1);
+
+        assert!(PeerFilter::Allow([ReplicaId::new(3)].into()).permits(ReplicaId::new(3)));
+        assert!(!PeerFilter::Allow([ReplicaId::new(3)].into()).permits(ReplicaId::new(2)));
+    }
+
+    #[test]
+    fn test_compaction_is_adopted_by_peers_that_saw_everything() {
+        let mut apps = connected_apps(3);
+        for app in apps.iter_mut() {
+            app.add_random_todos().expect("add todos");
+        }
+        for app in apps.iter_mut() {
+            app.drain_incoming_deltas().expect("receive");
+        }
+        // Compaction writes every value under our dots, making us their writer
+        let without_writers = |app: &App| {
+            let mut todos = app.get_todos_ordered();
+            for (_, todo) in &mut todos {
+                todo.text_writers.clear();
+                todo.done_writers.clear();
+                todo.last_writer_text = None;
+                todo.last_writer_done = None;
+            }
+            todos
+        };
+        let todos = without_writers(&apps[0]);
+        assert_eq!(apps[0].store.context.intervals().count(), 3);
+
+        apps[0].compact().expect("compact");
+        assert_eq!(apps[0].generation, 1);
+        assert_eq!(apps[0].store.context.intervals().count(), 1);
+        assert_eq!(without_writers(&apps[0]), todos);
+
+        // Edited after seeing everything: can't adopt without losing the edit
+        apps[2].ui_state.selected_index = 0;
+        apps[2].network_isolated = true;
+        crate::input::execute_action(&mut apps[2], crate::input::Action::ToggleDone)
+            .expect("toggle");
+        apps[2].network_isolated = false;
+
+        assert_eq!(apps[1].drain_incoming_deltas().expect("adopt"), 1);
+        assert_eq!(apps[1].generation, 1);
+        assert_eq!(apps[1].store, apps[0].store);
+        apps[2].drain_incoming_deltas().expect("refuse");
+        assert_eq!(apps[2].generation, 0);
+        assert!(
+            apps[2]
+                .log_buffer
+                .iter()
+                .any(|line| line.contains("not adopting compacted state: it lacks 2"))
+        );
+
+        // The new generation syncs as usual and ignores the old one
+        apps[1].add_random_todos().expect("add todos");
+        apps[2].add_random_todos().expect("add todos");
+        assert_eq!(apps[0].drain_incoming_deltas().expect("receive"), 1);
+        assert_eq!(apps[0].store, apps[1].store);
+        assert_eq!(apps[0].get_todos_ordered().len(), 12);
+    }
+
+    #[test]
+    fn test_peer_that_missed_compaction_is_offered_it_again() {
+        let mut apps = connected_apps(2);
+        apps[0].add_random_todos().expect("add todos");
+        apps[1].drain_incoming_deltas().expect("receive");
+
+        apps[0].compact().expect("compact");
+        assert_eq!(apps[0].generation, 1);
+        // The broadcast is lost
+        while apps[1]
+            .transport
+            .try_receive(false)
+            .expect("drop")
+            .is_some()
+        {}
+
+        // Its next context gets the compacted state in reply
+        apps[1].broadcast_context().expect("context");
+        apps[0].drain_incoming_deltas().expect("receive context");
+        assert_eq!(apps[1].drain_incoming_deltas().expect("adopt"), 1);
+        assert_eq!(apps[1].generation, 1);
+        assert_eq!(apps[1].store, apps[0].store);
+    }
+
+    #[test]
+    fn test_compaction_refused_while_isolated_or_over_mtu() {
+        let mut apps = apps_on(
+            InMemoryTransport::hub(2)
+                .into_iter()
+                .map(InMemoryTransport::limit_to_mtu),
+        );
+        apps[0].add_random_todos().expect("add todos");
+        let before = apps[0].store.clone();
+
+        apps[0].network_isolated = true;
+        apps[0].compact().expect("compact");
+        assert_eq!(apps[0].generation, 0);
+        assert_eq!(apps[0].store, before);
+
+        while apps[0].preview_full_state().expect("preview").is_some() {
+            apps[0].add_random_todos().expect("add more todos");
+        }
+        apps[0].network_isolated = false;
+        let before = apps[0].store.clone();
+        apps[0].compact().expect("compact");
+        assert_eq!(apps[0].generation, 0);
+        assert_eq!(apps[0].store, before);
+        assert!(
+            apps[0]
+                .log_buffer
+                .last()
+                .is_some_and(|line| line.contains("Not sent: compacted state"))
+        );
+    }
+
+    #[test]
+    fn test_compaction_refused_while_conflicted() {
+        let mut apps = connected_apps(2);
+        concurrent_done_edits(&mut apps);
+        apps[0].tick().expect("receive conflicting edit");
+        let before = apps[0].store.clone();
+        apps[0].compact().expect("compact");
+        assert_eq!(apps[0].generation, 0);
+        assert_eq!(apps[0].store, before);
+        assert!(
+            apps[0]
+                .log_buffer
+                .last()
+                .is_some_and(|line| line.contains("resolve 1 conflict(s) first"))
+        );
     }
 }
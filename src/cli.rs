@@ -0,0 +1,371 @@
+// ABOUTME: Command-line argument parsing.
+// ABOUTME: Handles the port argument and developer-facing flags.
+
+use crate::{
+    app::{AutoResolveStrategy, PeerFilter, ReplicaId},
+    network::{self, IpVersion, WireFormat},
+};
+use std::{collections::BTreeSet, net::Ipv4Addr, path::PathBuf};
+
+/// Options parsed from the command line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Options {
+    pub port: u16,
+    /// Run `N` in-process replicas instead of the terminal UI.
+    pub simulate: Option<usize>,
+    /// Show verbose log messages.
+    pub verbose: bool,
+    /// Debounce window for coalescing local edits into one delta (0 disables).
+    pub coalesce_ms: u64,
+    /// Merge two snapshot files into a third instead of starting the UI.
+    pub merge_snapshots: Option<(PathBuf, PathBuf, PathBuf)>,
+    /// Resolve incoming text conflicts automatically.
+    pub auto_resolve: AutoResolveStrategy,
+    /// Append every log message to this file, timestamped.
+    pub log_file: Option<PathBuf>,
+    /// Collapse incoming done conflicts to done (implies the done-wins display policy).
+    pub resolve_done: bool,
+    /// Replicas to accept (`--allow`) or ignore (`--deny`) messages from.
+    pub peer_filter: PeerFilter,
+    /// IPv4 broadcast, IPv4 multicast with `--multicast-group` (and
+    /// `--multicast-ttl`), or IPv6 multicast with `--ipv6`; each on
+    /// `--interface` (an address for IPv4, an index for IPv6).
+    pub ip_version: IpVersion,
+    /// Most characters a todo's text can be typed to.
+    pub max_text_len: usize,
+    /// Random peers each delta is sent to once more peers are known.
+    pub gossip_fanout: usize,
+    /// Message encoding; every replica must use the same one.
+    pub wire_format: WireFormat,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            port: network::DEFAULT_PORT,
+            simulate: None,
+            verbose: false,
+            coalesce_ms: 50,
+            merge_snapshots: None,
+            auto_resolve: AutoResolveStrategy::None,
+            resolve_done: false,
+            log_file: None,
+            peer_filter: PeerFilter::All,
+            ip_version: IpVersion::default(),
+            max_text_len: crate::todo::DEFAULT_MAX_TEXT_LEN,
+            gossip_fanout: crate::app::DEFAULT_GOSSIP_FANOUT,
+            wire_format: WireFormat::default(),
+        }
+    }
+}
+
+/// Parse command-line arguments (without the program name).
+///
+/// A bare number is accepted as the port for compatibility with `cargo run 7879`.
+///
+/// # Errors
+/// Returns a human-readable message for unknown flags or invalid values.
+pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut args = args.into_iter();
+    let mut ipv6 = false;
+    let mut interface = None;
+    let mut multicast_group = None;
+    let mut multicast_ttl = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--port" => options.port = parse_value(&arg, args.next())?,
+            "--simulate" => options.simulate = Some(parse_value(&arg, args.next())?),
+            "--verbose" | "-v" => options.verbose = true,
+            "--coalesce-ms" => options.coalesce_ms = parse_value(&arg, args.next())?,
+            "--auto-resolve" => options.auto_resolve = parse_value(&arg, args.next())?,
+            "--resolve-done" => options.resolve_done = true,
+            "--ipv6" => ipv6 = true,
+            "--interface" => {
+                interface = Some(parse_value::<String>(&arg, args.next())?);
+            }
+            "--multicast-group" => {
+                let group: Ipv4Addr = parse_value(&arg, args.next())?;
+                if !group.is_multicast() {
+                    return Err(format!(
+                        "--multicast-group needs an address in 224.0.0.0/4, not {group}"
+                    ));
+                }
+                multicast_group = Some(group);
+            }
+            "--multicast-ttl" => multicast_ttl = Some(parse_value::<u8>(&arg, args.next())?),
+            "--gossip-fanout" => {
+                options.gossip_fanout = parse_value(&arg, args.next())?;
+                if options.gossip_fanout == 0 {
+                    return Err("--gossip-fanout must be at least 1".to_string());
+                }
+            }
+            "--max-text-len" => {
+                let max = parse_value(&arg, args.next())?;
+                if !(1..=crate::todo::MAX_DISPLAY_TEXT_LEN).contains(&max) {
+                    return Err(format!(
+                        "--max-text-len must be between 1 and {}",
+                        crate::todo::MAX_DISPLAY_TEXT_LEN
+                    ));
+                }
+                options.max_text_len = max;
+            }
+            "--wire-format" => options.wire_format = parse_value(&arg, args.next())?,
+            "--log-file" => options.log_file = Some(parse_value(&arg, args.next())?),
+            "--allow" | "--deny" => {
+                if options.peer_filter != PeerFilter::All {
+                    return Err("--allow and --deny can only be given once".to_string());
+                }
+                let ids = parse_replica_ids(&arg, args.next())?;
+                options.peer_filter = if arg == "--allow" {
+                    PeerFilter::Allow(ids)
+                } else {
+                    PeerFilter::Deny(ids)
+                };
+            }
+            "--merge-snapshots" => {
+                options.merge_snapshots = Some((
+                    parse_value(&arg, args.next())?,
+                    parse_value(&arg, args.next())?,
+                    parse_value(&arg, args.next())?,
+                ));
+            }
+            _ => {
+                options.port = arg
+                    .parse()
+                    .map_err(|_| format!("unrecognized argument: {arg}"))?;
+            }
+        }
+    }
+
+    if multicast_ttl.is_some() && multicast_group.is_none() {
+        return Err("--multicast-ttl requires --multicast-group".to_string());
+    }
+    options.ip_version = match (ipv6, multicast_group) {
+        (true, Some(_)) => {
+            return Err("--multicast-group can't be combined with --ipv6".to_string());
+        }
+        (true, None) => IpVersion::V6 {
+            interface: interface.map_or(Ok(0), |index| parse_value("--interface", Some(index)))?,
+        },
+        (false, group) => {
+            let interface = interface.map_or(Ok(Ipv4Addr::UNSPECIFIED), |addr| {
+                parse_value("--interface", Some(addr))
+            })?;
+            match group {
+                Some(group) => IpVersion::V4Multicast {
+                    group,
+                    ttl: multicast_ttl.map_or(1, u32::from),
+                    interface,
+                },
+                None => IpVersion::V4 { interface },
+            }
+        }
+    };
+
+    Ok(options)
+}
+
+/// Parse the value following a flag.
+fn parse_value<T: std::str::FromStr>(flag: &str, value: Option<String>) -> Result<T, String> {
+    let value = value.ok_or_else(|| format!("{flag} requires a value"))?;
+    value
+        .parse()
+        .map_err(|_| format!("invalid value for {flag}: {value}"))
+}
+
+/// Parse the comma-separated replica ids following a flag, e.g. `3a,7f`.
+fn parse_replica_ids(flag: &str, value: Option<String>) -> Result<BTreeSet<ReplicaId>, String> {
+    let value = value.ok_or_else(|| format!("{flag} requires a value"))?;
+    value
+        .split(',')
+        .map(|id| {
+            id.trim()
+                .parse()
+                .map_err(|e| format!("invalid value for {flag}: {e}"))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(s: &str) -> Vec<String> {
+        s.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_parse_defaults() {
+        assert_eq!(parse(args("")), Ok(Options::default()));
+    }
+
+    #[test]
+    fn test_parse_positional_port_and_flags() {
+        let options =
+            parse(args("7879 --simulate 4 --verbose --coalesce-ms 0")).expect("valid args");
+        assert_eq!(options.port, 7879);
+        assert_eq!(options.simulate, Some(4));
+        assert!(options.verbose);
+        assert_eq!(options.coalesce_ms, 0);
+    }
+
+    #[test]
+    fn test_parse_merge_snapshots() {
+        let options = parse(args("--merge-snapshots a.bin b.bin out.bin")).expect("valid args");
+        assert_eq!(
+            options.merge_snapshots,
+            Some(("a.bin".into(), "b.bin".into(), "out.bin".into()))
+        );
+    }
+
+    #[test]
+    fn test_parse_wire_format() {
+        assert_eq!(
+            parse(args("--wire-format json")).map(|options| options.wire_format),
+            Ok(WireFormat::Json)
+        );
+        assert_eq!(Options::default().wire_format, WireFormat::MessagePack);
+        assert!(parse(args("--wire-format xml")).is_err());
+    }
+
+    #[test]
+    fn test_parse_auto_resolve() {
+        let options = parse(args("--auto-resolve longer")).expect("valid args");
+        assert_eq!(options.auto_resolve, AutoResolveStrategy::TakeLonger);
+        assert!(parse(args("--auto-resolve")).is_err());
+        assert!(parse(args("--auto-resolve newest")).is_err());
+        assert!(
+            parse(args("--resolve-done"))
+                .expect("valid args")
+                .resolve_done
+        );
+    }
+
+    #[test]
+    fn test_parse_log_file() {
+        let options = parse(args("--log-file todo.log")).expect("valid args");
+        assert_eq!(options.log_file, Some("todo.log".into()));
+        assert_eq!(parse(args("")).expect("no args").log_file, None);
+        assert!(parse(args("--log-file")).is_err());
+    }
+
+    #[test]
+    fn test_parse_peer_filter() {
+        let options = parse(args("--allow 3a,07")).expect("valid args");
+        assert_eq!(
+            options.peer_filter,
+            PeerFilter::Allow([ReplicaId::new(0x3a), ReplicaId::new(7)].into())
+        );
+        let options = parse(args("--deny ff")).expect("valid args");
+        assert_eq!(
+            options.peer_filter,
+            PeerFilter::Deny([ReplicaId::new(0xff)].into())
+        );
+        assert!(parse(args("--allow")).is_err());
+        assert!(parse(args("--allow 3a,zz")).is_err());
+        assert!(parse(args("--deny 100")).is_err());
+        assert!(parse(args("--allow 3a --deny 7f")).is_err());
+    }
+
+    #[test]
+    fn test_parse_ipv6() {
+        assert_eq!(
+            parse(args("--ipv6")).expect("valid args").ip_version,
+            IpVersion::V6 { interface: 0 }
+        );
+        assert_eq!(
+            parse(args("--interface 2 --ipv6"))
+                .expect("valid args")
+                .ip_version,
+            IpVersion::V6 { interface: 2 }
+        );
+        assert!(parse(args("--ipv6 --interface eth0")).is_err());
+        assert!(parse(args("--ipv6 --interface 192.168.1.5")).is_err());
+    }
+
+    #[test]
+    fn test_parse_ipv4_interface() {
+        let interface = Ipv4Addr::new(192, 168, 1, 5);
+        assert_eq!(
+            parse(args("--interface 192.168.1.5"))
+                .expect("valid args")
+                .ip_version,
+            IpVersion::V4 { interface }
+        );
+        assert_eq!(
+            parse(args("--multicast-group 239.0.0.1 --interface 192.168.1.5"))
+                .expect("valid args")
+                .ip_version,
+            IpVersion::V4Multicast {
+                group: Ipv4Addr::new(239, 0, 0, 1),
+                ttl: 1,
+                interface
+            }
+        );
+        assert!(parse(args("--interface 2")).is_err());
+    }
+
+    #[test]
+    fn test_parse_multicast_group() {
+        let group = Ipv4Addr::new(239, 0, 0, 1);
+        assert_eq!(
+            parse(args("--multicast-group 239.0.0.1"))
+                .expect("valid args")
+                .ip_version,
+            IpVersion::V4Multicast {
+                group,
+                ttl: 1,
+                interface: Ipv4Addr::UNSPECIFIED
+            }
+        );
+        assert_eq!(
+            parse(args("--multicast-ttl 4 --multicast-group 239.0.0.1"))
+                .expect("valid args")
+                .ip_version,
+            IpVersion::V4Multicast {
+                group,
+                ttl: 4,
+                interface: Ipv4Addr::UNSPECIFIED
+            }
+        );
+        assert!(parse(args("--multicast-group 192.168.1.5")).is_err());
+        assert!(parse(args("--multicast-ttl 4")).is_err());
+        assert!(parse(args("--multicast-group 239.0.0.1 --multicast-ttl 300")).is_err());
+        assert!(parse(args("--multicast-group 239.0.0.1 --ipv6")).is_err());
+    }
+
+    #[test]
+    fn test_parse_gossip_fanout() {
+        assert_eq!(parse(args("")).expect("valid args").gossip_fanout, 3);
+        assert_eq!(
+            parse(args("--gossip-fanout 5"))
+                .expect("valid args")
+                .gossip_fanout,
+            5
+        );
+        assert!(parse(args("--gossip-fanout 0")).is_err());
+    }
+
+    #[test]
+    fn test_parse_max_text_len() {
+        assert_eq!(parse(args("")).expect("valid args").max_text_len, 500);
+        assert_eq!(
+            parse(args("--max-text-len 80"))
+                .expect("valid args")
+                .max_text_len,
+            80
+        );
+        assert!(parse(args("--max-text-len 0")).is_err());
+        assert!(parse(args("--max-text-len 1001")).is_err());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse(args("--merge-snapshots a.bin b.bin")).is_err());
+        assert!(parse(args("--simulate")).is_err());
+        assert!(parse(args("--simulate many")).is_err());
+        assert!(parse(args("--bogus")).is_err());
+    }
+}
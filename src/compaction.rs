@@ -0,0 +1,208 @@
+// ABOUTME: Rebuilding the store with a minimal causal context.
+// ABOUTME: Rewrites the observable state as a single transaction of fresh dots.
+
+use crate::app::TodoStore;
+use dson::{
+    Identifier, OrArray, OrMap,
+    crdts::{TypeVariantValue, mvreg::MvRegValue, snapshot::ToValue},
+    transaction::{ArrayTransaction, MapTransaction},
+};
+
+/// What a map entry or array element holds, if it holds exactly one thing.
+enum Single<'a> {
+    Empty,
+    Register(&'a MvRegValue),
+    Map(&'a OrMap<String>),
+    Array(&'a OrArray),
+}
+
+/// The single value of `value`, or `None` if it is conflicted: a register
+/// with several distinct values, or more than one of register, map and array.
+fn single(value: &TypeVariantValue<dson::crdts::NoExtensionTypes>) -> Option<Single<'_>> {
+    let values: Vec<&MvRegValue> = value.reg.values().into_iter().collect();
+    let mut kinds = Vec::new();
+    match values.split_first() {
+        None => {}
+        // Identical concurrent writes are no conflict
+        Some((first, rest)) if rest.iter().all(|value| value == first) => {
+            kinds.push(Single::Register(first));
+        }
+        Some(_) => return None,
+    }
+    if !value.map.is_empty() {
+        kinds.push(Single::Map(&value.map));
+    }
+    if !value.array.is_empty() {
+        kinds.push(Single::Array(&value.array));
+    }
+    match kinds.len() {
+        0 => Some(Single::Empty),
+        1 => kinds.pop(),
+        _ => None,
+    }
+}
+
+/// Number of conflicted values in `map` and everything nested in it.
+/// A conflict can't be carried over into a single transaction.
+pub fn count_conflicts(map: &OrMap<String>) -> usize {
+    map.inner()
+        .iter()
+        .map(|(_, value)| count_value_conflicts(value))
+        .sum()
+}
+
+fn count_value_conflicts(value: &TypeVariantValue<dson::crdts::NoExtensionTypes>) -> usize {
+    match single(value) {
+        None => 1,
+        Some(Single::Map(map)) => count_conflicts(map),
+        Some(Single::Array(array)) => items(array).into_iter().map(count_value_conflicts).sum(),
+        Some(Single::Empty | Single::Register(_)) => 0,
+    }
+}
+
+/// A fresh store with the observable state of `map`, written by `id` in one
+/// transaction, so its causal context is the single interval `id:1..=n`.
+///
+/// Arrays keep their order. Values must not be conflicted (see
+/// [`count_conflicts`]); of a conflicted value only one variant is kept.
+/// The fresh dots are unrelated to the old ones, so the result must never
+/// be joined with a store from before the rebuild.
+pub fn rebuild(map: &OrMap<String>, id: Identifier) -> TodoStore {
+    let mut store = TodoStore::default();
+    let mut tx = store.transact(id);
+    copy_map(map, &mut tx);
+    let _delta = tx.commit();
+    store
+}
+
+fn copy_map(map: &OrMap<String>, tx: &mut MapTransaction<'_, String>) {
+    for (key, value) in map.inner().iter() {
+        match single(value).unwrap_or_else(|| first(value)) {
+            Single::Empty => {}
+            Single::Register(value) => tx.write_register(key.clone(), value.clone()),
+            Single::Map(map) => tx.in_map(key.clone(), |map_tx| copy_map(map, map_tx)),
+            Single::Array(array) => tx.in_array(key.clone(), |arr_tx| copy_array(array, arr_tx)),
+        }
+    }
+}
+
+fn copy_array(array: &OrArray, tx: &mut ArrayTransaction<'_>) {
+    for value in items(array) {
+        let idx = tx.len();
+        match single(value).unwrap_or_else(|| first(value)) {
+            Single::Empty => {}
+            Single::Register(value) => tx.insert_register(idx, value.clone()),
+            Single::Map(map) => tx.insert_map(idx, |map_tx| copy_map(map, map_tx)),
+            Single::Array(array) => tx.insert_array(idx, |arr_tx| copy_array(array, arr_tx)),
+        }
+    }
+}
+
+/// Elements of `array` in order.
+///
+/// Concurrent inserts at the same index get equal positions, and
+/// `OrArray::get` breaks that tie differently per replica for the first and
+/// last index, so this walks the array in its fully sorted order instead.
+fn items(array: &OrArray) -> Vec<&TypeVariantValue<dson::crdts::NoExtensionTypes>> {
+    array
+        .with_list(|value, _, _| Ok::<_, std::convert::Infallible>(Some(value)))
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(value, _, _)| value)
+        .collect()
+}
+
+/// One variant of a conflicted value: a nested map or array before a register.
+fn first(value: &TypeVariantValue<dson::crdts::NoExtensionTypes>) -> Single<'_> {
+    if !value.map.is_empty() {
+        Single::Map(&value.map)
+    } else if !value.array.is_empty() {
+        Single::Array(&value.array)
+    } else {
+        value
+            .reg
+            .values()
+            .into_iter()
+            .next()
+            .map_or(Single::Empty, Single::Register)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Observable state of `map`, keys sorted, for comparing stores.
+    fn describe(map: &OrMap<String>) -> String {
+        let mut entries: Vec<String> = map
+            .inner()
+            .iter()
+            .map(|(key, value)| format!("{key}: {}", describe_value(value)))
+            .collect();
+        entries.sort();
+        format!("{{{}}}", entries.join(", "))
+    }
+
+    fn describe_value(value: &TypeVariantValue<dson::crdts::NoExtensionTypes>) -> String {
+        match single(value).expect("no conflicts") {
+            Single::Empty => "empty".to_string(),
+            Single::Register(value) => format!("{value:?}"),
+            Single::Map(map) => describe(map),
+            Single::Array(array) => {
+                let items: Vec<String> = items(array).into_iter().map(describe_value).collect();
+                format!("[{}]", items.join(", "))
+            }
+        }
+    }
+
+    #[test]
+    fn test_rebuild_keeps_state_with_one_interval() {
+        let mut store = TodoStore::default();
+        for node in 1..=3u8 {
+            let mut tx = store.transact(Identifier::new(node, 0));
+            tx.in_map("list", |list_tx| {
+                list_tx.write_register(format!("{node}"), MvRegValue::U64(node.into()));
+                list_tx.in_array("order", |arr_tx| {
+                    arr_tx.insert_register(0, MvRegValue::String(format!("{node}")));
+                });
+                list_tx.remove("gone");
+            });
+            let _delta = tx.commit();
+        }
+        assert_eq!(count_conflicts(&store.store), 0);
+
+        let id = Identifier::new(9, 0);
+        let rebuilt = rebuild(&store.store, id);
+
+        assert_eq!(describe(&rebuilt.store), describe(&store.store));
+        assert_eq!(
+            describe(&rebuilt.store),
+            r#"{list: {1: 1u, 2: 2u, 3: 3u, order: ["3", "2", "1"]}}"#
+        );
+        assert_eq!(rebuilt.context.intervals().count(), 1);
+        assert!(rebuilt.context.is_compact_for_node(9));
+    }
+
+    #[test]
+    fn test_count_conflicts_finds_nested_register_conflicts() {
+        let mut a = TodoStore::default();
+        let mut b = TodoStore::default();
+        let write = |store: &mut TodoStore, node: u8, text: &str| {
+            let mut tx = store.transact(Identifier::new(node, 0));
+            tx.in_map("todo", |todo_tx| {
+                todo_tx.write_register("text", MvRegValue::String(text.to_string()));
+            });
+            tx.commit()
+        };
+        let _delta = write(&mut a, 1, "same");
+        let delta = write(&mut b, 2, "same");
+        a.join_or_replace_with(delta.0.store, &delta.0.context);
+        // Identical concurrent values are no conflict
+        assert_eq!(count_conflicts(&a.store), 0);
+
+        let delta = write(&mut b, 2, "different");
+        let _delta = write(&mut a, 1, "other");
+        a.join_or_replace_with(delta.0.store, &delta.0.context);
+        assert_eq!(count_conflicts(&a.store), 1);
+    }
+}
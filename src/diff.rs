@@ -0,0 +1,156 @@
+// ABOUTME: Word-level diff between two strings using longest common subsequence.
+// ABOUTME: Used to show what differs between concurrent values of a text register.
+
+/// A run of text in a diff from `a` to `b`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffSegment {
+    /// Present in both strings.
+    Common(String),
+    /// Only present in `b`.
+    Added(String),
+    /// Only present in `a`.
+    Removed(String),
+}
+
+/// Diff `a` against `b` word by word.
+///
+/// Words and the whitespace between them are compared as whole tokens, so a
+/// replaced word shows up as one removal and one addition rather than as
+/// scattered letters. Adjacent tokens of the same kind are merged into one
+/// segment, and at each change removals come before additions. Quadratic in
+/// the number of tokens, which is fine for todo texts.
+pub fn diff_strings(a: &str, b: &str) -> Vec<DiffSegment> {
+    let a = tokenize(a);
+    let b = tokenize(b);
+
+    // lcs[i][j] = length of the LCS of a[i..] and b[j..]
+    let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut segments = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            push(&mut segments, DiffSegment::Common(a[i].to_string()));
+            i += 1;
+            j += 1;
+        } else if i < a.len() && (j == b.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            push(&mut segments, DiffSegment::Removed(a[i].to_string()));
+            i += 1;
+        } else {
+            push(&mut segments, DiffSegment::Added(b[j].to_string()));
+            j += 1;
+        }
+    }
+    segments
+}
+
+/// Split into runs of whitespace and runs of other characters.
+fn tokenize(s: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut start = 0;
+    for (i, c) in s.char_indices().skip(1) {
+        let prev = s[..i].chars().next_back().expect("not at the start");
+        if c.is_whitespace() != prev.is_whitespace() {
+            tokens.push(&s[start..i]);
+            start = i;
+        }
+    }
+    if start < s.len() {
+        tokens.push(&s[start..]);
+    }
+    tokens
+}
+
+/// Append `segment` to the last segment if it has the same kind, else push it.
+fn push(segments: &mut Vec<DiffSegment>, segment: DiffSegment) {
+    use DiffSegment::*;
+
+    match (segments.last_mut(), segment) {
+        (Some(Common(last)), Common(s))
+        | (Some(Added(last)), Added(s))
+        | (Some(Removed(last)), Removed(s)) => last.push_str(&s),
+        (_, segment) => segments.push(segment),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use DiffSegment::*;
+
+    fn common(s: &str) -> DiffSegment {
+        Common(s.to_string())
+    }
+
+    fn added(s: &str) -> DiffSegment {
+        Added(s.to_string())
+    }
+
+    fn removed(s: &str) -> DiffSegment {
+        Removed(s.to_string())
+    }
+
+    #[test]
+    fn test_identical_strings_are_common() {
+        assert_eq!(
+            diff_strings("Buy milk", "Buy milk"),
+            vec![common("Buy milk")]
+        );
+        assert_eq!(diff_strings("", ""), vec![]);
+    }
+
+    #[test]
+    fn test_replaced_word() {
+        assert_eq!(
+            diff_strings("Buy whole milk", "Buy oat milk"),
+            vec![
+                common("Buy "),
+                removed("whole"),
+                added("oat"),
+                common(" milk"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_insertions_and_deletions() {
+        assert_eq!(
+            diff_strings("Buy milk", "Buy milk today"),
+            vec![common("Buy milk"), added(" today")]
+        );
+        assert_eq!(
+            diff_strings("Call mom now", "Call mom"),
+            vec![common("Call mom"), removed(" now")]
+        );
+        assert_eq!(diff_strings("", "new"), vec![added("new")]);
+    }
+
+    #[test]
+    fn test_segments_reconstruct_both_strings() {
+        let (a, b) = ("Fix the spaceship engine", "Fix spaceship engines ✓");
+        let segments = diff_strings(a, b);
+
+        let side = |keep_added: bool| -> String {
+            segments
+                .iter()
+                .filter_map(|segment| match segment {
+                    Common(s) => Some(s.as_str()),
+                    Added(s) if keep_added => Some(s.as_str()),
+                    Removed(s) if !keep_added => Some(s.as_str()),
+                    _ => None,
+                })
+                .collect()
+        };
+        assert_eq!(side(false), a);
+        assert_eq!(side(true), b);
+    }
+}
@@ -0,0 +1,261 @@
+// ABOUTME: In-memory edit history per todo, derived from the deltas applied to the store.
+// ABOUTME: Describes which fields each delta touched and who made the change.
+
+use crate::{
+    app::{ReplicaId, TodoStore},
+    priority::parse_dot,
+    todo::read_todo,
+};
+use dson::{Dot, OrMap};
+use std::collections::{HashMap, VecDeque};
+
+/// Maximum number of entries kept per todo; older ones are dropped first.
+pub const MAX_ENTRIES_PER_TODO: usize = 50;
+
+/// Maximum number of entries kept over all todos.
+pub const MAX_ENTRIES: usize = 2000;
+
+/// One applied change to a todo.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryEntry {
+    /// Wall-clock time of the change (Unix ms): the author's send time for
+    /// remote deltas, ours for local edits.
+    pub at: i64,
+    /// Replica that made the change.
+    pub author: ReplicaId,
+    /// What changed, e.g. "set text to 'Buy milk', marked done".
+    pub change: String,
+    /// Order of recording over all todos, to drop the oldest entry first.
+    seq: u64,
+}
+
+/// Changes to each todo, oldest first, keyed by list name and todo dot.
+///
+/// History lives only in memory: it starts empty and only covers deltas
+/// applied since. Full states received from peers are not recorded, since
+/// they touch every todo and say nothing about who changed what.
+#[derive(Debug, Default)]
+pub struct EditHistory {
+    entries: HashMap<(String, Dot), VecDeque<HistoryEntry>>,
+    len: usize,
+    next_seq: u64,
+}
+
+impl EditHistory {
+    /// Record a change for every todo that `delta` writes to.
+    pub fn record(&mut self, delta: &TodoStore, author: ReplicaId, at: i64) {
+        for (list, value) in delta.store.inner().iter() {
+            let list_delta = &value.map;
+            for key in list_delta.inner().keys() {
+                let Some(dot) = parse_dot(key) else {
+                    continue;
+                };
+                if let Some(change) = describe_change(list_delta, key, &dot) {
+                    self.push(list, dot, author, at, change);
+                }
+            }
+        }
+    }
+
+    /// Entries for the todo `dot` in `list`, oldest first.
+    pub fn entries(&self, list: &str, dot: &Dot) -> impl Iterator<Item = &HistoryEntry> {
+        self.entries
+            .get(&(list.to_string(), *dot))
+            .into_iter()
+            .flatten()
+    }
+
+    /// Number of entries over all todos.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether no change was recorded yet.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn push(&mut self, list: &str, dot: Dot, author: ReplicaId, at: i64, change: String) {
+        let entries = self.entries.entry((list.to_string(), dot)).or_default();
+        entries.push_back(HistoryEntry {
+            at,
+            author,
+            change,
+            seq: self.next_seq,
+        });
+        self.next_seq += 1;
+        self.len += 1;
+        if entries.len() > MAX_ENTRIES_PER_TODO {
+            entries.pop_front();
+            self.len -= 1;
+        }
+
+        while self.len > MAX_ENTRIES {
+            self.drop_oldest();
+        }
+    }
+
+    /// Drop the oldest entry over all todos, forgetting todos left without any.
+    fn drop_oldest(&mut self) {
+        let Some(key) = self
+            .entries
+            .iter()
+            .filter_map(|(key, entries)| Some((entries.front()?.seq, key)))
+            .min()
+            .map(|(_, key)| key.clone())
+        else {
+            return;
+        };
+        if let Some(entries) = self.entries.get_mut(&key) {
+            entries.pop_front();
+            self.len -= 1;
+            if entries.is_empty() {
+                self.entries.remove(&key);
+            }
+        }
+    }
+}
+
+/// Describe the fields of the todo at `key` that `list_delta` writes, or
+/// `None` if it writes nothing worth showing (e.g. only the modification time,
+/// or a removal, which carries no values).
+fn describe_change(list_delta: &OrMap<String>, key: &str, dot: &Dot) -> Option<String> {
+    let fields = &list_delta.get(key)?.map;
+    let todo = read_todo(list_delta, dot)?;
+    let quoted = |values: &[String]| {
+        values
+            .iter()
+            .map(|value| format!("'{value}'"))
+            .collect::<Vec<_>>()
+            .join(" | ")
+    };
+
+    if fields.get(&"creator".to_string()).is_some() {
+        return Some(format!("created {}", quoted(&todo.text)));
+    }
+
+    let mut keys: Vec<&String> = fields.inner().keys().collect();
+    keys.sort();
+    let changes: Vec<String> = keys
+        .into_iter()
+        .filter_map(|field| match field.as_str() {
+            "modified_at" => None,
+            "text" => Some(format!("set text to {}", quoted(&todo.text))),
+            "done" => Some(match todo.done.as_slice() {
+                [true] => "marked done".to_string(),
+                [false] => "marked not done".to_string(),
+                _ => "changed done".to_string(),
+            }),
+            "notes" => Some("edited notes".to_string()),
+            "level" => Some(match todo.level.as_slice() {
+                [level] => format!("set level to {}", level.as_str()),
+                _ => "changed level".to_string(),
+            }),
+            "category" => Some(format!("set category to {}", quoted(&todo.category))),
+            "recur" => Some(match todo.recur.as_slice() {
+                [recur] => format!("set to repeat {}", recur.as_str()),
+                _ => "changed repeating".to_string(),
+            }),
+            "color" => Some(match todo.color.as_slice() {
+                [color] => format!("set color to {}", color.as_str()),
+                _ => "changed color".to_string(),
+            }),
+            "assignee" => Some("reassigned".to_string()),
+            "subtasks" | "subtask_items" => Some("changed subtasks".to_string()),
+            other => Some(format!("changed {other}")),
+        })
+        .collect();
+    (!changes.is_empty()).then(|| {
+        let mut changes = changes;
+        changes.dedup();
+        changes.join(", ")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dson::{CausalDotStore, Identifier, crdts::mvreg::MvRegValue};
+
+    const LIST: &str = "Todos";
+
+    /// Run `f` on the todo `dot` in `store` and return the delta.
+    fn todo_delta(
+        store: &mut TodoStore,
+        dot: &Dot,
+        f: impl FnOnce(&mut dson::transaction::MapTransaction<'_, String>),
+    ) -> TodoStore {
+        let key = crate::priority::DotKey::new(dot);
+        let mut tx = store.transact(Identifier::new(1, 0));
+        tx.in_map(LIST, |list_tx| list_tx.in_map(key.as_str(), f));
+        tx.commit().0
+    }
+
+    #[test]
+    fn test_describes_touched_fields() {
+        let mut store = CausalDotStore::<OrMap<String>>::default();
+        let dot = Dot::mint(Identifier::new(1, 0), 1);
+        let author = ReplicaId::new(0x3a);
+        let mut history = EditHistory::default();
+
+        let created = todo_delta(&mut store, &dot, |todo_tx| {
+            todo_tx.write_register("text", MvRegValue::String("Buy milk".to_string()));
+            todo_tx.write_register("done", MvRegValue::Bool(false));
+            crate::todo::write_creation_metadata(todo_tx, author, 1);
+        });
+        history.record(&created, author, 1);
+        let edited = todo_delta(&mut store, &dot, |todo_tx| {
+            todo_tx.write_register("text", MvRegValue::String("Buy oat milk".to_string()));
+            todo_tx.write_register("done", MvRegValue::Bool(true));
+            todo_tx.write_register("modified_at", MvRegValue::I64(2));
+        });
+        history.record(&edited, author, 2);
+        let touched = todo_delta(&mut store, &dot, |todo_tx| {
+            todo_tx.write_register("modified_at", MvRegValue::I64(3));
+        });
+        history.record(&touched, author, 3);
+
+        let changes: Vec<&str> = history
+            .entries(LIST, &dot)
+            .map(|entry| entry.change.as_str())
+            .collect();
+        assert_eq!(
+            changes,
+            [
+                "created 'Buy milk'",
+                "marked done, set text to 'Buy oat milk'"
+            ]
+        );
+        assert!(history.entries("Other", &dot).next().is_none());
+    }
+
+    #[test]
+    fn test_caps_entries_per_todo_and_overall() {
+        let mut store = CausalDotStore::<OrMap<String>>::default();
+        let mut history = EditHistory::default();
+        let toggle = |store: &mut TodoStore, dot: &Dot, done: bool| {
+            todo_delta(store, dot, |todo_tx| {
+                todo_tx.write_register("done", MvRegValue::Bool(done));
+            })
+        };
+
+        let first = Dot::mint(Identifier::new(1, 0), 1);
+        for i in 0..MAX_ENTRIES_PER_TODO + 5 {
+            let delta = toggle(&mut store, &first, i % 2 == 0);
+            history.record(&delta, ReplicaId::new(1), i as i64);
+        }
+        let entries: Vec<_> = history.entries(LIST, &first).collect();
+        assert_eq!(entries.len(), MAX_ENTRIES_PER_TODO);
+        assert_eq!(entries[0].at, 5);
+
+        // Filling up with other todos drops the first todo's oldest entries
+        for i in 0..MAX_ENTRIES {
+            let dot = Dot::mint(Identifier::new(2, 0), i as u64 + 1);
+            // A fresh store keeps the transaction cheap; only the delta matters
+            let delta = toggle(&mut TodoStore::default(), &dot, true);
+            history.record(&delta, ReplicaId::new(2), i as i64);
+        }
+        assert_eq!(history.len(), MAX_ENTRIES);
+        assert!(history.entries(LIST, &first).next().is_none());
+    }
+}
@@ -1,7 +1,9 @@
 // ABOUTME: Keyboard input handling and action execution.
 // ABOUTME: Maps key events to app state changes and CRDT operations.
 
-use crate::app::{App, Mode};
+use crate::app::{
+    App, ConflictField, EditForm, FormField, FormValues, Mode, PromptKind, ReplicaId, SortMode,
+};
 use crossterm::event::{KeyCode, KeyEvent};
 use dson::crdts::mvreg::MvRegValue;
 use std::io;
@@ -18,17 +20,353 @@ pub enum Action {
     Delete,
     EnterInsertMode,
     EnterEditMode,
+    /// Open the form editing text, done, level, repeating and color together.
+    EditForm,
     ToggleIsolation,
     AddRandomTodos,
     ScrollLogsUp,
     ScrollLogsDown,
+    ToggleDetail,
+    CloseDetail,
+    DuplicateTodo,
+    CopyText,
+    AddTag,
+    RemoveTag,
+    /// Prompt for tags to filter by, or clear the tag filter if one is active.
+    FilterByTag,
+    ClearTagFilter,
+    /// Filter by the next tag in use, then by none again.
+    CycleTagFilter,
+    Search,
+    EditNotes,
+    ToggleContextView,
+    NextList,
+    PreviousList,
+    NewList,
+    ExportSnapshot,
+    ExpandSubtasks,
+    ToggleContextFocus,
+    ScrollContextUp,
+    ScrollContextDown,
+    CycleLevel,
+    /// Cycle the selected todo's recurrence: none → daily → weekly.
+    CycleRecurrence,
+    /// Cycle the selected todo's color label through the palette.
+    CycleColor,
+    ToggleGroupByLevel,
+    /// Cycle how the list is shown: priority, alphabetical, done last, conflicted first.
+    CycleSortMode,
+    ToggleRanks,
+    /// Keep todos not matching the search in the list, or hide them again.
+    ToggleSearchShowsAll,
+    /// Select the next todo matching the search.
+    NextMatch,
+    /// Select the previous todo matching the search.
+    PreviousMatch,
+    CycleCategory,
+    SetCategory,
+    FilterByCategory,
+    /// Prompt for the replica whose todos to show.
+    FilterByCreator,
+    ToggleDoneWins,
+    ToggleAutoComplete,
+    RequestResync,
+    JumpToTop,
+    JumpToBottom,
+    ResolveConflict,
+    AddSubtask,
+    Grab,
+    RestoreOrphans,
+    /// Remove priority entries repeating a todo listed earlier.
+    RepairPriority,
+    ToggleWrap,
+    ToggleHideDone,
+    ToggleFriendlyNames,
+    Undo,
+    Redo,
+    PurgeOrphans,
+    /// Move the selected todo to this priority index, clamped to the list.
+    /// Also stands in for move-to-top (`MoveTo(0)`, Ctrl-K) and move-to-bottom
+    /// (`MoveTo(usize::MAX)`, Ctrl-J), so there are no separate variants.
+    MoveTo(usize),
+    /// Open the popup for picking who the selected todo is assigned to.
+    Assign,
+    /// Rebuild the store with a minimal causal context.
+    Compact,
+    /// Assign the selected todo to this replica.
+    AssignTo(ReplicaId),
+    /// Run the action waiting for confirmation.
+    ConfirmPending,
+    /// Drop the action waiting for confirmation.
+    CancelPending,
+    ToggleHelpOverlay,
+    ScrollHelpUp,
+    ScrollHelpDown,
+    /// Show the edit history of the selected todo.
+    ShowHistory,
+    CloseHistory,
+    ScrollHistoryUp,
+    ScrollHistoryDown,
+    /// Mark every todo in the list done, or all not done if they already are.
+    ToggleAllDone,
+    RequestContextReport,
+    CloseContextReport,
+    SelectNextPeer,
+    SelectPreviousPeer,
+    /// Exchange full states with this peer right away.
+    SyncWithPeer(ReplicaId),
+    /// Delete every done todo in the list.
+    ClearDone,
+    /// Delete every todo in the list.
+    ClearAll,
+    /// Reverse the shared priority order of the list.
+    ReversePriority,
+    /// Sort the shared priority order of the list by text.
+    SortAlphabetically,
+    /// Move done todos below open ones in the shared priority order.
+    SortByDone,
+}
+
+/// Normal mode keys with a short hint for the help bar and what they do, as
+/// listed in the help overlay.
+pub const KEY_BINDINGS: &[(&str, &str, &str)] = &[
+    ("q", "quit", "Quit"),
+    ("?", "help", "Show this help (?/Esc: close)"),
+    ("i", "add", "Add todo"),
+    ("Enter", "edit", "Edit todo"),
+    (
+        "M",
+        "form",
+        "Edit text, done, level, repeat and color in one form",
+    ),
+    ("Space", "toggle", "Toggle done"),
+    ("Ctrl-Space", "toggle all", "Toggle done on every todo"),
+    ("d", "delete", "Delete todo (asks y/n)"),
+    ("x", "clear done", "Delete all done todos (asks y/n)"),
+    (
+        "Ctrl-X",
+        "clear all",
+        "Delete every todo of the list (asks y/n)",
+    ),
+    ("u", "undo", "Undo"),
+    ("U/Ctrl-R", "redo", "Redo"),
+    ("j/k", "nav", "Navigate"),
+    ("J/K", "priority", "Change priority"),
+    (
+        "Ctrl-K/Ctrl-J",
+        "to top/bottom",
+        "Move todo to the top/bottom",
+    ),
+    ("a", "assign", "Assign todo to a replica"),
+    ("m", "grab", "Grab todo to move it"),
+    ("g/G", "top/bottom", "Jump to first/last todo"),
+    ("c", "resolve conflict", "Resolve conflict"),
+    ("↑/↓", "scroll logs", "Scroll logs"),
+    ("p", "isolate", "Toggle isolation"),
+    ("R", "resync", "Resync now"),
+    (
+        "S",
+        "peers",
+        "Peer list: compare contexts, s: sync with one",
+    ),
+    ("r", "random", "Add sample todos"),
+    ("v", "details", "Show todo details"),
+    ("H", "history", "Show the todo's edit history"),
+    ("y", "duplicate", "Duplicate todo"),
+    ("Y", "copy", "Copy todo text to the clipboard"),
+    ("n/o", "notes", "Edit notes"),
+    ("e", "subtasks", "Expand subtasks"),
+    ("I", "add subtask", "Add subtask"),
+    ("+/-", "tag", "Add/remove tag"),
+    ("#", "filter", "Filter by tags, or clear the tag filter"),
+    (
+        "Ctrl-T",
+        "next tag",
+        "Filter by the next tag in use, then show all",
+    ),
+    ("/", "search", "Search (Enter: keep, Esc: clear)"),
+    (
+        "V",
+        "show all",
+        "Keep non-matching todos listed; n/N: next/previous match",
+    ),
+    ("C", "context gaps", "Toggle causal context view"),
+    ("F", "focus context", "Focus causal context panel"),
+    ("N", "names", "Toggle friendly replica names"),
+    ("l", "level", "Cycle level"),
+    ("E", "repeat", "Cycle repeating: daily, weekly, off"),
+    ("B", "color", "Cycle the todo's color label"),
+    ("L", "group by level", "Group by level"),
+    ("~", "reverse", "Reverse the priority order"),
+    ("Ctrl-A", "sort a-z", "Sort the priority order by text"),
+    (
+        "Ctrl-D",
+        "done last",
+        "Move done todos to the bottom of the priority order",
+    ),
+    (
+        "s",
+        "sort",
+        "Cycle the view's sort (the shared order is kept)",
+    ),
+    (".", "ranks", "Show each todo's rank in the priority list"),
+    ("t", "category", "Cycle category"),
+    ("T", "new category", "Set a new category"),
+    ("f", "filter category", "Filter by category"),
+    ("b", "filter creator", "Filter by creator replica"),
+    ("w", "wrap", "Toggle wrapping"),
+    ("h", "hide done", "Hide/show done todos"),
+    ("D", "done wins", "Toggle done wins"),
+    (
+        "A",
+        "auto-complete",
+        "Toggle auto-completing todos with all subtasks done",
+    ),
+    ("Tab/Shift-Tab", "lists", "Switch list"),
+    ("Ctrl-N", "new list", "Create list"),
+    ("X", "export", "Export snapshot"),
+    ("Z", "compact", "Compact the causal context"),
+    ("O", "restore orphans", "Restore orphaned todos"),
+    ("P", "purge orphans", "Purge orphaned todos"),
+    (
+        "=",
+        "repair order",
+        "Remove repeated entries from the priority order",
+    ),
+];
+
+impl Action {
+    /// Whether the action destroys data and needs a y/n confirmation first.
+    pub fn needs_confirmation(self) -> bool {
+        matches!(self, Action::Delete | Action::ClearDone | Action::ClearAll)
+    }
+
+    /// Whether the action works on the selected todo, and so has nothing to do
+    /// while the list shows none.
+    fn needs_selected_todo(self) -> bool {
+        matches!(
+            self,
+            Action::MoveUp
+                | Action::MoveDown
+                | Action::JumpToTop
+                | Action::JumpToBottom
+                | Action::MovePriorityUp
+                | Action::MovePriorityDown
+                | Action::MoveTo(_)
+                | Action::Grab
+                | Action::ToggleDone
+                | Action::Delete
+                | Action::EnterEditMode
+                | Action::EditForm
+                | Action::DuplicateTodo
+                | Action::CopyText
+                | Action::AddTag
+                | Action::RemoveTag
+                | Action::EditNotes
+                | Action::ExpandSubtasks
+                | Action::AddSubtask
+                | Action::CycleLevel
+                | Action::CycleRecurrence
+                | Action::CycleColor
+                | Action::CycleCategory
+                | Action::SetCategory
+                | Action::ResolveConflict
+                | Action::Assign
+                | Action::AssignTo(_)
+                | Action::ShowHistory
+        )
+    }
+
+    /// Whether the action changes the priority order, which a sorted view hides.
+    fn reorders(self) -> bool {
+        matches!(
+            self,
+            Action::MovePriorityUp
+                | Action::MovePriorityDown
+                | Action::MoveTo(_)
+                | Action::Grab
+                | Action::ReversePriority
+                | Action::SortAlphabetically
+                | Action::SortByDone
+        )
+    }
 }
 
 /// Handle a key event and return the corresponding action.
 pub fn handle_key(key: KeyEvent, app: &App) -> Option<Action> {
+    // The help overlay swallows keys until closed with ? or Esc
+    if app.ui_state.show_help {
+        return match key.code {
+            KeyCode::Char('?') | KeyCode::Esc => Some(Action::ToggleHelpOverlay),
+            KeyCode::Char('j') | KeyCode::Down => Some(Action::ScrollHelpDown),
+            KeyCode::Char('k') | KeyCode::Up => Some(Action::ScrollHelpUp),
+            _ => None,
+        };
+    }
+    if app.ui_state.history_dot.is_some() {
+        return match key.code {
+            KeyCode::Char('H') | KeyCode::Esc => Some(Action::CloseHistory),
+            KeyCode::Char('j') | KeyCode::Down => Some(Action::ScrollHistoryDown),
+            KeyCode::Char('k') | KeyCode::Up => Some(Action::ScrollHistoryUp),
+            _ => None,
+        };
+    }
+    // The peer list handles its own keys
+    if app.ui_state.show_context_report {
+        return Some(handle_peer_list_key(key, app));
+    }
+
+    // Any key but y cancels a pending confirmation
+    if app.ui_state.pending_action.is_some() {
+        return Some(match key.code {
+            KeyCode::Char('y') => Action::ConfirmPending,
+            _ => Action::CancelPending,
+        });
+    }
+
+    // n/N step through the matches while non-matching todos are listed too
+    if app.ui_state.mode == Mode::Normal
+        && app.ui_state.search_shows_all
+        && !app.ui_state.search_query.is_empty()
+    {
+        match (key.code, key.modifiers) {
+            (KeyCode::Char('n'), crossterm::event::KeyModifiers::NONE) => {
+                return Some(Action::NextMatch);
+            }
+            (KeyCode::Char('N'), _) => return Some(Action::PreviousMatch),
+            _ => {}
+        }
+    }
+
     match app.ui_state.mode {
+        Mode::Normal if app.ui_state.context_focused => {
+            handle_context_focus_key(key).or_else(|| handle_normal_mode(key))
+        }
         Mode::Normal => handle_normal_mode(key),
-        Mode::Insert => None, // Insert mode handled differently
+        // Text input and subtasks are handled differently
+        Mode::Insert
+        | Mode::Notes
+        | Mode::Subtasks
+        | Mode::ResolveConflict
+        | Mode::Prompt(_)
+        | Mode::Grab
+        | Mode::Assign
+        | Mode::Form => None,
+    }
+}
+
+/// Handle keys in the peer list: j/k select, s syncs, anything else closes it.
+fn handle_peer_list_key(key: KeyEvent, app: &App) -> Action {
+    match key.code {
+        KeyCode::Char('j') => Action::SelectNextPeer,
+        KeyCode::Char('k') => Action::SelectPreviousPeer,
+        KeyCode::Char('s') => app
+            .sorted_peers()
+            .get(app.ui_state.peer_index)
+            .map_or(Action::CloseContextReport, |peer| {
+                Action::SyncWithPeer(*peer)
+            }),
+        _ => Action::CloseContextReport,
     }
 }
 
@@ -38,52 +376,140 @@ fn handle_normal_mode(key: KeyEvent) -> Option<Action> {
 
     match (key.code, key.modifiers) {
         (KeyCode::Char('q'), _) => Some(Action::Quit),
+        (KeyCode::Char('?'), _) => Some(Action::ToggleHelpOverlay),
         (KeyCode::Char('j'), KeyModifiers::NONE) => Some(Action::MoveDown),
         (KeyCode::Char('k'), KeyModifiers::NONE) => Some(Action::MoveUp),
         (KeyCode::Char('J'), _) => Some(Action::MovePriorityDown),
         (KeyCode::Char('K'), _) => Some(Action::MovePriorityUp),
+        (KeyCode::Char('k'), KeyModifiers::CONTROL) => Some(Action::MoveTo(0)),
+        (KeyCode::Char('j'), KeyModifiers::CONTROL) => Some(Action::MoveTo(usize::MAX)),
+        (KeyCode::Char('g'), _) => Some(Action::JumpToTop),
+        (KeyCode::Char('G'), _) => Some(Action::JumpToBottom),
+        (KeyCode::Char('c'), _) => Some(Action::ResolveConflict),
+        (KeyCode::Char(' '), KeyModifiers::CONTROL) => Some(Action::ToggleAllDone),
         (KeyCode::Char(' '), _) => Some(Action::ToggleDone),
+        (KeyCode::Char('d'), KeyModifiers::CONTROL) => Some(Action::SortByDone),
         (KeyCode::Char('d'), _) => Some(Action::Delete),
+        (KeyCode::Char('x'), KeyModifiers::CONTROL) => Some(Action::ClearAll),
+        (KeyCode::Char('x'), _) => Some(Action::ClearDone),
         (KeyCode::Char('i'), _) => Some(Action::EnterInsertMode),
         (KeyCode::Char('p'), _) => Some(Action::ToggleIsolation),
+        (KeyCode::Char('r'), KeyModifiers::CONTROL) => Some(Action::Redo),
         (KeyCode::Char('r'), _) => Some(Action::AddRandomTodos),
         (KeyCode::Up, _) => Some(Action::ScrollLogsUp),
         (KeyCode::Down, _) => Some(Action::ScrollLogsDown),
         (KeyCode::Enter, _) => Some(Action::EnterEditMode),
+        (KeyCode::Char('M'), _) => Some(Action::EditForm),
+        (KeyCode::Char('v'), _) => Some(Action::ToggleDetail),
+        (KeyCode::Char('H'), _) => Some(Action::ShowHistory),
+        (KeyCode::Char('y'), _) => Some(Action::DuplicateTodo),
+        (KeyCode::Char('Y'), _) => Some(Action::CopyText),
+        (KeyCode::Char('+'), _) => Some(Action::AddTag),
+        (KeyCode::Char('-'), _) => Some(Action::RemoveTag),
+        (KeyCode::Char('#'), _) => Some(Action::FilterByTag),
+        (KeyCode::Char('/'), _) => Some(Action::Search),
+        (KeyCode::Char('n'), KeyModifiers::NONE) | (KeyCode::Char('o'), _) => {
+            Some(Action::EditNotes)
+        }
+        (KeyCode::Char('C'), _) => Some(Action::ToggleContextView),
+        (KeyCode::Char('X'), _) => Some(Action::ExportSnapshot),
+        (KeyCode::Char('e'), _) => Some(Action::ExpandSubtasks),
+        (KeyCode::Char('I'), _) => Some(Action::AddSubtask),
+        (KeyCode::Char('F'), _) => Some(Action::ToggleContextFocus),
+        (KeyCode::Char('l'), _) => Some(Action::CycleLevel),
+        (KeyCode::Char('E'), _) => Some(Action::CycleRecurrence),
+        (KeyCode::Char('B'), _) => Some(Action::CycleColor),
+        (KeyCode::Char('L'), _) => Some(Action::ToggleGroupByLevel),
+        (KeyCode::Char('s'), KeyModifiers::NONE) => Some(Action::CycleSortMode),
+        (KeyCode::Char('.'), _) => Some(Action::ToggleRanks),
+        (KeyCode::Char('~'), _) => Some(Action::ReversePriority),
+        (KeyCode::Char('V'), _) => Some(Action::ToggleSearchShowsAll),
+        (KeyCode::Char('t'), KeyModifiers::CONTROL) => Some(Action::CycleTagFilter),
+        (KeyCode::Char('t'), _) => Some(Action::CycleCategory),
+        (KeyCode::Char('T'), _) => Some(Action::SetCategory),
+        (KeyCode::Char('f'), _) => Some(Action::FilterByCategory),
+        (KeyCode::Char('b'), _) => Some(Action::FilterByCreator),
+        (KeyCode::Char('D'), _) => Some(Action::ToggleDoneWins),
+        (KeyCode::Char('A'), _) => Some(Action::ToggleAutoComplete),
+        (KeyCode::Char('R'), _) => Some(Action::RequestResync),
+        (KeyCode::Char('S'), _) => Some(Action::RequestContextReport),
+        (KeyCode::Char('m'), _) => Some(Action::Grab),
+        (KeyCode::Char('a'), KeyModifiers::CONTROL) => Some(Action::SortAlphabetically),
+        (KeyCode::Char('a'), _) => Some(Action::Assign),
+        (KeyCode::Char('Z'), _) => Some(Action::Compact),
+        (KeyCode::Char('O'), _) => Some(Action::RestoreOrphans),
+        (KeyCode::Char('w'), _) => Some(Action::ToggleWrap),
+        (KeyCode::Char('h'), _) => Some(Action::ToggleHideDone),
+        (KeyCode::Char('N'), _) => Some(Action::ToggleFriendlyNames),
+        (KeyCode::Char('u'), _) => Some(Action::Undo),
+        (KeyCode::Char('U'), _) => Some(Action::Redo),
+        (KeyCode::Char('P'), _) => Some(Action::PurgeOrphans),
+        (KeyCode::Char('='), _) => Some(Action::RepairPriority),
+        (KeyCode::Tab, _) => Some(Action::NextList),
+        (KeyCode::BackTab, _) => Some(Action::PreviousList),
+        (KeyCode::Char('n'), KeyModifiers::CONTROL) => Some(Action::NewList),
+        (KeyCode::Esc, _) => Some(Action::CloseDetail),
         _ => None,
     }
 }
 
-/// Handle keys in insert mode.
+/// Keys that act on the causal context panel while it is focused.
+fn handle_context_focus_key(key: KeyEvent) -> Option<Action> {
+    match key.code {
+        KeyCode::Char('j') | KeyCode::Down => Some(Action::ScrollContextDown),
+        KeyCode::Char('k') | KeyCode::Up => Some(Action::ScrollContextUp),
+        KeyCode::Esc => Some(Action::ToggleContextFocus),
+        _ => None,
+    }
+}
+
+/// Handle keys in insert mode. Typing and Backspace work at the cursor,
+/// which Left/Right/Home/End move.
 pub fn handle_insert_key(key: KeyEvent, app: &mut App) -> io::Result<bool> {
+    // Tests and callers may fill the buffer directly, leaving the cursor behind
+    let len = app.ui_state.input_buffer.chars().count();
+    let cursor = app.ui_state.cursor_pos.min(len);
+
     match key.code {
         KeyCode::Enter => {
             let text = app.ui_state.input_buffer.clone();
             if !text.is_empty() {
                 if let Some(editing_dot) = app.ui_state.editing_dot.take() {
                     // Editing existing todo - inline transaction
+                    app.record_undo(&editing_dot);
                     let dot_key = crate::priority::DotKey::new(&editing_dot);
-                    let mut tx = app.store.transact(app.identifier());
-                    tx.in_map(dot_key.as_str(), |todo_tx| {
-                        todo_tx.write_register("text", MvRegValue::String(text));
+                    let delta = app.transact_list(|list_tx| {
+                        list_tx.in_map(dot_key.as_str(), |todo_tx| {
+                            todo_tx.write_register("text", MvRegValue::String(text));
+                            crate::todo::touch(todo_tx);
+                        });
                     });
-                    let delta = tx.commit();
                     app.broadcast_delta(delta)?;
                 } else {
                     // DEMO BEGIN #1: Complete transaction lifecycle
                     // Creating new todo - inline transaction
-                    let (dot_key, _dot) = app.next_dot_key();
+                    let (dot_key, dot) = app.next_dot_key();
+                    app.record_undo(&dot);
+                    let replica_id = app.replica_id;
                     let mut tx = app.store.transact(app.identifier());
 
-                    // Create the todo with text and done fields
-                    tx.in_map(dot_key.as_str(), |todo_tx| {
-                        todo_tx.write_register("text", MvRegValue::String(text));
-                        todo_tx.write_register("done", MvRegValue::Bool(false));
-                    });
+                    // Todos live inside the current list
+                    tx.in_map(app.current_list.as_str(), |list_tx| {
+                        // Create the todo with text and done fields
+                        list_tx.in_map(dot_key.as_str(), |todo_tx| {
+                            todo_tx.write_register("text", MvRegValue::String(text));
+                            todo_tx.write_register("done", MvRegValue::Bool(false));
+                            crate::todo::write_creation_metadata(
+                                todo_tx,
+                                replica_id,
+                                crate::todo::now_millis(),
+                            );
+                        });
 
-                    // Add to priority array at top
-                    tx.in_array("priority", |arr_tx| {
-                        arr_tx.insert_register(0, MvRegValue::String(dot_key.into_inner()));
+                        // Add to priority array at top
+                        list_tx.in_array("priority", |arr_tx| {
+                            arr_tx.insert_register(0, MvRegValue::String(dot_key.into_inner()));
+                        });
                     });
 
                     let delta = tx.commit();
@@ -93,30 +519,645 @@ pub fn handle_insert_key(key: KeyEvent, app: &mut App) -> io::Result<bool> {
             }
 
             app.ui_state.input_buffer.clear();
+            app.ui_state.cursor_pos = 0;
             app.ui_state.editing_dot = None;
             app.ui_state.mode = Mode::Normal;
             Ok(true)
         }
         KeyCode::Esc => {
             app.ui_state.input_buffer.clear();
+            app.ui_state.cursor_pos = 0;
             app.ui_state.editing_dot = None;
             app.ui_state.mode = Mode::Normal;
             Ok(true)
         }
         KeyCode::Char(c) => {
-            app.ui_state.input_buffer.push(c);
+            // Count characters, not bytes, so the limit is the same for any script
+            if len < app.max_text_len {
+                let at = byte_index(&app.ui_state.input_buffer, cursor);
+                app.ui_state.input_buffer.insert(at, c);
+                app.ui_state.cursor_pos = cursor + 1;
+            }
             Ok(true)
         }
         KeyCode::Backspace => {
-            app.ui_state.input_buffer.pop();
+            if cursor > 0 {
+                let at = byte_index(&app.ui_state.input_buffer, cursor - 1);
+                app.ui_state.input_buffer.remove(at);
+                app.ui_state.cursor_pos = cursor - 1;
+            }
+            Ok(true)
+        }
+        KeyCode::Left => {
+            app.ui_state.cursor_pos = cursor.saturating_sub(1);
+            Ok(true)
+        }
+        KeyCode::Right => {
+            app.ui_state.cursor_pos = (cursor + 1).min(len);
+            Ok(true)
+        }
+        KeyCode::Home => {
+            app.ui_state.cursor_pos = 0;
+            Ok(true)
+        }
+        KeyCode::End => {
+            app.ui_state.cursor_pos = len;
             Ok(true)
         }
         _ => Ok(true),
     }
 }
 
+/// Byte offset of the character at `chars` in `text`, or its length past the end.
+pub fn byte_index(text: &str, chars: usize) -> usize {
+    text.char_indices()
+        .nth(chars)
+        .map_or(text.len(), |(at, _)| at)
+}
+
+/// Separator placed between concurrent notes values when editing them.
+pub const NOTES_CONFLICT_SEPARATOR: &str = "\n-----\n";
+
+/// Handle keys in notes mode. Enter inserts a newline, Ctrl-S saves.
+pub fn handle_notes_key(key: KeyEvent, app: &mut App) -> io::Result<()> {
+    use crossterm::event::KeyModifiers;
+
+    match (key.code, key.modifiers) {
+        (KeyCode::Char('s'), KeyModifiers::CONTROL) => {
+            if let Some(dot) = app.ui_state.editing_dot.take() {
+                let notes = std::mem::take(&mut app.ui_state.input_buffer);
+                let dot_key = crate::priority::DotKey::new(&dot);
+                let delta = app.transact_list(|list_tx| {
+                    list_tx.in_map(dot_key.as_str(), |todo_tx| {
+                        todo_tx.write_register("notes", MvRegValue::String(notes));
+                        crate::todo::touch(todo_tx);
+                    });
+                });
+                app.broadcast_delta(delta)?;
+            }
+            app.ui_state.input_buffer.clear();
+            app.ui_state.mode = Mode::Normal;
+        }
+        (KeyCode::Esc, _) => {
+            app.ui_state.input_buffer.clear();
+            app.ui_state.editing_dot = None;
+            app.ui_state.mode = Mode::Normal;
+        }
+        (KeyCode::Enter, _) => app.ui_state.input_buffer.push('\n'),
+        (KeyCode::Backspace, _) => {
+            app.ui_state.input_buffer.pop();
+        }
+        (KeyCode::Char(c), KeyModifiers::NONE | KeyModifiers::SHIFT) => {
+            app.ui_state.input_buffer.push(c);
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Handle keys in subtasks mode, operating on the expanded todo's checklist.
+pub fn handle_subtasks_key(key: KeyEvent, app: &mut App) -> io::Result<()> {
+    // The todo may have been deleted by a peer while expanded
+    let Some((dot, todo)) = app
+        .ui_state
+        .editing_dot
+        .and_then(|dot| Some((dot, crate::todo::read_todo(app.list_store(), &dot)?)))
+    else {
+        app.ui_state.editing_dot = None;
+        app.ui_state.mode = Mode::Normal;
+        return Ok(());
+    };
+    let index = app.ui_state.subtask_index;
+    let dot_key = crate::priority::DotKey::new(&dot);
+
+    match key.code {
+        KeyCode::Char('j') | KeyCode::Down if index + 1 < todo.subtasks.len() => {
+            app.ui_state.subtask_index += 1;
+        }
+        KeyCode::Char('k') | KeyCode::Up => {
+            app.ui_state.subtask_index = index.saturating_sub(1);
+        }
+        KeyCode::Char(' ') => {
+            if let Some(subtask) = todo.subtasks.get(index) {
+                let done = !subtask.primary_done();
+                let delta = app.transact_list(|list_tx| {
+                    list_tx.in_map(dot_key.as_str(), |todo_tx| {
+                        crate::todo::set_subtask_done(todo_tx, &subtask.key, done);
+                        crate::todo::touch(todo_tx);
+                    });
+                });
+                app.broadcast_delta(delta)?;
+                if done {
+                    app.complete_if_subtasks_done(&dot)?;
+                }
+            }
+        }
+        KeyCode::Char('d') => {
+            if let Some(subtask) = todo.subtasks.get(index) {
+                let delta = app.transact_list(|list_tx| {
+                    list_tx.in_map(dot_key.as_str(), |todo_tx| {
+                        crate::todo::remove_subtask(todo_tx, index, &subtask.key);
+                        crate::todo::touch(todo_tx);
+                    });
+                });
+                app.broadcast_delta(delta)?;
+                if index + 1 >= todo.subtasks.len() {
+                    app.ui_state.subtask_index = index.saturating_sub(1);
+                }
+            }
+        }
+        KeyCode::Char('a') => {
+            app.ui_state.input_buffer.clear();
+            app.ui_state.mode = Mode::Prompt(PromptKind::AddSubtask);
+        }
+        KeyCode::Char('e') | KeyCode::Esc => {
+            app.ui_state.editing_dot = None;
+            app.ui_state.mode = Mode::Normal;
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Handle keys in the assign popup: j/k pick a replica, Enter assigns the
+/// todo to it, Esc closes the popup.
+pub fn handle_assign_key(key: KeyEvent, app: &mut App) -> io::Result<()> {
+    let candidates = app.assignee_candidates();
+    match key.code {
+        KeyCode::Char('j') | KeyCode::Down if app.ui_state.assign_index + 1 < candidates.len() => {
+            app.ui_state.assign_index += 1;
+        }
+        KeyCode::Char('k') | KeyCode::Up => {
+            app.ui_state.assign_index = app.ui_state.assign_index.saturating_sub(1);
+        }
+        KeyCode::Enter => {
+            let dot = app.ui_state.editing_dot.take();
+            app.ui_state.mode = Mode::Normal;
+            if let (Some(dot), Some(&assignee)) = (dot, candidates.get(app.ui_state.assign_index)) {
+                app.select_dot(&dot);
+                return execute_action(app, Action::AssignTo(assignee));
+            }
+        }
+        KeyCode::Esc => {
+            app.ui_state.editing_dot = None;
+            app.ui_state.mode = Mode::Normal;
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Handle keys in the edit form. Up/Down and Tab/Shift-Tab move between
+/// rows, typing edits the text, Space cycles the focused value, Enter moves
+/// to the next row and saves on the last one, Esc discards the changes.
+pub fn handle_form_key(key: KeyEvent, app: &mut App) -> io::Result<()> {
+    let max_text_len = app.max_text_len;
+    let Some(form) = app.ui_state.form.as_mut() else {
+        close_form(app);
+        return Ok(());
+    };
+    let field = form.field;
+
+    match key.code {
+        KeyCode::Down | KeyCode::Tab => {
+            if let Some(next) = field.next() {
+                form.field = next;
+            }
+        }
+        KeyCode::Up | KeyCode::BackTab => form.field = field.previous(),
+        KeyCode::Enter => match field.next() {
+            Some(next) => form.field = next,
+            None => return save_form(app),
+        },
+        KeyCode::Esc => close_form(app),
+        KeyCode::Backspace if field == FormField::Text => {
+            form.values.text.pop();
+        }
+        // Count characters, not bytes, like insert mode
+        KeyCode::Char(c)
+            if field == FormField::Text && form.values.text.chars().count() < max_text_len =>
+        {
+            form.values.text.push(c);
+        }
+        KeyCode::Char(' ') => {
+            let values = &mut form.values;
+            match field {
+                FormField::Text => {}
+                FormField::Done => values.done = !values.done,
+                FormField::Level => values.level = crate::todo::Level::cycle(values.level),
+                FormField::Recur => {
+                    values.recur = crate::todo::Recurrence::cycle(values.recur);
+                }
+                FormField::Color => values.color = crate::todo::TodoColor::cycle(values.color),
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Write the fields the form changed in one transaction and close it.
+///
+/// Untouched fields are not written, so a conflict on them stays for the
+/// conflict popup instead of being resolved in favour of the shown value.
+/// Like insert mode, an emptied text is not saved. Marking a repeating todo
+/// done respawns it like Space does.
+fn save_form(app: &mut App) -> io::Result<()> {
+    let form = app.ui_state.form.take();
+    let dot = app.ui_state.editing_dot.take();
+    app.ui_state.mode = Mode::Normal;
+    let (Some(form), Some(dot)) = (form, dot) else {
+        return Ok(());
+    };
+
+    let mut changed = form.changed_fields();
+    if form.values.text.is_empty() {
+        changed.retain(|field| *field != FormField::Text);
+    }
+    if changed.is_empty() {
+        app.log("No changes to save".to_string());
+        return Ok(());
+    }
+    // Writing to a todo a peer deleted meanwhile would leave an orphan
+    if crate::todo::read_todo(app.list_store(), &dot).is_none() {
+        app.log("The todo was deleted meanwhile; changes discarded".to_string());
+        return Ok(());
+    }
+
+    app.record_undo(&dot);
+    let values = &form.values;
+    let dot_key = crate::priority::DotKey::new(&dot);
+    let delta = app.transact_list(|list_tx| {
+        list_tx.in_map(dot_key.as_str(), |todo_tx| {
+            for field in &changed {
+                match field {
+                    FormField::Text => {
+                        todo_tx.write_register("text", MvRegValue::String(values.text.clone()));
+                    }
+                    FormField::Done => {
+                        todo_tx.write_register("done", MvRegValue::Bool(values.done));
+                    }
+                    FormField::Level => crate::todo::set_level(todo_tx, values.level),
+                    FormField::Recur => crate::todo::set_recur(todo_tx, values.recur),
+                    FormField::Color => crate::todo::set_color(todo_tx, values.color),
+                }
+            }
+            crate::todo::touch(todo_tx);
+        });
+    });
+    app.broadcast_delta(delta)?;
+    if values.done
+        && changed.contains(&FormField::Done)
+        && let Some(todo) = crate::todo::read_todo(app.list_store(), &dot)
+    {
+        respawn_if_recurring(app, &dot, &todo)?;
+    }
+
+    let fields: Vec<String> = changed
+        .iter()
+        .map(|field| field.label().to_lowercase())
+        .collect();
+    app.log(format!(
+        "Saved {} of '{}'",
+        fields.join(", "),
+        crate::todo::read_todo(app.list_store(), &dot)
+            .map(|todo| todo.primary_text().to_string())
+            .unwrap_or_default()
+    ));
+    // Grouping, sorting or a filter may move or hide the todo
+    app.select_dot(&dot);
+    app.clamp_selection();
+    Ok(())
+}
+
+fn close_form(app: &mut App) {
+    app.ui_state.form = None;
+    app.ui_state.editing_dot = None;
+    app.ui_state.mode = Mode::Normal;
+}
+
+/// Handle keys in the conflict popup.
+///
+/// Picking a value writes it back in a new transaction. The write causally
+/// follows every conflicting value we have seen, so once it propagates the
+/// register holds just the picked value on all replicas.
+pub fn handle_conflict_key(key: KeyEvent, app: &mut App) -> io::Result<()> {
+    let Some((dot, todo)) = app
+        .ui_state
+        .editing_dot
+        .and_then(|dot| Some((dot, crate::todo::read_todo(app.list_store(), &dot)?)))
+    else {
+        close_conflict_popup(app);
+        return Ok(());
+    };
+    let values = match app.ui_state.conflict_field {
+        ConflictField::Text => todo.text.len(),
+        ConflictField::Done => todo.done.len(),
+    };
+
+    match key.code {
+        KeyCode::Char('j') | KeyCode::Down if app.ui_state.conflict_index + 1 < values => {
+            app.ui_state.conflict_index += 1;
+        }
+        KeyCode::Char('k') | KeyCode::Up => {
+            app.ui_state.conflict_index = app.ui_state.conflict_index.saturating_sub(1);
+        }
+        KeyCode::Tab => {
+            let other = app.ui_state.conflict_field.other();
+            if conflicted(&todo, other) {
+                app.ui_state.conflict_field = other;
+                app.ui_state.conflict_index = 0;
+            }
+        }
+        KeyCode::Enter => {
+            let index = app.ui_state.conflict_index;
+            let value = match app.ui_state.conflict_field {
+                ConflictField::Text => todo.text.get(index).cloned().map(MvRegValue::String),
+                ConflictField::Done => todo.done.get(index).copied().map(MvRegValue::Bool),
+            };
+            let Some(value) = value else {
+                return Ok(());
+            };
+            let field = app.ui_state.conflict_field.key();
+
+            let dot_key = crate::priority::DotKey::new(&dot);
+            let delta = app.transact_list(|list_tx| {
+                list_tx.in_map(dot_key.as_str(), |todo_tx| {
+                    todo_tx.write_register(field, value);
+                    crate::todo::touch(todo_tx);
+                });
+            });
+            app.broadcast_delta(delta)?;
+            advance_conflict_popup(app, &todo);
+        }
+        KeyCode::Char('m') if app.ui_state.conflict_field == ConflictField::Text => {
+            // Keep both: every replica merging this conflict writes the same text
+            let merged = crate::todo::merge_text_values(&todo.text);
+            let dot_key = crate::priority::DotKey::new(&dot);
+            let delta = app.transact_list(|list_tx| {
+                list_tx.in_map(dot_key.as_str(), |todo_tx| {
+                    todo_tx.write_register("text", MvRegValue::String(merged));
+                    crate::todo::touch(todo_tx);
+                });
+            });
+            app.broadcast_delta(delta)?;
+            advance_conflict_popup(app, &todo);
+        }
+        KeyCode::Esc | KeyCode::Char('c') => close_conflict_popup(app),
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Whether the given register of a todo holds several values.
+fn conflicted(todo: &crate::todo::Todo, field: ConflictField) -> bool {
+    match field {
+        ConflictField::Text => todo.text.len() > 1,
+        ConflictField::Done => todo.done.len() > 1,
+    }
+}
+
+/// After resolving one register, move on to the other if it is still in
+/// conflict, else close the popup.
+fn advance_conflict_popup(app: &mut App, todo: &crate::todo::Todo) {
+    let other = app.ui_state.conflict_field.other();
+    if conflicted(todo, other) {
+        app.ui_state.conflict_field = other;
+        app.ui_state.conflict_index = 0;
+    } else {
+        close_conflict_popup(app);
+    }
+}
+
+fn close_conflict_popup(app: &mut App) {
+    app.ui_state.editing_dot = None;
+    app.ui_state.mode = Mode::Normal;
+}
+
+/// Execute a user's action, asking for confirmation first if it is destructive.
+///
+/// The todo a delete confirmation is about is kept in `editing_dot`.
+pub fn request_action(app: &mut App, action: Action) -> io::Result<()> {
+    if !action.needs_confirmation() {
+        return execute_action(app, action);
+    }
+    if action == Action::ClearDone {
+        if app.done_dots().is_empty() {
+            app.log("No done todos to clear".to_string());
+        } else {
+            app.ui_state.pending_action = Some(action);
+        }
+    } else if action == Action::ClearAll {
+        if app.get_todos_ordered().is_empty() && app.find_orphans().is_empty() {
+            app.log("No todos to clear".to_string());
+        } else {
+            app.ui_state.pending_action = Some(action);
+        }
+    } else if let Some((dot, _)) = app.visible_todos().get(app.ui_state.selected_index) {
+        app.ui_state.editing_dot = Some(*dot);
+        app.ui_state.pending_action = Some(action);
+    } else {
+        // Nothing selected: say so instead of asking
+        return execute_action(app, action);
+    }
+    Ok(())
+}
+
+/// Handle keys in a prompt.
+pub fn handle_prompt_key(key: KeyEvent, app: &mut App, kind: PromptKind) -> io::Result<()> {
+    // Adding a subtask returns to the checklist it was started from
+    let return_mode = if kind == PromptKind::AddSubtask {
+        Mode::Subtasks
+    } else {
+        Mode::Normal
+    };
+
+    match key.code {
+        KeyCode::Enter => {
+            let input = app.ui_state.input_buffer.trim();
+            let input = if matches!(kind, PromptKind::AddSubtask | PromptKind::Search) {
+                input.to_string()
+            } else {
+                input.trim_start_matches('#').to_string()
+            };
+            app.ui_state.input_buffer.clear();
+            app.ui_state.mode = return_mode;
+            submit_prompt(app, kind, input)
+        }
+        KeyCode::Esc => {
+            app.ui_state.input_buffer.clear();
+            app.ui_state.mode = return_mode;
+            if kind == PromptKind::Search {
+                update_search(app);
+            }
+            Ok(())
+        }
+        KeyCode::Char(c) => {
+            app.ui_state.input_buffer.push(c);
+            if kind == PromptKind::Search {
+                update_search(app);
+            }
+            Ok(())
+        }
+        KeyCode::Backspace => {
+            app.ui_state.input_buffer.pop();
+            if kind == PromptKind::Search {
+                update_search(app);
+            }
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Filter the list by the search prompt's input as it is typed, keeping the
+/// selected todo selected while it still matches.
+fn update_search(app: &mut App) {
+    let selected = app.selected_dot();
+    app.ui_state.search_query = app.ui_state.input_buffer.clone();
+    app.ui_state.selected_index = 0;
+    if let Some(dot) = selected {
+        app.select_dot(&dot);
+    }
+}
+
+/// Text of `todo` for the clipboard: every value of a conflicted text, one
+/// per line.
+fn clipboard_text(todo: &crate::todo::Todo) -> String {
+    todo.text.join("\n")
+}
+
+/// Apply the submitted input of a prompt.
+fn submit_prompt(app: &mut App, kind: PromptKind, input: String) -> io::Result<()> {
+    match kind {
+        PromptKind::AddTag | PromptKind::RemoveTag => {
+            let todos = app.visible_todos();
+            let Some((dot, _)) = todos.get(app.ui_state.selected_index) else {
+                return Ok(());
+            };
+            if input.is_empty() {
+                return Ok(());
+            }
+
+            let dot_key = crate::priority::DotKey::new(dot);
+            let delta = app.transact_list(|list_tx| {
+                list_tx.in_map(dot_key.as_str(), |todo_tx| {
+                    if kind == PromptKind::AddTag {
+                        crate::todo::add_tag(todo_tx, &input);
+                    } else {
+                        crate::todo::remove_tag(todo_tx, &input);
+                    }
+                    crate::todo::touch(todo_tx);
+                });
+            });
+            app.broadcast_delta(delta)?;
+            app.clamp_selection();
+        }
+        PromptKind::TagFilter => {
+            let tags = input
+                .split_whitespace()
+                .map(|tag| tag.trim_start_matches('#'))
+                .filter(|tag| !tag.is_empty());
+            app.ui_state.filter_tags.extend(tags.map(str::to_string));
+            app.ui_state.selected_index = 0;
+        }
+        PromptKind::SetCategory => {
+            let todos = app.visible_todos();
+            let Some((dot, _)) = todos.get(app.ui_state.selected_index) else {
+                return Ok(());
+            };
+
+            let category = (!input.is_empty()).then_some(input.as_str());
+            let dot_key = crate::priority::DotKey::new(dot);
+            let delta = app.transact_list(|list_tx| {
+                list_tx.in_map(dot_key.as_str(), |todo_tx| {
+                    crate::todo::set_category(todo_tx, category);
+                    crate::todo::touch(todo_tx);
+                });
+            });
+            app.broadcast_delta(delta)?;
+            app.clamp_selection();
+        }
+        PromptKind::CategoryFilter => {
+            app.ui_state.category_filter = (!input.is_empty()).then_some(input);
+            app.ui_state.selected_index = 0;
+        }
+        PromptKind::CreatorFilter => {
+            let input = input.trim();
+            if input.is_empty() || input == "*" {
+                app.ui_state.filter_creator = None;
+            } else if let Some(creator) = (input.len() == 2)
+                .then(|| u8::from_str_radix(input, 16).ok())
+                .flatten()
+            {
+                app.ui_state.filter_creator = Some(creator);
+            } else {
+                app.log(format!("Not a replica ID (2 hex digits): '{input}'"));
+                return Ok(());
+            }
+            app.ui_state.selected_index = 0;
+        }
+        PromptKind::Search => {
+            app.ui_state.search_query = input;
+            app.clamp_selection();
+        }
+        PromptKind::NewList => {
+            if !input.is_empty() {
+                app.create_list(&input)?;
+            }
+        }
+        PromptKind::AddSubtask => {
+            let Some(dot) = app.ui_state.editing_dot else {
+                return Ok(());
+            };
+            let Some(todo) = crate::todo::read_todo(app.list_store(), &dot) else {
+                return Ok(());
+            };
+            if input.is_empty() {
+                return Ok(());
+            }
+
+            // Insert below the selected subtask and select the new one
+            let index = if todo.subtasks.is_empty() {
+                0
+            } else {
+                app.ui_state.subtask_index + 1
+            };
+            let (subtask_key, _) = app.next_dot_key();
+            let dot_key = crate::priority::DotKey::new(&dot);
+            let delta = app.transact_list(|list_tx| {
+                list_tx.in_map(dot_key.as_str(), |todo_tx| {
+                    crate::todo::insert_subtask(todo_tx, index, subtask_key.as_str(), &input);
+                    crate::todo::touch(todo_tx);
+                });
+            });
+            app.broadcast_delta(delta)?;
+            app.ui_state.subtask_index = index;
+        }
+    }
+    Ok(())
+}
+
 /// Execute an action on the app state.
 pub fn execute_action(app: &mut App, action: Action) -> io::Result<()> {
+    let sort_mode = app.ui_state.sort_mode;
+    if action.reorders() && sort_mode != SortMode::Priority {
+        app.log(format!(
+            "Reordering is off while sorted by {}; press s for priority order",
+            sort_mode.as_str()
+        ));
+        return Ok(());
+    }
+    if action.needs_selected_todo() && app.visible_todos().is_empty() {
+        app.ui_state.selected_index = 0;
+        if app.get_todos_ordered().is_empty() {
+            app.log("No todos yet; press i to add one".to_string());
+        } else {
+            app.log("No todo shown; the filters hide every todo".to_string());
+        }
+        return Ok(());
+    }
+
     match action {
         Action::Quit => {
             // Handled by caller
@@ -129,54 +1170,175 @@ pub fn execute_action(app: &mut App, action: Action) -> io::Result<()> {
             Ok(())
         }
         Action::MoveDown => {
-            let todos = app.get_todos_ordered();
+            let todos = app.visible_todos();
             if app.ui_state.selected_index + 1 < todos.len() {
                 app.ui_state.selected_index += 1;
             }
             Ok(())
         }
+        Action::ResolveConflict => {
+            let todos = app.visible_todos();
+            if let Some((dot, todo)) = todos.get(app.ui_state.selected_index) {
+                let field = if conflicted(todo, ConflictField::Text) {
+                    ConflictField::Text
+                } else if conflicted(todo, ConflictField::Done) {
+                    ConflictField::Done
+                } else {
+                    app.log("Selected todo has no text or done conflict".to_string());
+                    return Ok(());
+                };
+                app.ui_state.mode = Mode::ResolveConflict;
+                app.ui_state.editing_dot = Some(*dot);
+                app.ui_state.conflict_field = field;
+                app.ui_state.conflict_index = 0;
+            }
+            Ok(())
+        }
+        Action::JumpToTop => {
+            app.ui_state.selected_index = 0;
+            Ok(())
+        }
+        Action::JumpToBottom => {
+            let todos = app.visible_todos();
+            app.ui_state.selected_index = todos.len().saturating_sub(1);
+            Ok(())
+        }
         Action::ToggleDone => {
-            let todos = app.get_todos_ordered();
+            let todos = app.visible_todos();
             if let Some((dot, todo)) = todos.get(app.ui_state.selected_index) {
-                let new_done = !todo.primary_done();
+                let new_done = !app.is_done(todo);
+                app.record_undo(dot);
                 let dot_key = crate::priority::DotKey::new(dot);
 
                 // DEMO BEGIN #2: Simple nested transaction
                 let mut tx = app.store.transact(app.identifier());
-                tx.in_map(dot_key.as_str(), |todo_tx| {
-                    todo_tx.write_register("done", MvRegValue::Bool(new_done));
+                // Todos live inside the current list
+                tx.in_map(app.current_list.as_str(), |list_tx| {
+                    list_tx.in_map(dot_key.as_str(), |todo_tx| {
+                        todo_tx.write_register("done", MvRegValue::Bool(new_done));
+                        crate::todo::touch(todo_tx);
+                    });
                 });
                 let delta = tx.commit();
                 // DEMO END #2
 
                 app.broadcast_delta(delta)?;
+                if new_done {
+                    respawn_if_recurring(app, dot, todo)?;
+                }
             }
             Ok(())
         }
-        Action::Delete => {
+        Action::ClearDone => {
+            app.clear_done()?;
+            Ok(())
+        }
+        Action::ClearAll => {
+            app.clear_all()?;
+            Ok(())
+        }
+        Action::ToggleAllDone => {
             let todos = app.get_todos_ordered();
+            if todos.is_empty() {
+                app.log("No todos to mark done".to_string());
+                return Ok(());
+            }
+            let new_done = todos.iter().any(|(_, todo)| !app.is_done(todo));
+
+            // One transaction over every todo, so peers get a single delta
+            let delta = app.transact_list(|list_tx| {
+                for (dot, _) in &todos {
+                    let dot_key = crate::priority::DotKey::new(dot);
+                    list_tx.in_map(dot_key.as_str(), |todo_tx| {
+                        todo_tx.write_register("done", MvRegValue::Bool(new_done));
+                        crate::todo::touch(todo_tx);
+                    });
+                }
+            });
+            app.broadcast_delta(delta)?;
+            app.log(format!(
+                "[Replica {}] Marked all {} todos {}",
+                app.replica_id,
+                todos.len(),
+                if new_done { "done" } else { "not done" }
+            ));
+            Ok(())
+        }
+        Action::Delete => {
+            let todos = app.visible_todos();
+            let indices = todos
+                .get(app.ui_state.selected_index)
+                .map(|(dot, _)| crate::priority::priority_indices(app.list_store(), dot))
+                .unwrap_or_default();
             if let Some((dot, _)) = todos.get(app.ui_state.selected_index)
-                && let Some(index) = crate::priority::find_priority_index(&app.store.store, dot)
+                && !indices.is_empty()
             {
-                let mut tx = app.store.transact(app.identifier());
-                tx.in_array("priority", |arr_tx| {
-                    arr_tx.remove(index);
+                app.record_undo(dot);
+
+                // Drop the todo's own map too, or its registers would stay in the
+                // store (and every full-state payload) forever
+                let dot_key = crate::priority::DotKey::new(dot);
+                let delta = app.transact_list(|list_tx| {
+                    list_tx.in_array("priority", |arr_tx| {
+                        // Repeated entries too, highest first so the indices hold
+                        for index in indices.iter().rev() {
+                            arr_tx.remove(*index);
+                        }
+                    });
+                    list_tx.remove(dot_key.as_str());
                 });
-                let delta = tx.commit();
 
                 app.broadcast_delta(delta)?;
 
-                // Adjust selection if needed
-                let todos_after = app.get_todos_ordered();
-                if app.ui_state.selected_index >= todos_after.len() && !todos_after.is_empty() {
-                    app.ui_state.selected_index = todos_after.len() - 1;
-                }
+                app.clamp_selection();
             }
             Ok(())
         }
+        Action::ConfirmPending => {
+            let Some(action) = app.ui_state.pending_action.take() else {
+                return Ok(());
+            };
+            let Some(dot) = app.ui_state.editing_dot.take() else {
+                return execute_action(app, action);
+            };
+            // The list may have changed under the dialog; act on the todo it showed
+            if app.visible_todos().iter().any(|(d, _)| *d == dot) {
+                app.select_dot(&dot);
+                execute_action(app, action)
+            } else {
+                app.log("Todo is gone, nothing to delete".to_string());
+                Ok(())
+            }
+        }
+        Action::RequestContextReport => app.request_context_report(),
+        Action::CloseContextReport => {
+            app.ui_state.show_context_report = false;
+            Ok(())
+        }
+        Action::SelectNextPeer => {
+            let last = app.peers.len().saturating_sub(1);
+            app.ui_state.peer_index = (app.ui_state.peer_index + 1).min(last);
+            Ok(())
+        }
+        Action::SelectPreviousPeer => {
+            app.ui_state.peer_index = app.ui_state.peer_index.saturating_sub(1);
+            Ok(())
+        }
+        Action::SyncWithPeer(peer_id) => app.sync_with_peer(peer_id),
+        Action::ToggleHelpOverlay => {
+            app.ui_state.show_help = !app.ui_state.show_help;
+            app.ui_state.help_scroll = 0;
+            Ok(())
+        }
+        Action::CancelPending => {
+            app.ui_state.pending_action = None;
+            app.ui_state.editing_dot = None;
+            Ok(())
+        }
         Action::EnterInsertMode => {
             app.ui_state.mode = Mode::Insert;
             app.ui_state.input_buffer.clear();
+            app.ui_state.cursor_pos = 0;
             app.ui_state.editing_dot = None;
             Ok(())
         }
@@ -184,6 +1346,51 @@ pub fn execute_action(app: &mut App, action: Action) -> io::Result<()> {
             app.toggle_isolation()?;
             Ok(())
         }
+        Action::Undo => {
+            app.undo()?;
+            Ok(())
+        }
+        Action::Redo => {
+            app.redo()?;
+            Ok(())
+        }
+        Action::ToggleFriendlyNames => {
+            app.ui_state.friendly_names = !app.ui_state.friendly_names;
+            Ok(())
+        }
+        Action::ToggleWrap => {
+            // Items keep their index when they grow, so the selection needs no remapping
+            app.ui_state.wrap_mode = app.ui_state.wrap_mode.toggle();
+            Ok(())
+        }
+        Action::ToggleHideDone => {
+            // Keep the selected todo selected; if it is now hidden, the
+            // selection stays at its index
+            let selected = app.selected_dot();
+            app.ui_state.hide_done = !app.ui_state.hide_done;
+            if let Some(dot) = selected {
+                app.select_dot(&dot);
+            }
+            app.clamp_selection();
+            Ok(())
+        }
+        Action::RestoreOrphans => {
+            app.restore_orphans()?;
+            Ok(())
+        }
+        Action::PurgeOrphans => {
+            app.purge_orphans()?;
+            app.clamp_selection();
+            Ok(())
+        }
+        Action::RepairPriority => {
+            app.repair_priority()?;
+            Ok(())
+        }
+        Action::RequestResync => {
+            app.request_resync()?;
+            Ok(())
+        }
         Action::AddRandomTodos => {
             app.add_random_todos()?;
             Ok(())
@@ -196,8 +1403,460 @@ pub fn execute_action(app: &mut App, action: Action) -> io::Result<()> {
             app.ui_state.log_scroll = app.ui_state.log_scroll.saturating_sub(3);
             Ok(())
         }
+        Action::ToggleDetail => {
+            app.ui_state.show_detail = !app.ui_state.show_detail;
+            Ok(())
+        }
+        Action::CycleRecurrence => {
+            let todos = app.visible_todos();
+            if let Some((dot, todo)) = todos.get(app.ui_state.selected_index) {
+                let recur = crate::todo::Recurrence::cycle(todo.primary_recur());
+                let dot_key = crate::priority::DotKey::new(dot);
+                app.record_undo(dot);
+                let delta = app.transact_list(|list_tx| {
+                    list_tx.in_map(dot_key.as_str(), |todo_tx| {
+                        crate::todo::set_recur(todo_tx, recur);
+                        crate::todo::touch(todo_tx);
+                    });
+                });
+                app.broadcast_delta(delta)?;
+            }
+            Ok(())
+        }
+        Action::CycleColor => {
+            let todos = app.visible_todos();
+            if let Some((dot, todo)) = todos.get(app.ui_state.selected_index) {
+                let color = crate::todo::TodoColor::cycle(todo.primary_color());
+                let dot_key = crate::priority::DotKey::new(dot);
+                app.record_undo(dot);
+                let delta = app.transact_list(|list_tx| {
+                    list_tx.in_map(dot_key.as_str(), |todo_tx| {
+                        crate::todo::set_color(todo_tx, color);
+                        crate::todo::touch(todo_tx);
+                    });
+                });
+                app.broadcast_delta(delta)?;
+            }
+            Ok(())
+        }
+        Action::CycleLevel => {
+            let todos = app.visible_todos();
+            if let Some((dot, todo)) = todos.get(app.ui_state.selected_index) {
+                let level = crate::todo::Level::cycle(todo.primary_level());
+                let dot_key = crate::priority::DotKey::new(dot);
+                let delta = app.transact_list(|list_tx| {
+                    list_tx.in_map(dot_key.as_str(), |todo_tx| {
+                        crate::todo::set_level(todo_tx, level);
+                        crate::todo::touch(todo_tx);
+                    });
+                });
+                app.broadcast_delta(delta)?;
+
+                // Grouping may move the todo
+                app.select_dot(dot);
+            }
+            Ok(())
+        }
+        Action::CycleCategory => {
+            let todos = app.visible_todos();
+            if let Some((dot, todo)) = todos.get(app.ui_state.selected_index) {
+                let categories = app.categories();
+                if categories.is_empty() {
+                    app.log("No categories yet; press T to name one".to_string());
+                    return Ok(());
+                }
+                let category = crate::todo::cycle_through(&categories, todo.primary_category());
+                let dot_key = crate::priority::DotKey::new(dot);
+                let delta = app.transact_list(|list_tx| {
+                    list_tx.in_map(dot_key.as_str(), |todo_tx| {
+                        crate::todo::set_category(todo_tx, category.as_deref());
+                        crate::todo::touch(todo_tx);
+                    });
+                });
+                app.broadcast_delta(delta)?;
+
+                // A category filter may hide the todo
+                app.select_dot(dot);
+                app.clamp_selection();
+            }
+            Ok(())
+        }
+        Action::ToggleGroupByLevel => {
+            let selected = app.selected_dot();
+            app.ui_state.group_by_level = !app.ui_state.group_by_level;
+            if let Some(dot) = selected {
+                app.select_dot(&dot);
+            }
+            Ok(())
+        }
+        Action::ReversePriority => {
+            let selected = app.selected_dot();
+            if app.reverse_priority()? == 0 {
+                app.log("Fewer than two todos; nothing to reverse".to_string());
+            }
+            if let Some(dot) = selected {
+                app.select_dot(&dot);
+            }
+            Ok(())
+        }
+        Action::SortAlphabetically => {
+            let selected = app.selected_dot();
+            if app.sort_priority_alphabetically()? == 0 {
+                app.log("Already in alphabetical order".to_string());
+            }
+            if let Some(dot) = selected {
+                app.select_dot(&dot);
+            }
+            Ok(())
+        }
+        Action::SortByDone => {
+            let selected = app.selected_dot();
+            if app.sort_priority_by_done()? == 0 {
+                app.log("Done todos are already last".to_string());
+            }
+            if let Some(dot) = selected {
+                app.select_dot(&dot);
+            }
+            Ok(())
+        }
+        Action::ToggleRanks => {
+            app.ui_state.show_ranks = !app.ui_state.show_ranks;
+            Ok(())
+        }
+        Action::ToggleSearchShowsAll => {
+            if app.ui_state.search_query.is_empty() {
+                app.log("No search to show matches of; press / to search".to_string());
+                return Ok(());
+            }
+            let selected = app.selected_dot();
+            app.ui_state.search_shows_all = !app.ui_state.search_shows_all;
+            if let Some(dot) = selected {
+                app.select_dot(&dot);
+            }
+            Ok(())
+        }
+        Action::NextMatch | Action::PreviousMatch => {
+            if !app.select_next_match(action == Action::NextMatch) {
+                app.log(format!(
+                    "No other todo matches '{}'",
+                    app.ui_state.search_query
+                ));
+            }
+            Ok(())
+        }
+        Action::CycleSortMode => {
+            let selected = app.selected_dot();
+            app.ui_state.sort_mode = app.ui_state.sort_mode.cycle();
+            if let Some(dot) = selected {
+                app.select_dot(&dot);
+            }
+            Ok(())
+        }
+        Action::ToggleDoneWins => {
+            app.done_wins = !app.done_wins;
+            let state = if app.done_wins { "on" } else { "off" };
+            app.log(format!("Done-wins policy {state}"));
+            Ok(())
+        }
+        Action::ToggleAutoComplete => {
+            app.auto_complete = !app.auto_complete;
+            let state = if app.auto_complete { "on" } else { "off" };
+            app.log(format!(
+                "Auto-completing todos with all subtasks done {state}"
+            ));
+            Ok(())
+        }
+        Action::ToggleContextFocus => {
+            app.ui_state.context_focused = !app.ui_state.context_focused;
+            Ok(())
+        }
+        Action::ShowHistory => {
+            if let Some((dot, _)) = app.visible_todos().get(app.ui_state.selected_index) {
+                app.ui_state.history_dot = Some(*dot);
+                app.ui_state.history_scroll = 0;
+            }
+            Ok(())
+        }
+        Action::CloseHistory => {
+            app.ui_state.history_dot = None;
+            Ok(())
+        }
+        Action::ScrollHistoryUp => {
+            app.ui_state.history_scroll = app.ui_state.history_scroll.saturating_sub(1);
+            Ok(())
+        }
+        Action::ScrollHistoryDown => {
+            if let Some(dot) = app.ui_state.history_dot {
+                let entries = app.history.entries(&app.current_list, &dot).count();
+                if app.ui_state.history_scroll + 1 < entries {
+                    app.ui_state.history_scroll += 1;
+                }
+            }
+            Ok(())
+        }
+        Action::ScrollHelpUp => {
+            app.ui_state.help_scroll = app.ui_state.help_scroll.saturating_sub(1);
+            Ok(())
+        }
+        Action::ScrollHelpDown => {
+            if app.ui_state.help_scroll + 1 < crate::ui::help_overlay_len() {
+                app.ui_state.help_scroll += 1;
+            }
+            Ok(())
+        }
+        Action::ScrollContextUp => {
+            app.ui_state.context_scroll = app.ui_state.context_scroll.saturating_sub(1);
+            Ok(())
+        }
+        Action::ScrollContextDown => {
+            // One row per node in either context view
+            let nodes = crate::anti_entropy::context_gaps(&app.store.context).len();
+            if app.ui_state.context_scroll + 1 < nodes {
+                app.ui_state.context_scroll += 1;
+            }
+            Ok(())
+        }
+        Action::ToggleContextView => {
+            app.ui_state.show_context_gaps = !app.ui_state.show_context_gaps;
+            Ok(())
+        }
+        Action::CloseDetail => {
+            if app.ui_state.show_detail {
+                app.ui_state.show_detail = false;
+            } else if !app.ui_state.search_query.is_empty() {
+                // Esc also drops a kept search
+                let selected = app.selected_dot();
+                app.ui_state.search_query.clear();
+                app.ui_state.search_shows_all = false;
+                if let Some(dot) = selected {
+                    app.select_dot(&dot);
+                }
+            }
+            Ok(())
+        }
+        Action::DuplicateTodo => {
+            let todos = app.visible_todos();
+            if let Some((dot, todo)) = todos.get(app.ui_state.selected_index)
+                && let Some(index) = crate::priority::find_priority_index(app.list_store(), dot)
+            {
+                let text = todo.primary_text().to_string();
+                let done = app.is_done(todo);
+                let (dot_key, new_dot) = app.next_dot_key();
+                app.record_undo(&new_dot);
+                let replica_id = app.replica_id;
+
+                // New todo with its own dot, inserted right below the original
+                let delta = app.transact_list(|list_tx| {
+                    list_tx.in_map(dot_key.as_str(), |todo_tx| {
+                        todo_tx.write_register("text", MvRegValue::String(text.clone()));
+                        todo_tx.write_register("done", MvRegValue::Bool(done));
+                        crate::todo::write_creation_metadata(
+                            todo_tx,
+                            replica_id,
+                            crate::todo::now_millis(),
+                        );
+                    });
+                    list_tx.in_array("priority", |arr_tx| {
+                        arr_tx.insert_register(index + 1, MvRegValue::String(dot_key.into_inner()));
+                    });
+                });
+                app.broadcast_delta(delta)?;
+                app.log(format!("Duplicated todo: {text}"));
+
+                app.select_dot(&new_dot);
+            }
+            Ok(())
+        }
+        Action::CopyText => {
+            let todos = app.visible_todos();
+            if let Some((_, todo)) = todos.get(app.ui_state.selected_index) {
+                let text = clipboard_text(todo);
+                match app.copy_to_clipboard(&text) {
+                    Ok(()) => app.log(format!(
+                        "[Replica {}] Copied to clipboard: {}",
+                        app.replica_id,
+                        text.replace('\n', " | ")
+                    )),
+                    Err(e) => app.log(format!(
+                        "[Replica {}] Copy failed, no clipboard: {e}",
+                        app.replica_id
+                    )),
+                }
+            }
+            Ok(())
+        }
+        Action::ExportSnapshot => {
+            let path = std::path::PathBuf::from(format!("snapshot-{}.bin", app.replica_id));
+            match app.export_binary_snapshot(&path) {
+                Ok(()) => app.log(format!(
+                    "[Replica {}] Exported snapshot to {}",
+                    app.replica_id,
+                    path.display()
+                )),
+                Err(e) => app.log(format!(
+                    "[Replica {}] Snapshot export failed: {e}",
+                    app.replica_id
+                )),
+            }
+            Ok(())
+        }
+        Action::NextList => {
+            app.cycle_list(1);
+            Ok(())
+        }
+        Action::PreviousList => {
+            app.cycle_list(-1);
+            Ok(())
+        }
+        Action::FilterByTag if !app.ui_state.filter_tags.is_empty() => {
+            execute_action(app, Action::ClearTagFilter)
+        }
+        Action::ClearTagFilter => {
+            app.ui_state.filter_tags.clear();
+            app.clamp_selection();
+            Ok(())
+        }
+        Action::CycleTagFilter => {
+            let tags = app.tags();
+            if tags.is_empty() {
+                app.log("No tags yet; press + to add one".to_string());
+                return Ok(());
+            }
+            // A filter on several tags, typed with #, starts over at the first
+            let filter = app.ui_state.sorted_filter_tags();
+            let current = match filter.as_slice() {
+                [tag] => Some(tag.as_str()),
+                _ => None,
+            };
+            let next = crate::todo::cycle_through(&tags, current);
+            let selected = app.selected_dot();
+            app.ui_state.filter_tags.clear();
+            app.ui_state.filter_tags.extend(next);
+            if let Some(dot) = selected {
+                app.select_dot(&dot);
+            }
+            app.clamp_selection();
+            Ok(())
+        }
+        Action::AddTag
+        | Action::RemoveTag
+        | Action::FilterByTag
+        | Action::Search
+        | Action::NewList
+        | Action::SetCategory
+        | Action::FilterByCategory
+        | Action::FilterByCreator => {
+            let kind = match action {
+                Action::AddTag => PromptKind::AddTag,
+                Action::RemoveTag => PromptKind::RemoveTag,
+                Action::NewList => PromptKind::NewList,
+                Action::Search => PromptKind::Search,
+                Action::SetCategory => PromptKind::SetCategory,
+                Action::FilterByCategory => PromptKind::CategoryFilter,
+                Action::FilterByCreator => PromptKind::CreatorFilter,
+                _ => PromptKind::TagFilter,
+            };
+            app.ui_state.input_buffer.clear();
+            if kind == PromptKind::Search {
+                // Start from the current query so it can be refined
+                app.ui_state
+                    .input_buffer
+                    .clone_from(&app.ui_state.search_query);
+            }
+            app.ui_state.mode = Mode::Prompt(kind);
+            Ok(())
+        }
+        Action::Grab => {
+            let todos = app.visible_todos();
+            if let Some((dot, _)) = todos.get(app.ui_state.selected_index)
+                && let Some(index) = crate::priority::find_priority_index(app.list_store(), dot)
+            {
+                app.ui_state.editing_dot = Some(*dot);
+                app.ui_state.grab_target = index;
+                app.ui_state.input_buffer.clear();
+                app.ui_state.mode = Mode::Grab;
+            }
+            Ok(())
+        }
+        Action::Compact => {
+            app.compact()?;
+            Ok(())
+        }
+        Action::Assign => {
+            let todos = app.visible_todos();
+            if let Some((dot, todo)) = todos.get(app.ui_state.selected_index) {
+                // Start on the current assignee
+                let candidates = app.assignee_candidates();
+                app.ui_state.assign_index = todo
+                    .assignee
+                    .first()
+                    .and_then(|assignee| {
+                        candidates.iter().position(|id| id.to_string() == *assignee)
+                    })
+                    .unwrap_or(0);
+                app.ui_state.editing_dot = Some(*dot);
+                app.ui_state.mode = Mode::Assign;
+            }
+            Ok(())
+        }
+        Action::AssignTo(assignee) => {
+            let todos = app.visible_todos();
+            if let Some((dot, _)) = todos.get(app.ui_state.selected_index) {
+                app.record_undo(dot);
+                let dot_key = crate::priority::DotKey::new(dot);
+                let delta = app.transact_list(|list_tx| {
+                    list_tx.in_map(dot_key.as_str(), |todo_tx| {
+                        crate::todo::set_assignee(todo_tx, assignee);
+                        crate::todo::touch(todo_tx);
+                    });
+                });
+                app.broadcast_delta(delta)?;
+                app.log(format!(
+                    "[Replica {}] Assigned todo to replica {}",
+                    app.replica_id, assignee
+                ));
+            }
+            Ok(())
+        }
+        Action::MoveTo(target) => {
+            let todos = app.visible_todos();
+            if let Some((dot, _)) = todos.get(app.ui_state.selected_index) {
+                move_to(app, dot, target)?;
+            }
+            Ok(())
+        }
+        Action::ExpandSubtasks => {
+            let todos = app.visible_todos();
+            if let Some((dot, _)) = todos.get(app.ui_state.selected_index) {
+                app.ui_state.mode = Mode::Subtasks;
+                app.ui_state.editing_dot = Some(*dot);
+                app.ui_state.subtask_index = 0;
+            }
+            Ok(())
+        }
+        Action::AddSubtask => {
+            let todos = app.visible_todos();
+            if let Some((dot, todo)) = todos.get(app.ui_state.selected_index) {
+                // Append after the last subtask; the checklist stays open afterwards
+                app.ui_state.editing_dot = Some(*dot);
+                app.ui_state.subtask_index = todo.subtasks.len().saturating_sub(1);
+                app.ui_state.input_buffer.clear();
+                app.ui_state.mode = Mode::Prompt(PromptKind::AddSubtask);
+            }
+            Ok(())
+        }
+        Action::EditNotes => {
+            let todos = app.visible_todos();
+            if let Some((dot, todo)) = todos.get(app.ui_state.selected_index) {
+                app.ui_state.mode = Mode::Notes;
+                // Concurrent versions are shown one after another to merge by hand
+                app.ui_state.input_buffer = todo.notes.join(NOTES_CONFLICT_SEPARATOR);
+                app.ui_state.editing_dot = Some(*dot);
+            }
+            Ok(())
+        }
         Action::EnterEditMode => {
-            let todos = app.get_todos_ordered();
+            let todos = app.visible_todos();
             if let Some((dot, todo)) = todos.get(app.ui_state.selected_index) {
                 app.ui_state.mode = Mode::Insert;
                 // Show all text values if there's a conflict, same as in the list view
@@ -206,71 +1865,2047 @@ pub fn execute_action(app: &mut App, action: Action) -> io::Result<()> {
                 } else {
                     todo.primary_text().to_string()
                 };
+                app.ui_state.cursor_pos = app.ui_state.input_buffer.chars().count();
                 app.ui_state.editing_dot = Some(*dot);
             }
             Ok(())
         }
+        Action::EditForm => {
+            let todos = app.visible_todos();
+            if let Some((dot, todo)) = todos.get(app.ui_state.selected_index) {
+                let values = FormValues::of(todo, app.is_done(todo));
+                app.ui_state.form = Some(EditForm::new(values));
+                app.ui_state.editing_dot = Some(*dot);
+                app.ui_state.mode = Mode::Form;
+            }
+            Ok(())
+        }
         Action::MovePriorityUp => {
-            let todos = app.get_todos_ordered();
-            let idx = app.ui_state.selected_index;
-            if idx > 0 && idx < todos.len() {
-                let (dot, _) = &todos[idx];
-
+            let todos = app.visible_todos();
+            if let Some((dot, _)) = todos.get(app.ui_state.selected_index) {
                 // Read current position
                 if let Some(current_pos) =
-                    crate::priority::find_priority_index(&app.store.store, dot)
-                    && current_pos > 0
+                    crate::priority::find_priority_index(app.list_store(), dot)
+                    && let Some(target) = app.adjacent_shown_index(current_pos, false)
                 {
-                    // Move up in priority (lower index)
+                    // Move up in priority (lower index), above the next shown todo
+                    app.record_undo(dot);
                     let dot_key = crate::priority::DotKey::new(dot);
-                    let mut tx = app.store.transact(app.identifier());
-                    tx.in_array("priority", |arr_tx| {
-                        arr_tx.remove(current_pos);
-                        arr_tx.insert_register(
-                            current_pos - 1,
-                            MvRegValue::String(dot_key.into_inner()),
-                        );
+                    let delta = app.transact_list(|list_tx| {
+                        list_tx.in_array("priority", |arr_tx| {
+                            arr_tx.remove(current_pos);
+                            arr_tx
+                                .insert_register(target, MvRegValue::String(dot_key.into_inner()));
+                        });
                     });
-                    let delta = tx.commit();
                     app.broadcast_delta(delta)?;
 
-                    // Update UI selection
-                    app.ui_state.selected_index -= 1;
+                    // Follow the moved todo
+                    app.select_dot(dot);
                 }
             }
             Ok(())
         }
         Action::MovePriorityDown => {
-            let todos = app.get_todos_ordered();
-            let idx = app.ui_state.selected_index;
-            if idx < todos.len() {
-                let (dot, _) = &todos[idx];
-
+            let todos = app.visible_todos();
+            if let Some((dot, _)) = todos.get(app.ui_state.selected_index) {
                 // Read current position
                 if let Some(current_pos) =
-                    crate::priority::find_priority_index(&app.store.store, dot)
+                    crate::priority::find_priority_index(app.list_store(), dot)
+                    && let Some(target) = app.adjacent_shown_index(current_pos, true)
                 {
-                    let priority_len = crate::priority::read_priority(&app.store.store).len();
-                    if current_pos + 1 < priority_len {
-                        // Move down in priority (higher index)
-                        let dot_key = crate::priority::DotKey::new(dot);
-                        let mut tx = app.store.transact(app.identifier());
-                        tx.in_array("priority", |arr_tx| {
+                    // Move down in priority (higher index), below the next shown todo
+                    app.record_undo(dot);
+                    let dot_key = crate::priority::DotKey::new(dot);
+                    let replica_id = app.replica_id;
+                    let own = crate::todo::own_postponed(app.list_store(), dot, replica_id);
+                    let delta = app.transact_list(|list_tx| {
+                        list_tx.in_array("priority", |arr_tx| {
                             arr_tx.remove(current_pos);
                             arr_tx.insert_register(
-                                current_pos + 1,
-                                MvRegValue::String(dot_key.into_inner()),
+                                target,
+                                MvRegValue::String(dot_key.clone().into_inner()),
                             );
                         });
-                        let delta = tx.commit();
-                        app.broadcast_delta(delta)?;
+                        list_tx.in_map(dot_key.as_str(), |todo_tx| {
+                            crate::todo::increment_postponed(todo_tx, replica_id, own);
+                        });
+                    });
+                    app.broadcast_delta(delta)?;
 
-                        // Update UI selection
-                        app.ui_state.selected_index += 1;
-                    }
+                    // Follow the moved todo
+                    app.select_dot(dot);
                 }
             }
             Ok(())
         }
     }
 }
+
+/// Handle keys while a todo is picked up.
+///
+/// j/k move the drop point, digits type a 1-based position, and Enter drops
+/// the todo there with a single remove+insert, so no intermediate positions
+/// are broadcast.
+pub fn handle_grab_key(key: KeyEvent, app: &mut App) -> io::Result<()> {
+    let last = crate::priority::priority_entries(app.list_store())
+        .len()
+        .saturating_sub(1);
+    // Hidden done todos are stepped over, so the drop point stays on screen
+    let below = app.adjacent_shown_index(app.ui_state.grab_target, true);
+    let above = app.adjacent_shown_index(app.ui_state.grab_target, false);
+    let ui = &mut app.ui_state;
+
+    match key.code {
+        KeyCode::Char('j') | KeyCode::Down => {
+            ui.input_buffer.clear();
+            ui.grab_target = below.unwrap_or(ui.grab_target).min(last);
+        }
+        KeyCode::Char('k') | KeyCode::Up => {
+            ui.input_buffer.clear();
+            ui.grab_target = above.unwrap_or(ui.grab_target);
+        }
+        KeyCode::Char(c) if c.is_ascii_digit() => {
+            ui.input_buffer.push(c);
+            if let Ok(position) = ui.input_buffer.parse::<usize>() {
+                ui.grab_target = position.saturating_sub(1).min(last);
+            }
+        }
+        KeyCode::Backspace => {
+            ui.input_buffer.pop();
+            if let Ok(position) = ui.input_buffer.parse::<usize>() {
+                ui.grab_target = position.saturating_sub(1).min(last);
+            }
+        }
+        KeyCode::Enter => {
+            let target = ui.grab_target;
+            let dot = ui.editing_dot.take();
+            ui.input_buffer.clear();
+            ui.mode = Mode::Normal;
+            if let Some(dot) = dot {
+                // A peer may have deleted it meanwhile; never move another todo instead
+                if crate::priority::find_priority_index(app.list_store(), &dot).is_none() {
+                    app.log("Grabbed todo was deleted, move dropped".to_string());
+                    return Ok(());
+                }
+                return move_to(app, &dot, target);
+            }
+        }
+        KeyCode::Esc | KeyCode::Char('m') => {
+            ui.editing_dot = None;
+            ui.input_buffer.clear();
+            ui.mode = Mode::Normal;
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Spawn the next instance of `todo` right below it if it recurs, in a
+/// transaction of its own. Called by the replica marking it done, only
+/// there, so peers receive exactly one copy.
+fn respawn_if_recurring(
+    app: &mut App,
+    dot: &dson::Dot,
+    todo: &crate::todo::Todo,
+) -> io::Result<()> {
+    let Some(recur) = todo.primary_recur() else {
+        return Ok(());
+    };
+    let Some(index) = crate::priority::find_priority_index(app.list_store(), dot) else {
+        return Ok(());
+    };
+    let (key, new_dot) = app.next_dot_key();
+    app.record_undo(&new_dot);
+    let replica_id = app.replica_id;
+    let delta = app.transact_list(|list_tx| {
+        list_tx.in_map(key.as_str(), |todo_tx| {
+            crate::todo::write_recurrence(todo_tx, todo, replica_id);
+        });
+        list_tx.in_array("priority", |arr_tx| {
+            arr_tx.insert_register(index + 1, MvRegValue::String(key.to_string()));
+        });
+    });
+    app.broadcast_delta(delta)?;
+    app.log(format!(
+        "Respawned recurring todo '{}' ({})",
+        todo.primary_text(),
+        recur.as_str()
+    ));
+    Ok(())
+}
+
+/// Move `dot` to `target` in the priority array with one remove+insert transaction.
+/// `target` is clamped to the last position; moving to the current position is a no-op.
+fn move_to(app: &mut App, dot: &dson::Dot, target: usize) -> io::Result<()> {
+    let priority_len = crate::priority::priority_entries(app.list_store()).len();
+    let Some(current_pos) = crate::priority::find_priority_index(app.list_store(), dot) else {
+        return Ok(());
+    };
+    let target = target.min(priority_len.saturating_sub(1));
+    if target == current_pos {
+        return Ok(());
+    }
+
+    app.record_undo(dot);
+    let dot_key = crate::priority::DotKey::new(dot);
+    let delta = app.transact_list(|list_tx| {
+        list_tx.in_array("priority", |arr_tx| {
+            arr_tx.remove(current_pos);
+            arr_tx.insert_register(target, MvRegValue::String(dot_key.into_inner()));
+        });
+    });
+    app.broadcast_delta(delta)?;
+
+    // Follow the moved todo
+    app.select_dot(dot);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        app::ReplicaId,
+        priority::{
+            duplicate_priority_indices, find_priority_index, priority_entries, priority_indices,
+        },
+        test_util::connected_apps,
+    };
+
+    fn test_app() -> App {
+        connected_apps(1).remove(0)
+    }
+
+    #[test]
+    fn test_insert_stops_at_max_text_len_counting_chars() {
+        let mut app = test_app();
+        app.max_text_len = 5;
+        execute_action(&mut app, Action::EnterInsertMode).expect("insert");
+        let type_char = |app: &mut App, c| {
+            handle_insert_key(KeyEvent::from(KeyCode::Char(c)), app).expect("type");
+        };
+
+        // Multibyte characters count once each
+        for c in "aé漢🎉".chars() {
+            type_char(&mut app, c);
+        }
+        assert_eq!(app.ui_state.input_buffer.len(), 10);
+        type_char(&mut app, 'ß');
+        assert_eq!(app.ui_state.input_buffer, "aé漢🎉ß");
+        type_char(&mut app, 'x');
+        assert_eq!(app.ui_state.input_buffer, "aé漢🎉ß");
+
+        handle_insert_key(KeyEvent::from(KeyCode::Backspace), &mut app).expect("delete");
+        type_char(&mut app, 'x');
+        assert_eq!(app.ui_state.input_buffer, "aé漢🎉x");
+
+        handle_insert_key(KeyEvent::from(KeyCode::Enter), &mut app).expect("save");
+        assert_eq!(app.get_todos_ordered()[0].1.primary_text(), "aé漢🎉x");
+    }
+
+    #[test]
+    fn test_insert_edits_at_the_cursor() {
+        let mut app = test_app();
+        let press = |app: &mut App, code| {
+            handle_insert_key(KeyEvent::from(code), app).expect("key");
+        };
+        execute_action(&mut app, Action::EnterInsertMode).expect("insert");
+        for c in "Tee 🎉".chars() {
+            press(&mut app, KeyCode::Char(c));
+        }
+        press(&mut app, KeyCode::Enter);
+
+        // Editing starts with the cursor at the end
+        execute_action(&mut app, Action::EnterEditMode).expect("edit");
+        assert_eq!(app.ui_state.cursor_pos, 5);
+        press(&mut app, KeyCode::Left);
+        press(&mut app, KeyCode::Left);
+        press(&mut app, KeyCode::Backspace);
+        for c in "für".chars() {
+            press(&mut app, KeyCode::Char(c));
+        }
+        assert_eq!(app.ui_state.input_buffer, "Tefür 🎉");
+        assert_eq!(app.ui_state.cursor_pos, 5);
+
+        press(&mut app, KeyCode::Home);
+        press(&mut app, KeyCode::Left);
+        press(&mut app, KeyCode::Backspace);
+        press(&mut app, KeyCode::Char('>'));
+        press(&mut app, KeyCode::End);
+        press(&mut app, KeyCode::Right);
+        press(&mut app, KeyCode::Char('!'));
+        assert_eq!(app.ui_state.input_buffer, ">Tefür 🎉!");
+
+        press(&mut app, KeyCode::Enter);
+        assert_eq!(app.get_todos_ordered()[0].1.primary_text(), ">Tefür 🎉!");
+        assert_eq!(app.ui_state.cursor_pos, 0);
+    }
+
+    #[test]
+    fn test_duplicate_inserts_copy_below_original() {
+        let mut app = test_app();
+        app.add_random_todos().expect("add todos");
+        app.ui_state.selected_index = 1;
+        let before = app.get_todos_ordered();
+
+        execute_action(&mut app, Action::DuplicateTodo).expect("duplicate");
+
+        let after = app.get_todos_ordered();
+        assert_eq!(after.len(), 4);
+        assert_eq!(app.ui_state.selected_index, 2);
+        let (original_dot, original) = &after[1];
+        let (copy_dot, copy) = &after[2];
+        assert_eq!(*original_dot, before[1].0);
+        assert_ne!(copy_dot, original_dot);
+        assert_eq!(copy.text, original.text);
+        assert_eq!(after[3].0, before[2].0);
+        assert_eq!(
+            app.log_buffer.last().map(String::as_str),
+            Some(format!("Duplicated todo: {}", original.primary_text()).as_str())
+        );
+    }
+
+    #[test]
+    fn test_duplicate_copies_done_state() {
+        let mut app = test_app();
+        app.add_random_todos().expect("add todos");
+        execute_action(&mut app, Action::ToggleDone).expect("toggle");
+        execute_action(&mut app, Action::DuplicateTodo).expect("duplicate");
+
+        let todos = app.get_todos_ordered();
+        assert!(todos[0].1.primary_done());
+        assert!(todos[1].1.primary_done());
+        assert!(!todos[2].1.primary_done());
+    }
+
+    #[test]
+    fn test_duplicate_is_independent_of_original() {
+        let mut app = test_app();
+        app.add_random_todos().expect("add todos");
+        execute_action(&mut app, Action::DuplicateTodo).expect("duplicate");
+
+        // Edit the copy
+        execute_action(&mut app, Action::EnterEditMode).expect("edit");
+        app.ui_state.input_buffer = "Changed copy".to_string();
+        handle_insert_key(KeyEvent::from(KeyCode::Enter), &mut app).expect("save");
+
+        let todos = app.get_todos_ordered();
+        assert_eq!(todos[1].1.primary_text(), "Changed copy");
+        assert_ne!(todos[0].1.primary_text(), "Changed copy");
+    }
+
+    fn submit(app: &mut App, action: Action, input: &str) {
+        execute_action(app, action).expect("open prompt");
+        let Mode::Prompt(kind) = app.ui_state.mode else {
+            panic!("expected a prompt");
+        };
+        for c in input.chars() {
+            handle_prompt_key(KeyEvent::from(KeyCode::Char(c)), app, kind).expect("type");
+        }
+        handle_prompt_key(KeyEvent::from(KeyCode::Enter), app, kind).expect("submit");
+    }
+
+    #[test]
+    fn test_category_prompt_cycle_and_filter() {
+        let mut app = test_app();
+        app.add_random_todos().expect("add todos");
+        let before = order(&app);
+
+        // Without categories there is nothing to cycle through
+        execute_action(&mut app, Action::CycleCategory).expect("cycle");
+        assert!(app.get_todos_ordered()[0].1.category.is_empty());
+
+        submit(&mut app, Action::SetCategory, "work");
+        app.ui_state.selected_index = 2;
+        submit(&mut app, Action::SetCategory, "home");
+        assert_eq!(app.categories(), vec!["home", "work"]);
+
+        // The middle todo cycles through the categories seen in the list
+        app.ui_state.selected_index = 1;
+        let cycled = |app: &App| app.get_todos_ordered()[1].1.category.clone();
+        execute_action(&mut app, Action::CycleCategory).expect("cycle");
+        assert_eq!(cycled(&app), vec!["home"]);
+        execute_action(&mut app, Action::CycleCategory).expect("cycle");
+        assert_eq!(cycled(&app), vec!["work"]);
+        execute_action(&mut app, Action::CycleCategory).expect("cycle");
+        assert!(cycled(&app).is_empty());
+        execute_action(&mut app, Action::CycleCategory).expect("cycle");
+
+        // The filter keeps priority order
+        submit(&mut app, Action::FilterByCategory, "home");
+        let visible: Vec<dson::Dot> = app
+            .visible_todos()
+            .into_iter()
+            .map(|(dot, _)| dot)
+            .collect();
+        assert_eq!(visible, vec![before[1], before[2]]);
+        assert_eq!(app.ui_state.selected_index, 0);
+
+        // Clearing the category hides the todo from the filtered view
+        submit(&mut app, Action::SetCategory, "");
+        assert_eq!(app.visible_todos().len(), 1);
+
+        submit(&mut app, Action::FilterByCategory, "");
+        assert_eq!(app.ui_state.category_filter, None);
+        assert_eq!(order(&app), before);
+    }
+
+    #[test]
+    fn test_creator_filter_combines_with_search() {
+        let mut apps = connected_apps(2);
+        apps[0].add_random_todos().expect("add todos");
+        apps[1].tick().expect("receive todos");
+        apps[1].add_random_todos().expect("add todos");
+        let app = &mut apps[1];
+        let all = order(app);
+
+        submit(app, Action::FilterByCreator, "01");
+        assert_eq!(app.ui_state.filter_creator, Some(1));
+        let created_by = |app: &App| -> Vec<Option<String>> {
+            app.visible_todos()
+                .into_iter()
+                .map(|(_, todo)| todo.creator)
+                .collect()
+        };
+        assert_eq!(created_by(app), vec![Some("01".to_string()); 3]);
+
+        // Both filters apply at once
+        let (dot, todo) = app.visible_todos().remove(1);
+        submit(app, Action::Search, todo.primary_text());
+        let found = app.visible_todos();
+        assert!(found.len() < 3 && found.iter().any(|(found, _)| *found == dot));
+        assert!(
+            found
+                .iter()
+                .all(|(_, todo)| todo.creator.as_deref() == Some("01"))
+        );
+        app.ui_state.search_query.clear();
+
+        // A bad ID keeps the filter and says why
+        submit(app, Action::FilterByCreator, "xyz");
+        assert_eq!(app.ui_state.filter_creator, Some(1));
+        assert!(
+            app.log_buffer
+                .last()
+                .is_some_and(|line| line.contains("Not a replica ID"))
+        );
+
+        submit(app, Action::FilterByCreator, "*");
+        assert_eq!(app.ui_state.filter_creator, None);
+        assert_eq!(app.visible_todos().len(), all.len());
+    }
+
+    #[test]
+    fn test_clipboard_text_lists_conflicting_values() {
+        let mut app = test_app();
+        app.add_random_todos().expect("add todos");
+        let mut todo = app.get_todos_ordered()[0].1.clone();
+        assert_eq!(clipboard_text(&todo), todo.primary_text());
+
+        todo.text = vec!["Buy whole milk".to_string(), "Buy oat milk".to_string()];
+        assert_eq!(clipboard_text(&todo), "Buy whole milk\nBuy oat milk");
+    }
+
+    /// Clipboard keeping the copied text, or failing like a headless session.
+    struct TestClipboard {
+        copied: std::sync::Arc<std::sync::Mutex<Option<String>>>,
+        available: bool,
+    }
+
+    impl crate::app::Clipboard for TestClipboard {
+        fn set_text(&mut self, text: &str) -> io::Result<()> {
+            if !self.available {
+                return Err(io::Error::other("no clipboard"));
+            }
+            *self.copied.lock().expect("lock") = Some(text.to_string());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_copy_puts_text_on_the_clipboard() {
+        let mut app = test_app();
+        let copied = std::sync::Arc::default();
+        app.clipboard = Box::new(TestClipboard {
+            copied: std::sync::Arc::clone(&copied),
+            available: true,
+        });
+        app.add_random_todos().expect("add todos");
+        let text = app.get_todos_ordered()[0].1.primary_text().to_string();
+
+        execute_action(&mut app, Action::CopyText).expect("copy");
+        assert_eq!(*copied.lock().expect("lock"), Some(text));
+        let line = app.log_buffer.last().expect("log line");
+        assert!(line.contains("Copied to clipboard"));
+    }
+
+    #[test]
+    fn test_copy_without_clipboard_logs_instead_of_panicking() {
+        let mut app = test_app();
+        app.clipboard = Box::new(TestClipboard {
+            copied: std::sync::Arc::default(),
+            available: false,
+        });
+        app.add_random_todos().expect("add todos");
+        let logs = app.log_buffer.len();
+
+        execute_action(&mut app, Action::CopyText).expect("copy");
+        assert_eq!(app.log_buffer.len(), logs + 1);
+        let line = app.log_buffer.last().expect("log line");
+        assert!(line.contains("Copy failed, no clipboard: no clipboard"));
+    }
+
+    #[test]
+    fn test_tag_filter_restricts_visible_todos() {
+        let mut app = test_app();
+        app.add_random_todos().expect("add todos");
+        app.ui_state.selected_index = 1;
+        submit(&mut app, Action::AddTag, "#urgent");
+
+        let tagged = app.get_todos_ordered()[1].0;
+        assert_eq!(app.get_todos_ordered()[1].1.tags, vec!["urgent"]);
+
+        submit(&mut app, Action::FilterByTag, "urgent");
+        let visible = app.visible_todos();
+        assert_eq!(visible.len(), 1);
+        assert_eq!(visible[0].0, tagged);
+        assert_eq!(app.ui_state.selected_index, 0);
+
+        // Removing the tag hides the todo from the filtered view
+        submit(&mut app, Action::RemoveTag, "urgent");
+        assert!(app.visible_todos().is_empty());
+
+        // # again clears instead of prompting
+        execute_action(&mut app, Action::FilterByTag).expect("clear");
+        assert!(app.ui_state.filter_tags.is_empty());
+        assert_eq!(app.ui_state.mode, Mode::Normal);
+        assert_eq!(app.visible_todos().len(), 3);
+    }
+
+    #[test]
+    fn test_cycle_tag_filter() {
+        let mut app = test_app();
+        let ctrl_t = KeyEvent::new(KeyCode::Char('t'), crossterm::event::KeyModifiers::CONTROL);
+        let press = |app: &mut App| {
+            let action = handle_key(ctrl_t, app).expect("bound");
+            execute_action(app, action).expect("cycle");
+        };
+        app.add_random_todos().expect("add todos");
+        press(&mut app);
+        assert!(app.ui_state.filter_tags.is_empty());
+        assert_eq!(
+            app.log_buffer.last().map(String::as_str),
+            Some("No tags yet; press + to add one")
+        );
+
+        for (index, tag) in [(0, "#work"), (1, "#home"), (2, "#work")] {
+            app.ui_state.selected_index = index;
+            submit(&mut app, Action::AddTag, tag);
+        }
+        let todos = order(&app);
+        let shown = |app: &App| -> Vec<dson::Dot> {
+            app.visible_todos()
+                .into_iter()
+                .map(|(dot, _)| dot)
+                .collect()
+        };
+
+        // The selection stays on its todo while the filter shows it
+        press(&mut app);
+        assert_eq!(app.ui_state.sorted_filter_tags(), ["home"]);
+        assert_eq!(shown(&app), [todos[1]]);
+        assert_eq!(app.ui_state.selected_index, 0);
+        press(&mut app);
+        assert_eq!(app.ui_state.sorted_filter_tags(), ["work"]);
+        assert_eq!(shown(&app), [todos[0], todos[2]]);
+
+        // Actions go to the todo shown, not the one at that index overall
+        app.ui_state.selected_index = 1;
+        execute_action(&mut app, Action::ToggleDone).expect("toggle");
+        assert!(app.get_todos_ordered()[2].1.primary_done());
+        assert!(!app.get_todos_ordered()[1].1.primary_done());
+
+        press(&mut app);
+        assert!(app.ui_state.filter_tags.is_empty());
+        assert_eq!(shown(&app), todos);
+        assert_eq!(app.ui_state.selected_index, 2);
+    }
+
+    #[test]
+    fn test_tag_filter_needs_every_tag() {
+        let mut app = test_app();
+        app.add_random_todos().expect("add todos");
+        for (index, tags) in [(0, "#home"), (1, "#home"), (1, "#urgent"), (2, "#urgent")] {
+            app.ui_state.selected_index = index;
+            submit(&mut app, Action::AddTag, tags);
+        }
+        let both = app.get_todos_ordered()[1].0;
+
+        submit(&mut app, Action::FilterByTag, "home #urgent");
+        let visible: Vec<dson::Dot> = app
+            .visible_todos()
+            .into_iter()
+            .map(|(dot, _)| dot)
+            .collect();
+        assert_eq!(visible, vec![both]);
+        assert_eq!(app.ui_state.sorted_filter_tags(), vec!["home", "urgent"]);
+    }
+
+    #[test]
+    fn test_search_filters_while_typing() {
+        let mut app = test_app();
+        for text in ["Buy milk", "Call Mum", "Buy oat MILK"] {
+            execute_action(&mut app, Action::EnterInsertMode).expect("insert");
+            app.ui_state.input_buffer = text.to_string();
+            handle_insert_key(KeyEvent::from(KeyCode::Enter), &mut app).expect("save");
+        }
+
+        execute_action(&mut app, Action::Search).expect("open search");
+        let kind = PromptKind::Search;
+        handle_prompt_key(KeyEvent::from(KeyCode::Char('m')), &mut app, kind).expect("type");
+        assert_eq!(app.visible_todos().len(), 3);
+        for c in "ilk".chars() {
+            handle_prompt_key(KeyEvent::from(KeyCode::Char(c)), &mut app, kind).expect("type");
+        }
+        let texts: Vec<String> = app
+            .visible_todos()
+            .iter()
+            .map(|(_, todo)| todo.primary_text().to_string())
+            .collect();
+        assert_eq!(texts, ["Buy oat MILK", "Buy milk"]);
+
+        // Enter keeps the filter; reopening starts from the query
+        handle_prompt_key(KeyEvent::from(KeyCode::Enter), &mut app, kind).expect("submit");
+        assert_eq!(app.ui_state.mode, Mode::Normal);
+        assert_eq!(app.visible_todos().len(), 2);
+        execute_action(&mut app, Action::Search).expect("reopen search");
+        assert_eq!(app.ui_state.input_buffer, "milk");
+
+        // Esc clears it
+        handle_prompt_key(KeyEvent::from(KeyCode::Esc), &mut app, kind).expect("cancel");
+        assert_eq!(app.ui_state.search_query, "");
+        assert_eq!(app.visible_todos().len(), 3);
+    }
+
+    #[test]
+    fn test_search_keeps_selection_and_steps_through_matches() {
+        let mut app = test_app();
+        for text in ["Buy milk", "Call Mum", "Buy oat MILK"] {
+            execute_action(&mut app, Action::EnterInsertMode).expect("insert");
+            app.ui_state.input_buffer = text.to_string();
+            handle_insert_key(KeyEvent::from(KeyCode::Enter), &mut app).expect("save");
+        }
+        let selected_text = |app: &App| {
+            let todos = app.visible_todos();
+            todos[app.ui_state.selected_index]
+                .1
+                .primary_text()
+                .to_string()
+        };
+        let press = |app: &mut App, c: char| {
+            let action = handle_key(KeyEvent::from(KeyCode::Char(c)), app).expect("bound");
+            execute_action(app, action).expect("execute");
+        };
+        app.ui_state.selected_index = 2;
+        assert_eq!(selected_text(&app), "Buy milk");
+
+        // Narrowing the list keeps the selected todo selected
+        execute_action(&mut app, Action::Search).expect("open search");
+        for c in "milk".chars() {
+            handle_prompt_key(
+                KeyEvent::from(KeyCode::Char(c)),
+                &mut app,
+                PromptKind::Search,
+            )
+            .expect("type");
+        }
+        handle_prompt_key(KeyEvent::from(KeyCode::Enter), &mut app, PromptKind::Search)
+            .expect("submit");
+        assert_eq!(app.visible_todos().len(), 2);
+        assert_eq!(selected_text(&app), "Buy milk");
+        execute_action(&mut app, Action::ToggleDone).expect("toggle");
+        let done: Vec<bool> = app
+            .get_todos_ordered()
+            .iter()
+            .map(|(_, todo)| app.is_done(todo))
+            .collect();
+        assert_eq!(done, [false, false, true]);
+
+        // Showing all todos, n/N step over the match-less one and wrap around
+        press(&mut app, 'V');
+        assert_eq!(app.visible_todos().len(), 3);
+        assert_eq!(selected_text(&app), "Buy milk");
+        press(&mut app, 'n');
+        assert_eq!(selected_text(&app), "Buy oat MILK");
+        press(&mut app, 'n');
+        assert_eq!(selected_text(&app), "Buy milk");
+        press(&mut app, 'N');
+        assert_eq!(selected_text(&app), "Buy oat MILK");
+
+        // Esc drops the search, and n edits notes again
+        let action = handle_key(KeyEvent::from(KeyCode::Esc), &app).expect("bound");
+        execute_action(&mut app, action).expect("clear search");
+        assert_eq!(app.ui_state.search_query, "");
+        assert!(!app.ui_state.search_shows_all);
+        assert_eq!(selected_text(&app), "Buy oat MILK");
+        assert_eq!(
+            handle_key(KeyEvent::from(KeyCode::Char('n')), &app),
+            Some(Action::EditNotes)
+        );
+    }
+
+    #[test]
+    fn test_notes_editor_saves_multiple_lines() {
+        use crossterm::event::KeyModifiers;
+
+        let mut app = test_app();
+        app.add_random_todos().expect("add todos");
+        for c in ['n', 'o'] {
+            let action = handle_key(KeyEvent::from(KeyCode::Char(c)), &app);
+            assert_eq!(action, Some(Action::EditNotes));
+        }
+        execute_action(&mut app, Action::EditNotes).expect("edit notes");
+        assert_eq!(app.ui_state.mode, Mode::Notes);
+
+        for code in [KeyCode::Char('a'), KeyCode::Enter, KeyCode::Char('b')] {
+            handle_notes_key(KeyEvent::from(code), &mut app).expect("type");
+        }
+        assert_eq!(app.ui_state.mode, Mode::Notes);
+
+        let save = KeyEvent::new(KeyCode::Char('s'), KeyModifiers::CONTROL);
+        handle_notes_key(save, &mut app).expect("save");
+
+        assert_eq!(app.ui_state.mode, Mode::Normal);
+        assert_eq!(app.get_todos_ordered()[0].1.notes, vec!["a\nb"]);
+    }
+
+    #[test]
+    fn test_subtasks_add_toggle_delete() {
+        let mut app = test_app();
+        app.add_random_todos().expect("add todos");
+        execute_action(&mut app, Action::ExpandSubtasks).expect("expand");
+        assert_eq!(app.ui_state.mode, Mode::Subtasks);
+
+        let key = |c| KeyEvent::from(KeyCode::Char(c));
+        for text in ["Socks", "Towel"] {
+            handle_subtasks_key(key('a'), &mut app).expect("open prompt");
+            for c in text.chars() {
+                handle_prompt_key(key(c), &mut app, PromptKind::AddSubtask).expect("type");
+            }
+            handle_prompt_key(
+                KeyEvent::from(KeyCode::Enter),
+                &mut app,
+                PromptKind::AddSubtask,
+            )
+            .expect("submit");
+            assert_eq!(app.ui_state.mode, Mode::Subtasks);
+        }
+
+        // The new subtask is inserted below the selection and becomes selected
+        assert_eq!(app.ui_state.subtask_index, 1);
+        handle_subtasks_key(key(' '), &mut app).expect("toggle");
+        handle_subtasks_key(key('k'), &mut app).expect("up");
+        handle_subtasks_key(key('d'), &mut app).expect("delete");
+
+        let subtasks = &app.get_todos_ordered()[0].1.subtasks;
+        assert_eq!(subtasks.len(), 1);
+        assert_eq!(subtasks[0].primary_text(), "Towel");
+        assert!(subtasks[0].primary_done());
+
+        handle_subtasks_key(KeyEvent::from(KeyCode::Esc), &mut app).expect("close");
+        assert_eq!(app.ui_state.mode, Mode::Normal);
+        assert_eq!(app.ui_state.editing_dot, None);
+    }
+
+    #[test]
+    fn test_focused_context_panel_takes_navigation_keys() {
+        let mut app = test_app();
+        let j = KeyEvent::from(KeyCode::Char('j'));
+        assert_eq!(handle_key(j, &app), Some(Action::MoveDown));
+
+        let focus = handle_key(KeyEvent::from(KeyCode::Char('F')), &app).expect("focus");
+        execute_action(&mut app, focus).expect("toggle focus");
+        assert_eq!(handle_key(j, &app), Some(Action::ScrollContextDown));
+        // Other keys keep working while focused
+        assert_eq!(
+            handle_key(KeyEvent::from(KeyCode::Char('q')), &app),
+            Some(Action::Quit)
+        );
+
+        // Scrolling stops at the last node
+        app.add_random_todos().expect("add todos");
+        execute_action(&mut app, Action::ScrollContextDown).expect("scroll");
+        assert_eq!(app.ui_state.context_scroll, 0);
+
+        let esc = handle_key(KeyEvent::from(KeyCode::Esc), &app).expect("unfocus");
+        execute_action(&mut app, esc).expect("toggle focus");
+        assert!(!app.ui_state.context_focused);
+    }
+
+    #[test]
+    fn test_jump_to_top_and_bottom() {
+        let mut app = test_app();
+        execute_action(&mut app, Action::JumpToBottom).expect("jump on empty list");
+        assert_eq!(app.ui_state.selected_index, 0);
+
+        app.add_random_todos().expect("add todos");
+        let g = handle_key(KeyEvent::from(KeyCode::Char('G')), &app).expect("G");
+        execute_action(&mut app, g).expect("jump to bottom");
+        assert_eq!(app.ui_state.selected_index, 2);
+
+        let g = handle_key(KeyEvent::from(KeyCode::Char('g')), &app).expect("g");
+        execute_action(&mut app, g).expect("jump to top");
+        assert_eq!(app.ui_state.selected_index, 0);
+    }
+
+    #[test]
+    fn test_conflict_popup_resolution_converges() {
+        let mut apps = connected_apps(2);
+        apps[0].add_random_todos().expect("add todos");
+        apps[1].tick().expect("receive todos");
+
+        // Concurrent edits of the first todo's text
+        for (app, text) in apps.iter_mut().zip(["Buy whole milk", "Buy oat milk"]) {
+            execute_action(app, Action::EnterEditMode).expect("edit");
+            app.ui_state.input_buffer = text.to_string();
+            handle_insert_key(KeyEvent::from(KeyCode::Enter), app).expect("save");
+        }
+        apps[0].tick().expect("receive edit");
+        apps[1].tick().expect("receive edit");
+        assert_eq!(apps[0].get_todos_ordered()[0].1.text.len(), 2);
+
+        execute_action(&mut apps[0], Action::ResolveConflict).expect("open popup");
+        assert_eq!(apps[0].ui_state.mode, Mode::ResolveConflict);
+        let chosen = apps[0].get_todos_ordered()[0].1.text[1].clone();
+        handle_conflict_key(KeyEvent::from(KeyCode::Char('j')), &mut apps[0]).expect("down");
+        handle_conflict_key(KeyEvent::from(KeyCode::Enter), &mut apps[0]).expect("pick");
+        assert_eq!(apps[0].ui_state.mode, Mode::Normal);
+
+        apps[1].tick().expect("receive resolution");
+        for app in &apps {
+            assert_eq!(app.get_todos_ordered()[0].1.text, vec![chosen.clone()]);
+        }
+        assert_eq!(apps[0].store, apps[1].store);
+    }
+
+    #[test]
+    fn test_form_saves_changed_fields_in_one_delta() {
+        let mut apps = connected_apps(2);
+        apps[0].add_random_todos().expect("add todos");
+        apps[1].tick().expect("receive todos");
+        let press = |app: &mut App, code| handle_form_key(KeyEvent::from(code), app).expect("key");
+
+        // Concurrent text edits leave a conflict the form must not resolve
+        for (app, text) in apps.iter_mut().zip(["Buy whole milk", "Buy oat milk"]) {
+            execute_action(app, Action::EnterEditMode).expect("edit");
+            app.ui_state.input_buffer = text.to_string();
+            handle_insert_key(KeyEvent::from(KeyCode::Enter), app).expect("save");
+        }
+        apps[0].tick().expect("receive edit");
+        apps[1].tick().expect("receive edit");
+        let texts = apps[0].get_todos_ordered()[0].1.text.clone();
+        assert_eq!(texts.len(), 2);
+
+        let action = handle_key(KeyEvent::from(KeyCode::Char('M')), &apps[0]).expect("bound");
+        execute_action(&mut apps[0], action).expect("open form");
+        assert_eq!(apps[0].ui_state.mode, Mode::Form);
+        // Typing in a field that isn't the text does nothing
+        press(&mut apps[0], KeyCode::Down);
+        press(&mut apps[0], KeyCode::Char('x'));
+        press(&mut apps[0], KeyCode::Char(' '));
+        press(&mut apps[0], KeyCode::Tab);
+        press(&mut apps[0], KeyCode::Tab);
+        press(&mut apps[0], KeyCode::Enter);
+        press(&mut apps[0], KeyCode::Char(' '));
+        assert_eq!(
+            apps[0]
+                .ui_state
+                .form
+                .as_ref()
+                .map(|form| form.changed_fields()),
+            Some(vec![FormField::Done, FormField::Color])
+        );
+        press(&mut apps[0], KeyCode::Enter);
+        assert_eq!(apps[0].ui_state.mode, Mode::Normal);
+        assert!(apps[0].ui_state.form.is_none());
+
+        assert_eq!(apps[1].drain_incoming_deltas().expect("receive"), 1);
+        for app in &apps {
+            let todo = &app.get_todos_ordered()[0].1;
+            assert_eq!(todo.text, texts);
+            assert_eq!(todo.done, [true]);
+            assert_eq!(todo.color, [crate::todo::TodoColor::Red]);
+            assert!(todo.level.is_empty());
+        }
+        assert!(
+            apps[0]
+                .log_buffer
+                .iter()
+                .any(|line| line.starts_with("Saved done, color of '"))
+        );
+    }
+
+    #[test]
+    fn test_form_escape_discards_and_unchanged_form_writes_nothing() {
+        let mut apps = connected_apps(2);
+        apps[0].add_random_todos().expect("add todos");
+        apps[1].tick().expect("receive todos");
+        let before = apps[0].store.clone();
+        let press = |app: &mut App, code| handle_form_key(KeyEvent::from(code), app).expect("key");
+
+        execute_action(&mut apps[0], Action::EditForm).expect("open form");
+        press(&mut apps[0], KeyCode::Char('!'));
+        press(&mut apps[0], KeyCode::Esc);
+        assert_eq!(apps[0].ui_state.mode, Mode::Normal);
+        assert!(apps[0].ui_state.editing_dot.is_none());
+
+        // Changing a value back counts as unchanged
+        execute_action(&mut apps[0], Action::EditForm).expect("open form");
+        press(&mut apps[0], KeyCode::Char('!'));
+        press(&mut apps[0], KeyCode::Backspace);
+        for _ in FormField::ALL {
+            press(&mut apps[0], KeyCode::Enter);
+        }
+        assert_eq!(apps[0].ui_state.mode, Mode::Normal);
+        assert_eq!(
+            apps[0].log_buffer.last().map(String::as_str),
+            Some("No changes to save")
+        );
+        assert_eq!(apps[0].store, before);
+        assert_eq!(apps[1].drain_incoming_deltas().expect("receive"), 0);
+    }
+
+    #[test]
+    fn test_conflict_popup_needs_a_conflict() {
+        let mut app = test_app();
+        app.add_random_todos().expect("add todos");
+        execute_action(&mut app, Action::ResolveConflict).expect("no popup");
+        assert_eq!(app.ui_state.mode, Mode::Normal);
+    }
+
+    #[test]
+    fn test_add_subtask_from_normal_mode_appends() {
+        let mut app = test_app();
+        app.add_random_todos().expect("add todos");
+
+        for text in ["first", "second"] {
+            submit(&mut app, Action::AddSubtask, text);
+            assert_eq!(app.ui_state.mode, Mode::Subtasks);
+            handle_subtasks_key(KeyEvent::from(KeyCode::Esc), &mut app).expect("close");
+        }
+
+        let subtasks = &app.get_todos_ordered()[0].1.subtasks;
+        let texts: Vec<_> = subtasks.iter().map(|s| s.primary_text()).collect();
+        assert_eq!(texts, vec!["first", "second"]);
+    }
+
+    #[test]
+    fn test_last_subtask_auto_completes_parent_when_enabled() {
+        let mut app = test_app();
+        app.add_random_todos().expect("add todos");
+        for text in ["first", "second"] {
+            submit(&mut app, Action::AddSubtask, text);
+        }
+        let space = || KeyEvent::from(KeyCode::Char(' '));
+        let parent_done = |app: &App| app.get_todos_ordered()[0].1.primary_done();
+
+        // Off by default: checking everything leaves the parent open
+        app.ui_state.subtask_index = 0;
+        handle_subtasks_key(space(), &mut app).expect("check first");
+        app.ui_state.subtask_index = 1;
+        handle_subtasks_key(space(), &mut app).expect("check second");
+        assert!(!parent_done(&app));
+
+        execute_action(&mut app, Action::ToggleAutoComplete).expect("toggle");
+        handle_subtasks_key(space(), &mut app).expect("uncheck second");
+        assert!(!parent_done(&app));
+        handle_subtasks_key(space(), &mut app).expect("check second");
+        assert!(parent_done(&app));
+        assert!(
+            app.log_buffer
+                .last()
+                .is_some_and(|line| line.contains("marked it done"))
+        );
+
+        // The auto-completion is its own edit to undo
+        handle_subtasks_key(KeyEvent::from(KeyCode::Esc), &mut app).expect("close");
+        execute_action(&mut app, Action::Undo).expect("undo");
+        assert!(!parent_done(&app));
+        assert_eq!(app.get_todos_ordered()[0].1.subtask_progress(), (2, 2));
+    }
+
+    /// Grab the selected todo, press `keys` and drop it with Enter.
+    fn move_selected_to(app: &mut App, keys: &str) {
+        let action = handle_key(KeyEvent::from(KeyCode::Char('m')), app).expect("m");
+        execute_action(app, action).expect("grab");
+        assert_eq!(app.ui_state.mode, Mode::Grab);
+        for c in keys.chars() {
+            handle_grab_key(KeyEvent::from(KeyCode::Char(c)), app).expect("key");
+        }
+        handle_grab_key(KeyEvent::from(KeyCode::Enter), app).expect("drop");
+    }
+
+    #[test]
+    fn test_move_to_position() {
+        let mut app = test_app();
+        app.add_random_todos().expect("add todos");
+        let dot = app.get_todos_ordered()[0].0;
+
+        // Letters other than j/k are ignored
+        move_selected_to(&mut app, "3x");
+        assert_eq!(app.ui_state.mode, Mode::Normal);
+        assert_eq!(app.get_todos_ordered()[2].0, dot);
+        assert_eq!(app.ui_state.selected_index, 2);
+
+        move_selected_to(&mut app, "1");
+        assert_eq!(app.get_todos_ordered()[0].0, dot);
+        assert_eq!(app.ui_state.selected_index, 0);
+    }
+
+    #[test]
+    fn test_move_to_clamps_target() {
+        let mut app = test_app();
+        app.add_random_todos().expect("add todos");
+        let dot = app.get_todos_ordered()[0].0;
+
+        move_selected_to(&mut app, "99");
+        assert_eq!(app.get_todos_ordered()[2].0, dot);
+
+        move_selected_to(&mut app, "0");
+        assert_eq!(app.get_todos_ordered()[0].0, dot);
+        assert_eq!(app.get_todos_ordered().len(), 3);
+    }
+
+    #[test]
+    fn test_grab_moves_with_one_delta() {
+        let mut apps = connected_apps(2);
+        apps[0].add_random_todos().expect("add todos");
+        apps[1].tick().expect("receive todos");
+        let dot = apps[0].get_todos_ordered()[0].0;
+
+        move_selected_to(&mut apps[0], "jjj");
+        assert_eq!(apps[0].get_todos_ordered()[2].0, dot, "clamped to the end");
+        assert_eq!(apps[0].ui_state.selected_index, 2);
+        let received = apps[1].process_incoming_deltas().expect("receive move");
+        assert_eq!(received, 1);
+        assert_eq!(apps[1].get_todos_ordered()[2].0, dot);
+    }
+
+    #[test]
+    fn test_grab_cancel_keeps_order() {
+        let mut app = test_app();
+        app.add_random_todos().expect("add todos");
+        let order = |app: &App| -> Vec<_> {
+            app.get_todos_ordered()
+                .into_iter()
+                .map(|(dot, _)| dot)
+                .collect()
+        };
+        let before = order(&app);
+
+        execute_action(&mut app, Action::Grab).expect("grab");
+        handle_grab_key(KeyEvent::from(KeyCode::Char('j')), &mut app).expect("j");
+        assert_eq!(app.ui_state.grab_target, 1);
+        handle_grab_key(KeyEvent::from(KeyCode::Esc), &mut app).expect("cancel");
+
+        assert_eq!(app.ui_state.mode, Mode::Normal);
+        assert_eq!(app.ui_state.editing_dot, None);
+        assert_eq!(order(&app), before);
+    }
+
+    #[test]
+    fn test_grab_of_deleted_todo_moves_nothing() {
+        let mut app = test_app();
+        app.add_random_todos().expect("add todos");
+
+        execute_action(&mut app, Action::Grab).expect("grab");
+        handle_grab_key(KeyEvent::from(KeyCode::Char('j')), &mut app).expect("j");
+        // Deleted meanwhile, as a peer could; another todo is selected now
+        execute_action(&mut app, Action::Delete).expect("delete");
+        let order: Vec<_> = app
+            .get_todos_ordered()
+            .into_iter()
+            .map(|(dot, _)| dot)
+            .collect();
+
+        handle_grab_key(KeyEvent::from(KeyCode::Enter), &mut app).expect("drop");
+        let after: Vec<_> = app
+            .get_todos_ordered()
+            .into_iter()
+            .map(|(dot, _)| dot)
+            .collect();
+        assert_eq!(after, order);
+        assert_eq!(app.ui_state.mode, Mode::Normal);
+        assert!(
+            app.log_buffer
+                .last()
+                .is_some_and(|line| line.contains("move dropped"))
+        );
+    }
+
+    #[test]
+    fn test_delete_removes_todo_map() {
+        let mut app = test_app();
+        app.add_random_todos().expect("add todos");
+        let dot = app.get_todos_ordered()[0].0;
+        let size_before = rmp_serde::to_vec(&app.store).expect("serialize").len();
+
+        execute_action(&mut app, Action::Delete).expect("delete");
+
+        assert!(crate::todo::read_todo(app.list_store(), &dot).is_none());
+        assert_eq!(app.get_todos_ordered().len(), 2);
+        let size_after = rmp_serde::to_vec(&app.store).expect("serialize").len();
+        assert!(size_after < size_before, "{size_after} >= {size_before}");
+    }
+
+    #[test]
+    fn test_concurrent_delete_and_edit_converge() {
+        let mut apps = connected_apps(2);
+        apps[0].add_random_todos().expect("add todos");
+        apps[1].tick().expect("receive todos");
+        let dot = apps[0].get_todos_ordered()[0].0;
+
+        // Delete on one replica while the other edits the same todo
+        execute_action(&mut apps[0], Action::Delete).expect("delete");
+        execute_action(&mut apps[1], Action::EnterEditMode).expect("edit");
+        apps[1].ui_state.input_buffer = "Edited meanwhile".to_string();
+        handle_insert_key(KeyEvent::from(KeyCode::Enter), &mut apps[1]).expect("save");
+        apps[0].tick().expect("receive edit");
+        apps[1].tick().expect("receive delete");
+
+        assert_eq!(apps[0].store, apps[1].store);
+        for app in &apps {
+            // The delete wins in the list: the priority entry is gone on both sides
+            assert!(app.get_todos_ordered().iter().all(|(d, _)| *d != dot));
+            // Observed-remove keeps only the registers written concurrently
+            let orphan = crate::todo::read_todo(app.list_store(), &dot).expect("edit survives");
+            assert_eq!(orphan.text, vec!["Edited meanwhile"]);
+            assert_eq!(orphan.creator, None);
+        }
+    }
+
+    /// Dots of the current list in priority order.
+    fn order(app: &App) -> Vec<dson::Dot> {
+        app.get_todos_ordered()
+            .into_iter()
+            .map(|(dot, _)| dot)
+            .collect()
+    }
+
+    #[test]
+    fn test_sorted_view_maps_actions_to_the_shown_todo() {
+        let mut app = test_app();
+        app.add_random_todos().expect("add todos");
+        let before = order(&app);
+        for (dot, text) in before.iter().zip(["Cherry", "Apple", "Banana"]) {
+            let dot_key = crate::priority::DotKey::new(dot);
+            let _delta = app.transact_list(|list_tx| {
+                list_tx.in_map(dot_key.as_str(), |todo_tx| {
+                    todo_tx.write_register("text", MvRegValue::String(text.to_string()));
+                });
+            });
+        }
+        app.ui_state.selected_index = 1;
+
+        let action = handle_key(KeyEvent::from(KeyCode::Char('s')), &app).expect("action");
+        execute_action(&mut app, action).expect("sort");
+        assert_eq!(app.ui_state.sort_mode, SortMode::Alphabetical);
+        assert_eq!(app.ui_state.selected_index, 0);
+        assert_eq!(app.visible_todos()[0].0, before[1]);
+
+        // Reordering is refused with a hint instead of moving in the shared order
+        execute_action(&mut app, Action::MovePriorityDown).expect("move");
+        assert_eq!(order(&app), before);
+        assert!(
+            app.log_buffer
+                .last()
+                .expect("hint")
+                .contains("press s for priority order")
+        );
+
+        // Deleting the last shown todo removes the first in priority order
+        app.ui_state.selected_index = 2;
+        execute_action(&mut app, Action::Delete).expect("delete");
+        assert_eq!(order(&app), before[1..]);
+    }
+
+    #[test]
+    fn test_undo_redo_delete() {
+        let mut app = test_app();
+        app.add_random_todos().expect("add todos");
+        app.ui_state.selected_index = 1;
+        let before = app.get_todos_ordered();
+
+        execute_action(&mut app, Action::Delete).expect("delete");
+        assert_eq!(app.get_todos_ordered().len(), 2);
+
+        execute_action(&mut app, Action::Undo).expect("undo");
+        let restored = app.get_todos_ordered();
+        assert_eq!(
+            order(&app),
+            before.iter().map(|(dot, _)| *dot).collect::<Vec<_>>()
+        );
+        assert_eq!(restored[1].1.text, before[1].1.text);
+        assert_eq!(restored[1].1.creator, before[1].1.creator);
+        assert_eq!(restored[1].1.created_at, before[1].1.created_at);
+        assert_eq!(app.ui_state.selected_index, 1);
+
+        execute_action(&mut app, Action::Redo).expect("redo");
+        assert_eq!(app.get_todos_ordered().len(), 2);
+        execute_action(&mut app, Action::Undo).expect("undo again");
+        assert_eq!(app.get_todos_ordered().len(), 3);
+    }
+
+    #[test]
+    fn test_undo_redo_text_edit_and_done_toggle() {
+        let mut app = test_app();
+        app.add_random_todos().expect("add todos");
+        let original = app.get_todos_ordered()[0].1.primary_text().to_string();
+
+        execute_action(&mut app, Action::EnterEditMode).expect("edit");
+        app.ui_state.input_buffer = "Changed".to_string();
+        handle_insert_key(KeyEvent::from(KeyCode::Enter), &mut app).expect("save");
+        execute_action(&mut app, Action::ToggleDone).expect("toggle");
+
+        execute_action(&mut app, Action::Undo).expect("undo toggle");
+        let todo = &app.get_todos_ordered()[0].1;
+        assert!(!todo.primary_done());
+        assert_eq!(todo.primary_text(), "Changed");
+
+        execute_action(&mut app, Action::Undo).expect("undo edit");
+        assert_eq!(app.get_todos_ordered()[0].1.primary_text(), original);
+
+        execute_action(&mut app, Action::Redo).expect("redo edit");
+        execute_action(&mut app, Action::Redo).expect("redo toggle");
+        let todo = &app.get_todos_ordered()[0].1;
+        assert_eq!(todo.primary_text(), "Changed");
+        assert!(todo.primary_done());
+        assert!(!app.redo().expect("nothing left"));
+    }
+
+    #[test]
+    fn test_undo_move_and_add() {
+        let mut app = test_app();
+        app.add_random_todos().expect("add todos");
+        let before = order(&app);
+
+        execute_action(&mut app, Action::MovePriorityDown).expect("move");
+        assert_ne!(order(&app), before);
+        execute_action(&mut app, Action::Undo).expect("undo move");
+        assert_eq!(order(&app), before);
+
+        execute_action(&mut app, Action::EnterInsertMode).expect("insert");
+        app.ui_state.input_buffer = "New".to_string();
+        handle_insert_key(KeyEvent::from(KeyCode::Enter), &mut app).expect("save");
+        assert_eq!(app.get_todos_ordered().len(), 4);
+        execute_action(&mut app, Action::Undo).expect("undo add");
+        assert_eq!(order(&app), before);
+    }
+
+    /// Replace the text of the selected todo through the editor.
+    fn edit_text(app: &mut App, text: &str) {
+        execute_action(app, Action::EnterEditMode).expect("edit");
+        app.ui_state.input_buffer = text.to_string();
+        handle_insert_key(KeyEvent::from(KeyCode::Enter), app).expect("save");
+    }
+
+    #[test]
+    fn test_undo_is_an_edit_peers_receive() {
+        let mut apps = connected_apps(2);
+        apps[0].add_random_todos().expect("add todos");
+        execute_action(&mut apps[0], Action::Delete).expect("delete");
+        apps[1].tick().expect("receive todos and delete");
+        assert_eq!(apps[1].get_todos_ordered().len(), 2);
+
+        execute_action(&mut apps[0], Action::Undo).expect("undo");
+        apps[1].tick().expect("receive undo");
+        assert_eq!(apps[1].get_todos_ordered().len(), 3);
+        assert_eq!(apps[0].store, apps[1].store);
+    }
+
+    #[test]
+    fn test_ctrl_r_redoes() {
+        let app = test_app();
+        let ctrl_r = KeyEvent::new(KeyCode::Char('r'), crossterm::event::KeyModifiers::CONTROL);
+        assert_eq!(handle_key(ctrl_r, &app), Some(Action::Redo));
+        assert_eq!(
+            handle_key(KeyEvent::from(KeyCode::Char('r')), &app),
+            Some(Action::AddRandomTodos)
+        );
+    }
+
+    #[test]
+    fn test_undo_redo_sequence_converges_with_peer() {
+        let mut apps = connected_apps(2);
+        apps[0].add_random_todos().expect("add todos");
+        edit_text(&mut apps[0], "Edited");
+        execute_action(&mut apps[0], Action::ToggleDone).expect("toggle");
+        execute_action(&mut apps[0], Action::MovePriorityDown).expect("move");
+        execute_action(&mut apps[0], Action::Delete).expect("delete");
+        apps[1].tick().expect("receive edits");
+        assert_eq!(apps[0].store, apps[1].store);
+
+        use Action::{Redo, Undo};
+        for (step, action) in [
+            Undo, Undo, Redo, Undo, Undo, Undo, Redo, Redo, Redo, Undo, Redo, Redo,
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            execute_action(&mut apps[0], action).expect("undo or redo");
+            apps[1].tick().expect("receive");
+            assert_eq!(apps[0].store, apps[1].store, "step {step}: {action:?}");
+            assert_eq!(order(&apps[0]), order(&apps[1]), "step {step}: {action:?}");
+        }
+        assert_eq!(apps[0].get_todos_ordered().len(), 2);
+    }
+
+    #[test]
+    fn test_redo_applies_over_remote_edit() {
+        let mut apps = connected_apps(2);
+        apps[0].add_random_todos().expect("add todos");
+        edit_text(&mut apps[0], "Mine");
+        execute_action(&mut apps[0], Action::Undo).expect("undo");
+        apps[1].tick().expect("receive undo");
+        let dot = order(&apps[0])[0];
+        let text = |app: &App| {
+            crate::todo::read_todo(app.list_store(), &dot)
+                .expect("todo")
+                .text
+        };
+
+        // A remote edit the redo has seen is overwritten, like by any later edit
+        edit_text(&mut apps[1], "Theirs");
+        apps[0].tick().expect("receive remote edit");
+        execute_action(&mut apps[0], Action::Redo).expect("redo");
+        apps[1].tick().expect("receive redo");
+        assert_eq!(apps[0].store, apps[1].store);
+        assert_eq!(text(&apps[1]), ["Mine"]);
+
+        // One it has not seen yet ends up as a conflict
+        execute_action(&mut apps[0], Action::Undo).expect("undo");
+        apps[1].tick().expect("receive undo");
+        edit_text(&mut apps[1], "Theirs again");
+        execute_action(&mut apps[0], Action::Redo).expect("redo");
+        apps[0].tick().expect("receive remote edit");
+        apps[1].tick().expect("receive redo");
+        assert_eq!(apps[0].store, apps[1].store);
+        let mut values = text(&apps[0]);
+        values.sort();
+        assert_eq!(values, ["Mine", "Theirs again"]);
+    }
+
+    #[test]
+    fn test_delete_waits_for_confirmation() {
+        let mut app = test_app();
+        app.add_random_todos().expect("add todos");
+        let press = |app: &mut App, c: char| {
+            let action = handle_key(KeyEvent::from(KeyCode::Char(c)), app).expect("action");
+            request_action(app, action).expect("run");
+        };
+
+        press(&mut app, 'd');
+        assert_eq!(app.ui_state.pending_action, Some(Action::Delete));
+        assert_eq!(app.get_todos_ordered().len(), 3);
+        press(&mut app, 'n');
+        assert_eq!(app.ui_state.pending_action, None);
+        assert_eq!(app.get_todos_ordered().len(), 3);
+
+        // Confirming deletes the todo the dialog showed, even if the selection moved
+        let first = order(&app)[0];
+        press(&mut app, 'd');
+        app.ui_state.selected_index = 2;
+        press(&mut app, 'y');
+        assert_eq!(app.ui_state.pending_action, None);
+        assert_eq!(app.ui_state.editing_dot, None);
+        assert_eq!(app.get_todos_ordered().len(), 2);
+        assert!(!order(&app).contains(&first));
+    }
+
+    #[test]
+    fn test_help_overlay_lists_bound_keys_and_closes_on_help_or_esc() {
+        // Single-character entries must match what normal mode actually binds
+        for (key, _, description) in KEY_BINDINGS {
+            let mut chars = key.chars();
+            if let (Some(c), None) = (chars.next(), chars.next()) {
+                assert!(
+                    handle_normal_mode(KeyEvent::from(KeyCode::Char(c))).is_some(),
+                    "{key} ({description}) is not bound"
+                );
+            }
+        }
+
+        let mut app = test_app();
+        let action = handle_key(KeyEvent::from(KeyCode::Char('?')), &app).expect("action");
+        execute_action(&mut app, action).expect("open help");
+        assert!(app.ui_state.show_help);
+
+        // Other keys, even q, must not act behind the overlay
+        assert_eq!(handle_key(KeyEvent::from(KeyCode::Char('q')), &app), None);
+        let action = handle_key(KeyEvent::from(KeyCode::Char('j')), &app).expect("action");
+        execute_action(&mut app, action).expect("scroll help");
+        assert_eq!(app.ui_state.help_scroll, 1);
+
+        for close in [KeyCode::Char('?'), KeyCode::Esc] {
+            let action = handle_key(KeyEvent::from(close), &app).expect("action");
+            assert_eq!(action, Action::ToggleHelpOverlay);
+            execute_action(&mut app, action).expect("close help");
+            assert!(!app.ui_state.show_help);
+            execute_action(&mut app, action).expect("reopen help");
+        }
+    }
+
+    #[test]
+    fn test_history_shows_local_and_remote_changes_by_author() {
+        let mut apps = connected_apps(2);
+        apps[0].add_random_todos().expect("add todos");
+        apps[1].tick().expect("receive todos");
+        let first = apps[1].visible_todos()[0].0;
+        execute_action(&mut apps[1], Action::ToggleDone).expect("toggle");
+
+        let key = KeyEvent::from(KeyCode::Char('H'));
+        let action = handle_key(key, &apps[1]).expect("action");
+        execute_action(&mut apps[1], action).expect("show history");
+        assert_eq!(apps[1].ui_state.history_dot, Some(first));
+        let entries: Vec<_> = apps[1]
+            .history
+            .entries(&apps[1].current_list, &first)
+            .map(|entry| (entry.author, entry.change.clone()))
+            .collect();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].0, ReplicaId::new(1));
+        assert!(entries[0].1.starts_with("created '"));
+        assert_eq!(entries[1], (ReplicaId::new(2), "marked done".to_string()));
+
+        // Scrolling stops at the last entry; other keys are swallowed
+        let j = KeyEvent::from(KeyCode::Char('j'));
+        for _ in 0..3 {
+            let action = handle_key(j, &apps[1]).expect("action");
+            execute_action(&mut apps[1], action).expect("scroll");
+        }
+        assert_eq!(apps[1].ui_state.history_scroll, 1);
+        assert_eq!(
+            handle_key(KeyEvent::from(KeyCode::Char('d')), &apps[1]),
+            None
+        );
+        let action = handle_key(KeyEvent::from(KeyCode::Esc), &apps[1]).expect("action");
+        execute_action(&mut apps[1], action).expect("close");
+        assert_eq!(apps[1].ui_state.history_dot, None);
+    }
+
+    #[test]
+    fn test_completing_a_recurring_todo_respawns_it_once() {
+        let mut apps = connected_apps(2);
+        apps[0].add_random_todos().expect("add todos");
+        execute_action(&mut apps[0], Action::CycleRecurrence).expect("repeat daily");
+        apps[1].tick().expect("receive todos");
+        let original = apps[0].get_todos_ordered()[0].1.clone();
+        assert_eq!(original.recur, vec![crate::todo::Recurrence::Daily]);
+
+        execute_action(&mut apps[0], Action::ToggleDone).expect("complete");
+        for app in apps.iter_mut() {
+            app.tick().expect("sync");
+        }
+
+        // Only the completing replica spawns; the other just receives it
+        for app in &apps {
+            let todos = app.get_todos_ordered();
+            assert_eq!(todos.len(), 4);
+            assert!(todos[0].1.primary_done());
+            let next = &todos[1].1;
+            assert_eq!(next.text, original.text);
+            assert_eq!(next.done, vec![false]);
+            assert_eq!(next.recur, original.recur);
+            assert_ne!(next.dot, original.dot);
+        }
+        assert_eq!(order(&apps[0]), order(&apps[1]));
+
+        // Reopening does not spawn again
+        execute_action(&mut apps[0], Action::ToggleDone).expect("reopen");
+        assert_eq!(apps[0].get_todos_ordered().len(), 4);
+
+        // Undo steps back through the reopen, the completion and the respawn
+        for _ in 0..3 {
+            execute_action(&mut apps[0], Action::Undo).expect("undo");
+        }
+        let todos = apps[0].get_todos_ordered();
+        assert_eq!(todos.len(), 3);
+        assert!(!todos[0].1.primary_done());
+    }
+
+    #[test]
+    fn test_form_completing_a_recurring_todo_respawns_it() {
+        let mut apps = connected_apps(2);
+        apps[0].add_random_todos().expect("add todos");
+        execute_action(&mut apps[0], Action::CycleRecurrence).expect("repeat daily");
+        apps[1].tick().expect("receive todos");
+        let original = apps[0].get_todos_ordered()[0].1.clone();
+
+        execute_action(&mut apps[0], Action::EditForm).expect("open form");
+        for code in [KeyCode::Down, KeyCode::Char(' ')] {
+            handle_form_key(KeyEvent::from(code), &mut apps[0]).expect("key");
+        }
+        // Enter on the last field saves
+        while apps[0].ui_state.mode == Mode::Form {
+            handle_form_key(KeyEvent::from(KeyCode::Enter), &mut apps[0]).expect("key");
+        }
+        apps[1].tick().expect("sync");
+
+        for app in &apps {
+            let todos = app.get_todos_ordered();
+            assert_eq!(todos.len(), 4);
+            assert!(todos[0].1.primary_done());
+            assert_eq!(todos[1].1.text, original.text);
+            assert_eq!(todos[1].1.done, vec![false]);
+        }
+    }
+
+    #[test]
+    fn test_actions_on_an_empty_list_are_logged_no_ops() {
+        let per_todo = [
+            Action::MoveUp,
+            Action::MoveDown,
+            Action::JumpToTop,
+            Action::JumpToBottom,
+            Action::MovePriorityUp,
+            Action::MovePriorityDown,
+            Action::MoveTo(0),
+            Action::MoveTo(usize::MAX),
+            Action::Grab,
+            Action::ToggleDone,
+            Action::Delete,
+            Action::EnterEditMode,
+            Action::DuplicateTodo,
+            Action::CopyText,
+            Action::AddTag,
+            Action::RemoveTag,
+            Action::EditNotes,
+            Action::ExpandSubtasks,
+            Action::AddSubtask,
+            Action::CycleLevel,
+            Action::CycleRecurrence,
+            Action::CycleColor,
+            Action::CycleCategory,
+            Action::SetCategory,
+            Action::ResolveConflict,
+            Action::Assign,
+            Action::AssignTo(ReplicaId::new(2)),
+            Action::ShowHistory,
+        ];
+        let list_wide = [
+            Action::ToggleAllDone,
+            Action::ClearDone,
+            Action::ClearAll,
+            Action::RestoreOrphans,
+            Action::PurgeOrphans,
+            Action::RepairPriority,
+            Action::ToggleDetail,
+            Action::ToggleHideDone,
+            Action::ToggleGroupByLevel,
+            Action::CycleSortMode,
+        ];
+
+        for (action, logs) in per_todo
+            .into_iter()
+            .map(|action| (action, true))
+            .chain(list_wide.into_iter().map(|action| (action, false)))
+        {
+            let mut app = test_app();
+            app.ui_state.selected_index = 3;
+            let store = app.store.clone();
+            let logged = app.log_buffer.len();
+            request_action(&mut app, action).expect("no-op");
+            if app.ui_state.pending_action.is_some() {
+                execute_action(&mut app, Action::ConfirmPending).expect("confirm");
+            }
+
+            assert_eq!(app.store, store, "{action:?} wrote to the store");
+            assert_eq!(
+                app.ui_state.mode,
+                Mode::Normal,
+                "{action:?} left normal mode"
+            );
+            assert!(app.ui_state.pending_action.is_none());
+            if logs {
+                assert_eq!(
+                    app.ui_state.selected_index, 0,
+                    "{action:?} kept the selection"
+                );
+                assert!(
+                    app.log_buffer[logged..]
+                        .iter()
+                        .any(|line| line.contains("No todos yet")),
+                    "{action:?} did not say why nothing happened"
+                );
+            }
+        }
+
+        // With todos that the filters all hide, the log says so
+        let mut app = test_app();
+        app.add_random_todos().expect("add todos");
+        app.ui_state.search_query = "no todo has this text".to_string();
+        execute_action(&mut app, Action::ToggleDone).expect("no-op");
+        assert!(
+            app.log_buffer
+                .last()
+                .is_some_and(|line| line.contains("filters hide every todo"))
+        );
+    }
+
+    #[test]
+    fn test_move_to_top_and_bottom() {
+        let mut app = test_app();
+        app.add_random_todos().expect("add todos");
+        let before = order(&app);
+        let press = |app: &mut App, c| {
+            let key = KeyEvent::new(KeyCode::Char(c), crossterm::event::KeyModifiers::CONTROL);
+            let action = handle_key(key, app).expect("action");
+            execute_action(app, action).expect("move");
+        };
+
+        app.ui_state.selected_index = 1;
+        press(&mut app, 'k');
+        assert_eq!(order(&app), vec![before[1], before[0], before[2]]);
+        assert_eq!(app.ui_state.selected_index, 0);
+
+        // Already on top: nothing to write or undo
+        let undo = app.undo_history.undo_len();
+        press(&mut app, 'k');
+        assert_eq!(app.undo_history.undo_len(), undo);
+
+        press(&mut app, 'j');
+        assert_eq!(order(&app), vec![before[0], before[2], before[1]]);
+        assert_eq!(app.ui_state.selected_index, 2);
+    }
+
+    #[test]
+    fn test_reverse_priority() {
+        let mut app = test_app();
+        app.add_random_todos().expect("add todos");
+        app.add_random_todos().expect("add more todos");
+        let before = order(&app);
+        let press = |app: &mut App| {
+            let action = handle_key(KeyEvent::from(KeyCode::Char('~')), app).expect("bound");
+            execute_action(app, action).expect("reverse");
+        };
+
+        app.ui_state.selected_index = 1;
+        press(&mut app);
+        let reversed: Vec<_> = before.iter().rev().copied().collect();
+        assert_eq!(order(&app), reversed);
+        assert_eq!(app.ui_state.selected_index, before.len() - 2);
+
+        // Reversing again restores the order
+        press(&mut app);
+        assert_eq!(order(&app), before);
+        assert_eq!(app.ui_state.selected_index, 1);
+
+        // Sorted views hide the shared order, so they don't reverse it
+        app.ui_state.sort_mode = SortMode::Alphabetical;
+        press(&mut app);
+        assert_eq!(order(&app), before);
+    }
+
+    #[test]
+    fn test_concurrent_reversals_converge() {
+        let mut apps = connected_apps(2);
+        apps[0].add_random_todos().expect("add todos");
+        apps[1].tick().expect("receive todos");
+        let before = order(&apps[0]);
+
+        for app in apps.iter_mut() {
+            execute_action(app, Action::ReversePriority).expect("reverse");
+        }
+        for app in apps.iter_mut() {
+            app.tick().expect("sync");
+        }
+
+        assert_eq!(apps[0].store, apps[1].store);
+        let after = order(&apps[0]);
+        assert_eq!(after, order(&apps[1]));
+
+        // Both replicas inserted every entry again; each todo is listed once
+        let reversed: Vec<_> = before.iter().rev().copied().collect();
+        assert_eq!(after, reversed);
+        let list_store = apps[0].list_store();
+        assert_eq!(duplicate_priority_indices(list_store).len(), before.len());
+        assert_eq!(priority_entries(list_store).len(), 2 * before.len());
+    }
+
+    #[test]
+    fn test_repair_priority_removes_repeats_and_converges() {
+        let mut apps = connected_apps(2);
+        apps[0].add_random_todos().expect("add todos");
+        apps[1].tick().expect("receive todos");
+        let before = order(&apps[0]);
+
+        // Both replicas move the same todo to the top before syncing
+        for app in apps.iter_mut() {
+            app.ui_state.selected_index = 2;
+            execute_action(app, Action::MoveTo(0)).expect("top");
+        }
+        for app in apps.iter_mut() {
+            app.tick().expect("sync");
+        }
+        let moved = before[2];
+        assert_eq!(priority_indices(apps[0].list_store(), &moved).len(), 2);
+        assert_eq!(order(&apps[0]), [moved, before[0], before[1]]);
+        assert_eq!(
+            find_priority_index(apps[0].list_store(), &moved),
+            priority_indices(apps[0].list_store(), &moved)
+                .first()
+                .copied()
+        );
+
+        // Both replicas repair at once and still agree on a clean order
+        let key = KeyEvent::from(KeyCode::Char('='));
+        for app in apps.iter_mut() {
+            let action = handle_key(key, app).expect("bound");
+            assert_eq!(action, Action::RepairPriority);
+            execute_action(app, action).expect("repair");
+        }
+        for app in apps.iter_mut() {
+            app.tick().expect("sync");
+        }
+        assert_eq!(apps[0].store, apps[1].store);
+        assert!(duplicate_priority_indices(apps[0].list_store()).is_empty());
+        assert_eq!(order(&apps[0]), [moved, before[0], before[1]]);
+
+        // Nothing left to repair
+        assert_eq!(apps[0].repair_priority().expect("repair"), 0);
+    }
+
+    #[test]
+    fn test_delete_removes_every_repeat() {
+        let mut apps = connected_apps(2);
+        apps[0].add_random_todos().expect("add todos");
+        apps[1].tick().expect("receive todos");
+        for app in apps.iter_mut() {
+            execute_action(app, Action::ReversePriority).expect("reverse");
+        }
+        for app in apps.iter_mut() {
+            app.tick().expect("sync");
+        }
+        let doomed = order(&apps[0])[0];
+
+        apps[0].ui_state.selected_index = 0;
+        execute_action(&mut apps[0], Action::Delete).expect("delete");
+        assert!(priority_indices(apps[0].list_store(), &doomed).is_empty());
+        assert_eq!(order(&apps[0]).len(), 2);
+    }
+
+    #[test]
+    fn test_sort_priority_alphabetically_and_by_done() {
+        let mut app = test_app();
+        app.add_random_todos().expect("add todos");
+        app.add_random_todos().expect("add more todos");
+        let ctrl = |c| KeyEvent::new(KeyCode::Char(c), crossterm::event::KeyModifiers::CONTROL);
+        let press = |app: &mut App, c| {
+            let action = handle_key(ctrl(c), app).expect("bound");
+            execute_action(app, action).expect("sort");
+        };
+        let texts = |app: &App| -> Vec<String> {
+            app.get_todos_ordered()
+                .into_iter()
+                .map(|(_, todo)| todo.primary_text().to_lowercase())
+                .collect()
+        };
+
+        press(&mut app, 'a');
+        let mut sorted = texts(&app);
+        sorted.sort();
+        assert_eq!(texts(&app), sorted);
+        let count = order(&app).len();
+        assert!(
+            app.log_buffer
+                .iter()
+                .any(|line| line == &format!("Sorted {count} todos alphabetically"))
+        );
+        press(&mut app, 'a');
+        assert_eq!(
+            app.log_buffer.last().map(String::as_str),
+            Some("Already in alphabetical order")
+        );
+
+        // Done todos sink, keeping their alphabetical order
+        let selected = order(&app)[0];
+        app.ui_state.selected_index = 0;
+        execute_action(&mut app, Action::ToggleDone).expect("toggle");
+        press(&mut app, 'd');
+        let after = order(&app);
+        assert_eq!(after.last(), Some(&selected));
+        assert_eq!(app.selected_dot(), Some(selected));
+        let open: Vec<String> = texts(&app)[..count - 1].to_vec();
+        assert_eq!(open, sorted[1..]);
+    }
+
+    #[test]
+    fn test_concurrent_moves_to_top_converge() {
+        let mut apps = connected_apps(2);
+        apps[0].add_random_todos().expect("add todos");
+        apps[1].tick().expect("receive todos");
+        let before = order(&apps[0]);
+
+        // Each replica sends a different todo to the top before syncing
+        apps[0].ui_state.selected_index = 1;
+        execute_action(&mut apps[0], Action::MoveTo(0)).expect("top");
+        apps[1].ui_state.selected_index = 2;
+        execute_action(&mut apps[1], Action::MoveTo(0)).expect("top");
+        for app in apps.iter_mut() {
+            app.tick().expect("sync");
+        }
+
+        let after = order(&apps[0]);
+        assert_eq!(after, order(&apps[1]));
+        assert_eq!(after.len(), 3);
+        let mut top = after[..2].to_vec();
+        top.sort();
+        let mut moved = vec![before[1], before[2]];
+        moved.sort();
+        assert_eq!(top, moved);
+        assert_eq!(after[2], before[0]);
+    }
+
+    #[test]
+    fn test_toggle_all_done_is_one_delta() {
+        let mut apps = connected_apps(2);
+        apps[0].add_random_todos().expect("add todos");
+        apps[1].tick().expect("receive todos");
+        let ctrl_space = KeyEvent::new(KeyCode::Char(' '), crossterm::event::KeyModifiers::CONTROL);
+        let action = handle_key(ctrl_space, &apps[0]).expect("action");
+        assert_eq!(action, Action::ToggleAllDone);
+
+        execute_action(&mut apps[0], action).expect("mark all done");
+        assert_eq!(apps[1].process_incoming_deltas().expect("receive"), 1);
+        assert!(
+            apps[1]
+                .get_todos_ordered()
+                .iter()
+                .all(|(_, todo)| todo.done == [true])
+        );
+
+        // With everything done, the next toggle marks everything not done
+        execute_action(&mut apps[0], Action::ToggleAllDone).expect("mark all not done");
+        apps[1].tick().expect("receive");
+        assert!(
+            apps[1]
+                .get_todos_ordered()
+                .iter()
+                .all(|(_, todo)| todo.done == [false])
+        );
+    }
+
+    #[test]
+    fn test_toggle_all_concurrent_with_single_toggle_converges() {
+        let mut apps = connected_apps(2);
+        apps[0].add_random_todos().expect("add todos");
+        apps[1].tick().expect("receive todos");
+        execute_action(&mut apps[1], Action::ToggleDone).expect("toggle first");
+        apps[0].tick().expect("receive toggle");
+        let first = order(&apps[0])[0];
+
+        // Bulk-done on one replica while the other unchecks the first todo again
+        execute_action(&mut apps[0], Action::ToggleAllDone).expect("mark all done");
+        execute_action(&mut apps[1], Action::ToggleDone).expect("untoggle first");
+        apps[0].tick().expect("receive single toggle");
+        apps[1].tick().expect("receive bulk toggle");
+
+        assert_eq!(apps[0].store, apps[1].store);
+        for (dot, todo) in apps[0].get_todos_ordered() {
+            if dot == first {
+                let mut done = todo.done.clone();
+                done.sort();
+                assert_eq!(done, [false, true]);
+                assert!(todo.has_conflicts());
+            } else {
+                assert_eq!(todo.done, [true]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_clear_done_keeps_order_of_the_rest() {
+        let mut apps = connected_apps(2);
+        apps[0].add_random_todos().expect("add todos");
+        apps[0].add_random_todos().expect("add more todos");
+        for index in [0, 2, 3, 5] {
+            apps[0].ui_state.selected_index = index;
+            execute_action(&mut apps[0], Action::ToggleDone).expect("toggle");
+        }
+        apps[1].tick().expect("receive todos");
+        let before = order(&apps[0]);
+        let survivors = vec![before[1], before[4]];
+
+        let x = handle_key(KeyEvent::from(KeyCode::Char('x')), &apps[0]).expect("action");
+        request_action(&mut apps[0], x).expect("ask");
+        assert_eq!(order(&apps[0]), before);
+        request_action(&mut apps[0], Action::ConfirmPending).expect("confirm");
+
+        assert_eq!(order(&apps[0]), survivors);
+        assert!(apps[0].find_orphans().is_empty());
+        assert_eq!(apps[1].process_incoming_deltas().expect("receive"), 1);
+        assert_eq!(apps[0].store, apps[1].store);
+
+        // Undo brings the cleared todos back one by one, each at its old place
+        for _ in 0..4 {
+            execute_action(&mut apps[0], Action::Undo).expect("undo");
+        }
+        assert_eq!(order(&apps[0]), before);
+    }
+
+    #[test]
+    fn test_clear_all_keeps_concurrent_additions() {
+        let mut apps = connected_apps(2);
+        let ctrl_x = KeyEvent::new(KeyCode::Char('x'), crossterm::event::KeyModifiers::CONTROL);
+        let action = handle_key(ctrl_x, &apps[0]).expect("action");
+        assert_eq!(action, Action::ClearAll);
+        request_action(&mut apps[0], action).expect("ask");
+        assert_eq!(apps[0].ui_state.pending_action, None);
+
+        apps[0].add_random_todos().expect("add todos");
+        apps[1].tick().expect("receive todos");
+        let cleared = order(&apps[0]);
+        // Added by the peer before it hears of the clear
+        apps[1].add_random_todos().expect("add more todos");
+        let added: std::collections::HashSet<_> = order(&apps[1])
+            .into_iter()
+            .filter(|dot| !cleared.contains(dot))
+            .collect();
+
+        request_action(&mut apps[0], action).expect("ask");
+        assert_eq!(order(&apps[0]), cleared);
+        request_action(&mut apps[0], Action::ConfirmPending).expect("confirm");
+        assert!(order(&apps[0]).is_empty());
+        for app in apps.iter_mut() {
+            app.tick().expect("sync");
+        }
+
+        assert_eq!(apps[0].store, apps[1].store);
+        assert_eq!(
+            order(&apps[0])
+                .into_iter()
+                .collect::<std::collections::HashSet<_>>(),
+            added
+        );
+        // The cleared todos' maps are gone too, not left behind as orphans
+        assert!(apps[0].find_orphans().is_empty());
+        let list = apps[0].list_store();
+        assert!(
+            cleared
+                .iter()
+                .all(|dot| crate::todo::read_todo(list, dot).is_none())
+        );
+    }
+
+    #[test]
+    fn test_peer_list_selects_peer_to_sync() {
+        let mut apps = connected_apps(3);
+        for app in &mut apps[1..] {
+            app.add_random_todos().expect("add todos");
+        }
+        apps[0].process_incoming_deltas().expect("hear peers");
+        execute_action(&mut apps[0], Action::RequestContextReport).expect("open peers");
+
+        let press = |app: &mut App, c: char| {
+            let action = handle_key(KeyEvent::from(KeyCode::Char(c)), app).expect("action");
+            execute_action(app, action).expect("run");
+            action
+        };
+        press(&mut apps[0], 'j');
+        press(&mut apps[0], 'j');
+        assert_eq!(apps[0].ui_state.peer_index, 1);
+        assert_eq!(
+            press(&mut apps[0], 's'),
+            Action::SyncWithPeer(ReplicaId::new(3))
+        );
+        assert!(apps[0].ui_state.show_context_report);
+        press(&mut apps[0], 'q');
+        assert!(!apps[0].ui_state.show_context_report);
+    }
+
+    #[test]
+    fn test_concurrent_postpones_add_up() {
+        let mut apps = connected_apps(2);
+        apps[0].add_random_todos().expect("add todos");
+        apps[1].tick().expect("receive todos");
+        let first = order(&apps[0])[0];
+
+        execute_action(&mut apps[0], Action::MovePriorityDown).expect("postpone");
+        execute_action(&mut apps[0], Action::MovePriorityDown).expect("postpone again");
+        execute_action(&mut apps[1], Action::MovePriorityDown).expect("postpone");
+        apps[0].tick().expect("receive");
+        apps[1].tick().expect("receive");
+
+        assert_eq!(apps[0].store, apps[1].store);
+        for app in &apps {
+            let todo = crate::todo::read_todo(app.list_store(), &first).expect("todo");
+            assert_eq!(todo.postponed, 3);
+            assert!(!todo.has_conflicts());
+        }
+    }
+
+    #[test]
+    fn test_hidden_done_todos_are_stepped_over() {
+        let mut app = test_app();
+        app.add_random_todos().expect("add todos");
+        let [a, b, c] = order(&app)[..] else {
+            panic!("expected three todos");
+        };
+        app.ui_state.selected_index = 1;
+        execute_action(&mut app, Action::ToggleDone).expect("toggle");
+
+        let h = handle_key(KeyEvent::from(KeyCode::Char('h')), &app).expect("action");
+        execute_action(&mut app, h).expect("hide done");
+        let shown: Vec<_> = app
+            .visible_todos()
+            .into_iter()
+            .map(|(dot, _)| dot)
+            .collect();
+        assert_eq!(shown, vec![a, c]);
+        assert_eq!(app.progress(), (1, 3));
+
+        // J moves below the next shown todo, not just past the hidden one
+        app.ui_state.selected_index = 0;
+        execute_action(&mut app, Action::MovePriorityDown).expect("move down");
+        assert_eq!(order(&app), vec![b, c, a]);
+        assert_eq!(app.ui_state.selected_index, 1);
+
+        // Actions on the selection hit the shown todo, not the hidden one
+        execute_action(&mut app, Action::Delete).expect("delete");
+        assert_eq!(order(&app), vec![b, c]);
+
+        execute_action(&mut app, Action::ToggleHideDone).expect("show done");
+        assert_eq!(app.visible_todos().len(), 2);
+    }
+
+    #[test]
+    fn test_assign_picks_from_known_replicas() {
+        let mut apps = connected_apps(2);
+        apps[0].add_random_todos().expect("add todos");
+        apps[1].tick().expect("receive todos");
+        let assignee = |app: &App| app.visible_todos()[0].1.assignee.clone();
+        assert_eq!(assignee(&apps[1]), vec!["01".to_string()]);
+
+        let a = handle_key(KeyEvent::from(KeyCode::Char('a')), &apps[1]).expect("action");
+        execute_action(&mut apps[1], a).expect("open picker");
+        assert_eq!(apps[1].ui_state.mode, Mode::Assign);
+        assert_eq!(
+            apps[1].assignee_candidates(),
+            vec![ReplicaId::new(1), ReplicaId::new(2)]
+        );
+        // Starts on the current assignee
+        assert_eq!(apps[1].ui_state.assign_index, 0);
+
+        handle_assign_key(KeyEvent::from(KeyCode::Char('j')), &mut apps[1]).expect("next");
+        handle_assign_key(KeyEvent::from(KeyCode::Enter), &mut apps[1]).expect("assign");
+        assert_eq!(apps[1].ui_state.mode, Mode::Normal);
+        assert_eq!(assignee(&apps[1]), vec!["02".to_string()]);
+
+        apps[0].tick().expect("receive assignment");
+        assert_eq!(assignee(&apps[0]), vec!["02".to_string()]);
+
+        execute_action(&mut apps[1], Action::Undo).expect("undo");
+        assert_eq!(assignee(&apps[1]), vec!["01".to_string()]);
+    }
+}
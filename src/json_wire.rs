@@ -0,0 +1,134 @@
+// ABOUTME: JSON encoding of network messages, for reading traffic while debugging.
+// ABOUTME: Map keys that are not strings (e.g. dots) are written as JSON text.
+
+use ciborium::value::{Integer, Value};
+use serde::{Serialize, de::DeserializeOwned};
+use serde_json::{Map, Number, Value as Json};
+use std::io;
+
+/// Prefix of an object key that holds a non-string key as JSON text.
+/// String keys starting with it get it doubled.
+const KEY_PREFIX: char = '#';
+
+/// Encode `value` as JSON.
+///
+/// The causal stores inside messages use dots as map keys, which JSON
+/// objects cannot hold, so the value goes through a [`Value`] tree first and
+/// such keys are written as `#` followed by their JSON text, e.g.
+/// `"#[1,0,5]"`. A string key that starts with `#` gets a second one.
+pub fn to_vec<T: Serialize>(value: &T) -> io::Result<Vec<u8>> {
+    let tree = Value::serialized(value).map_err(invalid_data)?;
+    serde_json::to_vec(&to_json(tree)?).map_err(invalid_data)
+}
+
+/// Decode JSON written by [`to_vec`].
+pub fn from_slice<T: DeserializeOwned>(data: &[u8]) -> io::Result<T> {
+    let json: Json = serde_json::from_slice(data).map_err(invalid_data)?;
+    from_json(json)?.deserialized().map_err(invalid_data)
+}
+
+fn to_json(value: Value) -> io::Result<Json> {
+    Ok(match value {
+        Value::Null => Json::Null,
+        Value::Bool(b) => Json::Bool(b),
+        Value::Integer(i) => {
+            let i = i128::from(i);
+            let number = match u64::try_from(i) {
+                Ok(u) => Number::from(u),
+                Err(_) => i64::try_from(i).map(Number::from).map_err(invalid_data)?,
+            };
+            Json::Number(number)
+        }
+        // NaN and infinities have no JSON form and become null
+        Value::Float(f) => Number::from_f64(f).map_or(Json::Null, Json::Number),
+        Value::Text(s) => Json::String(s),
+        Value::Bytes(bytes) => Json::Array(bytes.into_iter().map(Json::from).collect()),
+        Value::Tag(_, value) => to_json(*value)?,
+        Value::Array(items) => {
+            Json::Array(items.into_iter().map(to_json).collect::<Result<_, _>>()?)
+        }
+        Value::Map(entries) => {
+            let mut object = Map::with_capacity(entries.len());
+            for (key, value) in entries {
+                object.insert(key_to_json(key)?, to_json(value)?);
+            }
+            Json::Object(object)
+        }
+        other => return Err(invalid_data(format!("unsupported value {other:?}"))),
+    })
+}
+
+fn key_to_json(key: Value) -> io::Result<String> {
+    Ok(match key {
+        Value::Text(s) if s.starts_with(KEY_PREFIX) => format!("{KEY_PREFIX}{s}"),
+        Value::Text(s) => s,
+        key => format!("{KEY_PREFIX}{}", to_json(key)?),
+    })
+}
+
+fn from_json(json: Json) -> io::Result<Value> {
+    Ok(match json {
+        Json::Null => Value::Null,
+        Json::Bool(b) => Value::Bool(b),
+        Json::Number(n) => match (n.as_u64(), n.as_i64(), n.as_f64()) {
+            (Some(u), _, _) => Value::Integer(Integer::from(u)),
+            (_, Some(i), _) => Value::Integer(Integer::from(i)),
+            (_, _, Some(f)) => Value::Float(f),
+            _ => return Err(invalid_data(format!("unsupported number {n}"))),
+        },
+        Json::String(s) => Value::Text(s),
+        Json::Array(items) => {
+            Value::Array(items.into_iter().map(from_json).collect::<Result<_, _>>()?)
+        }
+        Json::Object(object) => Value::Map(
+            object
+                .into_iter()
+                .map(|(key, value)| Ok((key_from_json(key)?, from_json(value)?)))
+                .collect::<io::Result<_>>()?,
+        ),
+    })
+}
+
+fn key_from_json(key: String) -> io::Result<Value> {
+    match key.strip_prefix(KEY_PREFIX) {
+        Some(rest) if rest.starts_with(KEY_PREFIX) => Ok(Value::Text(rest.to_string())),
+        Some(rest) => from_json(serde_json::from_str(rest).map_err(invalid_data)?),
+        None => Ok(Value::Text(key)),
+    }
+}
+
+fn invalid_data(e: impl ToString) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_non_string_keys_roundtrip() {
+        let mut map = BTreeMap::new();
+        map.insert((1u8, 5u64), "dot key".to_string());
+        map.insert((2, 1), "#hash".to_string());
+
+        let data = to_vec(&map).expect("encode");
+        let json = String::from_utf8(data.clone()).expect("utf-8");
+        assert!(json.contains(r##""#[1,5]":"dot key""##));
+
+        let back: BTreeMap<(u8, u64), String> = from_slice(&data).expect("decode");
+        assert_eq!(back, map);
+    }
+
+    #[test]
+    fn test_string_keys_stay_readable_and_escape_the_prefix() {
+        let mut map = BTreeMap::new();
+        map.insert("text".to_string(), -3i64);
+        map.insert("#tag".to_string(), 7);
+
+        let data = to_vec(&map).expect("encode");
+        assert_eq!(data, br###"{"##tag":7,"text":-3}"###);
+        let back: BTreeMap<String, i64> = from_slice(&data).expect("decode");
+        assert_eq!(back, map);
+    }
+}
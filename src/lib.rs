@@ -0,0 +1,309 @@
+// ABOUTME: Library root exposing the todo app's modules.
+// ABOUTME: Shared by the terminal binary and the benchmarks.
+
+//! # P2P Todo List - DSON CRDT Demo
+//!
+//! Terminal-based collaborative todo list demonstrating delta-state CRDT synchronization.
+//! Multiple instances communicate via UDP broadcast, automatically syncing changes and
+//! preserving concurrent edits as multi-value conflicts.
+//!
+//! ## Quick Start
+//!
+//! All instances must use the same port (default 7878). Run multiple terminals:
+//!
+//! ```bash
+//! cargo run    # Terminal 1
+//! cargo run    # Terminal 2
+//! cargo run    # Terminal 3
+//! ```
+//!
+//! Pass a port as the first argument (`cargo run -- 7879`) to use a different port.
+//! `--log-file todo.log` appends every log message, timestamped, to a file; the
+//! log panel only keeps the last 50.
+//! `--allow 3a,7f` syncs only with those replica ids; `--deny 3a` syncs with
+//! everyone else. Sender ids are not authenticated, so this keeps teams on a shared
+//! LAN apart but is no protection against a hostile peer.
+//! `cargo run -- --simulate 5` runs five in-process replicas without a terminal UI
+//! and prints a convergence report.
+//! `X` in the UI exports the store to `snapshot-{replica}.bin`;
+//! `cargo run -- --merge-snapshots a.bin b.bin out.bin` merges two snapshots offline.
+//!
+//! ## Keyboard Controls
+//!
+//! - `q` - Quit
+//! - `?` - Show all keys and what the conflict markers mean over the whole screen (j/k: scroll, ?/Esc: close)
+//! - `i` - Add todo
+//! - `Enter` - Edit todo; while typing, Left/Right/Home/End move the cursor
+//! - `M` - Edit text, done, level, repeat and color in one form (Up/Down:
+//!   field, Space: change value, Enter on the last field: save, Esc: discard).
+//!   Only the changed fields are written, in a single delta, so a conflict on an
+//!   untouched field stays
+//! - `Space` - Toggle done
+//! - `Ctrl-Space` - Mark every todo done (or all not done if they already are), as
+//!   one transaction and one delta
+//! - `d` - Delete todo (asks y/n first, naming the todo and how many peers see it)
+//! - `x` - Delete all done todos in one transaction (asks y/n first)
+//! - `Ctrl-X` - Delete every todo of the list in one transaction (asks y/n first);
+//!   todos peers add concurrently survive
+//! - `u`/`U` - Undo/redo your last add, edit, toggle, delete or move (written as a
+//!   new edit that peers receive, not a rollback); `Ctrl-R` also redoes
+//! - `j/k` - Navigate
+//! - `J/K` - Change priority; `J` counts as postponing the todo, shown as
+//!   `(postponed ×3)` and summed across replicas
+//! - `Ctrl-K/Ctrl-J` - Move the todo straight to the top/bottom of the list
+//! - `~` - Reverse the priority order of the list (press again to restore it).
+//!   Every entry is removed and inserted again, so if two replicas reverse at
+//!   once, each todo has two entries (shown once; see `=`)
+//! - `Ctrl-A` - Sort the priority order of the list by text, ignoring case
+//! - `Ctrl-D` - Move done todos below open ones in the priority order. Both
+//!   rewrite the shared order like `~`, unlike the view's sort on `s`
+//! - `a` - Assign the todo to yourself or a known peer (j/k: choose, Enter: assign,
+//!   Esc: cancel); new todos are assigned to their creator and shown as `@3a` in
+//!   the replica's color
+//! - `m` - Grab the todo (j/k or a typed position: drop point, Enter: drop,
+//!   Esc: cancel); the move is a single delta
+//! - `g/G` - Jump to first/last todo
+//! - `c` - Resolve a conflict (j/k: choose, Tab: text/done, Enter: keep,
+//!   m: merge texts, Esc: cancel); each value is prefixed by the replica that
+//!   wrote it, as is each conflicting text and done value in the details
+//! - `↑/↓` - Scroll logs
+//! - `p` - Toggle isolation (the status bar shows `isolated for 00:42, 5 local change(s)
+//!   pending`)
+//! - `R` - Resync now (broadcast context and request full state; at most every 2s)
+//! - `S` - List known peers, asking each for its causal context to show how many
+//!   operations each side is missing (j/k: select, s: exchange full states with
+//!   the selected peer now, other keys: close)
+//! - `r` - Add sample todos
+//! - `v` - Show todo details, including which replica last changed its text and
+//!   its done state
+//! - `H` - Show the selected todo's edit history: each change seen since startup,
+//!   with when and by which replica (j/k: scroll, H/Esc: close)
+//! - `y` - Duplicate todo
+//! - `Y` - Copy the todo's text to the system clipboard (every value, one per line,
+//!   if conflicted); without a clipboard, e.g. over SSH, the log says so
+//! - `n`/`o` - Edit notes (Enter: newline, Ctrl-S: save, Esc: cancel); todos with
+//!   notes show ✎ in the list
+//! - `e` - Expand subtasks (j/k: nav, space: toggle, a: add, d: delete, Esc: close)
+//! - `I` - Add a subtask to the selected todo (the row shows progress like `(2/5 ▰▰▱▱▱)`)
+//! - `+`/`-` - Add/remove a tag on the selected todo
+//! - `#` - Filter by tags: shows todos carrying all the space-separated tags entered;
+//!   press `#` again to clear
+//! - `Ctrl-T` - Filter by the next tag used in the list, after the last one show
+//!   all todos again; the list title shows the active tag
+//! - `/` - Search todo texts as you type, highlighting matches (Enter: keep,
+//!   Esc: clear); the selected todo stays selected while it matches. Esc in the
+//!   list also clears a kept search
+//! - `V` - Keep todos that don't match the search listed; `n`/`N` then jump to
+//!   the next/previous match
+//! - `C` - Toggle causal context view (version vector / missing dots)
+//! - `F` - Focus the causal context panel (j/k: scroll, Esc: unfocus)
+//! - `N` - Show replicas by friendly name (e.g. `fancy-koala` for `3a`) in logs and
+//!   the context panel
+//! - `l` - Cycle level (none → high → medium → low)
+//! - `E` - Cycle repeating (none → daily → weekly); completing a repeating todo adds
+//!   a fresh copy below it (same text, tags, level, category and color) on this
+//!   replica only
+//! - `B` - Cycle the todo's color label (none → red → yellow → green → blue →
+//!   purple), shown as a bullet before its text; concurrent changes show a
+//!   half-filled bullet until the next cycle
+//! - `L` - Group the list by level
+//! - `.` - Prefix each todo with its rank in the priority list (`3.`), which stays
+//!   the same in filtered and sorted views
+//! - `s` - Cycle the view's sort: priority, alphabetical, done last, conflicted
+//!   first. Only this replica's view changes, not the shared order; `J`/`K` and
+//!   the other moves are off until back in priority order
+//! - `t` - Cycle the category through those used in the list (and none); `T` names
+//!   a new one (empty input clears it). Categories show as colored badges, the color
+//!   derived from the name so it is the same on every replica
+//! - `f` - Filter by category (empty input shows all)
+//! - `b` - Show only todos created by a replica, given as 2 hex digits (`*` shows
+//!   all); combines with the other filters and search
+//! - `w` - Wrap long todo texts instead of truncating them
+//! - `h` - Hide done todos (they still count in the progress; the status bar says
+//!   they are hidden, and `J/K` step over them)
+//! - `A` - Toggle auto-complete (checking a todo's last subtask also marks the todo done, as its own undoable edit)
+//! - `D` - Toggle done wins (a todo marked done concurrently with undone shows as done)
+//! - `Tab`/`Shift-Tab` - Switch list
+//! - `Ctrl-N` - Create a list
+//! - `X` - Export a binary snapshot of the store
+//! - `Z` - Compact: rebuild the store so the causal context is a single interval and
+//!   offer it to peers (refused while isolated or while conflicts exist)
+//! - `O`/`P` - Restore orphaned todos to the bottom / purge them (the status bar
+//!   counts todos left without a priority entry by a delete concurrent with an edit)
+//! - `=` - Repair the priority order: remove the entries repeating a todo listed
+//!   earlier (the status bar counts them; concurrent moves of the same todo
+//!   leave one per replica, and only the first counts). Every 30 seconds, once
+//!   no delta has arrived for 5 seconds, every list is repaired automatically
+//!   in one transaction: repeats are removed and orphans restored
+//!
+//! ## Architecture
+//!
+//! ### Data Model
+//!
+//! ```text
+//! CausalDotStore<OrMap<String>>
+//!   └─ "{list name}" → OrMap
+//!        ├─ "created_at" → MvReg<I64>
+//!        ├─ "{replica_id}:{counter}" → OrMap
+//!        │    ├─ "text" → MvReg<String>
+//!        │    ├─ "done" → MvReg<Bool>
+//!        │    ├─ "creator" → MvReg<String>
+//!        │    ├─ "assignee" → MvReg<String> (hex replica id)
+//!        │    ├─ "created_at" → MvReg<I64>
+//!        │    ├─ "modified_at" → MvReg<I64>
+//!        │    ├─ "notes" → MvReg<String>
+//!        │    ├─ "level" → MvReg<String> ("high" / "medium" / "low")
+//!        │    ├─ "category" → MvReg<String>
+//!        │    ├─ "recur" → MvReg<String> ("daily" / "weekly")
+//!        │    ├─ "color" → MvReg<String> ("red" / "yellow" / "green" / "blue" / "purple")
+//!        │    ├─ "subtasks" → OrArray
+//!        │    │    └─ ["{replica_id}:{counter}", ...]
+//!        │    ├─ "subtask_items" → OrMap
+//!        │    │    └─ "{replica_id}:{counter}" → OrMap { "text", "done" }
+//!        │    ├─ "tags" → OrMap (tag name → MvReg<Bool>, add-wins set)
+//!        │    └─ "postponed" → OrMap (replica id → MvReg<U64>, summed grow-only counter)
+//!        └─ "priority" → OrArray
+//!             └─ ["{replica_id}:{counter}", ...]
+//! ```
+//!
+//! ### CRDT Types
+//!
+//! - **OrMap** - Observed-remove map
+//! - **MvReg** - Multi-value register (preserves concurrent writes)
+//! - **OrArray** - Ordered list
+//!
+//! ### Network
+//!
+//! - UDP broadcast to 255.255.255.255, or with `--ipv6` multicast to the all-nodes
+//!   group `ff02::1` (`--interface 2` picks the interface by index; default: the OS's)
+//! - `--multicast-group 239.0.0.1` multicasts over IPv4 instead of broadcasting, so
+//!   switches only forward the traffic to hosts running the app; `--multicast-ttl`
+//!   sets how many routers it may cross (default 1: LAN only)
+//! - `--interface 192.168.1.5` sends broadcasts and multicasts from that interface on
+//!   machines with several NICs (with `--ipv6` it takes an interface index); the
+//!   shared port stays bound to all addresses so broadcasts still arrive. The log
+//!   names the port and interface at startup
+//! - SO_REUSEPORT enables multiple instances on one port
+//! - Delta-based sync broadcasts minimal changes
+//! - Once more than 3 peers are known, each delta is sent to 3 random peers instead
+//!   (`--gossip-fanout N`), and every replica passes a new delta on once the same
+//!   way; anyone it misses catches up through anti-entropy
+//! - The last 1000 received deltas are remembered by a hash of their send time and
+//!   dots, so a replay or a copy forwarded by another peer is skipped unprocessed
+//! - Rapid local edits are coalesced into one delta after 50ms of quiet
+//!   (`--coalesce-ms 0` disables)
+//! - Anti-entropy broadcasts context every 10s, skipped while deltas flow both ways
+//!   (`--verbose` logs the skips)
+//! - Comparing contexts counts the operations each side is missing; when both miss
+//!   some, the side missing fewer sends its full state and the other only asks for it
+//! - New replicas broadcast a full-state request at startup (retried with backoff)
+//! - Deltas carry the sender's wall-clock time; the status bar shows the average
+//!   apparent propagation latency (includes clock skew between machines)
+//! - Every message carries a schema version; messages from newer clients are
+//!   ignored with a log line asking to upgrade
+//! - Messages are MessagePack; `--wire-format json` sends readable JSON instead for
+//!   inspecting traffic (dot-keyed maps get keys like `"#[16778240,1]"`). Every
+//!   replica must use the same format, messages in the other one fail to decode
+//! - At most 64 messages are read per frame, so a sync storm can't freeze the UI;
+//!   the rest are read on the next frames
+//! - After applying a delta, the causal context must have grown and new priority
+//!   entries must be todo keys; anything else is logged as a suspicious delta
+//! - Messages from replicas excluded by `--allow`/`--deny` are dropped unread and
+//!   summed up in the log every 10s
+//! - Compacting starts a new store generation; peers adopt it only if it covers
+//!   all their operations. A peer still on the previous generation is sent the
+//!   compacted state again when its context or a delta arrives; one that edited
+//!   while partitioned can't adopt it and stays behind until restarted (losing
+//!   those edits)
+//! - Messages larger than the 1472-byte MTU are not sent (no fragmentation yet);
+//!   the log names the size so a list that outgrew UDP is easy to spot. A full
+//!   state is measured before any send and logged with how far over it is
+//!
+//! ## Observing CRDTs
+//!
+//! ### Concurrent Edits
+//!
+//! 1. Add todo: "Buy milk"
+//! 2. Edit simultaneously in two instances:
+//!    - Instance 1: "Buy whole milk"
+//!    - Instance 2: "Buy oat milk"
+//! 3. Both show: `⚠ [Buy whole milk, Buy oat milk]`
+//!
+//! The system preserves conflicts, not resolves them. Press `c` on a conflicted
+//! todo to pick the value to keep, or start an instance with
+//! `--auto-resolve longer` (or `shorter`, `alphabetical`) to have it write a
+//! winner as soon as a conflicting delta arrives; the winner is an ordinary
+//! edit that every replica then receives. `--resolve-done` does the same for
+//! done conflicts, always keeping done.
+//!
+//! ### Network Partitions
+//!
+//! 1. Start two instances
+//! 2. Press `p` to isolate instance 1
+//! 3. Make changes in both; instance 1's status bar counts its pending changes
+//! 4. Press `p` to reconnect
+//! 5. Reconnecting logs how long instance 1 was isolated and how many changes it
+//!    syncs, then broadcasts its context at once; anti-entropy merges state automatically
+//!
+//! ### Priority Conflicts
+//!
+//! Concurrent reordering may interleave, but replicas converge.
+//!
+//! ## Implementation
+//!
+//! - Each replica gets an 8-bit ID from the timestamp
+//! - Todos use dot encoding: `"{replica_id}:{counter}"`
+//! - Transactions (`App::with_transaction`) work on a copy of the store that is
+//!   swapped in on commit: their reads see their own writes, but no delta from a
+//!   peer is applied until they commit, and racing writes merge as concurrent values
+//! - Logs and creator badges use 6 colors, cycling by replica ID
+//! - The status bar shows the current list's progress (`7/12 done (58%)`); a todo
+//!   counts as done when its checkbox shows it so (with conflicting done values, the
+//!   first one, or any `true` with done wins on)
+//! - Typing stops at 500 characters per todo text (`--max-text-len`, counting
+//!   characters, not bytes; the insert title shows `n/500`); longer texts written by
+//!   other clients are shown cut to 1000 characters with a warning in the log
+//! - Quitting saves the selection, log scroll and filters to `ui-state-{port}.bin` in
+//!   the working directory; the next start on that port restores them, clamping the
+//!   selection to the list once it has todos
+//!
+//! ## File Organization
+//!
+//! - `main.rs` - Event loop and terminal setup
+//! - `lib.rs` - Crate documentation and module tree
+//! - `app.rs` - Application state and sync logic
+//! - `todo.rs` - Todo CRDT operations
+//! - `priority.rs` - Priority array management
+//! - `network.rs` - UDP broadcast and serialization
+//! - `ui.rs` - Terminal rendering (ratatui)
+//! - `input.rs` - Keyboard handling
+//! - `anti_entropy.rs` - Partition recovery protocol
+//! - `cli.rs` - Command-line arguments
+//! - `simulation.rs` - In-process multi-replica simulation
+//! - `snapshot.rs` - Binary snapshot export and offline merge
+//! - `diff.rs` - Word diff of conflicting text values
+//! - `compaction.rs` - Rebuilding the store with a minimal causal context
+//! - `json_wire.rs` - JSON wire format for debugging
+//! - `undo.rs` - Undo/redo through compensating transactions
+//! - `history.rs` - Per-todo edit history derived from applied deltas
+//! - `sim.rs` - Headless convergence test harness (tests only)
+
+pub mod anti_entropy;
+pub mod app;
+pub mod cli;
+pub mod compaction;
+pub mod diff;
+pub mod history;
+pub mod input;
+pub mod json_wire;
+pub mod network;
+pub mod priority;
+#[cfg(test)]
+mod sim;
+pub mod simulation;
+pub mod snapshot;
+#[cfg(test)]
+mod test_util;
+pub mod todo;
+pub mod ui;
+pub mod undo;
@@ -1,128 +1,65 @@
 // ABOUTME: P2P todo list demonstrating DSON's transaction API.
 // ABOUTME: Run multiple instances to observe CRDT synchronization.
 
-//! # P2P Todo List - DSON CRDT Demo
-//!
-//! Terminal-based collaborative todo list demonstrating delta-state CRDT synchronization.
-//! Multiple instances communicate via UDP broadcast, automatically syncing changes and
-//! preserving concurrent edits as multi-value conflicts.
-//!
-//! ## Quick Start
-//!
-//! All instances must use the same port (default 7878). Run multiple terminals:
-//!
-//! ```bash
-//! cargo run    # Terminal 1
-//! cargo run    # Terminal 2
-//! cargo run    # Terminal 3
-//! ```
-//!
-//! ## Keyboard Controls
-//!
-//! - `q` - Quit
-//! - `i` - Add todo
-//! - `Enter` - Edit todo
-//! - `Space` - Toggle done
-//! - `d` - Delete todo
-//! - `j/k` - Navigate
-//! - `J/K` - Change priority
-//! - `↑/↓` - Scroll logs
-//! - `p` - Toggle isolation
-//! - `r` - Add sample todos
-//!
-//! ## Architecture
-//!
-//! ### Data Model
-//!
-//! ```text
-//! CausalDotStore<OrMap<String>>
-//!   ├─ "{replica_id}:{counter}" → OrMap
-//!   │    ├─ "text" → MvReg<String>
-//!   │    └─ "done" → MvReg<Bool>
-//!   └─ "priority" → OrArray
-//!        └─ ["{replica_id}:{counter}", ...]
-//! ```
-//!
-//! ### CRDT Types
-//!
-//! - **OrMap** - Observed-remove map
-//! - **MvReg** - Multi-value register (preserves concurrent writes)
-//! - **OrArray** - Ordered list
-//!
-//! ### Network
-//!
-//! - UDP broadcast to 255.255.255.255
-//! - SO_REUSEPORT enables multiple instances on one port
-//! - Delta-based sync broadcasts minimal changes
-//! - Anti-entropy broadcasts context every 10s
-//!
-//! ## Observing CRDTs
-//!
-//! ### Concurrent Edits
-//!
-//! 1. Add todo: "Buy milk"
-//! 2. Edit simultaneously in two instances:
-//!    - Instance 1: "Buy whole milk"
-//!    - Instance 2: "Buy oat milk"
-//! 3. Both show: `⚠ [Buy whole milk, Buy oat milk]`
-//!
-//! The system preserves conflicts, not resolves them.
-//!
-//! ### Network Partitions
-//!
-//! 1. Start two instances
-//! 2. Press `p` to isolate instance 1
-//! 3. Make changes in both
-//! 4. Press `p` to reconnect
-//! 5. Anti-entropy merges state automatically
-//!
-//! ### Priority Conflicts
-//!
-//! Concurrent reordering may interleave, but replicas converge.
-//!
-//! ## Implementation
-//!
-//! - Each replica gets an 8-bit ID from the timestamp
-//! - Todos use dot encoding: `"{replica_id}:{counter}"`
-//! - Transactions provide read-committed isolation
-//! - Logs use 6 colors, cycling by replica ID
-//!
-//! ## File Organization
-//!
-//! - `main.rs` - Event loop and terminal setup
-//! - `app.rs` - Application state and sync logic
-//! - `todo.rs` - Todo CRDT operations
-//! - `priority.rs` - Priority array management
-//! - `network.rs` - UDP broadcast and serialization
-//! - `ui.rs` - Terminal rendering (ratatui)
-//! - `input.rs` - Keyboard handling
-//! - `anti_entropy.rs` - Partition recovery protocol
-
-mod anti_entropy;
-mod app;
-mod input;
-mod network;
-mod priority;
-mod todo;
-mod ui;
-
-use app::App;
 use crossterm::{
     event::{self, Event},
     execute,
     terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
 };
+use dson_p2p_todo::{
+    app::{self, App},
+    cli, input, network, simulation, snapshot, ui,
+};
 use ratatui::{Terminal, backend::CrosstermBackend};
 use std::{io, time::Duration};
 
 fn main() -> io::Result<()> {
-    // Parse port from args or use default
-    let port = std::env::args()
-        .nth(1)
-        .and_then(|s| s.parse().ok())
-        .unwrap_or(network::DEFAULT_PORT);
+    let options = cli::parse(std::env::args().skip(1))
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+    if let Some(replicas) = options.simulate {
+        return simulation::run(replicas);
+    }
 
-    let mut app = App::new(port)?;
+    if let Some((a, b, out)) = &options.merge_snapshots {
+        snapshot::merge_snapshots(a, b, out)?;
+        println!(
+            "Merged {} and {} into {}",
+            a.display(),
+            b.display(),
+            out.display()
+        );
+        return Ok(());
+    }
+
+    let mut app = App::new(options.port, options.ip_version)?;
+    app.verbose = options.verbose;
+    app.auto_resolve = options.auto_resolve;
+    app.resolve_done = options.resolve_done;
+    app.peer_filter = options.peer_filter;
+    app.max_text_len = options.max_text_len;
+    app.gossip_fanout = options.gossip_fanout;
+    app.wire_format = options.wire_format;
+    app.done_wins = options.resolve_done;
+    if let Some(path) = &options.log_file {
+        app.open_log_file(path)?;
+    }
+    app.log(format!(
+        "[Replica {}] Bound port {}, {}",
+        app.replica_id, options.port, options.ip_version
+    ));
+    if app.wire_format == network::WireFormat::Json {
+        app.log("Sending JSON; peers must also run with --wire-format json".to_string());
+    }
+    app.coalesce_window =
+        (options.coalesce_ms > 0).then(|| Duration::from_millis(options.coalesce_ms));
+    let ui_state_path = app::ui_state_path(options.port);
+    if let Err(e) = app.restore_ui_state(&ui_state_path) {
+        app.log(format!(
+            "WARNING could not restore UI state from {}: {e}",
+            ui_state_path.display()
+        ));
+    }
 
     // Setup terminal
     enable_raw_mode()?;
@@ -139,6 +76,13 @@ fn main() -> io::Result<()> {
     execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
     terminal.show_cursor()?;
 
+    if let Err(e) = app.save_ui_state(&ui_state_path) {
+        app.log(format!(
+            "WARNING could not save UI state to {}: {e}",
+            ui_state_path.display()
+        ));
+    }
+    app.flush_log()?;
     result
 }
 
@@ -150,21 +94,48 @@ fn run_app<B: ratatui::backend::Backend>(
         terminal.draw(|f| ui::draw(f, app))?;
 
         // Poll for events with timeout to allow network processing.
-        if event::poll(Duration::from_millis(100))?
-            && let Event::Key(key) = event::read()?
-        {
-            match app.ui_state.mode {
-                app::Mode::Normal => {
-                    if let Some(action) = input::handle_key(key, app) {
-                        if action == input::Action::Quit {
-                            return Ok(());
+        if event::poll(Duration::from_millis(100))? {
+            match event::read()? {
+                Event::Key(key) => match app.ui_state.mode {
+                    app::Mode::Normal => {
+                        if let Some(action) = input::handle_key(key, app) {
+                            if action == input::Action::Quit {
+                                return app.flush_pending_delta();
+                            }
+                            input::request_action(app, action)?;
                         }
-                        input::execute_action(app, action)?;
                     }
+                    app::Mode::Insert => {
+                        input::handle_insert_key(key, app)?;
+                    }
+                    app::Mode::Notes => {
+                        input::handle_notes_key(key, app)?;
+                    }
+                    app::Mode::Subtasks => {
+                        input::handle_subtasks_key(key, app)?;
+                    }
+                    app::Mode::ResolveConflict => {
+                        input::handle_conflict_key(key, app)?;
+                    }
+                    app::Mode::Prompt(kind) => {
+                        input::handle_prompt_key(key, app, kind)?;
+                    }
+                    app::Mode::Grab => {
+                        input::handle_grab_key(key, app)?;
+                    }
+                    app::Mode::Assign => {
+                        input::handle_assign_key(key, app)?;
+                    }
+                    app::Mode::Form => {
+                        input::handle_form_key(key, app)?;
+                    }
+                },
+                Event::Resize(width, height) => {
+                    // Keep selection and log scroll on screen before the next draw.
+                    app.clamp_selection();
+                    app.clamp_log_scroll(ui::visible_log_lines(width, height));
                 }
-                app::Mode::Insert => {
-                    input::handle_insert_key(key, app)?;
-                }
+                _ => {}
             }
         }
 
@@ -5,24 +5,149 @@ use crate::app::ReplicaId;
 use dson::{CausalDotStore, Delta, OrMap};
 use serde::{Deserialize, Serialize};
 use std::{
+    collections::VecDeque,
     io,
-    net::{SocketAddr, UdpSocket},
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6, UdpSocket},
+    sync::{Arc, Mutex},
 };
 
 pub const DEFAULT_PORT: u16 = 7878;
 
+/// Link-local all-nodes multicast group, IPv6's stand-in for broadcast.
+pub const IPV6_ALL_NODES: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 1);
+
+/// IP version the UDP transport reaches peers over.
+///
+/// An IPv4 `interface` is the address of the network interface to send on;
+/// [`Ipv4Addr::UNSPECIFIED`] lets the OS pick.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IpVersion {
+    /// Broadcast to 255.255.255.255.
+    V4 { interface: Ipv4Addr },
+    /// Multicast to an IPv4 group (e.g. 239.0.0.1), reaching only hosts that
+    /// joined it instead of flooding the LAN. `ttl` is how many routers the
+    /// datagrams may cross; 1 keeps them on the local network.
+    V4Multicast {
+        group: Ipv4Addr,
+        ttl: u32,
+        interface: Ipv4Addr,
+    },
+    /// Multicast to [`IPV6_ALL_NODES`] on the interface with this index
+    /// (0 lets the OS pick). The kernel fragments datagrams over the
+    /// 1452 bytes left by the larger IPv6 header.
+    V6 { interface: u32 },
+}
+
+impl Default for IpVersion {
+    fn default() -> Self {
+        IpVersion::V4 {
+            interface: Ipv4Addr::UNSPECIFIED,
+        }
+    }
+}
+
+impl std::fmt::Display for IpVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let on_v4 = |interface: &Ipv4Addr| {
+            if interface.is_unspecified() {
+                "the default interface".to_string()
+            } else {
+                interface.to_string()
+            }
+        };
+        match self {
+            IpVersion::V4 { interface } => write!(f, "broadcasting on {}", on_v4(interface)),
+            IpVersion::V4Multicast {
+                group,
+                ttl,
+                interface,
+            } => write!(
+                f,
+                "multicasting to {group} (ttl {ttl}) on {}",
+                on_v4(interface)
+            ),
+            IpVersion::V6 { interface: 0 } => {
+                write!(
+                    f,
+                    "multicasting to {IPV6_ALL_NODES} on the default interface"
+                )
+            }
+            IpVersion::V6 { interface } => {
+                write!(
+                    f,
+                    "multicasting to {IPV6_ALL_NODES} on interface {interface}"
+                )
+            }
+        }
+    }
+}
+
+/// Largest datagram sent over UDP: a 1500 byte Ethernet MTU minus IPv4 and UDP headers.
+/// Larger messages would need fragmentation, which is not implemented.
+pub const MTU: usize = 1472;
+
+/// Schema version of the messages this build sends and understands.
+/// Bump it whenever a variant or field is added.
+///
+/// - v2: `ContextRequest` and `ContextReply`
+/// - v3: `Compacted` and the `generation` of `Delta` and `Context`
+pub const CURRENT_VERSION: u8 = 3;
+
 /// Network message types for CRDT synchronization.
+///
+/// `schema_version` must stay the first field of every variant so that
+/// [`deserialize_message`] can read it from variants it does not know.
 #[derive(Serialize, Deserialize, Debug)]
 pub enum NetworkMessage {
     /// Full delta containing CRDT state.
     Delta {
+        schema_version: u8,
         sender_id: ReplicaId,
+        /// Sender's wall-clock time (Unix ms) for incremental deltas.
+        /// `None` for full-state replies, whose dots may be arbitrarily old.
+        sent_at: Option<i64>,
         delta: Delta<CausalDotStore<OrMap<String>>>,
+        /// How often the sender's store was compacted. Stores of different
+        /// generations never join. Last, so older peers' messages decode as 0.
+        #[serde(default)]
+        generation: u32,
     },
     /// Anti-entropy: just the causal context for comparison.
     Context {
+        schema_version: u8,
         sender_id: ReplicaId,
         context: dson::CausalContext,
+        /// Compaction generation of `context`, see [`NetworkMessage::Delta`].
+        #[serde(default)]
+        generation: u32,
+    },
+    /// Ask peers for their full state (sent by freshly started replicas).
+    FullStateRequest {
+        schema_version: u8,
+        sender_id: ReplicaId,
+    },
+    /// Ask a peer for its causal context, to report how far apart we are.
+    ContextRequest {
+        schema_version: u8,
+        sender_id: ReplicaId,
+    },
+    /// Answer to a [`NetworkMessage::ContextRequest`]. Unlike
+    /// [`NetworkMessage::Context`], receiving it never triggers a sync.
+    ContextReply {
+        schema_version: u8,
+        sender_id: ReplicaId,
+        context: dson::CausalContext,
+    },
+    /// The sender rebuilt its store with a minimal causal context. Peers whose
+    /// context is covered by `base` lose nothing by adopting `state`.
+    Compacted {
+        schema_version: u8,
+        sender_id: ReplicaId,
+        /// Generation of `state`.
+        generation: u32,
+        /// The sender's causal context before compacting.
+        base: dson::CausalContext,
+        state: Delta<CausalDotStore<OrMap<String>>>,
     },
 }
 
@@ -31,6 +156,205 @@ impl NetworkMessage {
         match self {
             NetworkMessage::Delta { sender_id, .. } => *sender_id,
             NetworkMessage::Context { sender_id, .. } => *sender_id,
+            NetworkMessage::FullStateRequest { sender_id, .. } => *sender_id,
+            NetworkMessage::ContextRequest { sender_id, .. } => *sender_id,
+            NetworkMessage::ContextReply { sender_id, .. } => *sender_id,
+            NetworkMessage::Compacted { sender_id, .. } => *sender_id,
+        }
+    }
+}
+
+/// Datagram transport used by the app to exchange messages with peers.
+pub trait Transport: Send {
+    /// Broadcast a message to all peers.
+    /// If isolated is true, returns Ok without sending (simulates network partition).
+    fn broadcast(&self, data: &[u8], isolated: bool) -> io::Result<()>;
+
+    /// Send a message to a single peer.
+    /// If isolated is true, returns Ok without sending (simulates network partition).
+    fn send_to(&self, data: &[u8], addr: SocketAddr, isolated: bool) -> io::Result<()>;
+
+    /// Try to receive a message from the network (non-blocking).
+    /// If isolated is true, returns Ok(None) without reading (simulates network partition).
+    fn try_receive(&self, isolated: bool) -> io::Result<Option<(Vec<u8>, SocketAddr)>>;
+
+    /// Largest message this transport can carry, or `None` if unlimited.
+    fn mtu(&self) -> Option<usize>;
+}
+
+/// UDP broadcast transport on a shared port.
+pub struct UdpTransport {
+    socket: TransportSocket,
+    port: u16,
+}
+
+impl UdpTransport {
+    /// Bind a broadcast socket on the given port.
+    pub fn bind(port: u16, version: IpVersion) -> io::Result<Self> {
+        Ok(Self {
+            socket: create_broadcast_socket(port, version)?,
+            port,
+        })
+    }
+}
+
+impl Transport for UdpTransport {
+    fn broadcast(&self, data: &[u8], isolated: bool) -> io::Result<()> {
+        broadcast(&self.socket, data, self.port, isolated)
+    }
+
+    fn send_to(&self, data: &[u8], addr: SocketAddr, isolated: bool) -> io::Result<()> {
+        if isolated {
+            return Ok(());
+        }
+
+        check_mtu(data)?;
+        self.socket.sender().send_to(data, addr)?;
+        Ok(())
+    }
+
+    fn try_receive(&self, isolated: bool) -> io::Result<Option<(Vec<u8>, SocketAddr)>> {
+        try_receive(&self.socket, isolated)
+    }
+
+    fn mtu(&self) -> Option<usize> {
+        Some(MTU)
+    }
+}
+
+type Inbox = VecDeque<(Vec<u8>, SocketAddr)>;
+
+/// In-process transport for simulations and tests.
+/// Every transport created from the same hub receives the broadcasts of all others.
+#[derive(Clone)]
+pub struct InMemoryTransport {
+    inboxes: Arc<Mutex<Vec<Inbox>>>,
+    index: usize,
+    mtu_limited: bool,
+}
+
+impl InMemoryTransport {
+    /// Create `n` transports connected to a shared in-memory hub.
+    pub fn hub(n: usize) -> Vec<Self> {
+        let inboxes = Arc::new(Mutex::new(vec![Inbox::new(); n]));
+        (0..n)
+            .map(|index| Self {
+                inboxes: Arc::clone(&inboxes),
+                index,
+                mtu_limited: false,
+            })
+            .collect()
+    }
+
+    /// Reject messages larger than [`MTU`] like UDP does; by default any size
+    /// is carried.
+    pub fn limit_to_mtu(mut self) -> Self {
+        self.mtu_limited = true;
+        self
+    }
+
+    /// Fake socket address identifying this transport on the hub.
+    pub fn addr(&self) -> SocketAddr {
+        SocketAddr::V4(SocketAddrV4::new(
+            Ipv4Addr::LOCALHOST,
+            DEFAULT_PORT + self.index as u16,
+        ))
+    }
+}
+
+impl Transport for InMemoryTransport {
+    fn broadcast(&self, data: &[u8], isolated: bool) -> io::Result<()> {
+        if isolated {
+            return Ok(());
+        }
+
+        if self.mtu_limited {
+            check_mtu(data)?;
+        }
+        let from = self.addr();
+        let mut inboxes = self.inboxes.lock().expect("hub lock poisoned");
+        for (index, inbox) in inboxes.iter_mut().enumerate() {
+            if index != self.index {
+                inbox.push_back((data.to_vec(), from));
+            }
+        }
+        Ok(())
+    }
+
+    fn send_to(&self, data: &[u8], addr: SocketAddr, isolated: bool) -> io::Result<()> {
+        if isolated {
+            return Ok(());
+        }
+
+        if self.mtu_limited {
+            check_mtu(data)?;
+        }
+        let from = self.addr();
+        let mut inboxes = self.inboxes.lock().expect("hub lock poisoned");
+        let index = usize::from(addr.port().wrapping_sub(DEFAULT_PORT));
+        if let Some(inbox) = inboxes.get_mut(index) {
+            inbox.push_back((data.to_vec(), from));
+        }
+        Ok(())
+    }
+
+    fn try_receive(&self, isolated: bool) -> io::Result<Option<(Vec<u8>, SocketAddr)>> {
+        if isolated {
+            return Ok(None);
+        }
+
+        let mut inboxes = self.inboxes.lock().expect("hub lock poisoned");
+        Ok(inboxes[self.index].pop_front())
+    }
+
+    fn mtu(&self) -> Option<usize> {
+        self.mtu_limited.then_some(MTU)
+    }
+}
+
+/// A bound UDP socket that reaches every peer on the link, over either IP version.
+#[derive(Debug)]
+pub enum TransportSocket {
+    /// Broadcasts to 255.255.255.255, from `egress` if bound to an interface.
+    V4 {
+        socket: UdpSocket,
+        egress: Option<UdpSocket>,
+    },
+    /// Multicasts to `group`.
+    V4Multicast { socket: UdpSocket, group: Ipv4Addr },
+    /// Multicasts to [`IPV6_ALL_NODES`] on `interface`.
+    V6 { socket: UdpSocket, interface: u32 },
+}
+
+impl TransportSocket {
+    /// The socket bound to the shared port, receiving messages to every peer.
+    pub fn socket(&self) -> &UdpSocket {
+        match self {
+            TransportSocket::V4 { socket, .. }
+            | TransportSocket::V4Multicast { socket, .. }
+            | TransportSocket::V6 { socket, .. } => socket,
+        }
+    }
+
+    /// The socket messages to peers are sent from.
+    pub fn sender(&self) -> &UdpSocket {
+        match self {
+            TransportSocket::V4 {
+                egress: Some(egress),
+                ..
+            } => egress,
+            _ => self.socket(),
+        }
+    }
+
+    /// Address that reaches every peer listening on `port`.
+    pub fn broadcast_addr(&self, port: u16) -> SocketAddr {
+        match self {
+            TransportSocket::V4 { .. } => SocketAddrV4::new(Ipv4Addr::BROADCAST, port).into(),
+            TransportSocket::V4Multicast { group, .. } => SocketAddrV4::new(*group, port).into(),
+            TransportSocket::V6 { interface, .. } => {
+                SocketAddrV6::new(IPV6_ALL_NODES, port, 0, *interface).into()
+            }
         }
     }
 }
@@ -38,40 +362,121 @@ impl NetworkMessage {
 /// Create and configure a UDP socket for broadcasting.
 /// Binds to the specified port for receiving, and allows broadcasting to any port.
 /// Uses SO_REUSEPORT on macOS/BSD to allow multiple instances on the same port.
-pub fn create_broadcast_socket(port: u16) -> io::Result<UdpSocket> {
+/// For IPv4 multicast, joins the group on the given interface instead of
+/// enabling broadcast. For IPv6, binds to `[::]` and joins the all-nodes
+/// multicast group.
+///
+/// The shared port is always bound to the unspecified address: a socket bound
+/// to an interface's address no longer receives broadcasts or multicasts.
+pub fn create_broadcast_socket(port: u16, version: IpVersion) -> io::Result<TransportSocket> {
     use socket2::{Domain, Socket, Type};
-    use std::net::{Ipv4Addr, SocketAddrV4};
+
+    let domain = match version {
+        IpVersion::V4 { .. } | IpVersion::V4Multicast { .. } => Domain::IPV4,
+        IpVersion::V6 { .. } => Domain::IPV6,
+    };
 
     // Create socket with socket2 to set SO_REUSEPORT before binding
     // On macOS/BSD, SO_REUSEPORT allows multiple processes to bind to the same port
     // and all will receive copies of broadcast packets
-    let socket = Socket::new(Domain::IPV4, Type::DGRAM, None)?;
+    let socket = Socket::new(domain, Type::DGRAM, None)?;
     socket.set_reuse_address(true)?;
     #[cfg(unix)]
     socket.set_reuse_port(true)?;
-
-    socket.set_broadcast(true)?;
     socket.set_nonblocking(true)?;
 
-    let addr = SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, port);
-    socket.bind(&addr.into())?;
-
-    Ok(socket.into())
+    match version {
+        IpVersion::V4 { interface } => {
+            socket.set_broadcast(true)?;
+            let addr = SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, port);
+            socket.bind(&addr.into())?;
+            // Broadcasts leave through the interface owning the sending
+            // socket's address, so a second socket bound to it sends them.
+            // Peers answer it directly, so it is read as well.
+            let egress = if interface.is_unspecified() {
+                None
+            } else {
+                let egress = UdpSocket::bind(SocketAddrV4::new(interface, 0))?;
+                egress.set_broadcast(true)?;
+                egress.set_nonblocking(true)?;
+                Some(egress)
+            };
+            Ok(TransportSocket::V4 {
+                socket: socket.into(),
+                egress,
+            })
+        }
+        IpVersion::V4Multicast {
+            group,
+            ttl,
+            interface,
+        } => {
+            let addr = SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, port);
+            socket.bind(&addr.into())?;
+            socket.join_multicast_v4(&group, &interface)?;
+            socket.set_multicast_ttl_v4(ttl)?;
+            if !interface.is_unspecified() {
+                socket.set_multicast_if_v4(&interface)?;
+            }
+            // Other instances on this host must see our messages too
+            socket.set_multicast_loop_v4(true)?;
+            Ok(TransportSocket::V4Multicast {
+                socket: socket.into(),
+                group,
+            })
+        }
+        IpVersion::V6 { interface } => {
+            socket.set_only_v6(true)?;
+            let addr = SocketAddrV6::new(Ipv6Addr::UNSPECIFIED, port, 0, 0);
+            socket.bind(&addr.into())?;
+            socket.join_multicast_v6(&IPV6_ALL_NODES, interface)?;
+            // Other instances on this host must see our messages too
+            socket.set_multicast_loop_v6(true)?;
+            if interface != 0 {
+                socket.set_multicast_if_v6(interface)?;
+            }
+            Ok(TransportSocket::V6 {
+                socket: socket.into(),
+                interface,
+            })
+        }
+    }
 }
 
 /// Broadcast a message to all peers.
 /// If isolated is true, returns Ok without sending (simulates network partition).
 ///
 /// # Errors
-/// Returns an error if `data.len()` exceeds the network MTU (typically ~1500 bytes for Ethernet).
-pub fn broadcast(socket: &UdpSocket, data: &[u8], port: u16, isolated: bool) -> io::Result<()> {
+/// Returns `ErrorKind::InvalidInput` if `data.len()` exceeds [`MTU`].
+pub fn broadcast(
+    socket: &TransportSocket,
+    data: &[u8],
+    port: u16,
+    isolated: bool,
+) -> io::Result<()> {
     if isolated {
         // Silently drop when isolated
         return Ok(());
     }
 
-    let broadcast_addr = format!("255.255.255.255:{port}");
-    socket.send_to(data, broadcast_addr)?;
+    check_mtu(data)?;
+
+    socket.sender().send_to(data, socket.broadcast_addr(port))?;
+    Ok(())
+}
+
+/// Reject datagrams larger than [`MTU`] with a descriptive error instead of
+/// leaving it to the OS, which fails (or silently drops) opaquely.
+pub fn check_mtu(data: &[u8]) -> io::Result<()> {
+    if data.len() > MTU {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "{} bytes exceeds MTU {MTU}, consider enabling fragmentation",
+                data.len()
+            ),
+        ));
+    }
     Ok(())
 }
 
@@ -82,7 +487,7 @@ const MAX_UDP_PACKET_SIZE: usize = 65536;
 /// If isolated is true, returns Ok(None) without reading (simulates network partition).
 /// Returns Ok(None) if no message is available (WouldBlock).
 pub fn try_receive(
-    socket: &UdpSocket,
+    socket: &TransportSocket,
     isolated: bool,
 ) -> io::Result<Option<(Vec<u8>, SocketAddr)>> {
     if isolated {
@@ -91,24 +496,171 @@ pub fn try_receive(
     }
 
     let mut buf = vec![0u8; MAX_UDP_PACKET_SIZE];
-    match socket.recv_from(&mut buf) {
-        Ok((size, addr)) => {
-            buf.truncate(size);
-            Ok(Some((buf, addr)))
+    let mut sockets = vec![socket.socket()];
+    if let TransportSocket::V4 {
+        egress: Some(egress),
+        ..
+    } = socket
+    {
+        sockets.push(egress);
+    }
+    for socket in sockets {
+        match socket.recv_from(&mut buf) {
+            Ok((size, addr)) => {
+                buf.truncate(size);
+                return Ok(Some((buf, addr)));
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(None)
+}
+
+/// Encoding of messages on the wire. Every replica must use the same one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WireFormat {
+    /// Compact MessagePack.
+    #[default]
+    MessagePack,
+    /// Readable JSON, for inspecting traffic while debugging. Messages are
+    /// several times larger, so full states exceed the [`MTU`] sooner.
+    Json,
+}
+
+impl std::str::FromStr for WireFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "msgpack" => Ok(WireFormat::MessagePack),
+            "json" => Ok(WireFormat::Json),
+            _ => Err(format!("unknown wire format {s} (msgpack, json)")),
+        }
+    }
+}
+
+/// Serialize a network message to bytes in `format`.
+pub fn serialize_message(msg: &NetworkMessage, format: WireFormat) -> io::Result<Vec<u8>> {
+    match format {
+        WireFormat::MessagePack => {
+            rmp_serde::to_vec(msg).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
         }
-        Err(e) if e.kind() == io::ErrorKind::WouldBlock => Ok(None),
-        Err(e) => Err(e),
+        WireFormat::Json => crate::json_wire::to_vec(msg),
     }
 }
 
-/// Serialize a network message to bytes using MessagePack.
-pub fn serialize_message(msg: &NetworkMessage) -> io::Result<Vec<u8>> {
-    rmp_serde::to_vec(msg).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+/// Deserialize bytes in `format` to a network message.
+///
+/// # Errors
+/// Returns `ErrorKind::Unsupported` for messages from a newer schema version,
+/// and `ErrorKind::InvalidData` for anything else that cannot be decoded,
+/// including messages from peers using another wire format.
+pub fn deserialize_message(data: &[u8], format: WireFormat) -> io::Result<NetworkMessage> {
+    decode(data, format, CURRENT_VERSION)
 }
 
-/// Deserialize bytes to a network message using MessagePack.
-pub fn deserialize_message(data: &[u8]) -> io::Result<NetworkMessage> {
-    rmp_serde::from_slice(data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+/// [`deserialize_message`] for a build that understands schema versions up
+/// to `supported`.
+fn decode(data: &[u8], format: WireFormat, supported: u8) -> io::Result<NetworkMessage> {
+    let version = match format {
+        WireFormat::MessagePack => rmp_serde::from_slice(data).ok(),
+        WireFormat::Json => crate::json_wire::from_slice(data).ok(),
+    };
+    // Check the version first, newer clients may send variants we cannot decode
+    if let Some(SchemaVersion(version)) = version
+        && version > supported
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("Ignoring message from newer client (v{version}); please upgrade."),
+        ));
+    }
+
+    match format {
+        WireFormat::MessagePack => {
+            rmp_serde::from_slice(data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+        }
+        WireFormat::Json => crate::json_wire::from_slice(data),
+    }
+}
+
+/// Just the `schema_version` of an encoded message, whatever its variant.
+///
+/// A variant is encoded as a single-entry map from its name to its fields:
+/// an array in MessagePack, where the version is always the first field, or
+/// a map with the version under its field name in JSON.
+struct SchemaVersion(u8);
+
+impl<'de> Deserialize<'de> for SchemaVersion {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::{self, IgnoredAny, MapAccess, SeqAccess, Visitor};
+
+        struct FirstField(u8);
+
+        impl<'de> Deserialize<'de> for FirstField {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                struct FieldsVisitor;
+
+                impl<'de> Visitor<'de> for FieldsVisitor {
+                    type Value = FirstField;
+
+                    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                        f.write_str("variant fields starting with a schema version")
+                    }
+
+                    fn visit_seq<A: SeqAccess<'de>>(
+                        self,
+                        mut seq: A,
+                    ) -> Result<FirstField, A::Error> {
+                        let version = seq
+                            .next_element()?
+                            .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+                        while seq.next_element::<IgnoredAny>()?.is_some() {}
+                        Ok(FirstField(version))
+                    }
+
+                    fn visit_map<A: MapAccess<'de>>(
+                        self,
+                        mut map: A,
+                    ) -> Result<FirstField, A::Error> {
+                        let mut version = None;
+                        while let Some(field) = map.next_key::<String>()? {
+                            if field == "schema_version" {
+                                version = Some(map.next_value()?);
+                            } else {
+                                map.next_value::<IgnoredAny>()?;
+                            }
+                        }
+                        version
+                            .map(FirstField)
+                            .ok_or_else(|| de::Error::missing_field("schema_version"))
+                    }
+                }
+
+                deserializer.deserialize_any(FieldsVisitor)
+            }
+        }
+
+        struct VariantVisitor;
+
+        impl<'de> Visitor<'de> for VariantVisitor {
+            type Value = SchemaVersion;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("a message variant")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<SchemaVersion, A::Error> {
+                let (_, FirstField(version)) = map
+                    .next_entry::<IgnoredAny, FirstField>()?
+                    .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+                Ok(SchemaVersion(version))
+            }
+        }
+
+        deserializer.deserialize_map(VariantVisitor)
+    }
 }
 
 #[cfg(test)]
@@ -128,28 +680,322 @@ mod tests {
         let delta = tx.commit();
 
         let msg = NetworkMessage::Delta {
+            schema_version: CURRENT_VERSION,
             sender_id: ReplicaId::new(42),
+            sent_at: Some(1_700_000_000_000),
             delta,
+            generation: 2,
         };
 
-        let serialized = serialize_message(&msg).expect("Failed to serialize");
-        let deserialized = deserialize_message(&serialized).expect("Failed to deserialize");
+        let serialized =
+            serialize_message(&msg, WireFormat::default()).expect("Failed to serialize");
+        let deserialized =
+            deserialize_message(&serialized, WireFormat::default()).expect("Failed to deserialize");
 
         assert_eq!(deserialized.sender_id(), ReplicaId::new(42));
+        assert!(matches!(
+            deserialized,
+            NetworkMessage::Delta { generation: 2, .. }
+        ));
+    }
+
+    #[test]
+    fn test_json_roundtrip_keeps_causal_state() {
+        let mut store = CausalDotStore::<OrMap<String>>::default();
+        let mut tx = store.transact(Identifier::new(1, 0));
+        tx.in_map("Todos", |list_tx| {
+            list_tx.in_array("priority", |arr_tx| {
+                arr_tx.insert_register(0, dson::crdts::mvreg::MvRegValue::String("1:1".into()));
+            });
+        });
+        let delta = tx.commit();
+        let msg = NetworkMessage::Delta {
+            schema_version: CURRENT_VERSION,
+            sender_id: ReplicaId::new(42),
+            sent_at: None,
+            delta: delta.clone(),
+            generation: 0,
+        };
+
+        let data = serialize_message(&msg, WireFormat::Json).expect("serialize");
+        assert!(
+            std::str::from_utf8(&data)
+                .expect("utf-8")
+                .contains("\"Todos\"")
+        );
+        let NetworkMessage::Delta { delta: decoded, .. } =
+            deserialize_message(&data, WireFormat::Json).expect("deserialize")
+        else {
+            panic!("expected a delta");
+        };
+        assert_eq!(decoded.0, delta.0);
+
+        // MessagePack peers can't read it, and the other way around
+        assert!(deserialize_message(&data, WireFormat::MessagePack).is_err());
+        let data = serialize_message(&msg, WireFormat::MessagePack).expect("serialize");
+        assert!(deserialize_message(&data, WireFormat::Json).is_err());
+    }
+
+    #[test]
+    fn test_json_newer_schema_version_is_unsupported() {
+        let msg = NetworkMessage::FullStateRequest {
+            schema_version: CURRENT_VERSION + 1,
+            sender_id: ReplicaId::new(7),
+        };
+        let data = serialize_message(&msg, WireFormat::Json).expect("serialize");
+
+        let err = deserialize_message(&data, WireFormat::Json).expect_err("newer version");
+        assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+    }
+
+    #[test]
+    fn test_messages_without_generation_decode_as_generation_zero() {
+        // A delta as sent before compaction existed
+        #[derive(Serialize)]
+        enum OldMessage {
+            Delta {
+                schema_version: u8,
+                sender_id: ReplicaId,
+                sent_at: Option<i64>,
+                delta: Delta<CausalDotStore<OrMap<String>>>,
+            },
+        }
+        let old = OldMessage::Delta {
+            schema_version: CURRENT_VERSION,
+            sender_id: ReplicaId::new(7),
+            sent_at: None,
+            delta: Delta(CausalDotStore::default()),
+        };
+        let data = rmp_serde::to_vec(&old).expect("serialize");
+
+        let msg = deserialize_message(&data, WireFormat::default()).expect("deserialize");
+        assert!(matches!(msg, NetworkMessage::Delta { generation: 0, .. }));
     }
 
     #[test]
     fn test_broadcast_when_isolated_does_not_send() {
         // This is a behavioral test - when isolated, broadcast should succeed but not actually send
-        let socket = create_broadcast_socket(0).expect("Failed to create socket");
+        let socket =
+            create_broadcast_socket(0, IpVersion::default()).expect("Failed to create socket");
         let result = broadcast(&socket, b"test", DEFAULT_PORT, true);
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_broadcast_rejects_datagrams_over_mtu() {
+        let socket =
+            create_broadcast_socket(0, IpVersion::default()).expect("Failed to create socket");
+        let data = vec![0; MTU + 1];
+
+        let err = broadcast(&socket, &data, DEFAULT_PORT, false).expect_err("too large");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(
+            err.to_string(),
+            "1473 bytes exceeds MTU 1472, consider enabling fragmentation"
+        );
+        assert!(check_mtu(&data[..MTU]).is_ok());
+    }
+
+    #[test]
+    fn test_broadcast_addr_per_ip_version() {
+        let socket =
+            create_broadcast_socket(0, IpVersion::default()).expect("Failed to create socket");
+        assert_eq!(
+            socket.broadcast_addr(DEFAULT_PORT).to_string(),
+            "255.255.255.255:7878"
+        );
+
+        let bound = create_broadcast_socket(
+            0,
+            IpVersion::V4 {
+                interface: Ipv4Addr::LOCALHOST,
+            },
+        )
+        .expect("Failed to create socket");
+        assert_eq!(
+            bound.broadcast_addr(DEFAULT_PORT).to_string(),
+            "255.255.255.255:7878"
+        );
+
+        let multicast = TransportSocket::V4Multicast {
+            socket: UdpSocket::bind("127.0.0.1:0").expect("bind"),
+            group: Ipv4Addr::new(239, 0, 0, 1),
+        };
+        assert_eq!(
+            multicast.broadcast_addr(DEFAULT_PORT).to_string(),
+            "239.0.0.1:7878"
+        );
+
+        let v6 = TransportSocket::V6 {
+            socket: UdpSocket::bind("127.0.0.1:0").expect("bind"),
+            interface: 3,
+        };
+        assert_eq!(
+            v6.broadcast_addr(DEFAULT_PORT).to_string(),
+            "[ff02::1%3]:7878"
+        );
+    }
+
+    #[test]
+    fn test_interface_socket_sends_from_and_receives_on_the_interface() {
+        let socket = create_broadcast_socket(
+            0,
+            IpVersion::V4 {
+                interface: Ipv4Addr::LOCALHOST,
+            },
+        )
+        .expect("Failed to create socket");
+        let sender = socket.sender().local_addr().expect("local addr");
+        assert_eq!(sender.ip(), Ipv4Addr::LOCALHOST);
+
+        // Peers reply to the address our messages come from
+        let peer = UdpSocket::bind("127.0.0.1:0").expect("bind");
+        peer.send_to(b"reply", sender).expect("send");
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(1);
+        let received = loop {
+            if let Some(received) = try_receive(&socket, false).expect("receive") {
+                break received;
+            }
+            assert!(std::time::Instant::now() < deadline, "no reply received");
+        };
+        assert_eq!(
+            received,
+            (b"reply".to_vec(), peer.local_addr().expect("addr"))
+        );
+    }
+
+    #[test]
+    fn test_ip_version_describes_the_interface() {
+        assert_eq!(
+            IpVersion::default().to_string(),
+            "broadcasting on the default interface"
+        );
+        assert_eq!(
+            IpVersion::V4Multicast {
+                group: Ipv4Addr::new(239, 0, 0, 1),
+                ttl: 1,
+                interface: Ipv4Addr::new(192, 168, 1, 5),
+            }
+            .to_string(),
+            "multicasting to 239.0.0.1 (ttl 1) on 192.168.1.5"
+        );
+        assert_eq!(
+            IpVersion::V6 { interface: 2 }.to_string(),
+            "multicasting to ff02::1 on interface 2"
+        );
+    }
+
     #[test]
     fn test_try_receive_when_isolated_returns_none() {
-        let socket = create_broadcast_socket(0).expect("Failed to create socket");
+        let socket =
+            create_broadcast_socket(0, IpVersion::default()).expect("Failed to create socket");
         let result = try_receive(&socket, true).expect("Failed to try_receive");
         assert!(result.is_none());
     }
+
+    #[test]
+    fn test_in_memory_broadcast_reaches_other_peers_only() {
+        let hub = InMemoryTransport::hub(3);
+
+        hub[0].broadcast(b"hello", false).expect("broadcast");
+
+        assert!(hub[0].try_receive(false).expect("receive").is_none());
+        for peer in &hub[1..] {
+            let (data, from) = peer.try_receive(false).expect("receive").expect("message");
+            assert_eq!(data, b"hello");
+            assert_eq!(from, hub[0].addr());
+        }
+    }
+
+    #[test]
+    fn test_in_memory_isolation_drops_messages() {
+        let hub = InMemoryTransport::hub(2);
+
+        hub[0].broadcast(b"dropped", true).expect("broadcast");
+        assert!(hub[1].try_receive(false).expect("receive").is_none());
+
+        hub[0].broadcast(b"queued", false).expect("broadcast");
+        assert!(hub[1].try_receive(true).expect("receive").is_none());
+        assert!(hub[1].try_receive(false).expect("receive").is_some());
+    }
+
+    #[test]
+    fn test_in_memory_send_to_reaches_single_peer() {
+        let hub = InMemoryTransport::hub(3);
+
+        hub[0]
+            .send_to(b"direct", hub[2].addr(), false)
+            .expect("send");
+
+        assert!(hub[1].try_receive(false).expect("receive").is_none());
+        let (data, from) = hub[2]
+            .try_receive(false)
+            .expect("receive")
+            .expect("message");
+        assert_eq!(data, b"direct");
+        assert_eq!(from, hub[0].addr());
+    }
+
+    /// A message as a future client might send it.
+    #[derive(Serialize)]
+    enum FutureMessage {
+        Presence {
+            schema_version: u8,
+            sender_id: ReplicaId,
+            cursor: u32,
+        },
+    }
+
+    #[test]
+    fn test_newer_schema_version_is_unsupported() {
+        let msg = NetworkMessage::FullStateRequest {
+            schema_version: CURRENT_VERSION + 1,
+            sender_id: ReplicaId::new(7),
+        };
+        let data = serialize_message(&msg, WireFormat::default()).expect("serialize");
+
+        let err = deserialize_message(&data, WireFormat::default()).expect_err("newer version");
+        assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "Ignoring message from newer client (v{}); please upgrade.",
+                CURRENT_VERSION + 1
+            )
+        );
+    }
+
+    #[test]
+    fn test_unknown_variant_from_newer_client_is_unsupported() {
+        let msg = FutureMessage::Presence {
+            schema_version: CURRENT_VERSION + 1,
+            sender_id: ReplicaId::new(7),
+            cursor: 3,
+        };
+        let data = rmp_serde::to_vec(&msg).expect("serialize");
+
+        let err = deserialize_message(&data, WireFormat::default()).expect_err("unknown variant");
+        assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+    }
+
+    #[test]
+    fn test_v1_decoder_rejects_compacted_as_unsupported() {
+        let msg = NetworkMessage::Compacted {
+            schema_version: CURRENT_VERSION,
+            sender_id: ReplicaId::new(7),
+            generation: 1,
+            base: dson::CausalContext::default(),
+            state: Delta(CausalDotStore::default()),
+        };
+        let data = serialize_message(&msg, WireFormat::default()).expect("serialize");
+
+        let err = decode(&data, WireFormat::default(), 1).expect_err("compacted from v1");
+        assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+    }
+
+    #[test]
+    fn test_garbage_is_invalid_data() {
+        let err = deserialize_message(b"not msgpack", WireFormat::default()).expect_err("garbage");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
 }
@@ -5,6 +5,7 @@ use dson::{
     Dot, OrMap,
     crdts::{mvreg::MvRegValue, snapshot::ToValue},
 };
+use std::collections::HashSet;
 
 const PRIORITY_KEY: &str = "priority";
 
@@ -54,48 +55,118 @@ impl std::fmt::Display for DotKey {
     }
 }
 
-/// Read the priority array, returning dots in order.
+/// Read the priority array, returning dots in order, each once.
+///
+/// Concurrent moves of the same todo leave an entry per replica, so a dot can
+/// be in the array several times; only its first occurrence counts. Indices
+/// into the result are not array indices then: writes use [`priority_entries`].
+/// Entries that don't parse as a dot key are skipped as well.
 pub fn read_priority(store: &OrMap<String>) -> Vec<Dot> {
+    let mut seen = HashSet::new();
+    priority_entries(store)
+        .into_iter()
+        .flatten()
+        .filter(|dot| seen.insert(*dot))
+        .collect()
+}
+
+/// Every entry of the priority array in order, repeated dots included, so
+/// indices into it are the array's. Entries without a value that parses as a
+/// dot key are `None`: `OrArray::remove` counts them too.
+///
+/// Walks the array once in sorted order instead of indexing it: concurrent
+/// inserts at the same index get equal positions, and `OrArray::get` breaks
+/// that tie differently per replica for the first and last index.
+pub fn priority_entries(store: &OrMap<String>) -> Vec<Option<Dot>> {
     let priority_field = match store.get(PRIORITY_KEY) {
         Some(field) => &field.array,
         None => return Vec::new(),
     };
 
-    let mut dots = Vec::new();
-    for idx in 0..priority_field.len() {
-        if let Some(item) = priority_field.get(idx) {
+    let entries = priority_field
+        .with_list(|item, _, _| {
             // Handle both single value and multi-value cases
-            if let Ok(MvRegValue::String(dot_str)) = item.reg.value() {
-                if let Some(dot) = parse_dot(dot_str) {
-                    dots.push(dot);
-                }
+            let dot = if let Ok(MvRegValue::String(dot_str)) = item.reg.value() {
+                parse_dot(dot_str)
             } else {
                 // Multi-value - take first
-                for val in item.reg.values() {
-                    if let MvRegValue::String(dot_str) = val
-                        && let Some(dot) = parse_dot(dot_str)
-                    {
-                        dots.push(dot);
-                        break; // Only take first
-                    }
-                }
-            }
-        }
-    }
-    dots
+                item.reg.values().into_iter().find_map(|val| match val {
+                    MvRegValue::String(dot_str) => parse_dot(dot_str),
+                    _ => None,
+                })
+            };
+            // Some for every entry: returning None would drop it from the list
+            Ok::<_, std::convert::Infallible>(Some(dot))
+        })
+        .unwrap_or_default();
+    entries.into_iter().map(|(dot, _, _)| dot).collect()
 }
 
-/// Find index of a dot in the priority list.
+/// Number of priority entries without any value that parses as a dot key.
+/// [`read_priority`] skips these, so they only come from buggy or malicious peers.
+pub fn invalid_priority_entries(store: &OrMap<String>) -> usize {
+    let Some(priority_field) = store.get(PRIORITY_KEY) else {
+        return 0;
+    };
+
+    priority_field
+        .array
+        .iter_as_is()
+        .filter(|(item, _, _)| {
+            !item.reg.values().into_iter().any(|value| {
+                matches!(value, MvRegValue::String(dot_str) if parse_dot(dot_str).is_some())
+            })
+        })
+        .count()
+}
+
+/// Find the array index of a dot's first entry in the priority list.
 ///
 /// # Errors
 /// Returns `None` if the dot is not found in the priority array.
 pub fn find_priority_index(store: &OrMap<String>, dot: &Dot) -> Option<usize> {
-    let priority = read_priority(store);
-    priority.iter().position(|d| d == dot)
+    priority_entries(store)
+        .iter()
+        .position(|d| d.as_ref() == Some(dot))
+}
+
+/// Array indices of every entry of `dot` in the priority list, in order.
+pub fn priority_indices(store: &OrMap<String>, dot: &Dot) -> Vec<usize> {
+    priority_entries(store)
+        .iter()
+        .enumerate()
+        .filter(|(_, d)| d.as_ref() == Some(dot))
+        .map(|(index, _)| index)
+        .collect()
+}
+
+/// Array indices of the entries repeating a dot listed earlier, in order.
+/// [`read_priority`] skips these.
+pub fn duplicate_priority_indices(store: &OrMap<String>) -> Vec<usize> {
+    let mut seen = HashSet::new();
+    priority_entries(store)
+        .into_iter()
+        .enumerate()
+        .filter(|(_, dot)| dot.is_some_and(|dot| !seen.insert(dot)))
+        .map(|(index, _)| index)
+        .collect()
+}
+
+/// Todos of the list `store` without any priority entry, in key order.
+pub fn orphaned_dots(store: &OrMap<String>) -> Vec<Dot> {
+    let priority: HashSet<Dot> = priority_entries(store).into_iter().flatten().collect();
+
+    // Dot keys only; "priority" and the list's "created_at" don't parse
+    let mut keys: Vec<&String> = store.inner().keys().collect();
+    keys.sort();
+    keys.into_iter()
+        .filter_map(|key| parse_dot(key))
+        .filter(|dot| !priority.contains(dot))
+        .collect()
 }
 
 /// Parse dot from "node_id:counter" format.
-fn parse_dot(s: &str) -> Option<Dot> {
+pub fn parse_dot(s: &str) -> Option<Dot> {
     DotKey(s.to_string()).parse()
 }
 
@@ -174,6 +245,32 @@ mod tests {
         assert_eq!(priority[1], dot3);
     }
 
+    #[test]
+    fn test_repeated_dots_are_read_once() {
+        let mut store = TodoStore::default();
+        let id = Identifier::new(1, 0);
+
+        let dot1 = Dot::mint(id, 1);
+        let dot2 = Dot::mint(id, 2);
+
+        {
+            let mut tx = store.transact(id);
+            tx.in_array(PRIORITY_KEY, |arr_tx| {
+                for (index, dot) in [dot2, dot1, dot2, dot2].iter().enumerate() {
+                    arr_tx
+                        .insert_register(index, MvRegValue::String(DotKey::new(dot).into_inner()));
+                }
+            });
+            let _ = tx.commit();
+        }
+
+        assert_eq!(read_priority(&store.store), vec![dot2, dot1]);
+        assert_eq!(priority_entries(&store.store).len(), 4);
+        assert_eq!(find_priority_index(&store.store, &dot2), Some(0));
+        assert_eq!(priority_indices(&store.store, &dot2), vec![0, 2, 3]);
+        assert_eq!(duplicate_priority_indices(&store.store), vec![2, 3]);
+    }
+
     #[test]
     fn test_find_priority_index() {
         let mut store = TodoStore::default();
@@ -198,4 +295,60 @@ mod tests {
             None
         );
     }
+
+    #[test]
+    fn test_invalid_priority_entries() {
+        let mut store = TodoStore::default();
+        let id = Identifier::new(1, 0);
+        let dot = Dot::mint(id, 1);
+
+        let mut tx = store.transact(id);
+        tx.in_array(PRIORITY_KEY, |arr_tx| {
+            arr_tx.insert_register(0, MvRegValue::String(DotKey::new(&dot).into_inner()));
+            arr_tx.insert_register(1, MvRegValue::String("garbage".to_string()));
+            arr_tx.insert_register(2, MvRegValue::Bool(true));
+        });
+        let _ = tx.commit();
+
+        assert_eq!(read_priority(&store.store), vec![dot]);
+        assert_eq!(invalid_priority_entries(&store.store), 2);
+    }
+
+    #[test]
+    fn test_malformed_entry_keeps_array_indices() {
+        let mut store = TodoStore::default();
+        let id = Identifier::new(1, 0);
+        let a = Dot::mint(id, 1);
+        let b = Dot::mint(id, 2);
+
+        let mut tx = store.transact(id);
+        tx.in_array(PRIORITY_KEY, |arr_tx| {
+            arr_tx.insert_register(0, MvRegValue::String(DotKey::new(&a).into_inner()));
+            arr_tx.insert_register(1, MvRegValue::String("garbage".to_string()));
+            arr_tx.insert_register(2, MvRegValue::String(DotKey::new(&b).into_inner()));
+            arr_tx.insert_register(3, MvRegValue::String(DotKey::new(&a).into_inner()));
+        });
+        let _ = tx.commit();
+
+        assert_eq!(
+            priority_entries(&store.store),
+            [Some(a), None, Some(b), Some(a)]
+        );
+        assert_eq!(find_priority_index(&store.store, &b), Some(2));
+        assert_eq!(priority_indices(&store.store, &a), vec![0, 3]);
+        let duplicates = duplicate_priority_indices(&store.store);
+        assert_eq!(duplicates, vec![3]);
+
+        // Removing the repeat leaves b's only entry alone
+        let mut tx = store.transact(id);
+        tx.in_array(PRIORITY_KEY, |arr_tx| {
+            for index in duplicates.iter().rev() {
+                arr_tx.remove(*index);
+            }
+        });
+        let _ = tx.commit();
+
+        assert_eq!(read_priority(&store.store), vec![a, b]);
+        assert_eq!(invalid_priority_entries(&store.store), 1);
+    }
 }
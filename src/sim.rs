@@ -0,0 +1,264 @@
+// ABOUTME: Headless simulation harness for convergence testing.
+// ABOUTME: Models N replicas over a lossy, partitionable in-memory network.
+
+use crate::{
+    anti_entropy::{AntiEntropy, SyncNeeded},
+    app::TodoStore,
+};
+use dson::{CausalContext, Identifier, transaction::MapTransaction};
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use std::collections::VecDeque;
+
+/// Upper bound of anti-entropy rounds before `settle` gives up.
+const MAX_SETTLE_ROUNDS: usize = 100;
+
+/// Payload of a simulated message.
+enum Payload {
+    Delta(TodoStore),
+    Context(CausalContext),
+}
+
+/// A message in flight between two replicas.
+struct Envelope {
+    from: usize,
+    to: usize,
+    payload: Payload,
+}
+
+/// A set of replicas connected by a simulated network.
+pub struct Sim {
+    stores: Vec<TodoStore>,
+    in_flight: VecDeque<Envelope>,
+    /// Probability that a message is dropped.
+    loss: f64,
+    /// Partition group of each replica; messages only flow within a group.
+    groups: Vec<usize>,
+    rng: StdRng,
+}
+
+impl Sim {
+    /// Create `n` empty replicas on a lossless, fully connected network.
+    pub fn new(n: usize) -> Self {
+        Self {
+            stores: vec![TodoStore::default(); n],
+            in_flight: VecDeque::new(),
+            loss: 0.0,
+            groups: vec![0; n],
+            rng: StdRng::seed_from_u64(42),
+        }
+    }
+
+    /// Drop each message with probability `loss`.
+    pub fn with_loss(mut self, loss: f64) -> Self {
+        self.loss = loss;
+        self
+    }
+
+    /// Split the network; replicas only reach others in the same group.
+    /// Replicas not listed stay in group 0 together with the first group.
+    pub fn partition(&mut self, groups: &[&[usize]]) {
+        for (group, members) in groups.iter().enumerate() {
+            for &replica in *members {
+                self.groups[replica] = group;
+            }
+        }
+    }
+
+    /// Reconnect all replicas.
+    pub fn heal(&mut self) {
+        self.groups.fill(0);
+    }
+
+    /// Store of a replica.
+    pub fn store(&self, replica: usize) -> &TodoStore {
+        &self.stores[replica]
+    }
+
+    /// Identifier used by a replica for its transactions.
+    pub fn identifier(replica: usize) -> Identifier {
+        Identifier::new(replica as u8 + 1, 0)
+    }
+
+    /// Run a transaction on a replica and broadcast the resulting delta.
+    pub fn transact(&mut self, replica: usize, f: impl FnOnce(&mut MapTransaction<'_, String>)) {
+        let mut tx = self.stores[replica].transact(Self::identifier(replica));
+        f(&mut tx);
+        let delta = tx.commit();
+        self.broadcast(replica, |_| Payload::Delta(delta.0.clone()));
+    }
+
+    /// Queue a message from `from` to every other replica.
+    fn broadcast(&mut self, from: usize, payload: impl Fn(usize) -> Payload) {
+        for to in 0..self.stores.len() {
+            if to != from {
+                self.in_flight.push_back(Envelope {
+                    from,
+                    to,
+                    payload: payload(to),
+                });
+            }
+        }
+    }
+
+    /// Deliver every queued message (and the replies they trigger),
+    /// dropping those that are lost or cross a partition.
+    pub fn deliver_all(&mut self) {
+        while let Some(envelope) = self.in_flight.pop_front() {
+            if self.groups[envelope.from] != self.groups[envelope.to]
+                || self.rng.gen_bool(self.loss)
+            {
+                continue;
+            }
+
+            let store = &mut self.stores[envelope.to];
+            match envelope.payload {
+                Payload::Delta(delta) => {
+                    store.join_or_replace_with(delta.store, &delta.context);
+                }
+                Payload::Context(context) => {
+                    match AntiEntropy::compare_contexts(&store.context, &context) {
+                        SyncNeeded::RemoteNeedsSync | SyncNeeded::BothNeedSync => {
+                            let full_state = Payload::Delta(store.clone());
+                            self.in_flight.push_back(Envelope {
+                                from: envelope.to,
+                                to: envelope.from,
+                                payload: full_state,
+                            });
+                        }
+                        SyncNeeded::InSync | SyncNeeded::LocalNeedsSync => {}
+                    }
+                }
+            }
+        }
+    }
+
+    /// Every replica broadcasts its causal context, then all messages are delivered.
+    pub fn anti_entropy_round(&mut self) {
+        for replica in 0..self.stores.len() {
+            let context = self.stores[replica].context.clone();
+            self.broadcast(replica, |_| Payload::Context(context.clone()));
+        }
+        self.deliver_all();
+    }
+
+    /// Deliver pending messages and run anti-entropy until all replicas converge.
+    /// Returns the number of anti-entropy rounds needed.
+    ///
+    /// # Panics
+    /// Panics if the replicas do not converge within `MAX_SETTLE_ROUNDS`.
+    pub fn settle(&mut self) -> usize {
+        self.deliver_all();
+        for round in 0..MAX_SETTLE_ROUNDS {
+            if self.converged() {
+                return round;
+            }
+            self.anti_entropy_round();
+        }
+        panic!("replicas did not converge within {MAX_SETTLE_ROUNDS} rounds");
+    }
+
+    /// True if all pairs of replicas hold equal stores.
+    pub fn converged(&self) -> bool {
+        self.stores.windows(2).all(|pair| pair[0] == pair[1])
+    }
+
+    /// Assert that all pairs of replicas hold equal stores.
+    pub fn assert_converged(&self) {
+        for (i, a) in self.stores.iter().enumerate() {
+            for (j, b) in self.stores.iter().enumerate().skip(i + 1) {
+                assert_eq!(a, b, "replica {i} and replica {j} diverged");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{priority, priority::DotKey, todo};
+    use dson::{Dot, crdts::mvreg::MvRegValue};
+
+    /// Create a todo on `replica` with the given sequence number, appended to the priority list.
+    fn add_todo(sim: &mut Sim, replica: usize, seq: u64, text: &str) -> Dot {
+        let dot = Dot::mint(Sim::identifier(replica), seq);
+        let dot_key = DotKey::new(&dot);
+        sim.transact(replica, |tx| {
+            tx.in_map(dot_key.as_str(), |todo_tx| {
+                todo_tx.write_register("text", MvRegValue::String(text.to_string()));
+                todo_tx.write_register("done", MvRegValue::Bool(false));
+            });
+            tx.in_array("priority", |arr_tx| {
+                arr_tx.insert_register(arr_tx.len(), MvRegValue::String(dot_key.into_inner()));
+            });
+        });
+        dot
+    }
+
+    #[test]
+    fn test_lossless_concurrent_adds_converge() {
+        let mut sim = Sim::new(3);
+
+        for replica in 0..3 {
+            add_todo(&mut sim, replica, 1, "Concurrent");
+        }
+        sim.deliver_all();
+
+        sim.assert_converged();
+        assert_eq!(priority::read_priority(&sim.store(0).store).len(), 3);
+    }
+
+    #[test]
+    fn test_partition_heals_through_anti_entropy() {
+        let mut sim = Sim::new(4);
+        sim.partition(&[&[0, 1], &[2, 3]]);
+
+        add_todo(&mut sim, 0, 1, "Left side");
+        add_todo(&mut sim, 3, 1, "Right side");
+        sim.deliver_all();
+        assert!(!sim.converged());
+
+        // Deltas sent during the partition are lost for good; anti-entropy repairs
+        sim.heal();
+        let rounds = sim.settle();
+
+        assert!(rounds >= 1);
+        sim.assert_converged();
+        assert_eq!(priority::read_priority(&sim.store(1).store).len(), 2);
+    }
+
+    #[test]
+    fn test_lossy_network_eventually_converges() {
+        let mut sim = Sim::new(5).with_loss(0.3);
+
+        for replica in 0..5 {
+            for seq in 1..=3 {
+                add_todo(&mut sim, replica, seq, "Lossy");
+            }
+        }
+        sim.settle();
+
+        sim.assert_converged();
+        assert_eq!(priority::read_priority(&sim.store(4).store).len(), 15);
+    }
+
+    #[test]
+    fn test_concurrent_edits_preserved_across_replicas() {
+        let mut sim = Sim::new(3);
+        let dot = add_todo(&mut sim, 0, 1, "Buy milk");
+        sim.deliver_all();
+
+        for (replica, text) in [(1, "Buy oat milk"), (2, "Buy whole milk")] {
+            sim.transact(replica, |tx| {
+                tx.in_map(DotKey::new(&dot).as_str(), |todo_tx| {
+                    todo_tx.write_register("text", MvRegValue::String(text.to_string()));
+                });
+            });
+        }
+        sim.settle();
+
+        sim.assert_converged();
+        let todo = todo::read_todo(&sim.store(0).store, &dot).expect("todo exists");
+        assert_eq!(todo.text.len(), 2);
+        assert!(todo.has_conflicts());
+    }
+}
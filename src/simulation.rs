@@ -0,0 +1,163 @@
+// ABOUTME: Developer simulation mode running several replicas in-process.
+// ABOUTME: Drives random operations over an in-memory network and checks convergence.
+
+use crate::{
+    app::{App, ReplicaId},
+    input::{self, Action},
+    network::{self, InMemoryTransport},
+    priority::DotKey,
+};
+use rand::Rng;
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    io,
+    sync::{Arc, Barrier},
+    thread,
+};
+
+/// Number of rounds in which every replica performs a random operation.
+const OPERATION_ROUNDS: usize = 20;
+
+/// Number of quiet anti-entropy rounds after the operations.
+const SETTLE_ROUNDS: usize = 3;
+
+/// Run `replicas` in-process apps, apply random operations and report convergence.
+pub fn run(replicas: usize) -> io::Result<()> {
+    if replicas == 0 || replicas > usize::from(u8::MAX) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "--simulate expects between 1 and 255 replicas",
+        ));
+    }
+
+    println!(
+        "Simulating {replicas} replicas: {OPERATION_ROUNDS} operation rounds, {SETTLE_ROUNDS} settle rounds"
+    );
+
+    let barrier = Arc::new(Barrier::new(replicas));
+    let handles: Vec<_> = InMemoryTransport::hub(replicas)
+        .into_iter()
+        .enumerate()
+        .map(|(i, transport)| {
+            let barrier = Arc::clone(&barrier);
+            let replica_id = ReplicaId::new(i as u8 + 1);
+            thread::spawn(move || run_replica(replica_id, transport, &barrier))
+        })
+        .collect();
+
+    let apps = handles
+        .into_iter()
+        .map(|handle| handle.join().expect("replica thread panicked"))
+        .collect::<io::Result<Vec<_>>>()?;
+
+    print_report(&apps);
+    Ok(())
+}
+
+/// Drive a single replica through all rounds in lockstep with the others.
+///
+/// A failing step doesn't end the replica early: the others would wait at the
+/// barrier forever. The first error is returned once all rounds are done.
+fn run_replica(
+    replica_id: ReplicaId,
+    transport: InMemoryTransport,
+    barrier: &Barrier,
+) -> io::Result<App> {
+    let mut app = App::with_transport(replica_id, network::DEFAULT_PORT, Box::new(transport));
+    let mut rng = rand::thread_rng();
+    let mut error = None;
+
+    for _ in 0..OPERATION_ROUNDS {
+        keep_first_error(&mut error, random_operation(&mut app, &mut rng));
+        barrier.wait();
+        keep_first_error(&mut error, app.drain_incoming_deltas());
+        barrier.wait();
+    }
+
+    // Anti-entropy: exchange contexts until everybody has answered everybody.
+    for _ in 0..SETTLE_ROUNDS {
+        keep_first_error(&mut error, app.broadcast_context());
+        barrier.wait();
+        keep_first_error(&mut error, app.drain_incoming_deltas());
+        barrier.wait();
+        keep_first_error(&mut error, app.drain_incoming_deltas());
+        barrier.wait();
+    }
+
+    match error {
+        Some(e) => Err(e),
+        None => Ok(app),
+    }
+}
+
+/// Remember the error of `result` unless an earlier one is already kept.
+fn keep_first_error<T>(error: &mut Option<io::Error>, result: io::Result<T>) {
+    if let Err(e) = result {
+        error.get_or_insert(e);
+    }
+}
+
+/// Apply one random local operation through the regular input actions.
+fn random_operation(app: &mut App, rng: &mut impl Rng) -> io::Result<()> {
+    let len = app.get_todos_ordered().len();
+    if len == 0 || rng.gen_bool(0.2) {
+        return app.add_random_todos();
+    }
+
+    app.ui_state.selected_index = rng.gen_range(0..len);
+    let action = match rng.gen_range(0..3) {
+        0 => Action::MovePriorityUp,
+        1 => Action::MovePriorityDown,
+        _ => Action::ToggleDone,
+    };
+    input::execute_action(app, action)
+}
+
+/// Hash the observable state of a replica.
+fn state_hash(app: &App) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    for (dot, todo) in app.get_todos_ordered() {
+        DotKey::new(&dot).hash(&mut hasher);
+        todo.text.hash(&mut hasher);
+        todo.done.hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+/// Print per-replica state and whether all replicas converged.
+fn print_report(apps: &[App]) {
+    println!();
+    println!(
+        "{:<8} {:<18} {:>6} {:>9}  priority",
+        "replica", "state hash", "todos", "conflicts"
+    );
+
+    for app in apps {
+        let todos = app.get_todos_ordered();
+        let conflicts = todos.iter().filter(|(_, t)| t.has_conflicts()).count();
+        let order: Vec<_> = todos
+            .iter()
+            .map(|(dot, _)| DotKey::new(dot).into_inner())
+            .collect();
+        println!(
+            "{:<8} {:016x}   {:>6} {:>9}  [{}]",
+            app.replica_id.to_string(),
+            state_hash(app),
+            todos.len(),
+            conflicts,
+            order.join(", ")
+        );
+    }
+
+    let converged = apps.windows(2).all(|pair| pair[0].store == pair[1].store);
+    println!();
+    if converged {
+        println!(
+            "CONVERGED: all {} replicas hold identical state",
+            apps.len()
+        );
+    } else {
+        println!("DIVERGED: replicas hold different state");
+    }
+}
@@ -0,0 +1,27 @@
+// ABOUTME: Fixtures shared by the unit tests of several modules.
+// ABOUTME: Builds apps connected through an in-memory network.
+
+use crate::{
+    app::{App, ReplicaId},
+    network::{self, InMemoryTransport, Transport},
+};
+
+/// Create `n` apps connected through an in-memory hub.
+pub(crate) fn connected_apps(n: usize) -> Vec<App> {
+    apps_on(InMemoryTransport::hub(n))
+}
+
+/// Create one app per transport, with replica ids counting from 1.
+pub(crate) fn apps_on<T: Transport + 'static>(transports: impl IntoIterator<Item = T>) -> Vec<App> {
+    transports
+        .into_iter()
+        .enumerate()
+        .map(|(i, transport)| {
+            App::with_transport(
+                ReplicaId::new(i as u8 + 1),
+                network::DEFAULT_PORT,
+                Box::new(transport),
+            )
+        })
+        .collect()
+}
@@ -1,12 +1,22 @@
 // ABOUTME: Todo item representation and CRDT operations.
 // ABOUTME: Handles reading todos from the CRDT store.
 
-use crate::priority::DotKey;
+use std::convert::Infallible;
+
+use crate::{app::ReplicaId, priority::DotKey};
 use dson::{
     Dot, OrMap,
     crdts::{mvreg::MvRegValue, snapshot::ToValue},
+    transaction::MapTransaction,
 };
 
+/// Default limit on the characters typed into a todo's text.
+pub const DEFAULT_MAX_TEXT_LEN: usize = 500;
+
+/// Text values longer than this many characters, which only another client
+/// can write, are cut short by [`read_todo`]; the store keeps them whole.
+pub const MAX_DISPLAY_TEXT_LEN: usize = 1000;
+
 /// Todo item read from CRDT.
 /// Fields may have multiple concurrent values due to conflicts.
 #[derive(Debug, Clone, PartialEq)]
@@ -14,12 +24,220 @@ pub struct Todo {
     pub dot: Dot,
     pub text: Vec<String>,
     pub done: Vec<bool>,
+    /// Replicas that wrote each value of `text`, in the same order; several
+    /// for a value written concurrently by more than one replica. Read from
+    /// the values' dots, so after a compaction the compacting replica is the
+    /// writer of everything.
+    pub text_writers: Vec<Vec<ReplicaId>>,
+    /// Replicas that wrote each value of `done`, in the same order.
+    pub done_writers: Vec<Vec<ReplicaId>>,
+    /// Replica that wrote the value of `text` with the largest dot, if any.
+    pub last_writer_text: Option<ReplicaId>,
+    /// Replica that wrote the value of `done` with the largest dot, if any.
+    pub last_writer_done: Option<ReplicaId>,
+    /// Replica that created the todo (hex replica ID).
+    pub creator: Option<String>,
+    /// Replica responsible for the todo (hex replica ID); several values
+    /// after concurrent reassignments.
+    pub assignee: Vec<String>,
+    /// Creation time in Unix milliseconds.
+    pub created_at: Option<i64>,
+    /// Time of the last modification in Unix milliseconds.
+    pub modified_at: Option<i64>,
+    /// Whether concurrent modifications left several times; the latest is shown.
+    pub modified_concurrently: bool,
+    /// Tag names, sorted. Stored as an observed-remove set (add wins).
+    pub tags: Vec<String>,
+    /// Free-form multi-line notes; several values after concurrent edits.
+    pub notes: Vec<String>,
+    /// Checklist items in order.
+    pub subtasks: Vec<Subtask>,
+    /// Categorical priority; several values after concurrent changes.
+    pub level: Vec<Level>,
+    /// Free-form category such as "work" or "home"; several values after
+    /// concurrent changes.
+    pub category: Vec<String>,
+    /// How often the todo was moved down the list, summed over all replicas.
+    pub postponed: u64,
+    /// How often the todo comes back once done; several values after
+    /// concurrent changes.
+    pub recur: Vec<Recurrence>,
+    /// Color label for grouping todos at a glance; several values after
+    /// concurrent changes.
+    pub color: Vec<TodoColor>,
+}
+
+/// Categorical priority of a todo, independent of its position in the list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Level {
+    High,
+    Medium,
+    Low,
+}
+
+impl Level {
+    /// Value stored in the "level" register.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Level::High => "high",
+            Level::Medium => "medium",
+            Level::Low => "low",
+        }
+    }
+
+    /// Parse a stored register value.
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "high" => Some(Level::High),
+            "medium" => Some(Level::Medium),
+            "low" => Some(Level::Low),
+            _ => None,
+        }
+    }
+
+    /// Next level when cycling: none → high → medium → low → none.
+    pub fn cycle(level: Option<Self>) -> Option<Self> {
+        match level {
+            None => Some(Level::High),
+            Some(Level::High) => Some(Level::Medium),
+            Some(Level::Medium) => Some(Level::Low),
+            Some(Level::Low) => None,
+        }
+    }
+}
+
+/// Interval after which a recurring todo is due again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Recurrence {
+    Daily,
+    Weekly,
+}
+
+impl Recurrence {
+    /// Value stored in the "recur" register.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Recurrence::Daily => "daily",
+            Recurrence::Weekly => "weekly",
+        }
+    }
+
+    /// Parse a stored register value.
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "daily" => Some(Recurrence::Daily),
+            "weekly" => Some(Recurrence::Weekly),
+            _ => None,
+        }
+    }
+
+    /// Next recurrence when cycling: none → daily → weekly → none.
+    pub fn cycle(recur: Option<Self>) -> Option<Self> {
+        match recur {
+            None => Some(Recurrence::Daily),
+            Some(Recurrence::Daily) => Some(Recurrence::Weekly),
+            Some(Recurrence::Weekly) => None,
+        }
+    }
+}
+
+/// Color label of a todo, from a small fixed palette.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TodoColor {
+    Red,
+    Yellow,
+    Green,
+    Blue,
+    Purple,
+}
+
+impl TodoColor {
+    /// Value stored in the "color" register.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TodoColor::Red => "red",
+            TodoColor::Yellow => "yellow",
+            TodoColor::Green => "green",
+            TodoColor::Blue => "blue",
+            TodoColor::Purple => "purple",
+        }
+    }
+
+    /// Parse a stored register value.
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "red" => Some(TodoColor::Red),
+            "yellow" => Some(TodoColor::Yellow),
+            "green" => Some(TodoColor::Green),
+            "blue" => Some(TodoColor::Blue),
+            "purple" => Some(TodoColor::Purple),
+            _ => None,
+        }
+    }
+
+    /// Next color when cycling: none → red → yellow → green → blue → purple → none.
+    pub fn cycle(color: Option<Self>) -> Option<Self> {
+        match color {
+            None => Some(TodoColor::Red),
+            Some(TodoColor::Red) => Some(TodoColor::Yellow),
+            Some(TodoColor::Yellow) => Some(TodoColor::Green),
+            Some(TodoColor::Green) => Some(TodoColor::Blue),
+            Some(TodoColor::Blue) => Some(TodoColor::Purple),
+            Some(TodoColor::Purple) => None,
+        }
+    }
+}
+
+/// Checklist item of a todo.
+/// Fields may have multiple concurrent values due to conflicts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Subtask {
+    /// Key of the subtask in the todo's "subtask_items" map.
+    pub key: String,
+    pub text: Vec<String>,
+    pub done: Vec<bool>,
+}
+
+impl Subtask {
+    /// Check if this subtask has any conflicts.
+    pub fn has_conflicts(&self) -> bool {
+        self.text.len() > 1 || self.done.len() > 1
+    }
+
+    /// Get primary text value (first one).
+    pub fn primary_text(&self) -> &str {
+        self.text.first().map(|s| s.as_str()).unwrap_or("")
+    }
+
+    /// Get primary done value (first one).
+    pub fn primary_done(&self) -> bool {
+        self.done.first().copied().unwrap_or(false)
+    }
 }
 
 impl Todo {
     /// Check if this todo has any conflicts.
     pub fn has_conflicts(&self) -> bool {
-        self.text.len() > 1 || self.done.len() > 1
+        self.text.len() > 1
+            || self.done.len() > 1
+            || self.notes.len() > 1
+            || self.level.len() > 1
+            || self.category.len() > 1
+            || self.recur.len() > 1
+            || self.color.len() > 1
+            || self.assignee.len() > 1
+            || self.subtasks.iter().any(Subtask::has_conflicts)
+    }
+
+    /// Done and total subtask counts. A subtask with conflicting done values
+    /// counts as not done.
+    pub fn subtask_progress(&self) -> (usize, usize) {
+        let done = self
+            .subtasks
+            .iter()
+            .filter(|subtask| subtask.done == [true])
+            .count();
+        (done, self.subtasks.len())
     }
 
     /// Get primary text value (first one).
@@ -31,6 +249,285 @@ impl Todo {
     pub fn primary_done(&self) -> bool {
         self.done.first().copied().unwrap_or(false)
     }
+
+    /// Done value to display: with `done_wins`, any concurrent `true` wins
+    /// over `false` instead of whichever value happens to be first.
+    pub fn resolved_done(&self, done_wins: bool) -> bool {
+        if done_wins {
+            self.done.contains(&true)
+        } else {
+            self.primary_done()
+        }
+    }
+
+    /// Get primary level value (first one).
+    pub fn primary_level(&self) -> Option<Level> {
+        self.level.first().copied()
+    }
+
+    /// Get primary category value (first one).
+    pub fn primary_category(&self) -> Option<&str> {
+        self.category.first().map(|s| s.as_str())
+    }
+
+    /// Get primary recurrence value (first one).
+    pub fn primary_recur(&self) -> Option<Recurrence> {
+        self.recur.first().copied()
+    }
+
+    /// Get primary color value (first one).
+    pub fn primary_color(&self) -> Option<TodoColor> {
+        self.color.first().copied()
+    }
+}
+
+/// Next value when cycling through `values` (sorted), as for a todo's
+/// category or the tag filter: none → first → ... → last → none.
+pub fn cycle_through(values: &[String], current: Option<&str>) -> Option<String> {
+    let next = current
+        .and_then(|current| values.iter().position(|value| value == current))
+        .map_or(0, |index| index + 1);
+    values.get(next).cloned()
+}
+
+/// Current wall-clock time in Unix milliseconds.
+pub fn now_millis() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .expect("system clock should be after Unix epoch")
+        .as_millis() as i64
+}
+
+/// Write the immutable creation metadata of a new todo, and assign it to its
+/// creator.
+///
+/// These registers are written exactly once, in the transaction that creates
+/// the todo. Dot keys are unique per replica, so two replicas never create the
+/// same todo concurrently and the registers never conflict in practice. Only
+/// the assignee changes later, see [`set_assignee`].
+pub fn write_creation_metadata(
+    todo_tx: &mut MapTransaction<'_, String>,
+    creator: ReplicaId,
+    created_at: i64,
+) {
+    todo_tx.write_register("creator", MvRegValue::String(creator.to_string()));
+    todo_tx.write_register("created_at", MvRegValue::I64(created_at));
+    set_assignee(todo_tx, creator);
+}
+
+/// Make `assignee` responsible for a todo.
+/// Concurrent reassignments keep every assignee as a conflict.
+pub fn set_assignee(todo_tx: &mut MapTransaction<'_, String>, assignee: ReplicaId) {
+    todo_tx.write_register("assignee", MvRegValue::String(assignee.to_string()));
+}
+
+/// Record that a todo was modified now.
+/// Call in every transaction that writes to an existing todo.
+pub fn touch(todo_tx: &mut MapTransaction<'_, String>) {
+    todo_tx.write_register("modified_at", MvRegValue::I64(now_millis()));
+}
+
+/// Add a tag to a todo.
+///
+/// Tags are keys of the nested "tags" OrMap with a dummy register value, which
+/// makes them an observed-remove set: a concurrent add and remove of the same
+/// tag keeps the tag.
+pub fn add_tag(todo_tx: &mut MapTransaction<'_, String>, tag: &str) {
+    todo_tx.in_map("tags", |tags_tx| {
+        tags_tx.write_register(tag, MvRegValue::Bool(true));
+    });
+}
+
+/// Remove a tag from a todo. Only removes the adds this replica has observed.
+pub fn remove_tag(todo_tx: &mut MapTransaction<'_, String>, tag: &str) {
+    todo_tx.in_map("tags", |tags_tx| {
+        tags_tx.remove(tag);
+    });
+}
+
+/// How often `replica_id` postponed the todo `dot` in `store`: its own entry
+/// in the todo's "postponed" counter.
+pub fn own_postponed(store: &OrMap<String>, dot: &Dot, replica_id: ReplicaId) -> u64 {
+    store
+        .get(DotKey::new(dot).as_str())
+        .and_then(|todo| todo.map.get(&"postponed".to_string()))
+        .and_then(|postponed| {
+            extract_u64_values(&postponed.map, &replica_id.to_string())
+                .into_iter()
+                .max()
+        })
+        .unwrap_or(0)
+}
+
+/// Count one more postponement by `replica_id`, given its `own` count so far
+/// (see [`own_postponed`]).
+///
+/// DSON has no counter type, so "postponed" is a grow-only counter built
+/// from a map: every replica only writes its own entry, holding how often it
+/// postponed the todo, and the count is the sum of all entries. Concurrent
+/// increments touch different keys, so they add up instead of conflicting.
+pub fn increment_postponed(
+    todo_tx: &mut MapTransaction<'_, String>,
+    replica_id: ReplicaId,
+    own: u64,
+) {
+    todo_tx.in_map("postponed", |postponed_tx| {
+        postponed_tx.write_register(replica_id.to_string(), MvRegValue::U64(own + 1));
+    });
+}
+
+/// Sum of every replica's entry in a todo's "postponed" counter map.
+fn read_postponed(todo_map: &OrMap<String>) -> u64 {
+    let Some(postponed) = todo_map.get(&"postponed".to_string()) else {
+        return 0;
+    };
+    // An entry only holds several values if two replicas share an id; the
+    // larger one includes the other's increments up to then
+    postponed
+        .map
+        .inner()
+        .keys()
+        .map(|key| {
+            extract_u64_values(&postponed.map, key)
+                .into_iter()
+                .max()
+                .unwrap_or(0)
+        })
+        .sum()
+}
+
+/// Set the level of a todo, or clear it with `None`.
+pub fn set_level(todo_tx: &mut MapTransaction<'_, String>, level: Option<Level>) {
+    match level {
+        Some(level) => {
+            todo_tx.write_register("level", MvRegValue::String(level.as_str().to_string()))
+        }
+        None => todo_tx.remove("level"),
+    }
+}
+
+/// Set the category of a todo, or clear it with `None`.
+pub fn set_category(todo_tx: &mut MapTransaction<'_, String>, category: Option<&str>) {
+    match category {
+        Some(category) => {
+            todo_tx.write_register("category", MvRegValue::String(category.to_string()))
+        }
+        None => todo_tx.remove("category"),
+    }
+}
+
+/// Make a todo recur, or stop it with `None`.
+pub fn set_recur(todo_tx: &mut MapTransaction<'_, String>, recur: Option<Recurrence>) {
+    match recur {
+        Some(recur) => {
+            todo_tx.write_register("recur", MvRegValue::String(recur.as_str().to_string()))
+        }
+        None => todo_tx.remove("recur"),
+    }
+}
+
+/// Set the color label of a todo, or clear it with `None`.
+pub fn set_color(todo_tx: &mut MapTransaction<'_, String>, color: Option<TodoColor>) {
+    match color {
+        Some(color) => {
+            todo_tx.write_register("color", MvRegValue::String(color.as_str().to_string()))
+        }
+        None => todo_tx.remove("color"),
+    }
+}
+
+/// Write the next instance of the recurring `todo` into a new todo map: the
+/// same text, tags, level, category, color and recurrence, not done, created now by
+/// `creator`. Subtasks and notes stay with the completed instance.
+pub fn write_recurrence(todo_tx: &mut MapTransaction<'_, String>, todo: &Todo, creator: ReplicaId) {
+    todo_tx.write_register("text", MvRegValue::String(todo.primary_text().to_string()));
+    todo_tx.write_register("done", MvRegValue::Bool(false));
+    write_creation_metadata(todo_tx, creator, now_millis());
+    for tag in &todo.tags {
+        add_tag(todo_tx, tag);
+    }
+    if let Some(level) = todo.primary_level() {
+        set_level(todo_tx, Some(level));
+    }
+    if let Some(category) = todo.primary_category() {
+        set_category(todo_tx, Some(category));
+    }
+    if let Some(color) = todo.primary_color() {
+        set_color(todo_tx, Some(color));
+    }
+    set_recur(todo_tx, todo.primary_recur());
+}
+
+/// Insert a subtask at `index` of a todo's checklist.
+///
+/// DSON transactions can insert a map into an array but not update one in
+/// place, so subtasks follow the priority array pattern: the "subtasks"
+/// OrArray holds keys in order and "subtask_items" maps each key to its
+/// "text" and "done" registers, which can then be edited like a todo.
+pub fn insert_subtask(
+    todo_tx: &mut MapTransaction<'_, String>,
+    index: usize,
+    key: &str,
+    text: &str,
+) {
+    todo_tx.in_map("subtask_items", |items_tx| {
+        items_tx.in_map(key, |subtask_tx| {
+            subtask_tx.write_register("text", MvRegValue::String(text.to_string()));
+            subtask_tx.write_register("done", MvRegValue::Bool(false));
+        });
+    });
+    todo_tx.in_array("subtasks", |arr_tx| {
+        arr_tx.insert_register(index, MvRegValue::String(key.to_string()));
+    });
+}
+
+/// Set the done state of a subtask.
+pub fn set_subtask_done(todo_tx: &mut MapTransaction<'_, String>, key: &str, done: bool) {
+    todo_tx.in_map("subtask_items", |items_tx| {
+        items_tx.in_map(key, |subtask_tx| {
+            subtask_tx.write_register("done", MvRegValue::Bool(done));
+        });
+    });
+}
+
+/// Remove the subtask at `index` with the given key.
+pub fn remove_subtask(todo_tx: &mut MapTransaction<'_, String>, index: usize, key: &str) {
+    todo_tx.in_array("subtasks", |arr_tx| arr_tx.remove(index));
+    todo_tx.in_map("subtask_items", |items_tx| items_tx.remove(key));
+}
+
+/// Read the subtasks of a todo in checklist order.
+pub fn read_subtasks(todo_map: &OrMap<String>) -> Vec<Subtask> {
+    let (Some(order), Some(items)) = (
+        todo_map.get(&"subtasks".to_string()),
+        todo_map.get(&"subtask_items".to_string()),
+    ) else {
+        return Vec::new();
+    };
+
+    // with_list breaks position ties by uid; indexed get() does not, so two
+    // concurrent appends could otherwise read back as the same element.
+    let keys = order
+        .array
+        .with_list(|entry, _, _| {
+            // Keys are written once, so take the first value
+            Ok::<_, Infallible>(entry.reg.values().into_iter().find_map(|v| match v {
+                MvRegValue::String(key) => Some(key.clone()),
+                _ => None,
+            }))
+        })
+        .unwrap_or_default();
+
+    keys.into_iter()
+        .filter_map(|(key, _, _)| {
+            let item = &items.map.get(&key)?.map;
+            Some(Subtask {
+                text: extract_string_values(item, "text"),
+                done: extract_bool_values(item, "done"),
+                key,
+            })
+        })
+        .collect()
 }
 
 /// Read a todo from the store by its dot.
@@ -42,18 +539,195 @@ pub fn read_todo(store: &OrMap<String>, dot: &Dot) -> Option<Todo> {
     let todo_map = &store.get(dot_key.as_str())?.map;
 
     // Extract text field (handle multi-value)
-    let text = extract_string_values(todo_map, "text");
+    // Identical concurrent writes (e.g. two replicas merging the same
+    // conflict) keep one entry each in the register but are no conflict
+    let (text, text_writers) = distinct_with_writers(
+        extract_string_values_with_writers(todo_map, "text")
+            .into_iter()
+            .map(|(text, writer)| (truncate_overlong(text), writer))
+            .collect(),
+    );
 
     // Extract done field (handle multi-value)
-    let done = extract_bool_values(todo_map, "done");
+    let (done, done_writers) =
+        distinct_with_writers(extract_bool_values_with_writers(todo_map, "done"));
+    let last_writer_text = last_writer(todo_map, "text", |v| matches!(v, MvRegValue::String(_)));
+    let last_writer_done = last_writer(todo_map, "done", |v| matches!(v, MvRegValue::Bool(_)));
+
+    // Creation metadata is write-once. Should the registers ever hold several
+    // values (e.g. a replica ID collision minting the same dot key), the first
+    // value is shown; the others remain in the store.
+    let creator = extract_string_values(todo_map, "creator")
+        .into_iter()
+        .next();
+    let created_at = extract_i64_values(todo_map, "created_at")
+        .into_iter()
+        .next();
+    let assignee = distinct(extract_string_values(todo_map, "assignee"));
+    // Concurrent touches keep the most recent time.
+    let modified_values = extract_i64_values(todo_map, "modified_at");
+    let modified_concurrently = modified_values.len() > 1;
+    let modified_at = modified_values.into_iter().max();
+
+    let notes = distinct(extract_string_values(todo_map, "notes"));
+    let subtasks = read_subtasks(todo_map);
+    let level = distinct(
+        extract_string_values(todo_map, "level")
+            .iter()
+            .filter_map(|s| Level::parse(s))
+            .collect(),
+    );
+    let category = distinct(extract_string_values(todo_map, "category"));
+    let recur = distinct(
+        extract_string_values(todo_map, "recur")
+            .iter()
+            .filter_map(|s| Recurrence::parse(s))
+            .collect(),
+    );
+    let color = distinct(
+        extract_string_values(todo_map, "color")
+            .iter()
+            .filter_map(|s| TodoColor::parse(s))
+            .collect(),
+    );
+
+    let mut tags: Vec<String> = todo_map
+        .get(&"tags".to_string())
+        .map(|tags| tags.map.inner().keys().cloned().collect())
+        .unwrap_or_default();
+    tags.sort();
+    let postponed = read_postponed(todo_map);
 
     Some(Todo {
         dot: *dot,
         text,
         done,
+        text_writers,
+        done_writers,
+        last_writer_text,
+        last_writer_done,
+        creator,
+        assignee,
+        created_at,
+        modified_at,
+        modified_concurrently,
+        tags,
+        notes,
+        subtasks,
+        level,
+        category,
+        postponed,
+        recur,
+        color,
     })
 }
 
+/// `text`, cut to [`MAX_DISPLAY_TEXT_LEN`] characters ending in an ellipsis
+/// if longer.
+fn truncate_overlong(text: String) -> String {
+    if text.chars().count() <= MAX_DISPLAY_TEXT_LEN {
+        return text;
+    }
+    let mut truncated: String = text.chars().take(MAX_DISPLAY_TEXT_LEN - 1).collect();
+    truncated.push('…');
+    truncated
+}
+
+/// Number of todo text values in `store` (a whole store or a delta's) that
+/// [`read_todo`] cuts short.
+pub fn count_overlong_texts(store: &OrMap<String>) -> usize {
+    store
+        .inner()
+        .values()
+        .flat_map(|list| list.map.inner().values())
+        .flat_map(|todo| extract_string_values(&todo.map, "text"))
+        .filter(|text| text.chars().count() > MAX_DISPLAY_TEXT_LEN)
+        .count()
+}
+
+/// Drop repeated values, keeping the first occurrence of each.
+fn distinct<T: PartialEq>(values: Vec<T>) -> Vec<T> {
+    let mut unique = Vec::with_capacity(values.len());
+    for value in values {
+        if !unique.contains(&value) {
+            unique.push(value);
+        }
+    }
+    unique
+}
+
+/// Like [`distinct`], keeping the writers of each value, grouped when
+/// several replicas wrote the same value.
+fn distinct_with_writers<T: PartialEq>(
+    values: Vec<(T, ReplicaId)>,
+) -> (Vec<T>, Vec<Vec<ReplicaId>>) {
+    let mut unique: Vec<T> = Vec::with_capacity(values.len());
+    let mut writers: Vec<Vec<ReplicaId>> = Vec::with_capacity(values.len());
+    for (value, writer) in values {
+        match unique.iter().position(|seen| *seen == value) {
+            Some(i) if !writers[i].contains(&writer) => writers[i].push(writer),
+            Some(_) => {}
+            None => {
+                unique.push(value);
+                writers.push(vec![writer]);
+            }
+        }
+    }
+    (unique, writers)
+}
+
+/// Merge concurrent text values into one: sorted, without repeats, joined by " / ".
+///
+/// Deterministic, so replicas merging the same conflict concurrently write
+/// the same string and end up without a conflict.
+pub fn merge_text_values(values: &[String]) -> String {
+    let mut values = values.to_vec();
+    values.sort();
+    values.dedup();
+    values.join(" / ")
+}
+
+/// Byte ranges of every case-insensitive, non-overlapping occurrence of `query`
+/// in `text`. Empty for an empty query.
+pub fn match_ranges(text: &str, query: &str) -> Vec<std::ops::Range<usize>> {
+    let query: Vec<char> = query.chars().flat_map(char::to_lowercase).collect();
+    if query.is_empty() {
+        return Vec::new();
+    }
+
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    while start < chars.len() {
+        // Compare lowercased chars until the query is used up
+        let mut matched = 0;
+        let mut end = start;
+        while matched < query.len() && end < chars.len() {
+            for lower in chars[end].1.to_lowercase() {
+                if query.get(matched) == Some(&lower) {
+                    matched += 1;
+                } else {
+                    matched = usize::MAX;
+                    break;
+                }
+            }
+            if matched == usize::MAX {
+                break;
+            }
+            end += 1;
+        }
+
+        if matched == query.len() {
+            let end_byte = chars.get(end).map_or(text.len(), |(i, _)| *i);
+            ranges.push(chars[start].0..end_byte);
+            start = end;
+        } else {
+            start += 1;
+        }
+    }
+    ranges
+}
+
 // DEMO BEGIN #4: Conflict extraction - DSON's multi-value registers
 /// Extract all string values from a register field.
 /// Handles both single-value and multi-value (conflict) cases.
@@ -81,6 +755,73 @@ fn extract_string_values(map: &dson::OrMap<String>, key: &str) -> Vec<String> {
 }
 // DEMO END #4
 
+/// Replica that wrote the register value stored under `dot`.
+fn writer(dot: Dot) -> ReplicaId {
+    ReplicaId::new(dot.actor().node().value())
+}
+
+/// Replica that wrote the value with the largest dot among the values of a
+/// register field that `matches` accepts, or `None` if there is none.
+///
+/// Dots are compared by sequence number, then replica, as dson documents
+/// for dots (its derived `Ord` compares the replica first). Concurrent
+/// values have no real order, so for a conflict this only picks one of the
+/// writers, the same on every replica.
+fn last_writer(
+    map: &dson::OrMap<String>,
+    key: &str,
+    matches: impl Fn(&MvRegValue) -> bool,
+) -> Option<ReplicaId> {
+    map.get(&key.to_string())?
+        .reg
+        .0
+        .iter()
+        .filter(|(_, v)| matches(v))
+        .map(|(dot, _)| dot)
+        .max_by_key(|dot| (dot.sequence(), dot.actor()))
+        .map(writer)
+}
+
+/// Extract all string values from a register field, each with the replica
+/// that wrote it. Same order as [`extract_string_values`].
+fn extract_string_values_with_writers(
+    map: &dson::OrMap<String>,
+    key: &str,
+) -> Vec<(String, ReplicaId)> {
+    let Some(field) = map.get(&key.to_string()) else {
+        return Vec::new();
+    };
+    field
+        .reg
+        .0
+        .iter()
+        .filter_map(|(dot, v)| match v {
+            MvRegValue::String(s) => Some((s.clone(), writer(dot))),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Extract all bool values from a register field, each with the replica
+/// that wrote it.
+fn extract_bool_values_with_writers(
+    map: &dson::OrMap<String>,
+    key: &str,
+) -> Vec<(bool, ReplicaId)> {
+    let Some(field) = map.get(&key.to_string()) else {
+        return Vec::new();
+    };
+    field
+        .reg
+        .0
+        .iter()
+        .filter_map(|(dot, v)| match v {
+            MvRegValue::Bool(b) => Some((*b, writer(dot))),
+            _ => None,
+        })
+        .collect()
+}
+
 /// Extract all bool values from a register field.
 fn extract_bool_values(map: &dson::OrMap<String>, key: &str) -> Vec<bool> {
     let field = match map.get(&key.to_string()) {
@@ -105,6 +846,42 @@ fn extract_bool_values(map: &dson::OrMap<String>, key: &str) -> Vec<bool> {
         .collect()
 }
 
+/// Extract all u64 values from a register field.
+fn extract_u64_values(map: &dson::OrMap<String>, key: &str) -> Vec<u64> {
+    let field = match map.get(&key.to_string()) {
+        Some(f) => f,
+        None => return Vec::new(),
+    };
+
+    field
+        .reg
+        .values()
+        .into_iter()
+        .filter_map(|v| match v {
+            MvRegValue::U64(n) => Some(*n),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Extract all i64 values from a register field.
+fn extract_i64_values(map: &dson::OrMap<String>, key: &str) -> Vec<i64> {
+    let field = match map.get(&key.to_string()) {
+        Some(f) => f,
+        None => return Vec::new(),
+    };
+
+    field
+        .reg
+        .values()
+        .into_iter()
+        .filter_map(|v| match v {
+            MvRegValue::I64(n) => Some(*n),
+            _ => None,
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -113,6 +890,18 @@ mod tests {
 
     type TodoStore = CausalDotStore<OrMap<String>>;
 
+    #[test]
+    fn test_match_ranges_ignores_case() {
+        assert_eq!(match_ranges("Fix the fix", "FIX"), [0..3, 8..11]);
+        assert_eq!(match_ranges("aaaa", "aa"), [0..2, 2..4]);
+        assert_eq!(
+            match_ranges("Grüße ÜBER alles", "über").first(),
+            Some(&(8..13))
+        );
+        assert!(match_ranges("Buy milk", "").is_empty());
+        assert!(match_ranges("Buy milk", "oat").is_empty());
+    }
+
     #[test]
     fn test_read_nonexistent_todo() {
         let store = TodoStore::default();
@@ -203,6 +992,18 @@ mod tests {
         assert_eq!(todo_a.done, vec![false]);
         assert!(todo_a.has_conflicts());
 
+        // Each text is attributed to the replica that wrote it
+        let writers_of = |todo: &Todo, text: &str| {
+            let i = todo.text.iter().position(|t| t == text).expect("value");
+            todo.text_writers[i].clone()
+        };
+        let todo_b = read_todo(&replica_b.store, &dot).expect("Todo should exist");
+        for todo in [&todo_a, &todo_b] {
+            assert_eq!(writers_of(todo, "Buy whole milk"), [ReplicaId::new(1)]);
+            assert_eq!(writers_of(todo, "Buy oat milk"), [ReplicaId::new(2)]);
+            assert_eq!(todo.done_writers, [[ReplicaId::new(1)]]);
+        }
+
         // Verify convergence
         assert_eq!(replica_a, replica_b);
     }
@@ -229,6 +1030,61 @@ mod tests {
         assert_eq!(todo.done, vec![false]);
     }
 
+    #[test]
+    fn test_last_writer_per_field() {
+        let mut replica_a = TodoStore::default();
+        let mut replica_b = TodoStore::default();
+        let id_a = Identifier::new(1, 0);
+        let id_b = Identifier::new(2, 0);
+        let dot = Dot::mint(id_a, 1);
+        let dot_key = DotKey::new(&dot);
+        let write = |store: &mut TodoStore, id, f: &dyn Fn(&mut MapTransaction<'_, String>)| {
+            let mut tx = store.transact(id);
+            tx.in_map(dot_key.as_str(), f);
+            tx.commit()
+        };
+        let writers = |store: &TodoStore| {
+            let todo = read_todo(&store.store, &dot).expect("todo");
+            (todo.last_writer_text, todo.last_writer_done)
+        };
+
+        // Missing field: no writer
+        let created = write(&mut replica_a, id_a, &|todo_tx| {
+            todo_tx.write_register("text", MvRegValue::String("Buy milk".to_string()));
+        });
+        assert_eq!(writers(&replica_a), (Some(ReplicaId::new(1)), None));
+
+        // Single writer per field, each the replica that wrote it last
+        replica_b.join_or_replace_with(created.0.store, &created.0.context);
+        let done = write(&mut replica_b, id_b, &|todo_tx| {
+            todo_tx.write_register("done", MvRegValue::Bool(true));
+        });
+        replica_a.join_or_replace_with(done.0.store, &done.0.context);
+        let expected = (Some(ReplicaId::new(1)), Some(ReplicaId::new(2)));
+        assert_eq!(writers(&replica_a), expected);
+        assert_eq!(writers(&replica_b), expected);
+
+        // Concurrent writers: the larger dot wins, the same on both replicas.
+        // Both edits are their replica's second write, so the replica breaks the tie.
+        let delta_a = write(&mut replica_a, id_a, &|todo_tx| {
+            todo_tx.write_register("text", MvRegValue::String("Buy oat milk".to_string()));
+        });
+        let delta_b = write(&mut replica_b, id_b, &|todo_tx| {
+            todo_tx.write_register("text", MvRegValue::String("Buy soy milk".to_string()));
+        });
+        replica_a.join_or_replace_with(delta_b.0.store, &delta_b.0.context);
+        replica_b.join_or_replace_with(delta_a.0.store, &delta_a.0.context);
+        assert_eq!(read_todo(&replica_a.store, &dot).unwrap().text.len(), 2);
+        assert_eq!(writers(&replica_a), writers(&replica_b));
+        assert_eq!(writers(&replica_a).0, Some(ReplicaId::new(2)));
+
+        // Values of another type don't count
+        let _delta = write(&mut replica_a, id_a, &|todo_tx| {
+            todo_tx.write_register("done", MvRegValue::String("yes".to_string()));
+        });
+        assert_eq!(writers(&replica_a).1, None);
+    }
+
     #[test]
     fn test_update_text_inline() {
         let mut store = TodoStore::default();
@@ -286,4 +1142,577 @@ mod tests {
 
         assert_eq!(todo.done, vec![true]);
     }
+
+    #[test]
+    fn test_read_creation_metadata() {
+        let mut store = TodoStore::default();
+        let id = Identifier::new(0x3a, 0);
+        let dot = Dot::mint(id, 1);
+        let dot_key = DotKey::new(&dot);
+
+        {
+            let mut tx = store.transact(id);
+            tx.in_map(dot_key.as_str(), |todo_tx| {
+                todo_tx.write_register("text", MvRegValue::String("Test".to_string()));
+                todo_tx.write_register("done", MvRegValue::Bool(false));
+                write_creation_metadata(todo_tx, ReplicaId::new(0x3a), 1_700_000_000_000);
+            });
+            let _delta = tx.commit();
+        }
+
+        let todo = read_todo(&store.store, &dot).expect("Todo should exist");
+
+        assert_eq!(todo.creator.as_deref(), Some("3a"));
+        assert_eq!(todo.assignee, vec!["3a".to_string()]);
+        assert_eq!(todo.created_at, Some(1_700_000_000_000));
+    }
+
+    #[test]
+    fn test_read_todo_without_creation_metadata() {
+        let mut store = TodoStore::default();
+        let id = Identifier::new(1, 0);
+        let dot = Dot::mint(id, 1);
+        let dot_key = DotKey::new(&dot);
+
+        {
+            let mut tx = store.transact(id);
+            tx.in_map(dot_key.as_str(), |todo_tx| {
+                todo_tx.write_register("text", MvRegValue::String("Legacy".to_string()));
+            });
+            let _delta = tx.commit();
+        }
+
+        let todo = read_todo(&store.store, &dot).expect("Todo should exist");
+
+        assert_eq!(todo.creator, None);
+        assert_eq!(todo.assignee, Vec::<String>::new());
+        assert_eq!(todo.created_at, None);
+        assert_eq!(todo.modified_at, None);
+    }
+
+    #[test]
+    fn test_touch_sets_modified_at() {
+        let mut store = TodoStore::default();
+        let id = Identifier::new(1, 0);
+        let dot = Dot::mint(id, 1);
+        let dot_key = DotKey::new(&dot);
+
+        {
+            let mut tx = store.transact(id);
+            tx.in_map(dot_key.as_str(), |todo_tx| {
+                todo_tx.write_register("text", MvRegValue::String("Test".to_string()));
+            });
+            let _delta = tx.commit();
+        }
+        assert_eq!(read_todo(&store.store, &dot).unwrap().modified_at, None);
+
+        let before = now_millis();
+        {
+            let mut tx = store.transact(id);
+            tx.in_map(dot_key.as_str(), |todo_tx| {
+                todo_tx.write_register("done", MvRegValue::Bool(true));
+                touch(todo_tx);
+            });
+            let _delta = tx.commit();
+        }
+
+        let modified_at = read_todo(&store.store, &dot)
+            .unwrap()
+            .modified_at
+            .expect("modified_at should be set");
+        assert!(modified_at >= before);
+    }
+
+    #[test]
+    fn test_add_and_remove_tags() {
+        let mut store = TodoStore::default();
+        let id = Identifier::new(1, 0);
+        let dot = Dot::mint(id, 1);
+        let dot_key = DotKey::new(&dot);
+
+        {
+            let mut tx = store.transact(id);
+            tx.in_map(dot_key.as_str(), |todo_tx| {
+                todo_tx.write_register("text", MvRegValue::String("Buy milk".to_string()));
+                add_tag(todo_tx, "urgent");
+                add_tag(todo_tx, "groceries");
+            });
+            let _delta = tx.commit();
+        }
+        let todo = read_todo(&store.store, &dot).expect("Todo should exist");
+        assert_eq!(todo.tags, vec!["groceries", "urgent"]);
+
+        {
+            let mut tx = store.transact(id);
+            tx.in_map(dot_key.as_str(), |todo_tx| remove_tag(todo_tx, "urgent"));
+            let _delta = tx.commit();
+        }
+        let todo = read_todo(&store.store, &dot).expect("Todo should exist");
+        assert_eq!(todo.tags, vec!["groceries"]);
+    }
+
+    #[test]
+    fn test_concurrent_tag_add_and_remove_add_wins() {
+        let mut replica_a = TodoStore::default();
+        let mut replica_b = TodoStore::default();
+        let id_a = Identifier::new(1, 0);
+        let id_b = Identifier::new(2, 0);
+        let dot = Dot::mint(id_a, 1);
+        let dot_key = DotKey::new(&dot);
+
+        let delta_init = {
+            let mut tx = replica_a.transact(id_a);
+            tx.in_map(dot_key.as_str(), |todo_tx| {
+                todo_tx.write_register("text", MvRegValue::String("Buy milk".to_string()));
+                add_tag(todo_tx, "urgent");
+            });
+            tx.commit()
+        };
+        replica_b.join_or_replace_with(delta_init.0.store, &delta_init.0.context);
+
+        // A removes the tag while B concurrently re-adds it
+        let delta_a = {
+            let mut tx = replica_a.transact(id_a);
+            tx.in_map(dot_key.as_str(), |todo_tx| remove_tag(todo_tx, "urgent"));
+            tx.commit()
+        };
+        let delta_b = {
+            let mut tx = replica_b.transact(id_b);
+            tx.in_map(dot_key.as_str(), |todo_tx| add_tag(todo_tx, "urgent"));
+            tx.commit()
+        };
+
+        replica_a.join_or_replace_with(delta_b.0.store, &delta_b.0.context);
+        replica_b.join_or_replace_with(delta_a.0.store, &delta_a.0.context);
+
+        assert_eq!(replica_a, replica_b);
+        let todo = read_todo(&replica_a.store, &dot).expect("Todo should exist");
+        assert_eq!(todo.tags, vec!["urgent"]);
+    }
+
+    #[test]
+    fn test_concurrent_notes_edits_conflict() {
+        let mut replica_a = TodoStore::default();
+        let mut replica_b = TodoStore::default();
+        let id_a = Identifier::new(1, 0);
+        let id_b = Identifier::new(2, 0);
+        let dot = Dot::mint(id_a, 1);
+        let dot_key = DotKey::new(&dot);
+
+        let delta_init = {
+            let mut tx = replica_a.transact(id_a);
+            tx.in_map(dot_key.as_str(), |todo_tx| {
+                todo_tx.write_register("text", MvRegValue::String("Buy milk".to_string()));
+            });
+            tx.commit()
+        };
+        replica_b.join_or_replace_with(delta_init.0.store, &delta_init.0.context);
+
+        let write_notes = |store: &mut TodoStore, id: Identifier, notes: &str| {
+            let mut tx = store.transact(id);
+            tx.in_map(dot_key.as_str(), |todo_tx| {
+                todo_tx.write_register("notes", MvRegValue::String(notes.to_string()));
+            });
+            tx.commit()
+        };
+        let delta_a = write_notes(&mut replica_a, id_a, "Whole milk\nTwo bottles");
+        let delta_b = write_notes(&mut replica_b, id_b, "Oat milk");
+
+        replica_a.join_or_replace_with(delta_b.0.store, &delta_b.0.context);
+        replica_b.join_or_replace_with(delta_a.0.store, &delta_a.0.context);
+
+        assert_eq!(replica_a, replica_b);
+        let todo = read_todo(&replica_a.store, &dot).expect("Todo should exist");
+        assert_eq!(todo.notes.len(), 2);
+        assert!(todo.notes.contains(&"Whole milk\nTwo bottles".to_string()));
+        assert!(todo.notes.contains(&"Oat milk".to_string()));
+        assert!(todo.has_conflicts());
+    }
+
+    #[test]
+    fn test_concurrent_modifications_keep_latest_time() {
+        let mut replica_a = TodoStore::default();
+        let mut replica_b = TodoStore::default();
+        let id_a = Identifier::new(1, 0);
+        let id_b = Identifier::new(2, 0);
+        let dot = Dot::mint(id_a, 1);
+        let dot_key = DotKey::new(&dot);
+
+        let delta_init = {
+            let mut tx = replica_a.transact(id_a);
+            tx.in_map(dot_key.as_str(), |todo_tx| {
+                todo_tx.write_register("text", MvRegValue::String("Test".to_string()));
+            });
+            tx.commit()
+        };
+        replica_b.join_or_replace_with(delta_init.0.store, &delta_init.0.context);
+
+        let modify = |store: &mut TodoStore, id, at| {
+            let mut tx = store.transact(id);
+            tx.in_map(dot_key.as_str(), |todo_tx| {
+                todo_tx.write_register("modified_at", MvRegValue::I64(at));
+            });
+            tx.commit()
+        };
+        let _delta = modify(&mut replica_a, id_a, 2_000);
+        let delta_b = modify(&mut replica_b, id_b, 1_000);
+        replica_a.join_or_replace_with(delta_b.0.store, &delta_b.0.context);
+
+        let todo = read_todo(&replica_a.store, &dot).unwrap();
+        assert_eq!(todo.modified_at, Some(2_000));
+        assert!(todo.modified_concurrently);
+
+        // A later sequential touch resolves the conflict
+        let _delta = modify(&mut replica_a, id_a, 3_000);
+        let todo = read_todo(&replica_a.store, &dot).unwrap();
+        assert_eq!(todo.modified_at, Some(3_000));
+        assert!(!todo.modified_concurrently);
+    }
+
+    #[test]
+    fn test_concurrent_level_cycles_conflict() {
+        let mut replica_a = TodoStore::default();
+        let mut replica_b = TodoStore::default();
+        let id_a = Identifier::new(1, 0);
+        let id_b = Identifier::new(2, 0);
+        let dot = Dot::mint(id_a, 1);
+        let dot_key = DotKey::new(&dot);
+
+        let delta_init = {
+            let mut tx = replica_a.transact(id_a);
+            tx.in_map(dot_key.as_str(), |todo_tx| {
+                todo_tx.write_register("text", MvRegValue::String("Test".to_string()));
+                set_level(todo_tx, Some(Level::High));
+            });
+            tx.commit()
+        };
+        replica_b.join_or_replace_with(delta_init.0.store, &delta_init.0.context);
+
+        // Replica A cycles once, replica B twice
+        let set = |store: &mut TodoStore, id, level| {
+            let mut tx = store.transact(id);
+            tx.in_map(dot_key.as_str(), |todo_tx| set_level(todo_tx, level));
+            tx.commit()
+        };
+        let delta_a = set(&mut replica_a, id_a, Level::cycle(Some(Level::High)));
+        let _delta = set(&mut replica_b, id_b, Some(Level::Medium));
+        let delta_b = set(&mut replica_b, id_b, Some(Level::Low));
+
+        replica_a.join_or_replace_with(delta_b.0.store, &delta_b.0.context);
+        replica_b.join_or_replace_with(delta_a.0.store, &delta_a.0.context);
+
+        let todo = read_todo(&replica_a.store, &dot).unwrap();
+        let mut levels = todo.level.clone();
+        levels.sort();
+        assert_eq!(levels, vec![Level::Medium, Level::Low]);
+        assert!(todo.has_conflicts());
+
+        // Clearing the level removes every value this replica has seen
+        let _delta = set(&mut replica_a, id_a, None);
+        assert_eq!(read_todo(&replica_a.store, &dot).unwrap().level, vec![]);
+    }
+
+    #[test]
+    fn test_read_todo_truncates_overlong_text_only() {
+        let mut store = TodoStore::default();
+        let id = Identifier::new(1, 0);
+        let dot = Dot::mint(id, 1);
+        let dot_key = DotKey::new(&dot);
+        let long = "é".repeat(MAX_DISPLAY_TEXT_LEN + 5);
+        let mut tx = store.transact(id);
+        tx.in_map("list", |list_tx| {
+            list_tx.in_map(dot_key.as_str(), |todo_tx| {
+                todo_tx.write_register("text", MvRegValue::String(long.clone()));
+            });
+        });
+        let _delta = tx.commit();
+
+        let list = &store.store.get(&"list".to_string()).unwrap().map;
+        let text = read_todo(list, &dot).unwrap().primary_text().to_string();
+        assert_eq!(text.chars().count(), MAX_DISPLAY_TEXT_LEN);
+        assert_eq!(text, format!("{}…", "é".repeat(MAX_DISPLAY_TEXT_LEN - 1)));
+        assert_eq!(count_overlong_texts(&store.store), 1);
+
+        // Texts at the limit are shown whole
+        let at_limit = "é".repeat(MAX_DISPLAY_TEXT_LEN);
+        assert_eq!(truncate_overlong(at_limit.clone()), at_limit);
+    }
+
+    #[test]
+    fn test_concurrent_category_changes_conflict() {
+        let mut replica_a = TodoStore::default();
+        let mut replica_b = TodoStore::default();
+        let id_a = Identifier::new(1, 0);
+        let id_b = Identifier::new(2, 0);
+        let dot = Dot::mint(id_a, 1);
+        let dot_key = DotKey::new(&dot);
+
+        let set = |store: &mut TodoStore, id, category| {
+            let mut tx = store.transact(id);
+            tx.in_map(dot_key.as_str(), |todo_tx| {
+                todo_tx.write_register("text", MvRegValue::String("Test".to_string()));
+                set_category(todo_tx, category);
+            });
+            tx.commit()
+        };
+        let delta_init = set(&mut replica_a, id_a, Some("home"));
+        replica_b.join_or_replace_with(delta_init.0.store, &delta_init.0.context);
+        assert_eq!(
+            read_todo(&replica_b.store, &dot).unwrap().category,
+            vec!["home"]
+        );
+
+        let delta_a = set(&mut replica_a, id_a, Some("work"));
+        let delta_b = set(&mut replica_b, id_b, Some("errand"));
+        replica_a.join_or_replace_with(delta_b.0.store, &delta_b.0.context);
+        replica_b.join_or_replace_with(delta_a.0.store, &delta_a.0.context);
+
+        let todo = read_todo(&replica_a.store, &dot).unwrap();
+        let mut categories = todo.category.clone();
+        categories.sort();
+        assert_eq!(categories, vec!["errand", "work"]);
+        assert!(todo.has_conflicts());
+
+        // Clearing the category removes every value this replica has seen
+        let _delta = set(&mut replica_a, id_a, None);
+        assert_eq!(
+            read_todo(&replica_a.store, &dot).unwrap().category,
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn test_concurrent_color_changes_conflict() {
+        let mut replica_a = TodoStore::default();
+        let mut replica_b = TodoStore::default();
+        let id_a = Identifier::new(1, 0);
+        let id_b = Identifier::new(2, 0);
+        let dot = Dot::mint(id_a, 1);
+        let dot_key = DotKey::new(&dot);
+
+        let set = |store: &mut TodoStore, id, color| {
+            let mut tx = store.transact(id);
+            tx.in_map(dot_key.as_str(), |todo_tx| {
+                todo_tx.write_register("text", MvRegValue::String("Test".to_string()));
+                set_color(todo_tx, color);
+            });
+            tx.commit()
+        };
+        let delta_init = set(&mut replica_a, id_a, Some(TodoColor::Red));
+        replica_b.join_or_replace_with(delta_init.0.store, &delta_init.0.context);
+        let todo = read_todo(&replica_b.store, &dot).unwrap();
+        assert_eq!(todo.color, vec![TodoColor::Red]);
+        assert!(!todo.has_conflicts());
+
+        let next = TodoColor::cycle(todo.primary_color());
+        assert_eq!(next, Some(TodoColor::Yellow));
+        let delta_a = set(&mut replica_a, id_a, Some(TodoColor::Blue));
+        let delta_b = set(&mut replica_b, id_b, next);
+        replica_a.join_or_replace_with(delta_b.0.store, &delta_b.0.context);
+        replica_b.join_or_replace_with(delta_a.0.store, &delta_a.0.context);
+
+        for store in [&replica_a, &replica_b] {
+            let todo = read_todo(&store.store, &dot).unwrap();
+            let mut colors = todo.color.clone();
+            colors.sort();
+            assert_eq!(colors, vec![TodoColor::Yellow, TodoColor::Blue]);
+            assert!(todo.has_conflicts());
+        }
+
+        // Cycling again writes one color over both
+        let _delta = set(&mut replica_a, id_a, Some(TodoColor::Green));
+        assert_eq!(
+            read_todo(&replica_a.store, &dot).unwrap().color,
+            vec![TodoColor::Green]
+        );
+        assert_eq!(TodoColor::cycle(Some(TodoColor::Purple)), None);
+    }
+
+    #[test]
+    fn test_cycle_through_wraps_through_none() {
+        let categories = vec!["home".to_string(), "work".to_string()];
+        assert_eq!(cycle_through(&categories, None).as_deref(), Some("home"));
+        assert_eq!(
+            cycle_through(&categories, Some("home")).as_deref(),
+            Some("work")
+        );
+        assert_eq!(cycle_through(&categories, Some("work")), None);
+        assert_eq!(cycle_through(&[], None), None);
+    }
+
+    #[test]
+    fn test_concurrent_reassignment_conflicts() {
+        let mut replica_a = TodoStore::default();
+        let mut replica_b = TodoStore::default();
+        let id_a = Identifier::new(1, 0);
+        let id_b = Identifier::new(2, 0);
+        let dot = Dot::mint(id_a, 1);
+        let dot_key = DotKey::new(&dot);
+
+        let delta_init = {
+            let mut tx = replica_a.transact(id_a);
+            tx.in_map(dot_key.as_str(), |todo_tx| {
+                todo_tx.write_register("text", MvRegValue::String("Test".to_string()));
+                write_creation_metadata(todo_tx, ReplicaId::new(1), 0);
+            });
+            tx.commit()
+        };
+        replica_b.join_or_replace_with(delta_init.0.store, &delta_init.0.context);
+
+        let assign = |store: &mut TodoStore, id, assignee| {
+            let mut tx = store.transact(id);
+            tx.in_map(dot_key.as_str(), |todo_tx| set_assignee(todo_tx, assignee));
+            tx.commit()
+        };
+        let delta_a = assign(&mut replica_a, id_a, ReplicaId::new(0x3a));
+        let delta_b = assign(&mut replica_b, id_b, ReplicaId::new(0x7f));
+        replica_a.join_or_replace_with(delta_b.0.store, &delta_b.0.context);
+        replica_b.join_or_replace_with(delta_a.0.store, &delta_a.0.context);
+
+        let todo = read_todo(&replica_a.store, &dot).unwrap();
+        let mut assignees = todo.assignee.clone();
+        assignees.sort();
+        assert_eq!(assignees, vec!["3a".to_string(), "7f".to_string()]);
+        assert!(todo.has_conflicts());
+
+        // Reassigning after seeing both settles it
+        let _delta = assign(&mut replica_a, id_a, ReplicaId::new(0x3a));
+        assert_eq!(
+            read_todo(&replica_a.store, &dot).unwrap().assignee,
+            vec!["3a".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_concurrent_merges_reconverge() {
+        let mut replica_a = TodoStore::default();
+        let mut replica_b = TodoStore::default();
+        let id_a = Identifier::new(1, 0);
+        let id_b = Identifier::new(2, 0);
+        let dot = Dot::mint(id_a, 1);
+        let dot_key = DotKey::new(&dot);
+
+        let write_text = |store: &mut TodoStore, id, text: &str| {
+            let mut tx = store.transact(id);
+            tx.in_map(dot_key.as_str(), |todo_tx| {
+                todo_tx.write_register("text", MvRegValue::String(text.to_string()));
+            });
+            tx.commit()
+        };
+
+        let delta_init = write_text(&mut replica_a, id_a, "Buy milk");
+        replica_b.join_or_replace_with(delta_init.0.store, &delta_init.0.context);
+
+        let delta_a = write_text(&mut replica_a, id_a, "Buy whole milk");
+        let delta_b = write_text(&mut replica_b, id_b, "Buy oat milk");
+        replica_a.join_or_replace_with(delta_b.0.store, &delta_b.0.context);
+        replica_b.join_or_replace_with(delta_a.0.store, &delta_a.0.context);
+
+        // Both replicas merge concurrently, seeing the values in different order
+        let text_a = read_todo(&replica_a.store, &dot).unwrap().text;
+        let mut text_b = read_todo(&replica_b.store, &dot).unwrap().text;
+        text_b.reverse();
+        let merged = merge_text_values(&text_a);
+        assert_eq!(merged, "Buy oat milk / Buy whole milk");
+        assert_eq!(merge_text_values(&text_b), merged);
+
+        let merge_a = write_text(&mut replica_a, id_a, &merge_text_values(&text_a));
+        let merge_b = write_text(&mut replica_b, id_b, &merge_text_values(&text_b));
+        replica_a.join_or_replace_with(merge_b.0.store, &merge_b.0.context);
+        replica_b.join_or_replace_with(merge_a.0.store, &merge_a.0.context);
+
+        assert_eq!(replica_a, replica_b);
+        let todo = read_todo(&replica_a.store, &dot).unwrap();
+        assert_eq!(todo.text, vec![merged]);
+        assert!(!todo.has_conflicts());
+    }
+
+    #[test]
+    fn test_subtasks_insert_toggle_remove() {
+        let mut store = TodoStore::default();
+        let id = Identifier::new(1, 0);
+        let dot = Dot::mint(id, 1);
+        let dot_key = DotKey::new(&dot);
+
+        {
+            let mut tx = store.transact(id);
+            tx.in_map(dot_key.as_str(), |todo_tx| {
+                todo_tx.write_register("text", MvRegValue::String("Pack".to_string()));
+                insert_subtask(todo_tx, 0, "1:2", "Socks");
+                insert_subtask(todo_tx, 1, "1:3", "Towel");
+                insert_subtask(todo_tx, 0, "1:4", "Passport");
+            });
+            let _delta = tx.commit();
+        }
+        let subtasks = read_subtasks(&store.store.get(dot_key.as_str()).unwrap().map);
+        let texts: Vec<_> = subtasks.iter().map(Subtask::primary_text).collect();
+        assert_eq!(texts, vec!["Passport", "Socks", "Towel"]);
+
+        {
+            let mut tx = store.transact(id);
+            tx.in_map(dot_key.as_str(), |todo_tx| {
+                set_subtask_done(todo_tx, "1:3", true);
+                remove_subtask(todo_tx, 0, "1:4");
+            });
+            let _delta = tx.commit();
+        }
+        let todo = read_todo(&store.store, &dot).expect("Todo should exist");
+        assert_eq!(todo.subtasks.len(), 2);
+        assert_eq!(todo.subtasks[0].primary_text(), "Socks");
+        assert!(!todo.subtasks[0].primary_done());
+        assert_eq!(todo.subtasks[1].done, vec![true]);
+    }
+
+    #[test]
+    fn test_concurrent_subtask_inserts_converge() {
+        let mut replica_a = TodoStore::default();
+        let mut replica_b = TodoStore::default();
+        let id_a = Identifier::new(1, 0);
+        let id_b = Identifier::new(2, 0);
+        let dot = Dot::mint(id_a, 1);
+        let dot_key = DotKey::new(&dot);
+
+        let delta_init = {
+            let mut tx = replica_a.transact(id_a);
+            tx.in_map(dot_key.as_str(), |todo_tx| {
+                todo_tx.write_register("text", MvRegValue::String("Pack".to_string()));
+                insert_subtask(todo_tx, 0, "1:2", "Socks");
+            });
+            tx.commit()
+        };
+        replica_b.join_or_replace_with(delta_init.0.store, &delta_init.0.context);
+
+        // Both replicas append a subtask and toggle the shared one differently
+        let delta_a = {
+            let mut tx = replica_a.transact(id_a);
+            tx.in_map(dot_key.as_str(), |todo_tx| {
+                insert_subtask(todo_tx, 1, "1:3", "Towel");
+                set_subtask_done(todo_tx, "1:2", true);
+            });
+            tx.commit()
+        };
+        let delta_b = {
+            let mut tx = replica_b.transact(id_b);
+            tx.in_map(dot_key.as_str(), |todo_tx| {
+                insert_subtask(todo_tx, 1, "2:1", "Toothbrush");
+                set_subtask_done(todo_tx, "1:2", false);
+            });
+            tx.commit()
+        };
+
+        replica_a.join_or_replace_with(delta_b.0.store, &delta_b.0.context);
+        replica_b.join_or_replace_with(delta_a.0.store, &delta_a.0.context);
+
+        assert_eq!(replica_a, replica_b);
+        let todo = read_todo(&replica_a.store, &dot).expect("Todo should exist");
+        let mut texts: Vec<_> = todo.subtasks.iter().map(Subtask::primary_text).collect();
+        assert_eq!(texts.remove(0), "Socks");
+        texts.sort();
+        assert_eq!(texts, vec!["Toothbrush", "Towel"]);
+        assert_eq!(todo.subtasks[0].done.len(), 2);
+        assert!(todo.subtasks[0].has_conflicts());
+        // The conflicted subtask is not counted as done, and flags its parent
+        assert_eq!(todo.subtask_progress(), (0, 3));
+        assert!(todo.has_conflicts());
+    }
 }
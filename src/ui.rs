@@ -1,18 +1,31 @@
 // ABOUTME: Terminal UI rendering using ratatui.
 // ABOUTME: Displays todos, status bar, and help text.
 
-use crate::app::{App, Mode};
+use crate::app::{App, ConflictField, FormField, Mode, PromptKind, ReplicaId, WrapMode};
 use ratatui::{
     Frame,
-    layout::{Constraint, Direction, Layout},
+    layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, List, ListItem, Paragraph},
+    widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph, Tabs},
 };
 
-/// Draw the entire UI.
-pub fn draw(f: &mut Frame, app: &mut App) {
-    let chunks = Layout::default()
+/// Height of the notes pane below the list, including borders.
+const NOTES_PANE_HEIGHT: u16 = 8;
+
+/// Minimum list width at which relative modification times are shown.
+const MIN_WIDTH_FOR_TIMES: u16 = 60;
+
+/// Todo texts are never truncated below this many characters, even if that
+/// pushes the badges and tags after them out of view.
+const MIN_TRUNCATED_TEXT_WIDTH: usize = 10;
+
+/// Cells of the subtask progress bar after a todo's text.
+const SUBTASK_BAR_WIDTH: usize = 5;
+
+/// Split the terminal into status bar, todo list, log/context row and help text.
+fn main_layout(area: Rect) -> std::rc::Rc<[Rect]> {
+    Layout::default()
         .direction(Direction::Vertical)
         .constraints([
             Constraint::Length(3), // Status bar
@@ -20,10 +33,61 @@ pub fn draw(f: &mut Frame, app: &mut App) {
             Constraint::Length(8), // Log window + context
             Constraint::Length(3), // Help text
         ])
-        .split(f.area());
+        .split(area)
+}
+
+/// Number of log lines visible for a terminal of the given size.
+pub fn visible_log_lines(width: u16, height: u16) -> usize {
+    let log_row = main_layout(Rect::new(0, 0, width, height))[2];
+    log_row.height.saturating_sub(2) as usize
+}
+
+/// Draw the entire UI.
+pub fn draw(f: &mut Frame, app: &mut App) {
+    let chunks = main_layout(f.area());
 
     draw_status(f, app, chunks[0]);
-    draw_list(f, app, chunks[1]);
+
+    // Tab bar with all lists above the todo list
+    let tab_chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(1), Constraint::Min(0)])
+        .split(chunks[1]);
+    draw_tabs(f, app, tab_chunks[0]);
+
+    // Show the notes pane below the list while editing or when the selection has notes
+    let selected_notes = app
+        .visible_todos()
+        .get(app.ui_state.selected_index)
+        .map(|(_, todo)| todo.notes.clone())
+        .unwrap_or_default();
+    let list_area = if app.ui_state.mode == Mode::Notes || !selected_notes.is_empty() {
+        let list_chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(3), Constraint::Length(NOTES_PANE_HEIGHT)])
+            .split(tab_chunks[1]);
+        draw_notes(f, app, &selected_notes, list_chunks[1]);
+        list_chunks[0]
+    } else {
+        tab_chunks[1]
+    };
+    draw_list(f, app, list_area);
+
+    if app.ui_state.show_detail && app.ui_state.mode == Mode::Normal {
+        draw_detail(f, app, list_area);
+    }
+    if app.ui_state.mode == Mode::ResolveConflict {
+        draw_conflict(f, app, list_area);
+    }
+    if app.ui_state.mode == Mode::Assign {
+        draw_assign(f, app, list_area);
+    }
+    if app.ui_state.mode == Mode::Form {
+        draw_form(f, app, list_area);
+    }
+    if app.ui_state.pending_action.is_some() {
+        draw_confirm(f, app, list_area);
+    }
 
     // Split the log area into logs (2/3) and context (1/3)
     let log_chunks = Layout::default()
@@ -37,16 +101,88 @@ pub fn draw(f: &mut Frame, app: &mut App) {
     draw_logs(f, app, log_chunks[0]);
     draw_context(f, app, log_chunks[1]);
     draw_help(f, app, chunks[3]);
+
+    if app.ui_state.show_context_report {
+        draw_context_report(f, app, list_area);
+    }
+    if let Some(dot) = app.ui_state.history_dot {
+        draw_history(f, app, &dot, list_area);
+    }
+    if app.ui_state.show_help {
+        draw_help_overlay(f, app, f.area());
+    }
 }
 
 /// Draw the status bar.
 fn draw_status(f: &mut Frame, app: &App, area: ratatui::layout::Rect) {
-    let isolation_status = if app.network_isolated { "YES" } else { "NO" };
+    let isolation_status = match app.isolated_since {
+        Some(since) if app.network_isolated => format!(
+            "for {}, {} local change(s) pending",
+            crate::app::format_elapsed(since.elapsed()),
+            app.isolated_changes
+        ),
+        _ if app.network_isolated => "YES".to_string(),
+        _ => "NO".to_string(),
+    };
 
-    let text = format!(
+    let mut text = format!(
         "Replica: {} | Port: {} | Isolated: {}",
         app.replica_id, app.port, isolation_status
     );
+    let (done, total) = app.progress();
+    if let Some(percent) = (done * 100).checked_div(total) {
+        text.push_str(&format!(" | {done}/{total} done ({percent}%)"));
+    }
+    // Only replicas of the same generation sync
+    if app.generation > 0 {
+        text.push_str(&format!(" | Generation: {}", app.generation));
+    }
+    // J/K and other moves are off until back in priority order
+    if app.ui_state.sort_mode != crate::app::SortMode::Priority {
+        text.push_str(&format!(
+            " | Sort: {} (J/K off, s: next)",
+            app.ui_state.sort_mode.as_str()
+        ));
+    }
+    // Say why done todos are missing from the list
+    if app.ui_state.hide_done {
+        text.push_str(" | Hiding done (h: show)");
+    }
+    // Wall clocks of different machines may be skewed, hence "apparent"
+    if let Some(average) = app.latency.average() {
+        text.push_str(&format!(
+            " | Latency: {average} ms avg of {} (apparent)",
+            app.latency.len()
+        ));
+    }
+
+    // Todos with a map entry but no priority entry are invisible in the list
+    let orphans = app.find_orphans().len();
+    if orphans > 0 {
+        text.push_str(&format!(" | Orphans: {orphans} (O: restore, P: purge)"));
+    }
+    // Concurrent moves of a todo leave an entry per replica; the list shows the first
+    let repeated = crate::priority::duplicate_priority_indices(app.list_store()).len();
+    if repeated > 0 {
+        text.push_str(&format!(" | Repeated entries: {repeated} (=: repair)"));
+    }
+
+    // Where the selected todo is while a move target is being typed
+    if app.ui_state.mode == Mode::Grab {
+        let priority = crate::priority::priority_entries(app.list_store());
+        if let Some(position) = app
+            .ui_state
+            .editing_dot
+            .and_then(|dot| priority.iter().position(|d| *d == Some(dot)))
+        {
+            text.push_str(&format!(
+                " | Position: {} → {}/{}",
+                position + 1,
+                app.ui_state.grab_target + 1,
+                priority.len()
+            ));
+        }
+    }
 
     let paragraph =
         Paragraph::new(text).block(Block::default().borders(Borders::ALL).title("Status"));
@@ -54,15 +190,100 @@ fn draw_status(f: &mut Frame, app: &App, area: ratatui::layout::Rect) {
     f.render_widget(paragraph, area);
 }
 
+/// Draw the list names as tabs, highlighting the current list.
+fn draw_tabs(f: &mut Frame, app: &App, area: Rect) {
+    let names = app.list_names();
+    let selected = names.iter().position(|name| *name == app.current_list);
+    let tabs = Tabs::new(names).select(selected).highlight_style(
+        Style::default()
+            .fg(Color::Yellow)
+            .add_modifier(Modifier::BOLD),
+    );
+
+    f.render_widget(tabs, area);
+}
+
 /// Draw the todo list.
 fn draw_list(f: &mut Frame, app: &mut App, area: ratatui::layout::Rect) {
-    let todos = app.get_todos_ordered();
+    let todos = app.visible_todos();
+    let now = crate::todo::now_millis();
+    let show_times = area.width >= MIN_WIDTH_FOR_TIMES;
+    // The todo whose checklist is being edited is rendered with its subtasks
+    // and the selected subtask highlighted; otherwise the selected todo shows
+    // its subtasks, if any
+    let (expanded, selected_subtask) = match app.ui_state.mode {
+        Mode::Subtasks | Mode::Prompt(PromptKind::AddSubtask) => {
+            (app.ui_state.editing_dot, Some(app.ui_state.subtask_index))
+        }
+        _ => (
+            todos
+                .get(app.ui_state.selected_index)
+                .filter(|(_, todo)| !todo.subtasks.is_empty())
+                .map(|(dot, _)| *dot),
+            None,
+        ),
+    };
+
+    // While a todo is grabbed, mark it and show where it would be dropped:
+    // below the todo now at the target when moving down, above it when moving up
+    let priority = crate::priority::priority_entries(app.list_store());
+    let (grabbed, drop_at) = match app.ui_state.mode {
+        Mode::Grab => {
+            let grabbed = app.ui_state.editing_dot;
+            let current = grabbed.and_then(|dot| priority.iter().position(|d| *d == Some(dot)));
+            let target = app.ui_state.grab_target;
+            let drop_at = current
+                .filter(|current| *current != target)
+                .and_then(|current| Some(((*priority.get(target)?)?, target > current)));
+            (grabbed, drop_at)
+        }
+        _ => (None, None),
+    };
+    let drop_line = || {
+        Line::styled(
+            "    ▸ drop here",
+            Style::default()
+                .fg(Color::Magenta)
+                .add_modifier(Modifier::BOLD),
+        )
+    };
+
+    let done_count = todos.iter().filter(|(_, todo)| app.is_done(todo)).count();
+
+    // Ranks stay absolute in filtered or sorted views, padded to line up
+    let ranks: std::collections::HashMap<dson::Dot, usize> = if app.ui_state.show_ranks {
+        priority
+            .iter()
+            .enumerate()
+            .filter_map(|(i, dot)| Some(((*dot)?, i + 1)))
+            .collect()
+    } else {
+        std::collections::HashMap::new()
+    };
+    let rank_digits = priority.len().to_string().len();
+    let rank_span = |dot: &dson::Dot| {
+        ranks.get(dot).map(|rank| {
+            Span::styled(
+                format!("{rank:>rank_digits$}. "),
+                Style::default().fg(Color::DarkGray),
+            )
+        })
+    };
+
+    // Full text of the selected todo, if it had to be truncated
+    let inner_width = usize::from(area.width.saturating_sub(2));
+    let mut selected_full_text = None;
+    let search_query = app.ui_state.search_query.as_str();
+    // Bullets get a column only once some todo has a color
+    let any_colored = todos.iter().any(|(_, todo)| !todo.color.is_empty());
 
     let items: Vec<ListItem> = todos
         .iter()
         .enumerate()
-        .map(|(i, (_dot, todo))| {
-            let checkbox = if todo.primary_done() { "[✓]" } else { "[ ]" };
+        .map(|(i, (dot, todo))| {
+            let selected = i == app.ui_state.selected_index;
+            let done = app.is_done(todo);
+            let checkbox = if done { "[✓]" } else { "[ ]" };
             let conflict_indicator = if todo.has_conflicts() { " ⚠ " } else { "   " };
 
             // Show all text values if there's a conflict
@@ -72,9 +293,124 @@ fn draw_list(f: &mut Frame, app: &mut App, area: ratatui::layout::Rect) {
                 todo.primary_text().to_string()
             };
 
-            let content = format!("{checkbox} {conflict_indicator}{text}");
+            let mut spans = vec![
+                Span::raw(format!("{checkbox} {conflict_indicator}")),
+                Span::styled(
+                    text,
+                    if done {
+                        done_text_style(selected)
+                    } else {
+                        level_style(todo.primary_level())
+                    },
+                ),
+            ];
+            if todo.postponed > 0 {
+                spans.push(Span::styled(
+                    format!(" (postponed ×{})", todo.postponed),
+                    Style::default().fg(Color::DarkGray),
+                ));
+            }
+            if let Some(recur) = todo.primary_recur() {
+                spans.push(Span::styled(
+                    format!(" ↻ {}", recur.as_str()),
+                    Style::default().fg(Color::DarkGray),
+                ));
+            }
+            if let Some(creator) = &todo.creator {
+                let badge: String = creator.chars().take(2).collect();
+                spans.push(Span::raw(" "));
+                spans.push(Span::styled(
+                    format!("[{badge}]"),
+                    Style::default().fg(replica_color(creator)),
+                ));
+            }
+            // One chip per assignee; concurrent reassignments show them all
+            for assignee in &todo.assignee {
+                spans.push(Span::styled(
+                    format!(" @{}", assignee_label(app, assignee)),
+                    Style::default().fg(replica_color(assignee)),
+                ));
+            }
+            if todo.notes.iter().any(|notes| !notes.is_empty()) {
+                spans.push(Span::styled(" ✎", Style::default().fg(Color::DarkGray)));
+            }
+            if !todo.subtasks.is_empty() {
+                let (done, total) = todo.subtask_progress();
+                spans.push(Span::raw(format!(" ({done}/{total} ")));
+                spans.push(Span::styled(
+                    progress_bar(done, total, SUBTASK_BAR_WIDTH),
+                    Style::default().fg(Color::Green),
+                ));
+                spans.push(Span::raw(")"));
+            }
+            // One badge per category; concurrent changes show them all
+            for category in &todo.category {
+                spans.push(Span::raw(" "));
+                spans.push(Span::styled(
+                    format!(" {category} "),
+                    Style::default()
+                        .fg(Color::Black)
+                        .bg(category_color(category)),
+                ));
+            }
+            if !todo.tags.is_empty() {
+                let tags: Vec<String> = todo.tags.iter().map(|tag| format!("#{tag}")).collect();
+                spans.push(Span::styled(
+                    format!("  {}", tags.join(" ")),
+                    Style::default().fg(Color::Cyan),
+                ));
+            }
+            if show_times && let Some(at) = todo.modified_at.or(todo.created_at) {
+                spans.push(Span::raw(format!("  ({})", format_relative_time(now, at))));
+            }
 
-            let mut style = if i == app.ui_state.selected_index {
+            // Fit the text into the room the rest of the row leaves; the rank,
+            // grab and color markers are put in place once it is fit
+            let rank = rank_span(dot);
+            let bullet = any_colored.then(|| color_bullet(&todo.color));
+            let marker_width = if grabbed == Some(*dot) { 2 } else { 0 }
+                + rank.as_ref().map_or(0, Span::width)
+                + bullet.as_ref().map_or(0, Span::width);
+            let prefix_width = spans[0].width() + marker_width;
+            let other_width =
+                spans.iter().map(Span::width).sum::<usize>() - spans[1].width() + marker_width;
+            let max_width = inner_width
+                .saturating_sub(other_width)
+                .max(MIN_TRUNCATED_TEXT_WIDTH);
+            let mut continuation = Vec::new();
+            match app.ui_state.wrap_mode {
+                WrapMode::Truncate => {
+                    let truncated = truncate_text(&spans[1].content, max_width);
+                    if truncated != spans[1].content {
+                        if selected {
+                            selected_full_text = Some(spans[1].content.to_string());
+                        }
+                        spans[1].content = truncated.into();
+                    }
+                }
+                WrapMode::Wrap => {
+                    let mut rows = wrap_text(&spans[1].content, max_width).into_iter();
+                    spans[1].content = rows.next().unwrap_or_default().into();
+                    let text_style = spans[1].style;
+                    continuation = rows
+                        .map(|row| {
+                            let mut row_spans = vec![Span::raw(" ".repeat(prefix_width))];
+                            row_spans.extend(highlight_matches(&row, search_query, text_style));
+                            Line::from(row_spans)
+                        })
+                        .collect();
+                }
+            }
+            let text = spans.remove(1);
+            spans.splice(
+                1..1,
+                highlight_matches(&text.content, search_query, text.style),
+            );
+            if let Some(bullet) = bullet {
+                spans.insert(1, bullet);
+            }
+
+            let mut style = if selected {
                 Style::default()
                     .fg(Color::Yellow)
                     .add_modifier(Modifier::BOLD)
@@ -82,18 +418,84 @@ fn draw_list(f: &mut Frame, app: &mut App, area: ratatui::layout::Rect) {
                 Style::default()
             };
 
-            // Add strikethrough for completed todos
-            if todo.primary_done() {
-                style = style.add_modifier(Modifier::CROSSED_OUT);
+            if let Some(rank) = rank {
+                spans.insert(0, rank);
+            }
+            if grabbed == Some(*dot) {
+                spans.insert(0, Span::styled("» ", Style::default().fg(Color::Magenta)));
+                style = style.fg(Color::Magenta);
             }
 
-            ListItem::new(content).style(style)
+            let mut lines = vec![Line::from(spans).style(style)];
+            lines.extend(continuation.into_iter().map(|line| line.style(style)));
+            if expanded == Some(*dot) {
+                lines.extend(subtask_lines(todo, selected_subtask));
+            }
+            match drop_at {
+                Some((at, true)) if at == *dot => lines.push(drop_line()),
+                Some((at, false)) if at == *dot => lines.insert(0, drop_line()),
+                _ => {}
+            }
+            ListItem::new(lines)
         })
         .collect();
 
     // Show input mode if inserting
     let title = match app.ui_state.mode {
-        Mode::Normal => "Todos",
+        Mode::Normal
+        | Mode::Notes
+        | Mode::Subtasks
+        | Mode::ResolveConflict
+        | Mode::Prompt(_)
+        | Mode::Grab
+        | Mode::Assign
+        | Mode::Form => {
+            let mut title = app.current_list.clone();
+            if let Some(creator) = app.ui_state.filter_creator {
+                title.push_str(&format!(" [filter: creator={creator:02x}]"));
+            }
+            if !app.ui_state.filter_tags.is_empty() {
+                let tags: Vec<String> = app
+                    .ui_state
+                    .sorted_filter_tags()
+                    .iter()
+                    .map(|tag| format!("#{tag}"))
+                    .collect();
+                title.push_str(&format!(" [{}]", tags.join(" ")));
+            }
+            if let Some(category) = &app.ui_state.category_filter {
+                title.push_str(&format!(" [category: {category}]"));
+            }
+            if !search_query.is_empty() && app.ui_state.search_shows_all {
+                title.push_str(&format!(" [/{search_query}, n/N: matches]"));
+            } else if !search_query.is_empty() {
+                title.push_str(&format!(" [/{search_query}]"));
+            }
+            if app.ui_state.hide_done {
+                title.push_str(" [done hidden]");
+            }
+            if app.ui_state.filter_creator.is_some()
+                || !app.ui_state.filter_tags.is_empty()
+                || app.ui_state.category_filter.is_some()
+                || (!search_query.is_empty() && !app.ui_state.search_shows_all)
+                || app.ui_state.hide_done
+            {
+                let total = app.get_todos_ordered().len();
+                title.push_str(&format!(" ({done_count}/{} of {total})", todos.len()));
+            } else {
+                title.push_str(&format!(" ({done_count}/{})", todos.len()));
+            }
+            if app.ui_state.group_by_level {
+                title.push_str(" [by level]");
+            }
+            if app.done_wins {
+                title.push_str(" [done wins]");
+            }
+            if app.auto_complete {
+                title.push_str(" [auto-complete]");
+            }
+            title
+        }
         Mode::Insert => {
             let input = &app.ui_state.input_buffer;
             let edit_mode = if app.ui_state.editing_dot.is_some() {
@@ -101,28 +503,799 @@ fn draw_list(f: &mut Frame, app: &mut App, area: ratatui::layout::Rect) {
             } else {
                 "Add"
             };
-            return draw_insert_mode(f, area, input, edit_mode);
+            return draw_insert_mode(
+                f,
+                area,
+                input,
+                app.ui_state.cursor_pos,
+                edit_mode,
+                app.max_text_len,
+            );
+        }
+    };
+
+    let block = Block::default().borders(Borders::ALL).title(title);
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    // The last row of the panel shows the selected todo's text in full
+    let list_area = match selected_full_text {
+        Some(full_text) if inner.height > 1 => {
+            let [list_area, full_area] =
+                Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(inner);
+            f.render_widget(
+                Paragraph::new(format!("↳ {full_text}"))
+                    .style(Style::default().fg(Color::DarkGray)),
+                full_area,
+            );
+            list_area
         }
+        _ => inner,
+    };
+
+    // Scroll the list so the selection stays visible, e.g. after g/G jumps
+    let list = List::new(items);
+    let mut state = ListState::default().with_selected(Some(app.ui_state.selected_index));
+    f.render_stateful_widget(list, list_area, &mut state);
+}
+
+/// Split `text` into spans in `style`, with every case-insensitive occurrence
+/// of `query` bold on a yellow background.
+fn highlight_matches(text: &str, query: &str, style: Style) -> Vec<Span<'static>> {
+    let match_style = style
+        .fg(Color::Black)
+        .bg(Color::Yellow)
+        .add_modifier(Modifier::BOLD);
+    let mut spans = Vec::new();
+    let mut last = 0;
+    for range in crate::todo::match_ranges(text, query) {
+        if range.start > last {
+            spans.push(Span::styled(text[last..range.start].to_string(), style));
+        }
+        spans.push(Span::styled(text[range.clone()].to_string(), match_style));
+        last = range.end;
+    }
+    if last < text.len() || spans.is_empty() {
+        spans.push(Span::styled(text[last..].to_string(), style));
+    }
+    spans
+}
+
+/// Break `s` into rows of at most `max_width` characters, at spaces where possible.
+/// Words longer than a row are split.
+pub fn wrap_text(s: &str, max_width: usize) -> Vec<String> {
+    let max_width = max_width.max(1);
+    let mut rows = Vec::new();
+    let mut row = String::new();
+    let mut row_width = 0;
+
+    for word in s.split_whitespace() {
+        let mut word: Vec<char> = word.chars().collect();
+        if row_width > 0 && row_width + 1 + word.len() <= max_width {
+            row.push(' ');
+            row_width += 1;
+        } else if row_width > 0 {
+            rows.push(std::mem::take(&mut row));
+            row_width = 0;
+        }
+        while row_width + word.len() > max_width {
+            let rest = word.split_off(max_width - row_width);
+            row.extend(word);
+            rows.push(std::mem::take(&mut row));
+            row_width = 0;
+            word = rest;
+        }
+        row_width += word.len();
+        row.extend(word);
+    }
+    if row_width > 0 || rows.is_empty() {
+        rows.push(row);
+    }
+    rows
+}
+
+/// Shorten `s` to at most `max_width` characters, ending in "…" if anything was cut.
+pub fn truncate_text(s: &str, max_width: usize) -> String {
+    if s.chars().count() <= max_width {
+        return s.to_string();
+    }
+    let mut truncated: String = s.chars().take(max_width.saturating_sub(1)).collect();
+    if max_width > 0 {
+        truncated.push('…');
+    }
+    truncated
+}
+
+/// Indented checklist lines of an expanded todo, highlighting the selected subtask.
+fn subtask_lines(todo: &crate::todo::Todo, selected: Option<usize>) -> Vec<Line<'static>> {
+    if todo.subtasks.is_empty() {
+        return vec![Line::styled(
+            "      └─ (no subtasks, a: add)",
+            Style::default().fg(Color::DarkGray),
+        )];
+    }
+    let last = todo.subtasks.len() - 1;
+
+    todo.subtasks
+        .iter()
+        .enumerate()
+        .map(|(i, subtask)| {
+            let checkbox = if subtask.primary_done() {
+                "[✓]"
+            } else {
+                "[ ]"
+            };
+            let conflict_indicator = if subtask.has_conflicts() { " ⚠" } else { "" };
+            let text = if subtask.text.len() > 1 {
+                format!("[{}]", subtask.text.join(", "))
+            } else {
+                subtask.primary_text().to_string()
+            };
+
+            let branch = if i == last { "└─" } else { "├─" };
+            let mut style = if selected == Some(i) {
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD)
+            } else {
+                Style::default()
+            };
+            if subtask.primary_done() {
+                style = style.add_modifier(Modifier::CROSSED_OUT);
+            }
+
+            Line::styled(
+                format!("      {branch} {checkbox} {text}{conflict_indicator}"),
+                style,
+            )
+        })
+        .collect()
+}
+
+/// Draw the notes of the selected todo, or the notes editor in notes mode.
+fn draw_notes(f: &mut Frame, app: &App, notes: &[String], area: Rect) {
+    let (lines, title): (Vec<Line>, &str) = if app.ui_state.mode == Mode::Notes {
+        let mut lines: Vec<Line> = app
+            .ui_state
+            .input_buffer
+            .split('\n')
+            .map(Line::from)
+            .collect();
+        if let Some(last) = lines.last_mut() {
+            last.push_span(Span::styled(
+                "_",
+                Style::default().add_modifier(Modifier::SLOW_BLINK),
+            ));
+        }
+        (lines, "Notes (Enter: newline | Ctrl-S: save | Esc: cancel)")
+    } else if notes.len() > 1 {
+        // Concurrent edits: show every version under its own header
+        let conflict = Style::default().fg(Color::Red);
+        let lines = notes
+            .iter()
+            .enumerate()
+            .flat_map(|(i, value)| {
+                std::iter::once(Line::styled(format!("── version {} ──", i + 1), conflict))
+                    .chain(value.lines().map(|l| Line::from(l.to_string())))
+            })
+            .collect();
+        (lines, "Notes ⚠ conflict (n: merge)")
+    } else {
+        let lines = notes
+            .iter()
+            .flat_map(|value| value.lines().map(|l| Line::from(l.to_string())))
+            .collect();
+        (lines, "Notes")
+    };
+
+    // Keep the end of long notes (and the cursor) in view
+    let visible = area.height.saturating_sub(2) as usize;
+    let scroll = lines.len().saturating_sub(visible) as u16;
+    let paragraph = Paragraph::new(lines)
+        .scroll((scroll, 0))
+        .block(Block::default().borders(Borders::ALL).title(title));
+
+    f.render_widget(paragraph, area);
+}
+
+/// Draw the detail popup for the selected todo.
+fn draw_detail(f: &mut Frame, app: &App, area: ratatui::layout::Rect) {
+    let todos = app.visible_todos();
+    let Some((dot, todo)) = todos.get(app.ui_state.selected_index) else {
+        return;
+    };
+
+    let label = |s: &'static str| Span::styled(s, Style::default().add_modifier(Modifier::BOLD));
+    // Conflicting values name the replica that wrote each
+    let text = if todo.text.len() > 1 {
+        attributed_values(app, &todo.text, &todo.text_writers)
+    } else {
+        vec![Span::raw(todo.primary_text().to_string())]
+    };
+    let done = if todo.done.len() > 1 {
+        let values: Vec<String> = todo.done.iter().map(bool::to_string).collect();
+        let mut spans = attributed_values(app, &values, &todo.done_writers);
+        if app.done_wins {
+            spans.push(Span::raw(" (shown as done)"));
+        }
+        spans
+    } else {
+        vec![Span::raw(todo.primary_done().to_string())]
+    };
+    let level = match todo.level.as_slice() {
+        [] => "none".to_string(),
+        [level] => level.as_str().to_string(),
+        levels => format!(
+            "{:?}",
+            levels
+                .iter()
+                .map(|level| level.as_str())
+                .collect::<Vec<_>>()
+        ),
+    };
+    let category = match todo.category.as_slice() {
+        [] => "none".to_string(),
+        [category] => category.clone(),
+        categories => format!("⚠ [{}]", categories.join(", ")),
+    };
+    let color = match todo.color.as_slice() {
+        [] => "none".to_string(),
+        [color] => color.as_str().to_string(),
+        colors => format!(
+            "⚠ [{}]",
+            colors
+                .iter()
+                .map(|color| color.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    };
+    let recur = match todo.recur.as_slice() {
+        [] => "never".to_string(),
+        [recur] => recur.as_str().to_string(),
+        recurs => format!(
+            "⚠ [{}]",
+            recurs
+                .iter()
+                .map(|recur| recur.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    };
+    let changed_by = |field: &'static str, writer: Option<ReplicaId>| {
+        writer.map(|writer| {
+            vec![
+                Span::raw(format!("{field} by ")),
+                Span::styled(
+                    replica_label(app, writer.value()),
+                    Style::default().fg(replica_id_color(writer.value())),
+                ),
+            ]
+        })
+    };
+    let last_change = [
+        changed_by("text", todo.last_writer_text),
+        changed_by("done", todo.last_writer_done),
+    ]
+    .into_iter()
+    .flatten()
+    .reduce(|mut spans, more| {
+        spans.push(Span::raw(", "));
+        spans.extend(more);
+        spans
+    })
+    .unwrap_or_else(|| vec![Span::raw("unknown")]);
+    let creator = todo.creator.as_deref().unwrap_or("unknown").to_string();
+    let assignee = match todo.assignee.as_slice() {
+        [] => "nobody".to_string(),
+        [assignee] => assignee_label(app, assignee),
+        assignees => format!(
+            "⚠ [{}]",
+            assignees
+                .iter()
+                .map(|assignee| assignee_label(app, assignee))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    };
+    let now = crate::todo::now_millis();
+    let with_relative = |at: i64| {
+        format!(
+            "{} ({})",
+            format_timestamp(at),
+            format_relative_time(now, at)
+        )
+    };
+    let created_at = todo
+        .created_at
+        .map(with_relative)
+        .unwrap_or_else(|| "unknown".to_string());
+    let mut modified_at = todo
+        .modified_at
+        .map(with_relative)
+        .unwrap_or_else(|| "never".to_string());
+    if todo.modified_concurrently {
+        modified_at.push_str(" ⚠ concurrent");
+    }
+
+    let mut lines = vec![
+        Line::from(vec![
+            label("Key:        "),
+            Span::raw(crate::priority::DotKey::new(dot).into_inner()),
+        ]),
+        Line::from([vec![label("Text:       ")], text].concat()),
+        Line::from([vec![label("Done:       ")], done].concat()),
+        Line::from(vec![label("Level:      "), Span::raw(level)]),
+        Line::from(vec![label("Category:   "), Span::raw(category)]),
+        Line::from(vec![label("Color:      "), Span::raw(color)]),
+        Line::from(vec![label("Repeats:    "), Span::raw(recur)]),
+        Line::from(vec![label("Created by: "), Span::raw(creator)]),
+        Line::from([vec![label("Changed:    ")], last_change].concat()),
+        Line::from(vec![label("Assignee:   "), Span::raw(assignee)]),
+        Line::from(vec![label("Created at: "), Span::raw(created_at)]),
+        Line::from(vec![label("Modified:   "), Span::raw(modified_at)]),
+    ];
+
+    // Spell out how the first two concurrent texts differ
+    if let [a, b, ..] = todo.text.as_slice() {
+        let mut spans = vec![label("Diff:       ")];
+        spans.extend(diff_spans(a, b));
+        lines.insert(2, Line::from(spans));
+    }
+
+    let popup = centered_rect(area, 70, lines.len() as u16 + 2);
+    let paragraph = Paragraph::new(lines).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title("Details (v/Esc: close)"),
+    );
+
+    f.render_widget(Clear, popup);
+    f.render_widget(paragraph, popup);
+}
+
+/// Draw the popup listing every concurrent value of the conflicted todo.
+fn draw_conflict(f: &mut Frame, app: &App, area: Rect) {
+    let Some(todo) = app
+        .ui_state
+        .editing_dot
+        .and_then(|dot| crate::todo::read_todo(app.list_store(), &dot))
+    else {
+        return;
+    };
+
+    let bold = Style::default().add_modifier(Modifier::BOLD);
+    let value_line = |field: ConflictField, index: usize, value: String, writers: &[ReplicaId]| {
+        let selected = app.ui_state.conflict_field == field && app.ui_state.conflict_index == index;
+        let (marker, style) = if selected {
+            (
+                "> ",
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD),
+            )
+        } else {
+            ("  ", Style::default())
+        };
+        let mut spans = vec![Span::raw(marker)];
+        spans.extend(writer_spans(app, writers));
+        spans.push(Span::raw(value));
+        Line::from(spans).style(style)
+    };
+
+    let mut lines = Vec::new();
+    if todo.text.len() > 1 {
+        lines.push(Line::styled("Text", bold));
+        for (i, (text, writers)) in todo.text.iter().zip(&todo.text_writers).enumerate() {
+            lines.push(value_line(ConflictField::Text, i, text.clone(), writers));
+        }
+        if let [a, b, ..] = todo.text.as_slice() {
+            let mut spans = vec![Span::raw("  Diff: ")];
+            spans.extend(diff_spans(a, b));
+            lines.push(Line::from(spans));
+        }
+    }
+    if todo.done.len() > 1 {
+        lines.push(Line::styled("Done", bold));
+        for (i, (done, writers)) in todo.done.iter().zip(&todo.done_writers).enumerate() {
+            lines.push(value_line(
+                ConflictField::Done,
+                i,
+                done.to_string(),
+                writers,
+            ));
+        }
+    }
+
+    let popup = centered_rect(area, 70, lines.len() as u16 + 2);
+    let paragraph = Paragraph::new(lines).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title("Resolve conflict (Enter: keep selected, m: merge texts, Esc: cancel)"),
+    );
+
+    f.render_widget(Clear, popup);
+    f.render_widget(paragraph, popup);
+}
+
+/// Draw the popup listing the replicas the todo can be assigned to.
+fn draw_assign(f: &mut Frame, app: &App, area: Rect) {
+    let lines: Vec<Line> = app
+        .assignee_candidates()
+        .into_iter()
+        .enumerate()
+        .map(|(i, id)| {
+            let marker = if i == app.ui_state.assign_index {
+                "> "
+            } else {
+                "  "
+            };
+            let mut name = replica_label(app, id.value());
+            if id == app.replica_id {
+                name.push_str(" (us)");
+            }
+            let mut style = Style::default().fg(replica_id_color(id.value()));
+            if i == app.ui_state.assign_index {
+                style = style.add_modifier(Modifier::BOLD);
+            }
+            Line::styled(format!("{marker}{name}"), style)
+        })
+        .collect();
+
+    let popup = centered_rect(area, 50, lines.len() as u16 + 2);
+    let paragraph = Paragraph::new(lines).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title("Assign to (Enter: assign, Esc: cancel)"),
+    );
+
+    f.render_widget(Clear, popup);
+    f.render_widget(paragraph, popup);
+}
+
+/// Draw the form editing several fields of a todo. Changed fields are marked
+/// with `*`; conflicted ones show their primary value and stay conflicted
+/// unless changed.
+fn draw_form(f: &mut Frame, app: &App, area: Rect) {
+    let Some(form) = &app.ui_state.form else {
+        return;
+    };
+    let todo = app
+        .ui_state
+        .editing_dot
+        .and_then(|dot| crate::todo::read_todo(app.list_store(), &dot));
+    let changed = form.changed_fields();
+    let values = &form.values;
+
+    let lines: Vec<Line> = FormField::ALL
+        .into_iter()
+        .map(|field| {
+            let focused = field == form.field;
+            let value = match field {
+                FormField::Text => values.text.clone(),
+                FormField::Done => if values.done { "yes" } else { "no" }.to_string(),
+                FormField::Level => values
+                    .level
+                    .map_or("none", |level| level.as_str())
+                    .to_string(),
+                FormField::Recur => values
+                    .recur
+                    .map_or("never", |recur| recur.as_str())
+                    .to_string(),
+                FormField::Color => values
+                    .color
+                    .map_or("none", |color| color.as_str())
+                    .to_string(),
+            };
+            let conflicted = todo.as_ref().is_some_and(|todo| match field {
+                FormField::Text => todo.text.len() > 1,
+                FormField::Done => todo.done.len() > 1,
+                FormField::Level => todo.level.len() > 1,
+                FormField::Recur => todo.recur.len() > 1,
+                FormField::Color => todo.color.len() > 1,
+            });
+
+            let style = if focused {
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD)
+            } else {
+                Style::default()
+            };
+            let mut spans = vec![
+                Span::raw(if focused { "> " } else { "  " }),
+                Span::raw(format!("{:<8}", format!("{}:", field.label()))),
+                Span::raw(value),
+            ];
+            if focused && field == FormField::Text {
+                spans.push(Span::styled(
+                    "_",
+                    Style::default().add_modifier(Modifier::SLOW_BLINK),
+                ));
+            }
+            if changed.contains(&field) {
+                spans.push(Span::raw(" *"));
+            } else if conflicted {
+                spans.push(Span::styled(" (conflict)", Style::default().fg(Color::Red)));
+            }
+            Line::from(spans).style(style)
+        })
+        .collect();
+
+    let popup = centered_rect(area, 70, lines.len() as u16 + 2);
+    let paragraph = Paragraph::new(lines).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title("Edit todo (Enter on the last field: save, Esc: discard)"),
+    );
+
+    f.render_widget(Clear, popup);
+    f.render_widget(paragraph, popup);
+}
+
+/// Draw the y/n dialog for the pending destructive action.
+fn draw_confirm(f: &mut Frame, app: &App, area: Rect) {
+    let question = if app.ui_state.pending_action == Some(crate::input::Action::ClearDone) {
+        format!("Delete {} done todo(s)? (y/n)", app.done_dots().len())
+    } else if app.ui_state.pending_action == Some(crate::input::Action::ClearAll) {
+        let count = app.get_todos_ordered().len() + app.find_orphans().len();
+        format!("Delete all {count} todo(s) of {}? (y/n)", app.current_list)
+    } else {
+        let Some(todo) = app
+            .ui_state
+            .editing_dot
+            .and_then(|dot| crate::todo::read_todo(app.list_store(), &dot))
+        else {
+            return;
+        };
+        format!("Delete '{}'? (y/n)", todo.primary_text())
+    };
+
+    // Deletes propagate, so say how many other replicas will lose the todo
+    let peers = app.peers.len();
+    let impact = if peers == 0 {
+        "No other replicas seen yet; peers that sync later get the delete too".to_string()
+    } else {
+        format!("Also deleted on {peers} other known replica(s)")
     };
+    let lines = vec![
+        Line::styled(question, Style::default().add_modifier(Modifier::BOLD)),
+        Line::styled(impact, Style::default().fg(Color::DarkGray)),
+    ];
+
+    let popup = centered_rect(area, 70, lines.len() as u16 + 2);
+    let paragraph = Paragraph::new(lines).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title("Confirm")
+            .border_style(Style::default().fg(Color::Red)),
+    );
+
+    f.render_widget(Clear, popup);
+    f.render_widget(paragraph, popup);
+}
 
-    let list = List::new(items).block(Block::default().borders(Borders::ALL).title(title));
+/// Inline diff from `a` to `b`: removed text red and struck through, added text green and bold.
+fn diff_spans(a: &str, b: &str) -> Vec<Span<'static>> {
+    use crate::diff::DiffSegment;
 
-    f.render_widget(list, area);
+    crate::diff::diff_strings(a, b)
+        .into_iter()
+        .map(|segment| match segment {
+            DiffSegment::Common(s) => Span::raw(s),
+            DiffSegment::Removed(s) => Span::styled(
+                s,
+                Style::default()
+                    .fg(Color::Red)
+                    .add_modifier(Modifier::CROSSED_OUT),
+            ),
+            DiffSegment::Added(s) => Span::styled(
+                s,
+                Style::default()
+                    .fg(Color::Green)
+                    .add_modifier(Modifier::BOLD),
+            ),
+        })
+        .collect()
+}
+
+/// Text style of a done todo: struck through and dimmed, keeping the
+/// selection color instead of the dimming when it is selected.
+///
+/// Strikethrough (SGR 9) is ignored by the Linux console and some older
+/// terminal emulators; there the checkbox and dimming still mark the todo.
+fn done_text_style(selected: bool) -> Style {
+    let style = Style::default().add_modifier(Modifier::CROSSED_OUT);
+    if selected {
+        style
+    } else {
+        style.fg(Color::DarkGray)
+    }
+}
+
+/// Text color of a todo with the given level.
+fn level_style(level: Option<crate::todo::Level>) -> Style {
+    match level {
+        Some(crate::todo::Level::High) => Style::default().fg(Color::Red),
+        Some(crate::todo::Level::Medium) => Style::default().fg(Color::Yellow),
+        Some(crate::todo::Level::Low) | None => Style::default(),
+    }
+}
+
+/// Bullet in front of a todo's text showing its color label: blank without
+/// one, half-filled in the first color when concurrent changes left several.
+fn color_bullet(colors: &[crate::todo::TodoColor]) -> Span<'static> {
+    let color = |color: &crate::todo::TodoColor| match color {
+        crate::todo::TodoColor::Red => Color::Red,
+        crate::todo::TodoColor::Yellow => Color::Yellow,
+        crate::todo::TodoColor::Green => Color::Green,
+        crate::todo::TodoColor::Blue => Color::Blue,
+        crate::todo::TodoColor::Purple => Color::Magenta,
+    };
+    match colors {
+        [] => Span::raw("  "),
+        [single] => Span::styled("● ", Style::default().fg(color(single))),
+        [first, ..] => Span::styled("◐ ", Style::default().fg(color(first))),
+    }
+}
+
+/// Color for a hex replica ID, shared by log lines and creator badges.
+fn replica_color(replica_hex: &str) -> Color {
+    match u8::from_str_radix(replica_hex, 16) {
+        Ok(replica_id) => replica_id_color(replica_id),
+        Err(_) => Color::White,
+    }
+}
+
+/// `width` cells, the share `done / total` of them filled (rounded).
+fn progress_bar(done: usize, total: usize, width: usize) -> String {
+    let filled = (done * width + total / 2) / total.max(1);
+    format!("{}{}", "▰".repeat(filled), "▱".repeat(width - filled))
+}
+
+/// Badge color of a category, the same on every replica: the name's FNV-1a
+/// hash picks one of 6 colors.
+fn category_color(category: &str) -> Color {
+    let hash = category
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+        });
+    match hash % 6 {
+        0 => Color::LightCyan,
+        1 => Color::LightGreen,
+        2 => Color::LightYellow,
+        3 => Color::LightMagenta,
+        4 => Color::LightBlue,
+        _ => Color::LightRed,
+    }
+}
+
+/// Color for a replica id, cycling through 6 colors.
+fn replica_id_color(replica_id: u8) -> Color {
+    match replica_id % 6 {
+        0 => Color::Cyan,
+        1 => Color::Green,
+        2 => Color::Yellow,
+        3 => Color::Magenta,
+        4 => Color::Blue,
+        _ => Color::Red,
+    }
+}
+
+/// A replica as shown in the logs and context panel: hex id or friendly name.
+fn replica_label(app: &App, replica_id: u8) -> String {
+    if app.ui_state.friendly_names {
+        ReplicaId::new(replica_id).friendly_name()
+    } else {
+        format!("{replica_id:02x}")
+    }
+}
+
+/// Writers of a register value as a colored "3a, 7f: " prefix, following
+/// [`replica_label`].
+fn writer_spans(app: &App, writers: &[ReplicaId]) -> Vec<Span<'static>> {
+    let mut spans = Vec::new();
+    for (i, writer) in writers.iter().enumerate() {
+        if i > 0 {
+            spans.push(Span::raw(", "));
+        }
+        spans.push(Span::styled(
+            replica_label(app, writer.value()),
+            Style::default().fg(replica_id_color(writer.value())),
+        ));
+    }
+    if !writers.is_empty() {
+        spans.push(Span::raw(": "));
+    }
+    spans
+}
+
+/// Concurrent `values` joined by " / ", each after its writers, e.g.
+/// "3a: Buy whole milk / 7f: Buy oat milk".
+fn attributed_values(
+    app: &App,
+    values: &[String],
+    writers: &[Vec<ReplicaId>],
+) -> Vec<Span<'static>> {
+    let mut spans = Vec::new();
+    for (i, (value, writers)) in values.iter().zip(writers).enumerate() {
+        if i > 0 {
+            spans.push(Span::raw(" / "));
+        }
+        spans.extend(writer_spans(app, writers));
+        spans.push(Span::raw(value.clone()));
+    }
+    spans
+}
+
+/// A hex assignee as shown in the list and details, following [`replica_label`].
+fn assignee_label(app: &App, assignee: &str) -> String {
+    match u8::from_str_radix(assignee, 16) {
+        Ok(replica_id) => replica_label(app, replica_id),
+        Err(_) => assignee.to_string(),
+    }
+}
+
+/// Format the time between `then` and `now` (Unix milliseconds) as e.g. "2m ago".
+fn format_relative_time(now: i64, then: i64) -> String {
+    let secs = (now - then).max(0) / 1000;
+    match secs {
+        0..60 => "just now".to_string(),
+        60..3_600 => format!("{}m ago", secs / 60),
+        3_600..86_400 => format!("{}h ago", secs / 3_600),
+        _ => format!("{}d ago", secs / 86_400),
+    }
+}
+
+/// Format a Unix millisecond timestamp as UTC date and time.
+fn format_timestamp(millis: i64) -> String {
+    dson::chrono::DateTime::from_timestamp_millis(millis)
+        .map(|dt| dt.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+        .unwrap_or_else(|| format!("{millis} ms"))
+}
+
+/// Compute a rectangle centered in `area` with the given width percentage and height.
+fn centered_rect(
+    area: ratatui::layout::Rect,
+    percent_x: u16,
+    height: u16,
+) -> ratatui::layout::Rect {
+    let width = area.width * percent_x / 100;
+    let height = height.min(area.height);
+    ratatui::layout::Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    }
 }
 
 /// Draw the insert mode UI.
-fn draw_insert_mode(f: &mut Frame, area: ratatui::layout::Rect, input: &str, mode: &str) {
+fn draw_insert_mode(
+    f: &mut Frame,
+    area: ratatui::layout::Rect,
+    input: &str,
+    cursor: usize,
+    mode: &str,
+    max_len: usize,
+) {
+    // The character under the cursor is shown reversed; past the end, a space
+    let (before, rest) = input.split_at(crate::input::byte_index(input, cursor));
+    let mut rest = rest.chars();
+    let under = rest.next().map_or(" ".to_string(), String::from);
     let text = vec![Line::from(vec![
         Span::styled(
             format!("{mode} Todo: "),
             Style::default().add_modifier(Modifier::BOLD),
         ),
-        Span::raw(input),
-        Span::styled("_", Style::default().add_modifier(Modifier::SLOW_BLINK)),
+        Span::raw(before),
+        Span::styled(under, Style::default().add_modifier(Modifier::REVERSED)),
+        Span::raw(rest.as_str()),
     ])];
 
-    let paragraph =
-        Paragraph::new(text).block(Block::default().borders(Borders::ALL).title("Insert Mode"));
+    let title = format!("Insert Mode ({}/{max_len})", input.chars().count());
+    let paragraph = Paragraph::new(text).block(Block::default().borders(Borders::ALL).title(title));
 
     f.render_widget(paragraph, area);
 }
@@ -148,34 +1321,24 @@ fn draw_logs(f: &mut Frame, app: &App, area: ratatui::layout::Rect) {
         .map(|s| {
             // Color code by replica ID
             // Extract replica ID from log message like "[Replica 3a]"
-            let color = if s.contains("Replica") {
-                if let Some(start) = s.find("Replica ") {
-                    if let Some(end) = s[start..].find(']') {
-                        let replica_str = &s[start + 8..start + end];
-                        if let Ok(replica_id) = u8::from_str_radix(replica_str, 16) {
-                            // Assign colors based on replica ID
-                            match replica_id % 6 {
-                                0 => Color::Cyan,
-                                1 => Color::Green,
-                                2 => Color::Yellow,
-                                3 => Color::Magenta,
-                                4 => Color::Blue,
-                                _ => Color::Red,
-                            }
-                        } else {
-                            Color::White
-                        }
-                    } else {
-                        Color::White
-                    }
-                } else {
-                    Color::White
-                }
-            } else {
-                Color::White
+            let replica = s.find("Replica ").and_then(|start| {
+                let end = s[start..].find(']')?;
+                Some((start + 8, start + end))
+            });
+            let Some((start, end)) = replica else {
+                return Line::from(Span::styled(s.as_str(), Style::default().fg(Color::White)));
             };
+            let replica_str = &s[start..end];
+            let color = replica_color(replica_str);
 
-            Line::from(Span::styled(s.as_str(), Style::default().fg(color)))
+            // Swap in the friendly name; the stored message keeps the hex id
+            let text = match u8::from_str_radix(replica_str, 16) {
+                Ok(id) if app.ui_state.friendly_names => {
+                    format!("{}{}{}", &s[..start], replica_label(app, id), &s[end..])
+                }
+                _ => s.clone(),
+            };
+            Line::from(Span::styled(text, Style::default().fg(color)))
         })
         .collect();
 
@@ -218,35 +1381,337 @@ fn draw_context(f: &mut Frame, app: &App, area: ratatui::layout::Rect) {
 
     // Build the display lines
     let mut lines = Vec::new();
-    lines.push(Line::from(Span::styled(
-        "Node → Seq",
-        Style::default().add_modifier(Modifier::BOLD),
-    )));
-
-    for (node, seq) in node_seqs.iter() {
-        let line_str = format!("{node:02x} → {seq}");
-        lines.push(Line::from(line_str));
+    let header = if app.ui_state.show_context_gaps {
+        "Node: received missing"
+    } else {
+        "Node → Seq"
+    };
+    if app.ui_state.show_context_gaps {
+        for gaps in crate::anti_entropy::context_gaps(&app.store.context) {
+            let mut spans = vec![
+                Span::styled(
+                    replica_label(app, gaps.node),
+                    Style::default().fg(replica_id_color(gaps.node)),
+                ),
+                Span::raw(format!(": [{}]", format_ranges(&gaps.received))),
+            ];
+            if !gaps.missing.is_empty() {
+                spans.push(Span::styled(
+                    format!(" missing: [{}]", format_ranges(&gaps.missing)),
+                    Style::default().fg(Color::Red),
+                ));
+            }
+            lines.push(Line::from(spans));
+        }
+    } else {
+        for (node, seq) in node_seqs.iter() {
+            lines.push(Line::from(vec![
+                Span::styled(
+                    replica_label(app, *node),
+                    Style::default().fg(replica_id_color(*node)),
+                ),
+                Span::raw(format!(" → {seq}")),
+            ]));
+        }
     }
 
-    // TODO: Add missing dots detection if needed
-    // For now, just show the version vector
+    // Show the rows that fit below the header, summarizing the rest
+    let rows = area.height.saturating_sub(3) as usize;
+    let total = lines.len();
+    let (start, end) = context_window(total, rows, app.ui_state.context_scroll);
+    let mut lines: Vec<Line> = lines.drain(start..end).collect();
+    lines.insert(
+        0,
+        Line::from(Span::styled(
+            header,
+            Style::default().add_modifier(Modifier::BOLD),
+        )),
+    );
+    if end - start < total {
+        let hint = if app.ui_state.context_focused {
+            "j/k"
+        } else {
+            "F"
+        };
+        lines.push(Line::from(Span::styled(
+            format!("{start} above, +{} more ({hint}: scroll)", total - end),
+            Style::default().fg(Color::DarkGray),
+        )));
+    }
 
+    let title = match (app.ui_state.show_context_gaps, app.context_compacted) {
+        (true, _) => "Causal Context (gaps)",
+        (false, true) => "Causal Context [compacted]",
+        (false, false) => "Causal Context",
+    };
+    let border_style = if app.ui_state.context_focused {
+        Style::default().fg(Color::Yellow)
+    } else {
+        Style::default()
+    };
+    // No wrapping, so every node takes exactly one row
     let paragraph = Paragraph::new(lines).block(
         Block::default()
             .borders(Borders::ALL)
-            .title("Causal Context"),
+            .border_style(border_style)
+            .title(title),
     );
 
     f.render_widget(paragraph, area);
 }
 
+/// Range of `total` context rows shown in `rows` lines when scrolled by `scroll`.
+/// If not all rows fit, the last line is left free for a summary.
+fn context_window(total: usize, rows: usize, scroll: usize) -> (usize, usize) {
+    if total <= rows {
+        return (0, total);
+    }
+
+    let shown = rows.saturating_sub(1);
+    let start = scroll.min(total - shown);
+    (start, start + shown)
+}
+
+/// Format sequence ranges as "1-5, 7, 9-12".
+fn format_ranges(ranges: &[std::ops::RangeInclusive<u64>]) -> String {
+    ranges
+        .iter()
+        .map(|range| {
+            if range.start() == range.end() {
+                range.start().to_string()
+            } else {
+                format!("{}-{}", range.start(), range.end())
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Draw the known peers with how far each one's causal context is from ours,
+/// once it has answered our context request.
+fn draw_context_report(f: &mut Frame, app: &App, area: Rect) {
+    let lines: Vec<Line> = app
+        .sorted_peers()
+        .into_iter()
+        .enumerate()
+        .map(|(i, id)| {
+            let peer = replica_label(app, id.value());
+            let (marker, style) = if i == app.ui_state.peer_index {
+                (
+                    "> ",
+                    Style::default()
+                        .fg(Color::Yellow)
+                        .add_modifier(Modifier::BOLD),
+                )
+            } else {
+                ("  ", Style::default())
+            };
+            let text = match app.context_reports.get(&id) {
+                Some(diff) => format!(
+                    "We are missing: {} operations from peer {peer}. Peer is missing: {} operations from us.",
+                    diff.local_missing, diff.remote_missing
+                ),
+                None => format!("Peer {peer}: waiting for reply…"),
+            };
+            Line::styled(format!("{marker}{text}"), style)
+        })
+        .collect();
+
+    // Each report is one sentence; leave room for it to wrap
+    let inner_width = usize::from((area.width * 90 / 100).saturating_sub(2)).max(1);
+    let rows: usize = lines
+        .iter()
+        .map(|line| line.width().div_ceil(inner_width))
+        .sum();
+    let popup = centered_rect(area, 90, rows as u16 + 2);
+    let paragraph = Paragraph::new(lines)
+        .wrap(ratatui::widgets::Wrap { trim: true })
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("Peers (j/k: select, s: sync now, other keys: close)"),
+        );
+
+    f.render_widget(Clear, popup);
+    f.render_widget(paragraph, popup);
+}
+
+/// How concurrent edits show up in the UI, as explained in the help overlay.
+const CONFLICT_LEGEND: &[(&str, &str)] = &[
+    (
+        "⚠",
+        "Replicas edited this concurrently; every value is kept until resolved",
+    ),
+    ("[a, b]", "Conflicting todo texts, all shown on the row"),
+    ("badges", "Conflicting categories show one badge each"),
+    (
+        "── version n ──",
+        "Conflicting notes, one block per version",
+    ),
+    (
+        "D",
+        "Done wins: a todo marked done and undone shows as done",
+    ),
+    ("c", "Pick the value to keep, or merge the texts"),
+];
+
+/// Number of lines in the help overlay, which scrolls when the screen is
+/// shorter than this.
+pub fn help_overlay_len() -> usize {
+    // Key rows, a blank line, the legend heading and its entries
+    crate::input::KEY_BINDINGS.len().div_ceil(2) + 2 + CONFLICT_LEGEND.len()
+}
+
+/// Draw the recorded changes to the todo `dot`, oldest first, with who made
+/// each one.
+fn draw_history(f: &mut Frame, app: &App, dot: &dson::Dot, area: Rect) {
+    let now = crate::todo::now_millis();
+    let mut lines: Vec<Line> = app
+        .history
+        .entries(&app.current_list, dot)
+        .map(|entry| {
+            let author = entry.author.value();
+            Line::from(vec![
+                Span::styled(
+                    format!("{:>9}  ", format_relative_time(now, entry.at)),
+                    Style::default().fg(Color::DarkGray),
+                ),
+                Span::styled(
+                    replica_label(app, author),
+                    Style::default().fg(replica_id_color(author)),
+                ),
+                Span::raw(format!(" {}", entry.change)),
+            ])
+        })
+        .collect();
+    if lines.is_empty() {
+        lines.push(Line::styled(
+            "No changes seen since startup",
+            Style::default().fg(Color::DarkGray),
+        ));
+    }
+
+    let popup = centered_rect(area, 90, lines.len() as u16 + 2);
+    let paragraph = Paragraph::new(lines)
+        .scroll((app.ui_state.history_scroll as u16, 0))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("History (j/k: scroll, H/Esc: close)"),
+        );
+    f.render_widget(Clear, popup);
+    f.render_widget(paragraph, popup);
+}
+
+/// Draw every normal mode key binding in two columns over the whole screen,
+/// followed by what the conflict indicators mean.
+fn draw_help_overlay(f: &mut Frame, app: &App, area: Rect) {
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title("Help (?/Esc: close, j/k: scroll)");
+    let inner = block.inner(area);
+    let column_width = usize::from(inner.width / 2);
+
+    let bindings = crate::input::KEY_BINDINGS;
+    let rows = bindings.len().div_ceil(2);
+    let (left, right) = bindings.split_at(rows);
+    let key_width = |entries: &[(&str, &str, &str)]| {
+        entries
+            .iter()
+            .map(|(key, _, _)| key.chars().count())
+            .max()
+            .unwrap_or(0)
+    };
+    let (left_width, right_width) = (key_width(left), key_width(right));
+    let mut lines: Vec<Line> = (0..rows)
+        .map(|row| {
+            let (key, _, description) = left[row];
+            let mut spans = key_spans(key, left_width, description, column_width);
+            if let Some((key, _, description)) = right.get(row) {
+                // Pad the left column so the right one lines up
+                let used: usize = spans.iter().map(Span::width).sum();
+                spans.push(Span::raw(" ".repeat(column_width.saturating_sub(used))));
+                spans.extend(key_spans(key, right_width, description, column_width));
+            }
+            Line::from(spans)
+        })
+        .collect();
+
+    lines.push(Line::default());
+    lines.push(Line::styled(
+        "Conflicts",
+        Style::default().add_modifier(Modifier::BOLD),
+    ));
+    let legend_width = CONFLICT_LEGEND
+        .iter()
+        .map(|(symbol, _)| symbol.chars().count())
+        .max()
+        .unwrap_or(0);
+    lines.extend(CONFLICT_LEGEND.iter().map(|(symbol, meaning)| {
+        Line::from(key_spans(
+            symbol,
+            legend_width,
+            meaning,
+            usize::from(inner.width),
+        ))
+    }));
+
+    // Stop scrolling once the last line is at the bottom
+    let max_scroll = lines.len().saturating_sub(usize::from(inner.height));
+    let scroll = app.ui_state.help_scroll.min(max_scroll) as u16;
+    f.render_widget(Clear, area);
+    f.render_widget(Paragraph::new(lines).scroll((scroll, 0)).block(block), area);
+}
+
+/// A highlighted key padded to `key_width` and its description, cut to fit
+/// in `width` columns.
+fn key_spans<'a>(key: &str, key_width: usize, description: &'a str, width: usize) -> Vec<Span<'a>> {
+    let room = width.saturating_sub(key_width + 3);
+    vec![
+        Span::styled(
+            format!("{key:<key_width$}  "),
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+        ),
+        Span::raw(description.chars().take(room).collect::<String>()),
+    ]
+}
+
 /// Draw the help text.
 fn draw_help(f: &mut Frame, app: &App, area: ratatui::layout::Rect) {
     let help_text = match app.ui_state.mode {
-        Mode::Normal => {
-            "q: quit | i: add | r: random | Enter: edit | j/k: nav | J/K: priority | ↑↓: scroll logs | space: toggle | d: delete | p: isolate"
+        Mode::Normal => Line::from(
+            crate::input::KEY_BINDINGS
+                .iter()
+                .map(|(key, hint, _)| format!("{key}: {hint}"))
+                .collect::<Vec<_>>()
+                .join(" | "),
+        ),
+        Mode::Insert => Line::from("Enter: save | Left/Right/Home/End: move cursor | Esc: cancel"),
+        Mode::Notes => Line::from("Enter: newline | Ctrl-S: save | Esc: cancel"),
+        Mode::ResolveConflict => Line::from(
+            "j/k: choose value | Tab: text/done | Enter: keep selected | m: merge texts | Esc: cancel",
+        ),
+        Mode::Subtasks => {
+            Line::from("j/k: nav | space: toggle | a: add | d: delete | e/Esc: close")
+        }
+        Mode::Grab => {
+            Line::from("j/k: move drop point | digits: position | Enter: drop here | Esc/m: cancel")
         }
-        Mode::Insert => "Enter: save | Esc: cancel",
+        Mode::Assign => Line::from("j/k: choose replica | Enter: assign | Esc: cancel"),
+        Mode::Form => Line::from(
+            "Up/Down: field | type: edit text | Space: change value | Enter: next, save on last | Esc: discard",
+        ),
+        Mode::Prompt(kind) => Line::from(vec![
+            Span::styled(
+                format!("{}: ", kind.label()),
+                Style::default().add_modifier(Modifier::BOLD),
+            ),
+            Span::raw(app.ui_state.input_buffer.as_str()),
+            Span::styled("_", Style::default().add_modifier(Modifier::SLOW_BLINK)),
+            Span::raw("  (Enter: apply | Esc: cancel)"),
+        ]),
     };
 
     let paragraph =
@@ -254,3 +1719,545 @@ fn draw_help(f: &mut Frame, app: &App, area: ratatui::layout::Rect) {
 
     f.render_widget(paragraph, area);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_relative_time_ranges() {
+        let now = 1_700_000_000_000;
+        let ago = |secs: i64| format_relative_time(now, now - secs * 1000);
+
+        assert_eq!(ago(0), "just now");
+        assert_eq!(ago(59), "just now");
+        assert_eq!(ago(60), "1m ago");
+        assert_eq!(ago(59 * 60 + 59), "59m ago");
+        assert_eq!(ago(3_600), "1h ago");
+        assert_eq!(ago(23 * 3_600), "23h ago");
+        assert_eq!(ago(86_400), "1d ago");
+        assert_eq!(ago(30 * 86_400), "30d ago");
+    }
+
+    #[test]
+    fn test_context_window_leaves_room_for_summary() {
+        assert_eq!(context_window(3, 5, 0), (0, 3));
+        assert_eq!(context_window(5, 5, 2), (0, 5));
+        // 10 nodes in 5 rows: 4 nodes plus the summary line
+        assert_eq!(context_window(10, 5, 0), (0, 4));
+        assert_eq!(context_window(10, 5, 3), (3, 7));
+        assert_eq!(context_window(10, 5, 99), (6, 10));
+        assert_eq!(context_window(10, 0, 0), (0, 0));
+    }
+
+    #[test]
+    fn test_format_relative_time_clamps_future() {
+        // Peers with a fast clock can stamp times slightly ahead of ours
+        assert_eq!(format_relative_time(1_000, 5_000), "just now");
+    }
+
+    #[test]
+    fn test_highlight_matches_marks_every_occurrence() {
+        let spans = highlight_matches("Fix the fix script", "FIX", Style::default());
+        let contents: Vec<&str> = spans.iter().map(|span| span.content.as_ref()).collect();
+        assert_eq!(contents, ["Fix", " the ", "fix", " script"]);
+        assert_eq!(spans[0].style.bg, Some(Color::Yellow));
+        assert!(spans[0].style.add_modifier.contains(Modifier::BOLD));
+        assert_eq!(spans[1].style.bg, None);
+
+        let spans = highlight_matches("Buy milk", "", Style::default());
+        assert_eq!(spans.len(), 1);
+        assert_eq!(spans[0].content, "Buy milk");
+    }
+
+    #[test]
+    fn test_truncate_text() {
+        assert_eq!(truncate_text("Buy milk", 8), "Buy milk");
+        assert_eq!(truncate_text("Buy milk", 7), "Buy mi…");
+        assert_eq!(truncate_text("Überprüfen", 5), "Über…");
+        assert_eq!(truncate_text("Buy milk", 1), "…");
+        assert_eq!(truncate_text("Buy milk", 0), "");
+    }
+
+    /// App whose first todo has a text too long for a narrow terminal.
+    fn app_with_long_todo() -> App {
+        let mut app = crate::test_util::connected_apps(1).remove(0);
+        app.add_random_todos().expect("add todos");
+        // Fixed texts, so tests can find each todo on screen by its text
+        let texts = [
+            "Recalibrate the deflector dish before the fleet jumps to lightspeed",
+            "Water the plants",
+            "Call the bank",
+        ];
+        for ((dot, _), text) in app.get_todos_ordered().iter().zip(texts) {
+            let dot_key = crate::priority::DotKey::new(dot);
+            let _delta = app.transact_list(|list_tx| {
+                list_tx.in_map(dot_key.as_str(), |todo_tx| {
+                    todo_tx.write_register(
+                        "text",
+                        dson::crdts::mvreg::MvRegValue::String(text.to_string()),
+                    );
+                });
+            });
+        }
+        app
+    }
+
+    /// Draw `app` on a 50x30 terminal and return the rows of the screen.
+    fn render_rows(app: &mut App) -> Vec<String> {
+        use ratatui::{Terminal, backend::TestBackend};
+
+        let mut terminal = Terminal::new(TestBackend::new(50, 30)).expect("terminal");
+        terminal.draw(|f| draw(f, app)).expect("draw");
+        let buffer = terminal.backend().buffer();
+        (0..buffer.area.height)
+            .map(|y| {
+                (0..buffer.area.width)
+                    .map(|x| buffer[(x, y)].symbol())
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_done_todos_are_struck_through_and_dimmed() {
+        use ratatui::{Terminal, backend::TestBackend};
+
+        let mut app = app_with_long_todo();
+        let todos = app.get_todos_ordered();
+        for (dot, _) in &todos[..2] {
+            let dot_key = crate::priority::DotKey::new(dot);
+            let _delta = app.transact_list(|list_tx| {
+                list_tx.in_map(dot_key.as_str(), |todo_tx| {
+                    todo_tx.write_register("done", dson::crdts::mvreg::MvRegValue::Bool(true));
+                });
+            });
+        }
+
+        let mut terminal = Terminal::new(TestBackend::new(50, 30)).expect("terminal");
+        terminal.draw(|f| draw(f, &mut app)).expect("draw");
+        let buffer = terminal.backend().buffer();
+        // Style of the first character of the todo's text on screen
+        let text_style = |text: &str| {
+            let prefix: String = text.chars().take(6).collect();
+            (0..buffer.area.height)
+                .find_map(|y| {
+                    let row: String = (0..buffer.area.width)
+                        .map(|x| buffer[(x, y)].symbol())
+                        .collect();
+                    let x = row.find(&prefix)?;
+                    let x = row[..x].chars().count() as u16;
+                    Some(buffer[(x, y)].style())
+                })
+                .expect("todo on screen")
+        };
+
+        // The selected done todo keeps the selection color
+        let selected = text_style("Recalibrate");
+        assert!(selected.add_modifier.contains(Modifier::CROSSED_OUT));
+        assert_eq!(selected.fg, Some(Color::Yellow));
+
+        let other = text_style(todos[1].1.primary_text());
+        assert!(other.add_modifier.contains(Modifier::CROSSED_OUT));
+        assert_eq!(other.fg, Some(Color::DarkGray));
+
+        let open = text_style(todos[2].1.primary_text());
+        assert!(!open.add_modifier.contains(Modifier::CROSSED_OUT));
+    }
+
+    #[test]
+    fn test_ranks_stay_absolute_in_filtered_list() {
+        let mut app = app_with_long_todo();
+        let third = app.get_todos_ordered()[2].1.primary_text().to_string();
+        assert!(!render_rows(&mut app).iter().any(|row| row.contains("1. [")));
+
+        app.ui_state.show_ranks = true;
+        let rows = render_rows(&mut app);
+        assert!(rows.iter().any(|row| row.contains("│1. [ ]")));
+        assert!(rows.iter().any(|row| row.contains("│3. [ ]")));
+
+        // Only the third todo matches, and keeps its rank
+        app.ui_state.search_query = third;
+        let rows = render_rows(&mut app);
+        assert!(rows.iter().any(|row| row.contains("│3. [ ]")));
+        assert!(!rows.iter().any(|row| row.contains("│1. [")));
+    }
+
+    #[test]
+    fn test_color_bullets_keep_texts_aligned() {
+        use ratatui::{Terminal, backend::TestBackend};
+
+        let mut app = app_with_long_todo();
+        let todos = app.get_todos_ordered();
+        assert!(!render_rows(&mut app).iter().any(|row| row.contains('●')));
+
+        let dot_key = crate::priority::DotKey::new(&todos[1].0);
+        let _delta = app.transact_list(|list_tx| {
+            list_tx.in_map(dot_key.as_str(), |todo_tx| {
+                crate::todo::set_color(todo_tx, Some(crate::todo::TodoColor::Green));
+            });
+        });
+        let mut terminal = Terminal::new(TestBackend::new(50, 30)).expect("terminal");
+        terminal.draw(|f| draw(f, &mut app)).expect("draw");
+        let buffer = terminal.backend().buffer();
+        let text_column = |y: u16, text: &str| {
+            let row: String = (0..buffer.area.width)
+                .map(|x| buffer[(x, y)].symbol())
+                .collect();
+            row.find(text).map(|byte| row[..byte].chars().count())
+        };
+
+        let bullet = (0..buffer.area.height)
+            .find_map(|y| text_column(y, "●").map(|x| (x as u16, y)))
+            .expect("bullet");
+        assert_eq!(buffer[bullet].fg, Color::Green);
+        let colored = text_column(bullet.1, todos[1].1.primary_text()).expect("colored text");
+        assert_eq!(colored, usize::from(bullet.0) + 2);
+        let plain = (0..buffer.area.height)
+            .find_map(|y| text_column(y, todos[2].1.primary_text()))
+            .expect("plain text");
+        assert_eq!(plain, colored);
+    }
+
+    #[test]
+    fn test_selected_truncated_todo_shows_full_text() {
+        let rows = render_rows(&mut app_with_long_todo());
+
+        assert!(
+            rows.iter()
+                .any(|row| row.contains("Recalibrate the") && row.contains('…'))
+        );
+        assert!(rows.iter().any(|row| row.contains("↳ Recalibrate")));
+    }
+
+    #[test]
+    fn test_wrap_mode_continues_text_on_more_rows() {
+        let mut app = app_with_long_todo();
+        app.ui_state.wrap_mode = WrapMode::Wrap;
+        let rows = render_rows(&mut app);
+
+        assert!(rows.iter().any(|row| row.contains("jumps to")));
+        assert!(rows.iter().any(|row| row.contains("lightspeed")));
+        assert!(
+            !rows
+                .iter()
+                .any(|row| row.contains('…') || row.contains('↳'))
+        );
+    }
+
+    #[test]
+    fn test_wrap_text() {
+        assert_eq!(wrap_text("Buy milk", 20), vec!["Buy milk"]);
+        assert_eq!(
+            wrap_text("Fix the spaceship engine today", 12),
+            vec!["Fix the", "spaceship", "engine today"]
+        );
+        assert_eq!(wrap_text("Hyperdrive", 4), vec!["Hype", "rdri", "ve"]);
+        assert_eq!(
+            wrap_text("a Hyperdrive", 4),
+            vec!["a", "Hype", "rdri", "ve"]
+        );
+        assert_eq!(wrap_text("", 4), vec![""]);
+    }
+
+    #[test]
+    fn test_friendly_names_in_logs_and_context() {
+        let mut app = app_with_long_todo();
+        app.log("[Replica 01] Broadcast delta".to_string());
+        app.ui_state.friendly_names = true;
+        let rows = render_rows(&mut app);
+
+        assert!(
+            rows.iter()
+                .any(|row| row.contains("[Replica brave-badger]"))
+        );
+        assert!(rows.iter().any(|row| row.contains("brave-badger →")));
+        assert_eq!(
+            app.log_buffer.last().expect("log"),
+            "[Replica 01] Broadcast delta"
+        );
+    }
+
+    #[test]
+    fn test_list_title_counts_done_todos() {
+        let mut app = app_with_long_todo();
+        crate::input::execute_action(&mut app, crate::input::Action::ToggleDone).expect("toggle");
+        let rows = render_rows(&mut app);
+        assert!(rows.iter().any(|row| row.contains("Todos (1/3)")));
+
+        let dot_key = crate::priority::DotKey::new(&app.get_todos_ordered()[1].0);
+        let _delta = app.transact_list(|list_tx| {
+            list_tx.in_map(dot_key.as_str(), |todo_tx| {
+                crate::todo::add_tag(todo_tx, "ship");
+            });
+        });
+        app.ui_state.filter_tags.insert("ship".to_string());
+        let rows = render_rows(&mut app);
+        assert!(rows.iter().any(|row| row.contains("[#ship] (0/1 of 3)")));
+
+        app.ui_state.filter_tags.insert("crew".to_string());
+        let rows = render_rows(&mut app);
+        assert!(
+            rows.iter()
+                .any(|row| row.contains("[#crew #ship] (0/0 of 3)"))
+        );
+    }
+
+    #[test]
+    fn test_list_marks_todos_with_notes() {
+        let mut app = app_with_long_todo();
+        assert!(!render_rows(&mut app).iter().any(|row| row.contains('✎')));
+
+        let dot_key = crate::priority::DotKey::new(&app.get_todos_ordered()[1].0);
+        let _delta = app.transact_list(|list_tx| {
+            list_tx.in_map(dot_key.as_str(), |todo_tx| {
+                todo_tx.write_register(
+                    "notes",
+                    dson::crdts::mvreg::MvRegValue::String("Check the hyperdrive".to_string()),
+                );
+            });
+        });
+        assert!(render_rows(&mut app).iter().any(|row| row.contains('✎')));
+    }
+
+    #[test]
+    fn test_confirm_dialog_shows_todo_text() {
+        let mut app = app_with_long_todo();
+        crate::input::request_action(&mut app, crate::input::Action::Delete).expect("delete");
+        let rows = render_rows(&mut app);
+
+        assert!(rows.iter().any(|row| row.contains("Delete 'Recalibrate")));
+        assert!(rows.iter().any(|row| row.contains("No other replicas")));
+    }
+
+    #[test]
+    fn test_help_overlay_renders_bindings() {
+        let mut app = app_with_long_todo();
+        app.ui_state.show_help = true;
+        let rows = render_rows(&mut app);
+
+        assert!(rows.iter().any(|row| row.contains("Help (?/Esc: close")));
+        assert!(rows.iter().any(|row| row.contains("Quit")));
+        // Nothing of the list behind shows through the full-screen overlay
+        assert!(!rows.iter().any(|row| row.contains("Recalibrate")));
+    }
+
+    #[test]
+    fn test_history_popup_lists_changes_with_author() {
+        let mut app = app_with_long_todo();
+        let dot = app.get_todos_ordered()[1].0;
+        app.ui_state.history_dot = Some(dot);
+        let rows = render_rows(&mut app);
+
+        assert!(rows.iter().any(|row| row.contains("History (")));
+        assert!(
+            rows.iter()
+                .any(|row| row.contains("just now  01 created '"))
+        );
+    }
+
+    #[test]
+    fn test_help_overlay_scrolls_to_the_legend_on_short_screens() {
+        let mut app = app_with_long_todo();
+        app.ui_state.show_help = true;
+        let last = "Pick the value";
+        assert!(!render_rows(&mut app).iter().any(|row| row.contains(last)));
+
+        // Scrolling past the end stops with the last line at the bottom
+        app.ui_state.help_scroll = usize::MAX;
+        let rows = render_rows(&mut app);
+        assert!(rows[rows.len() - 2].contains(last));
+        assert!(rows.iter().any(|row| row.contains("Conflicts")));
+        assert!(!rows.iter().any(|row| row.contains("Quit")));
+    }
+
+    #[test]
+    fn test_conflicted_category_shows_every_badge() {
+        let mut app = app_with_long_todo();
+        let dot_key = crate::priority::DotKey::new(&app.get_todos_ordered()[1].0);
+        let _delta = app.transact_list(|list_tx| {
+            list_tx.in_map(dot_key.as_str(), |todo_tx| {
+                crate::todo::set_category(todo_tx, Some("home"));
+            });
+        });
+        // A concurrent write from another replica, merged in directly
+        let mut other = app.store.clone();
+        let mut tx = other.transact(dson::Identifier::new(2, 0));
+        tx.in_map(app.current_list.as_str(), |list_tx| {
+            list_tx.in_map(dot_key.as_str(), |todo_tx| {
+                crate::todo::set_category(todo_tx, Some("work"));
+            });
+        });
+        let delta = tx.commit();
+        let _delta = app.transact_list(|list_tx| {
+            list_tx.in_map(dot_key.as_str(), |todo_tx| {
+                crate::todo::set_category(todo_tx, Some("errand"));
+            });
+        });
+        app.store
+            .join_or_replace_with(delta.0.store, &delta.0.context);
+
+        let todo = &app.get_todos_ordered()[1].1;
+        assert!(todo.has_conflicts());
+        let rows = render_rows(&mut app);
+        let row = rows
+            .iter()
+            .find(|row| row.contains(" errand "))
+            .expect("category badge");
+        assert!(row.contains('⚠'));
+        assert!(row.contains(" work "));
+        assert!(!row.contains(" home "));
+    }
+
+    #[test]
+    fn test_conflict_popup_names_each_value_writer() {
+        let mut app = app_with_long_todo();
+        let dot_key = crate::priority::DotKey::new(&app.get_todos_ordered()[1].0);
+        let set_text = |list_tx: &mut dson::transaction::MapTransaction<'_, String>, text: &str| {
+            list_tx.in_map(dot_key.as_str(), |todo_tx| {
+                todo_tx.write_register(
+                    "text",
+                    dson::crdts::mvreg::MvRegValue::String(text.to_string()),
+                );
+            });
+        };
+        // A concurrent edit from replica 02, merged in directly
+        let mut other = app.store.clone();
+        let mut tx = other.transact(dson::Identifier::new(2, 0));
+        tx.in_map(app.current_list.as_str(), |list_tx| {
+            set_text(list_tx, "Water the roses")
+        });
+        let delta = tx.commit();
+        let _delta = app.transact_list(|list_tx| set_text(list_tx, "Water the ferns"));
+        app.store
+            .join_or_replace_with(delta.0.store, &delta.0.context);
+
+        app.ui_state.selected_index = 1;
+        crate::input::execute_action(&mut app, crate::input::Action::ResolveConflict)
+            .expect("open conflict popup");
+        assert_eq!(app.ui_state.mode, Mode::ResolveConflict);
+        let rows = render_rows(&mut app);
+        assert!(rows.iter().any(|row| row.contains("01: Water the ferns")));
+        assert!(rows.iter().any(|row| row.contains("02: Water the roses")));
+
+        app.ui_state.friendly_names = true;
+        let rows = render_rows(&mut app);
+        let name = ReplicaId::new(2).friendly_name();
+        assert!(
+            rows.iter()
+                .any(|row| row.contains(&format!("{name}: Water the roses")))
+        );
+    }
+
+    #[test]
+    fn test_form_marks_focus_and_changed_fields() {
+        let mut app = app_with_long_todo();
+        app.ui_state.selected_index = 2;
+        crate::input::execute_action(&mut app, crate::input::Action::EditForm).expect("open form");
+        for code in [
+            crossterm::event::KeyCode::Down,
+            crossterm::event::KeyCode::Char(' '),
+        ] {
+            crate::input::handle_form_key(crossterm::event::KeyEvent::from(code), &mut app)
+                .expect("key");
+        }
+
+        let rows = render_rows(&mut app);
+        assert!(
+            rows.iter()
+                .any(|row| row.contains("  Text:   Call the bank"))
+        );
+        assert!(rows.iter().any(|row| row.contains("> Done:   yes *")));
+        assert!(rows.iter().any(|row| row.contains("  Repeat: never")));
+    }
+
+    #[test]
+    fn test_category_color_is_deterministic() {
+        assert_eq!(category_color("work"), category_color("work"));
+        let colors: std::collections::HashSet<_> = ["work", "home", "errand", "garden"]
+            .into_iter()
+            .map(category_color)
+            .collect();
+        assert!(colors.len() > 1);
+    }
+
+    #[test]
+    fn test_insert_title_counts_characters() {
+        let mut app = app_with_long_todo();
+        app.ui_state.mode = Mode::Insert;
+        app.ui_state.input_buffer = "Tee für 🎉".to_string();
+        let rows = render_rows(&mut app);
+
+        assert!(rows.iter().any(|row| row.contains("Insert Mode (9/500)")));
+    }
+
+    #[test]
+    fn test_insert_cursor_is_reversed() {
+        use ratatui::{Terminal, backend::TestBackend};
+
+        let mut app = app_with_long_todo();
+        app.ui_state.mode = Mode::Insert;
+        app.ui_state.input_buffer = "Tee für 🎉".to_string();
+        let reversed = |app: &mut App| -> String {
+            let mut terminal = Terminal::new(TestBackend::new(50, 30)).expect("terminal");
+            terminal.draw(|f| draw(f, app)).expect("draw");
+            let buffer = terminal.backend().buffer();
+            buffer
+                .content()
+                .iter()
+                .filter(|cell| cell.modifier.contains(Modifier::REVERSED))
+                .map(|cell| cell.symbol())
+                .collect()
+        };
+
+        app.ui_state.cursor_pos = 5;
+        assert_eq!(reversed(&mut app), "ü");
+        // Past the end the cursor is a reversed space
+        app.ui_state.cursor_pos = 9;
+        assert_eq!(reversed(&mut app), " ");
+    }
+
+    #[test]
+    fn test_progress_bar_rounds_to_cells() {
+        assert_eq!(progress_bar(0, 4, 5), "▱▱▱▱▱");
+        assert_eq!(progress_bar(1, 2, 5), "▰▰▰▱▱");
+        assert_eq!(progress_bar(2, 5, 5), "▰▰▱▱▱");
+        assert_eq!(progress_bar(5, 5, 5), "▰▰▰▰▰");
+    }
+
+    #[test]
+    fn test_list_shows_subtask_progress() {
+        let mut app = app_with_long_todo();
+        app.ui_state.selected_index = 1;
+        for text in ["first", "second"] {
+            crate::input::execute_action(&mut app, crate::input::Action::AddSubtask)
+                .expect("prompt");
+            app.ui_state.input_buffer = text.to_string();
+            crate::input::handle_prompt_key(
+                crossterm::event::KeyEvent::from(crossterm::event::KeyCode::Enter),
+                &mut app,
+                crate::app::PromptKind::AddSubtask,
+            )
+            .expect("add subtask");
+        }
+        crate::input::handle_subtasks_key(
+            crossterm::event::KeyEvent::from(crossterm::event::KeyCode::Char(' ')),
+            &mut app,
+        )
+        .expect("toggle");
+        app.ui_state.mode = Mode::Normal;
+        let rows = render_rows(&mut app);
+
+        assert!(rows.iter().any(|row| row.contains("(1/2 ▰▰▰▱▱)")));
+    }
+
+    #[test]
+    fn test_list_shows_postponed_count() {
+        let mut app = app_with_long_todo();
+        crate::input::execute_action(&mut app, crate::input::Action::MovePriorityDown)
+            .expect("postpone");
+        let rows = render_rows(&mut app);
+
+        assert!(rows.iter().any(|row| row.contains("(postponed ×1)")));
+    }
+}
@@ -0,0 +1,223 @@
+// ABOUTME: Undo and redo of local edits through compensating transactions.
+// ABOUTME: Snapshots a todo and its position before an edit and writes them back on undo.
+
+use crate::{
+    priority::{DotKey, find_priority_index, priority_entries},
+    todo::{Todo, read_todo},
+};
+use dson::{Dot, OrMap, crdts::mvreg::MvRegValue, transaction::MapTransaction};
+use std::collections::VecDeque;
+
+/// Maximum number of entries on each of the undo and redo stacks.
+pub const MAX_UNDO_ENTRIES: usize = 50;
+
+/// A todo as it was before a local edit.
+///
+/// Undoing is not a rollback of CRDT state: restoring an entry writes the old
+/// values again in a new transaction, which peers receive like any other edit.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UndoEntry {
+    /// Name of the list holding the todo.
+    pub list: String,
+    pub dot: Dot,
+    /// The todo and its priority index, or `None` if it was not in the list.
+    pub before: Option<(Todo, usize)>,
+}
+
+impl UndoEntry {
+    /// Snapshot `dot` in `list_store`, the map of the list called `list`.
+    pub fn capture(list: &str, list_store: &OrMap<String>, dot: &Dot) -> Self {
+        let before = read_todo(list_store, dot)
+            .and_then(|todo| Some((todo, find_priority_index(list_store, dot)?)));
+        Self {
+            list: list.to_string(),
+            dot: *dot,
+            before,
+        }
+    }
+
+    /// Write what brings the todo back to this snapshot, given the list's
+    /// current state.
+    ///
+    /// Text, done, notes, level, category and recurrence are written only where
+    /// they differ, and the todo is moved back to its old index (clamped to the list). A todo
+    /// that no longer exists is recreated with its creation metadata and tags;
+    /// subtasks are not restored. Undoing a creation deletes the todo.
+    pub fn restore(&self, list_tx: &mut MapTransaction<'_, String>, list_store: &OrMap<String>) {
+        let dot_key = DotKey::new(&self.dot);
+        let current = read_todo(list_store, &self.dot);
+        let current_index = find_priority_index(list_store, &self.dot);
+
+        let Some((todo, index)) = &self.before else {
+            if let Some(current_index) = current_index {
+                list_tx.in_array("priority", |arr_tx| arr_tx.remove(current_index));
+            }
+            list_tx.remove(dot_key.as_str());
+            return;
+        };
+
+        list_tx.in_map(dot_key.as_str(), |todo_tx| {
+            restore_registers(todo_tx, todo, current.as_ref());
+        });
+
+        let len = priority_entries(list_store).len();
+        list_tx.in_array("priority", |arr_tx| match current_index {
+            Some(current_index) if current_index == *index => {}
+            Some(current_index) => {
+                arr_tx.remove(current_index);
+                arr_tx.insert_register(
+                    (*index).min(len - 1),
+                    MvRegValue::String(dot_key.into_inner()),
+                );
+            }
+            None => {
+                arr_tx.insert_register((*index).min(len), MvRegValue::String(dot_key.into_inner()))
+            }
+        });
+    }
+}
+
+/// Write the registers of `todo` that differ from `current`, or all of them
+/// if the todo no longer exists.
+fn restore_registers(
+    todo_tx: &mut MapTransaction<'_, String>,
+    todo: &Todo,
+    current: Option<&Todo>,
+) {
+    let text = todo.primary_text().to_string();
+    if current.is_none_or(|current| current.text != [text.as_str()]) {
+        todo_tx.write_register("text", MvRegValue::String(text));
+    }
+    let done = todo.primary_done();
+    if current.is_none_or(|current| current.done != [done]) {
+        todo_tx.write_register("done", MvRegValue::Bool(done));
+    }
+    if current.is_none_or(|current| current.notes != todo.notes.get(..1).unwrap_or_default()) {
+        match todo.notes.first() {
+            Some(notes) => todo_tx.write_register("notes", MvRegValue::String(notes.clone())),
+            None => todo_tx.remove("notes"),
+        }
+    }
+    if current.is_none_or(|current| current.level != todo.level.get(..1).unwrap_or_default()) {
+        crate::todo::set_level(todo_tx, todo.primary_level());
+    }
+    if current.is_none_or(|current| current.category != todo.category.get(..1).unwrap_or_default())
+    {
+        crate::todo::set_category(todo_tx, todo.primary_category());
+    }
+    if current.is_none_or(|current| current.recur != todo.recur.get(..1).unwrap_or_default()) {
+        crate::todo::set_recur(todo_tx, todo.primary_recur());
+    }
+    if current.is_none_or(|current| current.color != todo.color.get(..1).unwrap_or_default()) {
+        crate::todo::set_color(todo_tx, todo.primary_color());
+    }
+    if current.is_none_or(|current| current.assignee != todo.assignee.get(..1).unwrap_or_default())
+        && let Some(assignee) = todo.assignee.first()
+    {
+        todo_tx.write_register("assignee", MvRegValue::String(assignee.clone()));
+    }
+
+    if current.is_none() {
+        if let Some(creator) = &todo.creator {
+            todo_tx.write_register("creator", MvRegValue::String(creator.clone()));
+        }
+        if let Some(created_at) = todo.created_at {
+            todo_tx.write_register("created_at", MvRegValue::I64(created_at));
+        }
+        for tag in &todo.tags {
+            crate::todo::add_tag(todo_tx, tag);
+        }
+    }
+    crate::todo::touch(todo_tx);
+}
+
+/// Undo and redo stacks, each bounded to [`MAX_UNDO_ENTRIES`].
+#[derive(Debug, Default)]
+pub struct UndoHistory {
+    undo: VecDeque<UndoEntry>,
+    redo: VecDeque<UndoEntry>,
+}
+
+impl UndoHistory {
+    /// Record the state before a new local edit. Clears the redo stack.
+    pub fn record(&mut self, entry: UndoEntry) {
+        self.redo.clear();
+        push_bounded(&mut self.undo, entry);
+    }
+
+    /// Take the most recent undo entry.
+    pub fn pop_undo(&mut self) -> Option<UndoEntry> {
+        self.undo.pop_back()
+    }
+
+    /// Take the most recent redo entry.
+    pub fn pop_redo(&mut self) -> Option<UndoEntry> {
+        self.redo.pop_back()
+    }
+
+    /// Push the state before an undo, so it can be redone.
+    pub fn push_redo(&mut self, entry: UndoEntry) {
+        push_bounded(&mut self.redo, entry);
+    }
+
+    /// Push the state before a redo, so it can be undone again.
+    pub fn push_undo(&mut self, entry: UndoEntry) {
+        push_bounded(&mut self.undo, entry);
+    }
+
+    /// Number of entries on the undo stack.
+    pub fn undo_len(&self) -> usize {
+        self.undo.len()
+    }
+
+    /// Number of entries on the redo stack.
+    pub fn redo_len(&self) -> usize {
+        self.redo.len()
+    }
+}
+
+/// Push `entry`, dropping the oldest entry when full.
+fn push_bounded(stack: &mut VecDeque<UndoEntry>, entry: UndoEntry) {
+    stack.push_back(entry);
+    if stack.len() > MAX_UNDO_ENTRIES {
+        stack.pop_front();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dson::Identifier;
+
+    fn entry(seq: u64) -> UndoEntry {
+        UndoEntry {
+            list: "Todos".to_string(),
+            dot: Dot::mint(Identifier::new(1, 0), seq),
+            before: None,
+        }
+    }
+
+    #[test]
+    fn test_history_is_bounded() {
+        let mut history = UndoHistory::default();
+        for seq in 1..=MAX_UNDO_ENTRIES as u64 + 5 {
+            history.record(entry(seq));
+        }
+
+        assert_eq!(history.undo_len(), MAX_UNDO_ENTRIES);
+        assert_eq!(history.pop_undo(), Some(entry(MAX_UNDO_ENTRIES as u64 + 5)));
+    }
+
+    #[test]
+    fn test_new_edit_clears_redo() {
+        let mut history = UndoHistory::default();
+        history.record(entry(1));
+        let undone = history.pop_undo().expect("entry");
+        history.push_redo(undone);
+        assert_eq!(history.redo_len(), 1);
+
+        history.record(entry(2));
+        assert_eq!(history.redo_len(), 0);
+        assert_eq!(history.pop_redo(), None);
+    }
+}